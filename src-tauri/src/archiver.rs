@@ -0,0 +1,252 @@
+use anyhow::{Context, Result};
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::ssh_client::{BackupProgress, ProgressGranularity, ProgressThrottle, SshClient};
+
+/// バックアップ後に作成するアーカイブの形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArchiveFormat {
+    #[default]
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+/// バックアップしたフォルダをタイムスタンプ付きのアーカイブに圧縮
+///
+/// アーカイブは `<フォルダ名>_<Unix秒>.<拡張子>` として `archive_dir` に作成し、
+/// 作成したアーカイブのパスを返す。進捗は転送と同じ `granularity` で間引き、開始時と完了時は必ず通知する。
+/// MAX_PATH を超える深い階層も読めるよう、ファイルは拡張長パスで扱う（返すパスには付けない）
+pub fn create_archive<F>(
+    source_dir: &Path,
+    archive_dir: &Path,
+    format: ArchiveFormat,
+    granularity: ProgressGranularity,
+    progress_callback: F,
+) -> Result<PathBuf>
+where
+    F: Fn(BackupProgress),
+{
+    let dir_name = source_dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("backup");
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    std::fs::create_dir_all(SshClient::extended_length_path(archive_dir))
+        .with_context(|| format!("アーカイブ保存先の作成に失敗: {:?}", archive_dir))?;
+
+    let archive_path = archive_dir.join(format!("{}_{}.{}", dir_name, timestamp, format.extension()));
+    let source_dir = &SshClient::extended_length_path(source_dir);
+
+    // 進捗表示用に対象ファイルを先に列挙
+    let mut files = Vec::new();
+    collect_files(source_dir, &mut files)?;
+    let total_files = files.len();
+    let total_bytes: u64 = files.iter().map(|(_, size)| size).sum();
+
+    let mut throttle = ProgressThrottle::new(Duration::from_millis(granularity.interval_ms), granularity.byte_threshold);
+    let mut report = |archived_files: usize, archived_bytes: u64, current: Option<&Path>| {
+        let is_edge = archived_files == 0 || archived_files == total_files;
+        if !throttle.should_update_without_speed_sample(archived_bytes) && !is_edge {
+            return;
+        }
+        progress_callback(BackupProgress {
+            phase: "アーカイブ作成中".to_string(),
+            transferred_files: archived_files,
+            total_files: Some(total_files),
+            transferred_bytes: archived_bytes,
+            total_bytes: Some(total_bytes),
+            percent_complete: BackupProgress::calculate_percent(archived_bytes, Some(total_bytes)),
+            current_file: current.map(SshClient::display_path),
+            elapsed_seconds: throttle.get_elapsed_seconds(),
+            ..Default::default()
+        });
+    };
+    report(0, 0, None);
+
+    let archive_file = File::create(SshClient::extended_length_path(&archive_path))
+        .with_context(|| format!("アーカイブファイルの作成に失敗: {:?}", archive_path))?;
+
+    match format {
+        ArchiveFormat::TarGz => write_tar_gz(archive_file, source_dir, dir_name, &files, &mut report)?,
+        ArchiveFormat::Zip => write_zip(archive_file, source_dir, dir_name, &files, &mut report)?,
+    }
+
+    Ok(archive_path)
+}
+
+/// 列挙したファイルを tar.gz に書き込む（`dir_name` をアーカイブ内の最上位のフォルダにする）
+fn write_tar_gz(
+    archive_file: File,
+    source_dir: &Path,
+    dir_name: &str,
+    files: &[(PathBuf, u64)],
+    report: &mut impl FnMut(usize, u64, Option<&Path>),
+) -> Result<()> {
+    let encoder = GzEncoder::new(archive_file, Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+
+    let mut archived_bytes = 0u64;
+    for (index, (path, size)) in files.iter().enumerate() {
+        let relative = path.strip_prefix(source_dir).unwrap_or(path);
+        builder
+            .append_path_with_name(path, Path::new(dir_name).join(relative))
+            .with_context(|| format!("アーカイブへの追加に失敗: {:?}", path))?;
+        archived_bytes += size;
+        report(index + 1, archived_bytes, Some(path));
+    }
+
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .context("アーカイブの書き込み完了に失敗しました")?;
+    Ok(())
+}
+
+/// 列挙したファイルを zip に書き込む（`dir_name` をアーカイブ内の最上位のフォルダにする）
+fn write_zip(
+    archive_file: File,
+    source_dir: &Path,
+    dir_name: &str,
+    files: &[(PathBuf, u64)],
+    report: &mut impl FnMut(usize, u64, Option<&Path>),
+) -> Result<()> {
+    let mut writer = zip::ZipWriter::new(archive_file);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .large_file(true);
+
+    let mut archived_bytes = 0u64;
+    for (index, (path, size)) in files.iter().enumerate() {
+        let relative = path.strip_prefix(source_dir).unwrap_or(path);
+        // zip内のパス区切りは常に "/"
+        let entry_name = Path::new(dir_name)
+            .join(relative)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        writer
+            .start_file(entry_name, options)
+            .with_context(|| format!("アーカイブへの追加に失敗: {:?}", path))?;
+        let mut source = File::open(path)
+            .with_context(|| format!("ファイルのオープンに失敗: {:?}", path))?;
+        std::io::copy(&mut source, &mut writer)
+            .with_context(|| format!("アーカイブへの書き込みに失敗: {:?}", path))?;
+
+        archived_bytes += size;
+        report(index + 1, archived_bytes, Some(path));
+    }
+
+    writer.finish().context("アーカイブの書き込み完了に失敗しました")?;
+    Ok(())
+}
+
+/// ディレクトリ配下のファイルを再帰的に列挙（パスとサイズ）
+pub fn collect_files(dir: &Path, files: &mut Vec<(PathBuf, u64)>) -> Result<()> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("ディレクトリの読み取りに失敗: {:?}", dir))?;
+
+    for entry in entries {
+        let entry = entry.with_context(|| format!("ディレクトリの読み取りに失敗: {:?}", dir))?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+
+        if file_type.is_dir() {
+            collect_files(&path, files)?;
+        } else if file_type.is_file() {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            files.push((path, size));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::cell::RefCell;
+
+    /// Windows の MAX_PATH（260文字）を超える長さ
+    const LONG_PATH_LENGTH: usize = 320;
+
+    #[test]
+    fn reports_only_start_and_end_within_one_interval() {
+        let source = TempDir::new("archive-source");
+        for i in 0..50 {
+            source.write(&format!("site/file-{:02}.txt", i), b"content");
+        }
+        let archive_dir = TempDir::new("archive-dest");
+        let granularity = ProgressGranularity { interval_ms: 60_000, byte_threshold: u64::MAX };
+
+        let reported = RefCell::new(Vec::new());
+        let archive_path = create_archive(&source.path().join("site"), archive_dir.path(), ArchiveFormat::Zip, granularity, |progress| {
+            reported.borrow_mut().push(progress.transferred_files);
+        })
+        .unwrap();
+
+        assert!(archive_path.exists());
+        assert_eq!(reported.into_inner(), [0, 50]);
+    }
+
+    #[test]
+    fn archives_files_beyond_max_path() {
+        let source = TempDir::new("archive-long-path");
+        let site = source.path().join("site");
+        let mut deep_dir = site.clone();
+        while deep_dir.as_os_str().len() < LONG_PATH_LENGTH {
+            deep_dir.push("deeply-nested-directory");
+        }
+        std::fs::create_dir_all(SshClient::extended_length_path(&deep_dir)).unwrap();
+        std::fs::write(SshClient::extended_length_path(&deep_dir.join("index.html")), b"<html></html>").unwrap();
+        let archive_dir = TempDir::new("archive-long-path-dest");
+        let granularity = ProgressGranularity { interval_ms: 0, byte_threshold: 0 };
+
+        let current_files = RefCell::new(Vec::new());
+        let archive_path = create_archive(&site, archive_dir.path(), ArchiveFormat::TarGz, granularity, |progress| {
+            current_files.borrow_mut().extend(progress.current_file);
+        })
+        .unwrap();
+
+        assert_eq!(archive_path.parent(), Some(archive_dir.path()));
+        assert_eq!(current_files.into_inner(), [deep_dir.join("index.html").to_string_lossy()]);
+    }
+
+    #[test]
+    fn reports_every_file_when_the_byte_threshold_is_reached() {
+        let source = TempDir::new("archive-source-bytes");
+        for i in 0..3 {
+            source.write(&format!("site/file-{}.txt", i), b"content");
+        }
+        let archive_dir = TempDir::new("archive-dest-bytes");
+        let granularity = ProgressGranularity { interval_ms: 60_000, byte_threshold: 1 };
+
+        let reported = RefCell::new(Vec::new());
+        create_archive(&source.path().join("site"), archive_dir.path(), ArchiveFormat::TarGz, granularity, |progress| {
+            reported.borrow_mut().push(progress.transferred_files);
+        })
+        .unwrap();
+
+        assert_eq!(reported.into_inner(), [0, 1, 2, 3]);
+    }
+}
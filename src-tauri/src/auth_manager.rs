@@ -5,7 +5,9 @@ use argon2::{
 };
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::data_dir;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthSettings {
@@ -43,6 +45,27 @@ impl Default for LockoutInfo {
     }
 }
 
+/// ロック画面の表示用の認証状態（PINの検証はしない）
+#[derive(Debug, Serialize)]
+pub struct AuthStatus {
+    pub is_enabled: bool,
+    pub is_locked: bool,
+    /// ロックアウト解除までの残り時間（分、切り上げ）。ロックアウト中でなければNone
+    pub remaining_minutes: Option<u32>,
+    pub failed_attempts: u32,
+    /// ロックアウトされるまでに失敗できる残り回数
+    pub attempts_remaining: u32,
+}
+
+/// 診断情報に含める認証の設定（PINのハッシュは含めない）
+#[derive(Debug, Serialize)]
+pub struct AuthPolicy {
+    pub is_enabled: bool,
+    pub max_attempts: u32,
+    pub lockout_duration_minutes: u32,
+    pub status: AuthStatus,
+}
+
 pub struct AuthManager {
     config_path: PathBuf,
     lockout_path: PathBuf,
@@ -50,12 +73,8 @@ pub struct AuthManager {
 
 impl AuthManager {
     pub fn new() -> Result<Self> {
-        let config_dir = dirs::config_dir()
-            .ok_or_else(|| anyhow!("設定ディレクトリの取得に失敗しました"))?
-            .join("kyosho-backup");
-
-        // 設定ディレクトリを作成
-        fs::create_dir_all(&config_dir)?;
+        // 設定ディレクトリを取得（存在しない場合は作成）
+        let config_dir = data_dir::data_dir()?;
 
         Ok(Self {
             config_path: config_dir.join("auth_settings.json"),
@@ -253,13 +272,61 @@ impl AuthManager {
         self.save_lockout_info(&LockoutInfo::default())
     }
 
+    /// 認証設定ファイルのパス
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
+
+    /// ロックアウト情報ファイルのパス
+    pub fn lockout_path(&self) -> &Path {
+        &self.lockout_path
+    }
+
     /// ロックアウト残り時間を取得（分）
     pub fn get_lockout_remaining_minutes(&self) -> Result<Option<u32>> {
         let settings = self.load_auth_settings()?;
         let lockout_info = self.load_lockout_info()?;
 
+        Ok(self.lockout_remaining_minutes(&settings, &lockout_info))
+    }
+
+    /// ロック画面の表示用に認証状態を取得（設定・ロックアウト情報は変更しない）
+    ///
+    /// ロックアウト期間が過ぎている場合は、次のPIN認証でリセットされる状態として返す
+    pub fn get_auth_status(&self) -> Result<AuthStatus> {
+        let settings = self.load_auth_settings()?;
+        let lockout_info = self.load_lockout_info()?;
+
+        let is_enabled = settings.is_enabled && settings.pin_hash.is_some();
+        let remaining_minutes = self.lockout_remaining_minutes(&settings, &lockout_info);
+        let lockout_expired = lockout_info.is_locked && remaining_minutes.is_none();
+        let failed_attempts = if lockout_expired { 0 } else { lockout_info.failed_attempts };
+
+        Ok(AuthStatus {
+            is_enabled,
+            is_locked: is_enabled && remaining_minutes.is_some(),
+            remaining_minutes: remaining_minutes.filter(|_| is_enabled),
+            failed_attempts,
+            attempts_remaining: settings.max_attempts.saturating_sub(failed_attempts),
+        })
+    }
+
+    /// 認証の設定と現在の状態を取得（PINのハッシュは含めない）
+    pub fn get_auth_policy(&self) -> Result<AuthPolicy> {
+        let settings = self.load_auth_settings()?;
+
+        Ok(AuthPolicy {
+            is_enabled: settings.is_enabled,
+            max_attempts: settings.max_attempts,
+            lockout_duration_minutes: settings.lockout_duration_minutes,
+            status: self.get_auth_status()?,
+        })
+    }
+
+    /// ロックアウト解除までの残り時間（分、切り上げ）。ロックアウト中でなければNone
+    fn lockout_remaining_minutes(&self, settings: &AuthSettings, lockout_info: &LockoutInfo) -> Option<u32> {
         if !lockout_info.is_locked {
-            return Ok(None);
+            return None;
         }
 
         let current_time = self.current_timestamp();
@@ -267,11 +334,11 @@ impl AuthManager {
         let unlock_time = lockout_info.last_attempt_timestamp + lockout_duration_seconds;
 
         if current_time >= unlock_time {
-            Ok(None)
+            None
         } else {
             let remaining_seconds = unlock_time - current_time;
             let remaining_minutes = (remaining_seconds + 59) / 60; // 切り上げ
-            Ok(Some(remaining_minutes as u32))
+            Some(remaining_minutes as u32)
         }
     }
 }
\ No newline at end of file
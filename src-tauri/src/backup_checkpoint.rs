@@ -0,0 +1,189 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::ssh_client::{BackupOptions, SshConfig};
+
+/// 保存先フォルダに置くチェックポイントのファイル名（隠しファイルのため転送・ミラー削除の対象外）
+pub const CHECKPOINT_FILE_NAME: &str = ".kyosho-checkpoint.json";
+
+/// チェックポイントを書き出す間隔（ディレクトリの完了ごとに毎回は書かない）
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 中断したバックアップを続きから実行するための記録
+///
+/// フォルダは名前順に処理するため、最後に処理を終えたディレクトリより前のエントリは
+/// 処理済みとみなせる
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupCheckpoint {
+    pub remote_path: String,
+    pub local_path: String,
+    pub ssh: SshConfig,
+    pub options: BackupOptions,
+    /// 実行したプロファイル名（プロファイルを指定せずに実行した場合はNone）
+    #[serde(default)]
+    pub profile_name: Option<String>,
+    /// 最後に処理を終えたリモートのディレクトリ（まだない場合はNone）
+    pub last_completed_dir: Option<String>,
+    /// 最後に書き出した時刻（Unix秒）
+    pub updated_at: u64,
+}
+
+impl BackupCheckpoint {
+    /// チェックポイントのファイル、またはそれを含む保存先フォルダから読み込む
+    pub fn load(path: &Path) -> Result<Self> {
+        let path = if path.is_dir() {
+            path.join(CHECKPOINT_FILE_NAME)
+        } else {
+            path.to_path_buf()
+        };
+
+        let data = std::fs::read(&path)
+            .with_context(|| format!("チェックポイントの読み取りに失敗しました: {:?}", path))?;
+        serde_json::from_slice(&data)
+            .with_context(|| format!("チェックポイントを読み込めません: {:?}", path))
+    }
+
+    /// 処理済みとみなしてよいエントリか
+    ///
+    /// 最後に処理を終えたディレクトリとその配下、名前順でそれより前のエントリが該当する。
+    /// ただしそのディレクトリの親はまだ処理中のため該当しない
+    pub fn is_done(last_completed_dir: &Path, path: &Path) -> bool {
+        path.starts_with(last_completed_dir)
+            || (!last_completed_dir.starts_with(path) && path < last_completed_dir)
+    }
+}
+
+/// 転送中のチェックポイントの書き出し
+pub struct CheckpointWriter {
+    path: PathBuf,
+    checkpoint: BackupCheckpoint,
+    last_written: Option<Instant>,
+}
+
+impl CheckpointWriter {
+    pub fn new(local_root: &Path, checkpoint: BackupCheckpoint) -> Self {
+        Self {
+            path: local_root.join(CHECKPOINT_FILE_NAME),
+            checkpoint,
+            last_written: None,
+        }
+    }
+
+    /// 処理を終えたディレクトリを記録し、前回の書き出しから間隔が空いていれば書き出す
+    pub fn record_completed_dir(&mut self, remote_dir: &Path) {
+        self.checkpoint.last_completed_dir = Some(remote_dir.to_string_lossy().to_string());
+        if self.last_written.is_none_or(|written| written.elapsed() >= CHECKPOINT_INTERVAL) {
+            self.write_logged();
+        }
+    }
+
+    /// 途中で終わった場合に最新の状態を書き出す
+    pub fn write_logged(&mut self) {
+        if let Err(e) = self.write() {
+            tracing::warn!("チェックポイントの保存に失敗しました: {:#}", e);
+        }
+    }
+
+    fn write(&mut self) -> Result<()> {
+        self.checkpoint.updated_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let json = serde_json::to_vec_pretty(&self.checkpoint)
+            .context("チェックポイントのシリアライズに失敗しました")?;
+
+        // 書き込み途中で中断しても壊れたチェックポイントを残さないよう、一時ファイルから置き換える
+        let temp_path = self.path.with_file_name(format!("{}.tmp", CHECKPOINT_FILE_NAME));
+        std::fs::write(&temp_path, json)
+            .with_context(|| format!("チェックポイントの保存に失敗しました: {:?}", temp_path))?;
+        std::fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("チェックポイントの保存に失敗しました: {:?}", self.path))?;
+        self.last_written = Some(Instant::now());
+        Ok(())
+    }
+
+    /// バックアップが完了したらチェックポイントを削除する
+    pub fn remove(self) {
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("チェックポイントの削除に失敗しました: {:?}: {}", self.path, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn done(last_completed_dir: &str, path: &str) -> bool {
+        BackupCheckpoint::is_done(Path::new(last_completed_dir), Path::new(path))
+    }
+
+    #[test]
+    fn is_done_covers_earlier_siblings_and_their_contents() {
+        assert!(done("/home/u/site/b", "/home/u/site/a"));
+        assert!(done("/home/u/site/b", "/home/u/site/a/deep/file.txt"));
+        assert!(done("/home/u/site/b", "/home/u/site/a.txt"));
+        assert!(!done("/home/u/site/b", "/home/u/site/c"));
+        assert!(!done("/home/u/site/b", "/home/u/site/ba"));
+    }
+
+    #[test]
+    fn is_done_covers_the_completed_dir_and_everything_below_it() {
+        assert!(done("/home/u/site/b", "/home/u/site/b"));
+        assert!(done("/home/u/site/b", "/home/u/site/b/x"));
+        assert!(done("/home/u/site/b", "/home/u/site/b/z/file.txt"));
+    }
+
+    #[test]
+    fn is_done_leaves_parents_unfinished() {
+        assert!(!done("/home/u/site/b/c", "/home/u/site/b"));
+        assert!(!done("/home/u/site/b/c", "/home/u/site"));
+        // 親ディレクトリの中で後ろにあるファイル・フォルダもまだ処理されていない
+        assert!(!done("/home/u/site/b/c", "/home/u/site/b/d"));
+        assert!(!done("/home/u/site/b/c", "/home/u/site/c"));
+    }
+
+    #[test]
+    fn is_done_treats_files_after_a_dir_name_as_pending() {
+        // "b" < "b.txt" < "b0" のため、b の処理を終えた時点ではどれも未処理
+        assert!(!done("/home/u/site/b", "/home/u/site/b.txt"));
+        assert!(!done("/home/u/site/b", "/home/u/site/b0"));
+        assert!(!done("/home/u/site/b", "/home/u/site/b-old/index.html"));
+    }
+
+    #[test]
+    fn writer_round_trips_profile_name() {
+        let dir = TempDir::new("checkpoint");
+        let legacy = dir.write(
+            CHECKPOINT_FILE_NAME,
+            br#"{
+                "remote_path": "/home/u/site",
+                "local_path": "/backup/site",
+                "ssh": {"hostname": "sv1.example.jp", "port": 10022, "username": "u", "key_path": "/k"},
+                "options": {},
+                "last_completed_dir": "/home/u/site/b",
+                "updated_at": 1
+            }"#,
+        );
+        let mut checkpoint = BackupCheckpoint::load(&legacy).unwrap();
+        assert_eq!(checkpoint.profile_name, None);
+        assert_eq!(checkpoint.last_completed_dir.as_deref(), Some("/home/u/site/b"));
+
+        checkpoint.profile_name = Some("本番サイト".to_string());
+        let mut writer = CheckpointWriter::new(dir.path(), checkpoint);
+        writer.record_completed_dir(Path::new("/home/u/site/c"));
+
+        let reloaded = BackupCheckpoint::load(dir.path()).unwrap();
+        assert_eq!(reloaded.profile_name.as_deref(), Some("本番サイト"));
+        assert_eq!(reloaded.last_completed_dir.as_deref(), Some("/home/u/site/c"));
+        assert_eq!(reloaded.ssh.port, 10022);
+
+        writer.remove();
+        assert!(!dir.path().join(CHECKPOINT_FILE_NAME).exists());
+    }
+}
@@ -0,0 +1,539 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use anyhow::{Context, Result};
+use argon2::Argon2;
+use base64::{engine::general_purpose, Engine as _};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config_manager;
+use crate::ssh_key::wipe_string;
+
+/// 保存先フォルダに置く暗号化のマニフェストのファイル名（隠しファイルのため転送・ミラー削除の対象外）
+pub const MANIFEST_FILE_NAME: &str = ".kyosho-encryption.json";
+
+/// 暗号化したファイルに付ける拡張子
+pub const ENCRYPTED_EXTENSION: &str = ".enc";
+
+/// 暗号化の単位（転送と同じ128KB）。チャンクごとに16バイトの認証タグが付く
+const CHUNK_SIZE: usize = 128 * 1024;
+
+const TAG_SIZE: usize = 16;
+
+const MANIFEST_VERSION: u32 = 1;
+
+/// パスフレーズの確認用に暗号化しておく値
+const PASSPHRASE_CHECK: &[u8] = b"kyosho-backup";
+
+/// 暗号化のパスフレーズ（ログなどに出さないよう Debug では伏せ、破棄時にメモリ上の内容を上書きする）
+#[derive(Clone)]
+pub struct Passphrase(String);
+
+impl Passphrase {
+    pub fn new(passphrase: String) -> Self {
+        Self(passphrase)
+    }
+}
+
+impl std::fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Passphrase(***)")
+    }
+}
+
+impl Drop for Passphrase {
+    fn drop(&mut self) {
+        wipe_string(std::mem::take(&mut self.0));
+    }
+}
+
+/// 暗号化したファイルの情報
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EncryptedFile {
+    /// チャンクのNonceの先頭8バイト（Base64）。残り4バイトはチャンク番号
+    nonce: String,
+    /// 暗号化前のサイズ（バイト）
+    size: u64,
+}
+
+/// 暗号化のマニフェスト（鍵の導出に使うソルトと、ファイルごとのNonce）
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptionManifest {
+    version: u32,
+    /// Argon2id のソルト（Base64）
+    salt: String,
+    /// パスフレーズの確認用に既知の値を暗号化したもの
+    check: String,
+    /// 保存先からの相対パス（`.enc` 付き、区切りは "/"）ごとの情報
+    files: BTreeMap<String, EncryptedFile>,
+}
+
+/// 1回のバックアップで使う暗号化の状態
+pub struct BackupEncryption {
+    key: [u8; 32],
+    manifest: EncryptionManifest,
+}
+
+impl BackupEncryption {
+    /// 保存先のマニフェストを読み込み、パスフレーズから鍵を導出する
+    ///
+    /// マニフェストがない場合は新しいソルトで作成する。既存のマニフェストとパスフレーズが
+    /// 一致しない場合は、同じフォルダに異なる鍵のファイルが混ざらないようエラーにする
+    pub fn open(local_root: &Path, passphrase: &Passphrase) -> Result<Self> {
+        let path = local_root.join(MANIFEST_FILE_NAME);
+        if path.exists() {
+            let (key, manifest) = load_manifest(local_root, passphrase)?;
+            return Ok(Self { key, manifest });
+        }
+
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let key = derive_key(passphrase, &salt)?;
+
+        Ok(Self {
+            key,
+            manifest: EncryptionManifest {
+                version: MANIFEST_VERSION,
+                salt: general_purpose::STANDARD.encode(salt),
+                check: config_manager::encrypt_data(&key, PASSPHRASE_CHECK)?,
+                files: BTreeMap::new(),
+            },
+        })
+    }
+
+    /// 1ファイル分の暗号化器を作成（Nonceはファイルごとにランダム）
+    pub fn file_encryptor(&self) -> FileEncryptor {
+        let mut nonce_prefix = [0u8; 8];
+        rand::thread_rng().fill_bytes(&mut nonce_prefix);
+        FileEncryptor {
+            key: self.key,
+            nonce_prefix,
+        }
+    }
+
+    /// 暗号化して保存したファイルをマニフェストに記録
+    pub fn record(&mut self, relative_path: String, encryptor: &FileEncryptor, size: u64) {
+        self.manifest.files.insert(
+            relative_path,
+            EncryptedFile {
+                nonce: general_purpose::STANDARD.encode(encryptor.nonce_prefix),
+                size,
+            },
+        );
+    }
+
+    /// マニフェストを保存する（途中で失敗した場合も、それまでのファイルを復号できるように呼ぶ）
+    pub fn save(&self, local_root: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(&self.manifest)
+            .context("暗号化のマニフェストのシリアライズに失敗しました")?;
+
+        // 書き込み途中で中断しても壊れたマニフェストを残さないよう、一時ファイルから置き換える
+        let path = local_root.join(MANIFEST_FILE_NAME);
+        let temp_path = local_root.join(format!("{}.tmp", MANIFEST_FILE_NAME));
+        std::fs::write(&temp_path, json)
+            .with_context(|| format!("暗号化のマニフェストの保存に失敗しました: {:?}", temp_path))?;
+        std::fs::rename(&temp_path, &path)
+            .with_context(|| format!("暗号化のマニフェストの保存に失敗しました: {:?}", path))
+    }
+}
+
+/// 1ファイル分の鍵とNonce
+pub struct FileEncryptor {
+    key: [u8; 32],
+    nonce_prefix: [u8; 8],
+}
+
+impl FileEncryptor {
+    /// 書き込んだ内容をチャンクごとに暗号化して `inner` に書き出すライターを作成
+    pub fn writer<W: Write>(&self, inner: W) -> EncryptingWriter<W> {
+        EncryptingWriter {
+            inner,
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key)),
+            nonce_prefix: self.nonce_prefix,
+            counter: 0,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+        }
+    }
+}
+
+/// 128KBごとに AES-256-GCM で暗号化して書き出すライター
+///
+/// チャンクのNonceは「ファイルごとの8バイト + チャンク番号」で、最後のチャンクかどうかを
+/// 追加認証データに含める（並べ替え・切り詰めを検出するため）。`finish` で最後のチャンクを書き出す
+pub struct EncryptingWriter<W: Write> {
+    inner: W,
+    cipher: Aes256Gcm,
+    nonce_prefix: [u8; 8],
+    counter: u32,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptingWriter<W> {
+    /// 残りを最後のチャンクとして書き出し、元のライターを返す
+    pub fn finish(mut self) -> Result<W> {
+        self.write_chunk(true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn write_chunk(&mut self, last: bool) -> std::io::Result<()> {
+        let nonce = chunk_nonce(&self.nonce_prefix, self.counter);
+        let ciphertext = self.cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: &self.buffer, aad: &[last as u8] })
+            .map_err(|e| std::io::Error::other(format!("暗号化に失敗しました: {}", e)))?;
+        self.inner.write_all(&ciphertext)?;
+
+        self.buffer.clear();
+        self.counter = self.counter
+            .checked_add(1)
+            .ok_or_else(|| std::io::Error::other("暗号化できるファイルサイズの上限を超えました"))?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for EncryptingWriter<W> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        // 最後のチャンクかどうかは続きが来るまで分からないため、満杯のチャンクは次の書き込みで出す
+        if self.buffer.len() == CHUNK_SIZE && !data.is_empty() {
+            self.write_chunk(false)?;
+        }
+
+        let len = data.len().min(CHUNK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 暗号化後のファイルサイズ（チャンクごとに認証タグが付く。空のファイルもタグ1つ分）
+pub fn encrypted_size(size: u64) -> u64 {
+    size + chunk_count(size) * TAG_SIZE as u64
+}
+
+/// 暗号化前のサイズに対するチャンク数（空のファイルも最後のチャンクを1つ持つ）
+fn chunk_count(size: u64) -> u64 {
+    size.div_ceil(CHUNK_SIZE as u64).max(1)
+}
+
+/// 復号の結果
+#[derive(Debug, Default, Serialize)]
+pub struct DecryptReport {
+    pub decrypted_files: usize,
+    pub decrypted_bytes: u64,
+    /// マニフェストにあるがファイルが見つからなかったもの（相対パス）
+    pub missing_files: Vec<String>,
+    /// 破損・改ざんなどで復号できなかったもの
+    pub failed_files: Vec<DecryptFailure>,
+}
+
+/// 復号できなかったファイル
+#[derive(Debug, Serialize)]
+pub struct DecryptFailure {
+    /// 保存先からの相対パス
+    pub path: String,
+    pub error: String,
+}
+
+/// 暗号化したバックアップを `dest` に復号する
+///
+/// `.enc` を除いた名前で、元のフォルダ構成のまま書き出す。更新日時は暗号化したファイルに合わせる。
+/// 復号できないファイルがあっても残りのファイルは復号し、失敗したものは結果に含める
+pub fn decrypt_backup(path: &Path, passphrase: &Passphrase, dest: &Path) -> Result<DecryptReport> {
+    let (key, manifest) = load_manifest(path, passphrase)?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+
+    let mut report = DecryptReport::default();
+    for (relative_path, file) in &manifest.files {
+        let target = match decrypted_path(dest, relative_path) {
+            Ok(target) => target,
+            Err(e) => {
+                report.failed_files.push(DecryptFailure { path: relative_path.clone(), error: format!("{:#}", e) });
+                continue;
+            }
+        };
+        let source = path.join(relative_path);
+        if !source.is_file() {
+            tracing::warn!("暗号化したファイルが見つかりません: {:?}", source);
+            report.missing_files.push(relative_path.clone());
+            continue;
+        }
+
+        if let Err(e) = decrypt_entry(&cipher, file, &source, &target) {
+            tracing::warn!("復号に失敗しました: {}: {:#}", relative_path, e);
+            report.failed_files.push(DecryptFailure { path: relative_path.clone(), error: format!("{:#}", e) });
+            continue;
+        }
+
+        report.decrypted_files += 1;
+        report.decrypted_bytes += file.size;
+    }
+
+    Ok(report)
+}
+
+/// マニフェストの1件を復号する
+///
+/// 一時ファイルに書き出してから名前を変えるため、失敗しても復号先の既存のファイルは壊さない
+fn decrypt_entry(cipher: &Aes256Gcm, file: &EncryptedFile, source: &Path, target: &Path) -> Result<()> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("ローカルディレクトリの作成に失敗: {:?}", parent))?;
+    }
+
+    let nonce_prefix: [u8; 8] = general_purpose::STANDARD
+        .decode(&file.nonce)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .context("マニフェストのNonceが不正です")?;
+
+    let mut temp_name = target.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = target.with_file_name(temp_name);
+
+    let result = decrypt_file(cipher, &nonce_prefix, file.size, source, &temp_path).and_then(|_| {
+        std::fs::rename(&temp_path, target)
+            .with_context(|| format!("復号したファイルの名前変更に失敗: {:?}", temp_path))
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// 1ファイルを復号する（チャンク単位で読み取るため大きなファイルでもメモリを使い切らない）
+fn decrypt_file(cipher: &Aes256Gcm, nonce_prefix: &[u8; 8], size: u64, source: &Path, target: &Path) -> Result<()> {
+    let mut input = std::fs::File::open(source)
+        .with_context(|| format!("ファイルのオープンに失敗: {:?}", source))?;
+    let mut output = std::fs::File::create(target)
+        .with_context(|| format!("ローカルファイルの作成に失敗: {:?}", target))?;
+
+    let chunk_count = chunk_count(size);
+    let mut buffer = vec![0u8; CHUNK_SIZE + TAG_SIZE];
+    let mut remaining = size;
+
+    for counter in 0..chunk_count {
+        let counter = u32::try_from(counter).context("チャンク数が多すぎます")?;
+        let plaintext_len = remaining.min(CHUNK_SIZE as u64) as usize;
+        let chunk = &mut buffer[..plaintext_len + TAG_SIZE];
+        input.read_exact(chunk)
+            .context("暗号化したファイルが途中で終わっています")?;
+
+        let last = u64::from(counter) + 1 == chunk_count;
+        let nonce = chunk_nonce(nonce_prefix, counter);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: chunk, aad: &[last as u8] })
+            .map_err(|_| anyhow::anyhow!("復号に失敗しました（ファイルが破損しているか、改ざんされています）"))?;
+
+        output.write_all(&plaintext)
+            .with_context(|| format!("ローカルファイル書き込み失敗: {:?}", target))?;
+        remaining -= plaintext_len as u64;
+    }
+
+    if input.read(&mut [0u8; 1])? != 0 {
+        return Err(anyhow::anyhow!("暗号化したファイルの末尾に余分なデータがあります"));
+    }
+
+    if let Ok(modified) = std::fs::metadata(source).and_then(|m| m.modified()) {
+        output.set_modified(modified)
+            .with_context(|| format!("更新日時の設定に失敗: {:?}", target))?;
+    }
+
+    Ok(())
+}
+
+/// マニフェストを読み込み、パスフレーズから導出した鍵を確認して返す
+fn load_manifest(local_root: &Path, passphrase: &Passphrase) -> Result<([u8; 32], EncryptionManifest)> {
+    let path = local_root.join(MANIFEST_FILE_NAME);
+    let data = std::fs::read(&path)
+        .with_context(|| format!("暗号化のマニフェストの読み込みに失敗しました: {:?}", path))?;
+    let manifest: EncryptionManifest = serde_json::from_slice(&data)
+        .with_context(|| format!("暗号化のマニフェストのパースに失敗しました: {:?}", path))?;
+
+    if manifest.version != MANIFEST_VERSION {
+        return Err(anyhow::anyhow!("未対応の暗号化のマニフェストです（バージョン {}）", manifest.version));
+    }
+
+    let salt = general_purpose::STANDARD
+        .decode(&manifest.salt)
+        .context("暗号化のマニフェストのソルトが不正です")?;
+    let key = derive_key(passphrase, &salt)?;
+
+    let matches = config_manager::decrypt_data(&key, &manifest.check)
+        .is_ok_and(|check| check == PASSPHRASE_CHECK);
+    if !matches {
+        return Err(anyhow::anyhow!("パスフレーズが正しくありません（暗号化したときのパスフレーズと一致しません）"));
+    }
+
+    Ok((key, manifest))
+}
+
+/// パスフレーズから Argon2id で256ビットの鍵を導出
+fn derive_key(passphrase: &Passphrase, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.0.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("暗号化キーの導出に失敗しました: {}", e))?;
+    Ok(key)
+}
+
+/// チャンクのNonce（ファイルごとの8バイト + チャンク番号の4バイト）
+fn chunk_nonce(nonce_prefix: &[u8; 8], counter: u32) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..8].copy_from_slice(nonce_prefix);
+    nonce[8..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+/// マニフェストの相対パスから復号先のパスを作る（`.enc` を除き、保存先の外を指すものは拒否）
+fn decrypted_path(dest: &Path, relative_path: &str) -> Result<PathBuf> {
+    let relative = relative_path.strip_suffix(ENCRYPTED_EXTENSION).unwrap_or(relative_path);
+    let mut target = dest.to_path_buf();
+    for component in relative.split('/') {
+        if component.is_empty() || component == "." || component == ".." {
+            return Err(anyhow::anyhow!("マニフェストのパスが不正です: {}", relative_path));
+        }
+        target.push(component);
+    }
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn passphrase() -> Passphrase {
+        Passphrase::new("correct horse".to_string())
+    }
+
+    /// テスト用のデータ（チャンクの境界を確認できるよう位置ごとに値を変える）
+    fn sample(size: usize) -> Vec<u8> {
+        (0..size).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// バックアップと同じ手順で暗号化して保存し、マニフェストに記録する
+    fn write_encrypted(encryption: &mut BackupEncryption, root: &Path, relative: &str, data: &[u8]) -> PathBuf {
+        let encryptor = encryption.file_encryptor();
+        let path = root.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut writer = encryptor.writer(std::fs::File::create(&path).unwrap());
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap();
+        encryption.record(relative.to_string(), &encryptor, data.len() as u64);
+        path
+    }
+
+    #[test]
+    fn round_trip_preserves_content_across_chunk_boundaries() {
+        let backup = TempDir::new("crypto-backup");
+        let dest = TempDir::new("crypto-dest");
+        let mut encryption = BackupEncryption::open(backup.path(), &passphrase()).unwrap();
+
+        let files = [
+            ("empty.txt.enc", sample(0)),
+            ("small.txt.enc", sample(10)),
+            ("dir/exact.bin.enc", sample(CHUNK_SIZE * 2)),
+            ("dir/odd.bin.enc", sample(CHUNK_SIZE * 2 + 5)),
+        ];
+        for (relative, data) in &files {
+            let path = write_encrypted(&mut encryption, backup.path(), relative, data);
+            assert_eq!(std::fs::metadata(&path).unwrap().len(), encrypted_size(data.len() as u64));
+        }
+        encryption.save(backup.path()).unwrap();
+
+        let report = decrypt_backup(backup.path(), &passphrase(), dest.path()).unwrap();
+        assert_eq!(report.decrypted_files, files.len());
+        assert!(report.failed_files.is_empty());
+        for (relative, data) in &files {
+            let target = dest.path().join(relative.strip_suffix(ENCRYPTED_EXTENSION).unwrap());
+            assert_eq!(&std::fs::read(target).unwrap(), data, "{}", relative);
+        }
+    }
+
+    #[test]
+    fn wrong_passphrase_is_rejected() {
+        let backup = TempDir::new("crypto-backup");
+        let dest = TempDir::new("crypto-dest");
+        let mut encryption = BackupEncryption::open(backup.path(), &passphrase()).unwrap();
+        write_encrypted(&mut encryption, backup.path(), "a.txt.enc", b"secret");
+        encryption.save(backup.path()).unwrap();
+
+        let wrong = Passphrase::new("wrong".to_string());
+        assert!(decrypt_backup(backup.path(), &wrong, dest.path()).is_err());
+        assert!(BackupEncryption::open(backup.path(), &wrong).is_err());
+    }
+
+    #[test]
+    fn truncated_file_fails_and_others_are_still_decrypted() {
+        let backup = TempDir::new("crypto-backup");
+        let dest = TempDir::new("crypto-dest");
+        let mut encryption = BackupEncryption::open(backup.path(), &passphrase()).unwrap();
+        let truncated = write_encrypted(&mut encryption, backup.path(), "a.bin.enc", &sample(CHUNK_SIZE + 100));
+        write_encrypted(&mut encryption, backup.path(), "b.txt.enc", b"intact");
+        encryption.save(backup.path()).unwrap();
+
+        // 最後のチャンクをまるごと落とす（チャンク境界での切り詰め）
+        let file = std::fs::OpenOptions::new().write(true).open(&truncated).unwrap();
+        file.set_len((CHUNK_SIZE + TAG_SIZE) as u64).unwrap();
+        drop(file);
+
+        let report = decrypt_backup(backup.path(), &passphrase(), dest.path()).unwrap();
+        assert_eq!(report.decrypted_files, 1);
+        assert_eq!(report.failed_files.len(), 1);
+        assert_eq!(report.failed_files[0].path, "a.bin.enc");
+        assert!(!dest.path().join("a.bin").exists());
+        assert_eq!(std::fs::read(dest.path().join("b.txt")).unwrap(), b"intact");
+    }
+
+    #[test]
+    fn tampered_file_fails_without_overwriting_existing_output() {
+        let backup = TempDir::new("crypto-backup");
+        let dest = TempDir::new("crypto-dest");
+        let mut encryption = BackupEncryption::open(backup.path(), &passphrase()).unwrap();
+        let tampered = write_encrypted(&mut encryption, backup.path(), "a.txt.enc", b"original content");
+        encryption.save(backup.path()).unwrap();
+
+        let mut data = std::fs::read(&tampered).unwrap();
+        data[3] ^= 0x01;
+        std::fs::write(&tampered, data).unwrap();
+        dest.write("a.txt", b"previous");
+
+        let report = decrypt_backup(backup.path(), &passphrase(), dest.path()).unwrap();
+        assert_eq!(report.decrypted_files, 0);
+        assert_eq!(report.failed_files.len(), 1);
+        assert_eq!(std::fs::read(dest.path().join("a.txt")).unwrap(), b"previous");
+        assert!(!dest.path().join("a.txt.tmp").exists());
+    }
+
+    #[test]
+    fn trailing_data_is_rejected() {
+        let backup = TempDir::new("crypto-backup");
+        let dest = TempDir::new("crypto-dest");
+        let mut encryption = BackupEncryption::open(backup.path(), &passphrase()).unwrap();
+        let path = write_encrypted(&mut encryption, backup.path(), "a.txt.enc", b"content");
+        encryption.save(backup.path()).unwrap();
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"extra").unwrap();
+        drop(file);
+
+        let report = decrypt_backup(backup.path(), &passphrase(), dest.path()).unwrap();
+        assert_eq!(report.failed_files.len(), 1);
+    }
+
+    #[test]
+    fn manifest_paths_outside_destination_are_rejected() {
+        let dest = Path::new("/restore");
+        assert!(decrypted_path(dest, "../evil.enc").is_err());
+        assert!(decrypted_path(dest, "a//b.enc").is_err());
+        assert_eq!(decrypted_path(dest, "a/b.txt.enc").unwrap(), dest.join("a").join("b.txt"));
+    }
+}
@@ -0,0 +1,255 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::backup_crypto::{self, ENCRYPTED_EXTENSION, MANIFEST_FILE_NAME};
+use crate::ssh_client::PART_FILE_EXTENSION;
+
+/// 2つのバックアップフォルダの差分（パスはフォルダからの相対パス、区切りは "/"）
+#[derive(Debug, Default, Serialize)]
+pub struct BackupDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+    pub unchanged_count: usize,
+    /// 内容（SHA-256）まで比較したファイル数
+    pub hash_checked_count: usize,
+    /// ローカルのバックアップが暗号化されている（`.enc` を除いた名前で比較し、内容は比較しない）
+    pub encrypted: bool,
+}
+
+/// 比較用のファイル情報
+pub struct FileInfo {
+    pub size: u64,
+    pub mtime: Option<u64>,
+}
+
+/// 2つのローカルのバックアップフォルダを比較する
+///
+/// `path_a` を古いバックアップ、`path_b` を新しいバックアップとして追加・削除・変更を返す。
+/// 変更はサイズと更新日時で判定し、`compare_content` が有効な場合はサイズが同じファイルを
+/// SHA-256 で比較する（更新日時だけが異なるファイルは変更なしとみなす）
+pub fn diff_backups(path_a: &Path, path_b: &Path, compare_content: bool) -> Result<BackupDiff> {
+    let mut files_a = BTreeMap::new();
+    collect_files(path_a, path_a, 0, &mut files_a)?;
+
+    let mut files_b = BTreeMap::new();
+    collect_files(path_b, path_b, 0, &mut files_b)?;
+
+    let mut diff = BackupDiff::default();
+
+    for (relative, info_b) in &files_b {
+        let Some(info_a) = files_a.get(relative) else {
+            diff.added.push(relative.clone());
+            continue;
+        };
+
+        let modified = if info_a.size != info_b.size {
+            true
+        } else if compare_content {
+            file_hash(&path_a.join(relative))? != file_hash(&path_b.join(relative))?
+        } else {
+            info_a.mtime != info_b.mtime
+        };
+
+        if modified {
+            diff.modified.push(relative.clone());
+        } else {
+            diff.unchanged_count += 1;
+        }
+    }
+
+    diff.removed = files_a
+        .keys()
+        .filter(|relative| !files_b.contains_key(*relative))
+        .cloned()
+        .collect();
+
+    Ok(diff)
+}
+
+/// ローカルのバックアップをリモートのファイル一覧と比較する（内容は比較しない）
+///
+/// リモートを新しい側として、リモートにのみあるファイルを `added`、ローカルにのみあるファイルを
+/// `removed`、サイズか更新日時が異なるファイルを `modified` に入れる。
+/// 転送対象外の隠しファイルと、転送途中の `.kyosho-part` はローカル側でも無視する。
+/// 暗号化したバックアップ（マニフェストがある）では `.enc` を除いた名前で対応付け、
+/// 暗号化後のサイズで比較する
+pub fn diff_local_with_remote(local_root: &Path, remote_files: &BTreeMap<String, FileInfo>) -> Result<BackupDiff> {
+    let mut local_files = BTreeMap::new();
+    collect_files(local_root, local_root, 0, &mut local_files)?;
+    local_files.retain(|relative, _| {
+        !relative.split('/').any(|name| name.starts_with('.')) && !relative.ends_with(PART_FILE_EXTENSION)
+    });
+
+    let mut diff = BackupDiff {
+        encrypted: local_root.join(MANIFEST_FILE_NAME).is_file(),
+        ..Default::default()
+    };
+    if diff.encrypted {
+        local_files = local_files
+            .into_iter()
+            .map(|(relative, info)| match relative.strip_suffix(ENCRYPTED_EXTENSION) {
+                Some(logical) => (logical.to_string(), info),
+                None => (relative, info),
+            })
+            .collect();
+    }
+
+    for (relative, remote) in remote_files {
+        let Some(local) = local_files.get(relative) else {
+            diff.added.push(relative.clone());
+            continue;
+        };
+
+        let remote_size = if diff.encrypted { backup_crypto::encrypted_size(remote.size) } else { remote.size };
+        if local.size != remote_size || local.mtime != remote.mtime {
+            diff.modified.push(relative.clone());
+        } else {
+            diff.unchanged_count += 1;
+        }
+    }
+
+    diff.removed = local_files
+        .keys()
+        .filter(|relative| !remote_files.contains_key(*relative))
+        .cloned()
+        .collect();
+
+    Ok(diff)
+}
+
+/// フォルダ配下のファイルを再帰的に収集（キーはルートからの相対パス）
+fn collect_files(root: &Path, dir: &Path, depth: usize, files: &mut BTreeMap<String, FileInfo>) -> Result<()> {
+    // 深すぎる再帰を防ぐ（無限ループ対策）
+    if depth > 50 {
+        return Err(anyhow::anyhow!("ディレクトリの階層が深すぎます: {}", dir.display()));
+    }
+
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("ディレクトリの読み取りに失敗: {:?}", dir))?;
+
+    for entry in entries {
+        let entry = entry.with_context(|| format!("ディレクトリの読み取りに失敗: {:?}", dir))?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            collect_files(root, &path, depth + 1, files)?;
+        } else if file_type.is_file() {
+            let metadata = entry.metadata()
+                .with_context(|| format!("ファイル情報の取得に失敗: {:?}", path))?;
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs());
+
+            let relative = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            files.insert(relative, FileInfo { size: metadata.len(), mtime });
+        }
+    }
+
+    Ok(())
+}
+
+/// ファイル内容のSHA-256を16進文字列で取得
+pub fn file_hash_hex(path: &Path) -> Result<String> {
+    Ok(file_hash(path)?.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// ファイル内容のSHA-256を計算
+fn file_hash(path: &Path) -> Result<Vec<u8>> {
+    let mut file = std::fs::File::open(path)
+        .with_context(|| format!("ファイルのオープンに失敗: {:?}", path))?;
+
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("ファイルの読み取りに失敗: {:?}", path))?;
+
+    Ok(hasher.finalize().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn remote(files: &[(&str, u64, u64)]) -> BTreeMap<String, FileInfo> {
+        files
+            .iter()
+            .map(|(relative, size, mtime)| (relative.to_string(), FileInfo { size: *size, mtime: Some(*mtime) }))
+            .collect()
+    }
+
+    fn write_with_mtime(dir: &TempDir, relative: &str, size: usize, mtime: u64) {
+        let path = dir.write(relative, &vec![b'x'; size]);
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(mtime))
+            .unwrap();
+    }
+
+    #[test]
+    fn ignores_partial_downloads_in_the_local_backup() {
+        let local = TempDir::new("diff-partial");
+        write_with_mtime(&local, "index.html", 10, 1000);
+        write_with_mtime(&local, "large.bin.kyosho-part", 5, 1000);
+
+        let diff = diff_local_with_remote(
+            local.path(),
+            &remote(&[("index.html", 10, 1000), ("large.bin", 20, 1000)]),
+        )
+        .unwrap();
+
+        assert!(!diff.encrypted);
+        assert_eq!(diff.added, ["large.bin"]);
+        assert!(diff.removed.is_empty());
+        assert!(diff.modified.is_empty());
+        assert_eq!(diff.unchanged_count, 1);
+    }
+
+    #[test]
+    fn maps_encrypted_files_to_their_remote_names() {
+        let local = TempDir::new("diff-encrypted");
+        local.write(MANIFEST_FILE_NAME, b"{}");
+        write_with_mtime(&local, "index.html.enc", backup_crypto::encrypted_size(10) as usize, 1000);
+        write_with_mtime(&local, "dir/style.css.enc", backup_crypto::encrypted_size(30) as usize, 1000);
+        write_with_mtime(&local, "old.txt.enc", backup_crypto::encrypted_size(1) as usize, 1000);
+
+        let diff = diff_local_with_remote(
+            local.path(),
+            &remote(&[("index.html", 10, 1000), ("dir/style.css", 31, 1000), ("new.txt", 1, 1000)]),
+        )
+        .unwrap();
+
+        assert!(diff.encrypted);
+        assert_eq!(diff.added, ["new.txt"]);
+        assert_eq!(diff.removed, ["old.txt"]);
+        assert_eq!(diff.modified, ["dir/style.css"]);
+        assert_eq!(diff.unchanged_count, 1);
+    }
+
+    #[test]
+    fn keeps_enc_names_when_the_backup_is_not_encrypted() {
+        let local = TempDir::new("diff-plain-enc");
+        write_with_mtime(&local, "data.enc", 4, 1000);
+
+        let diff = diff_local_with_remote(local.path(), &remote(&[("data.enc", 4, 1000)])).unwrap();
+
+        assert!(!diff.encrypted);
+        assert_eq!(diff.unchanged_count, 1);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+    }
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// バックアップ処理で発生する分類済みエラー
+///
+/// `anyhow::Error` に包んで返し、`SshClient::classify_error` で
+/// ダウンキャストしてユーザー向けメッセージに変換する
+#[derive(Debug, Error)]
+pub enum BackupError {
+    /// ローカルの空き容量が不足している
+    #[error("保存先の空き容量が不足しています（必要: {required} バイト / 空き: {available} バイト）")]
+    DiskSpace { required: u64, available: u64 },
+
+    /// バックアップ全体が制限時間内に完了しなかった
+    #[error("バックアップ処理が{limit_seconds}秒でタイムアウトしました")]
+    Timeout { limit_seconds: u64 },
+
+    /// 1ファイルの転送が制限時間内に完了しなかった
+    #[error("ファイル転送がタイムアウトしました（{}秒）: {}（{} バイト）", .0.limit_seconds, .0.path, .0.size)]
+    FileTimeout(TimedOutFile),
+
+    /// ローカルの保存先が想定と異なる種類（ファイル・ディレクトリ）で存在する
+    #[error("{0}")]
+    FileSystem(String),
+
+    /// 踏み台サーバーでの接続・ホスト鍵の確認・認証・接続先への転送の失敗
+    #[error("踏み台サーバー（{jump_host}）で{stage}に失敗しました（接続先: {target}）")]
+    JumpHost {
+        jump_host: String,
+        target: String,
+        stage: JumpHostStage,
+    },
+}
+
+/// 接続テスト・ドメイン探索・ディレクトリ探索がユーザーの操作で中断された
+///
+/// 再試行やエラーの分類をせずにそのまま返すため、メッセージではなく型で判定する
+#[derive(Debug, Error)]
+#[error("🚫 キャンセルされました")]
+pub struct DiscoveryCancelled;
+
+impl DiscoveryCancelled {
+    /// エラー（`context` で包まれたものを含む）が中断によるものか
+    pub fn is(error: &anyhow::Error) -> bool {
+        error.downcast_ref::<DiscoveryCancelled>().is_some()
+            || error.chain().any(|cause| cause.is::<DiscoveryCancelled>())
+    }
+}
+
+/// 踏み台サーバー経由の接続で失敗した段階
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JumpHostStage {
+    /// 踏み台サーバーへのTCP接続・ハンドシェイク
+    Connect,
+    /// 踏み台サーバーのホスト鍵の確認
+    HostKey,
+    /// 踏み台サーバーでの認証
+    Authentication,
+    /// 踏み台サーバーから接続先への転送（direct-tcpip）
+    Forward,
+}
+
+impl std::fmt::Display for JumpHostStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            JumpHostStage::Connect => "接続",
+            JumpHostStage::HostKey => "ホスト鍵の確認",
+            JumpHostStage::Authentication => "認証",
+            JumpHostStage::Forward => "接続先への転送",
+        })
+    }
+}
+
+/// 転送がタイムアウトしたファイル（問題のあるファイルの特定とタイムアウトの調整用）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimedOutFile {
+    pub path: String,
+    pub size: u64,
+    /// 適用したタイムアウト（秒）
+    pub limit_seconds: u64,
+}
+
+/// `SshClient::classify_error` による分類（履歴で失敗原因を集計するために保存する）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BackupErrorKind {
+    Network,
+    Authentication,
+    Permission,
+    DiskSpace,
+    Timeout,
+    FileSystem,
+    Other,
+}
+
+/// ユーザー向けメッセージに変換済みのエラー
+///
+/// 表示はメッセージのみで、呼び出し側はダウンキャストして分類を取り出せる
+#[derive(Debug, Clone, Error)]
+#[error("{message}")]
+pub struct ClassifiedError {
+    pub kind: BackupErrorKind,
+    pub message: String,
+    /// 1ファイルの転送のタイムアウトで失敗した場合のファイル
+    pub timed_out_file: Option<TimedOutFile>,
+}
+
+impl ClassifiedError {
+    pub fn new(kind: BackupErrorKind, message: String) -> Self {
+        Self { kind, message, timed_out_file: None }
+    }
+
+    /// エラーの原因をたどって分類を取り出す（未分類ならNone）
+    pub fn kind_of(error: &anyhow::Error) -> Option<BackupErrorKind> {
+        error.chain().find_map(|e| e.downcast_ref::<ClassifiedError>()).map(|classified| classified.kind)
+    }
+
+    /// エラーの原因をたどって、転送がタイムアウトしたファイルを取り出す
+    pub fn timed_out_file_of(error: &anyhow::Error) -> Option<TimedOutFile> {
+        error.chain()
+            .find_map(|e| e.downcast_ref::<ClassifiedError>())
+            .and_then(|classified| classified.timed_out_file.clone())
+    }
+}
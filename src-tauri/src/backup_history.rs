@@ -1,8 +1,41 @@
 use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::Sha256;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::backup_error::{BackupErrorKind, TimedOutFile};
+use crate::config_manager;
+use crate::smoke_test::SmokeTestResult;
+use crate::data_dir;
+use crate::ssh_client::{BackupOptions, PhaseTimings};
+
+mod repair;
+mod signature;
+mod statistics;
+
+pub use repair::HistoryRepairReport;
+pub use statistics::{BackupStatistics, TargetStatistics};
+
+/// 履歴ファイルの署名検証に失敗した（破損または改ざん）
+///
+/// 既定値で上書きせず、元のファイルを退避してから返す
+#[derive(Debug, Error)]
+pub enum HistoryIntegrityError {
+    #[error("バックアップ履歴の署名が一致しません。履歴ファイルが破損または改ざんされた可能性があります（退避先: {})", .quarantined_path.display())]
+    SignatureMismatch { quarantined_path: PathBuf },
+
+    #[error("バックアップ履歴の署名ファイルがありません。履歴ファイルが改ざんされた可能性があります（退避先: {})", .quarantined_path.display())]
+    MissingSignature { quarantined_path: PathBuf },
+
+    /// 修復時に署名が一致しなかった（退避せず、ユーザーが内容を確認して再署名するまで待つ）
+    #[error("バックアップ履歴が署名と一致しません。手動で編集した場合は内容を確認したうえで、再署名して修復してください: {}", .history_path.display())]
+    UnsignedChanges { history_path: PathBuf },
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BackupHistoryEntry {
@@ -11,74 +44,189 @@ pub struct BackupHistoryEntry {
     pub remote_path: String,
     pub local_path: String,
     pub transferred_files: usize,
+    #[serde(default)]
+    pub transferred_bytes: u64,
     pub elapsed_seconds: u64,
     pub status: BackupStatus,
     pub message: String,
     pub ssh_host: String,
     pub ssh_user: String,
+    /// 実行したバックアッププロファイルの名前（プロファイルを使わない実行・記録前の履歴ではNone）
+    #[serde(default)]
+    pub profile_name: Option<String>,
+    /// バックアップ後に作成したアーカイブのパス
+    #[serde(default)]
+    pub archive_path: Option<String>,
+    /// フェーズ別の所要時間（記録前の履歴にはない）
+    #[serde(default)]
+    pub phase_timings: Option<PhaseTimings>,
+    /// 実行時に有効だったバックアップオプション（記録前の履歴にはない）
+    #[serde(default)]
+    pub options: Option<BackupOptions>,
+    /// 使用した秘密鍵のパス（再実行用。記録前の履歴にはない）
+    #[serde(default)]
+    pub key_path: Option<String>,
+    /// 失敗の原因の分類（成功時・分類できなかった場合・記録前の履歴ではNone）
+    #[serde(default)]
+    pub error_kind: Option<BackupErrorKind>,
+    /// 転送がタイムアウトして失敗したファイルとそのサイズ（記録前の履歴にはない）
+    #[serde(default)]
+    pub timed_out_file: Option<TimedOutFile>,
+    /// バックアップ・復元後に行ったサイトの表示確認（記録前の履歴にはない）
+    #[serde(default)]
+    pub smoke_tests: Vec<SmokeTestResult>,
+    /// 転送に失敗してスキップしたファイル数（`continue_on_error` 有効時。記録前の履歴では0）
+    #[serde(default)]
+    pub failed_files: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub enum BackupStatus {
     Success,
     Failed,
     Cancelled,
+    /// 完了したが、転送に失敗したファイルがある（`continue_on_error` 有効時）
+    PartialSuccess,
+}
+
+/// 履歴の絞り込み条件（指定したフィールドすべてに一致するエントリが対象）
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryQuery {
+    pub ssh_host: Option<String>,
+    pub profile_name: Option<String>,
+    pub status: Option<BackupStatus>,
+    pub start_timestamp: Option<u64>,
+    pub end_timestamp: Option<u64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl HistoryQuery {
+    /// 条件が1つも指定されていないか
+    pub fn is_empty(&self) -> bool {
+        self.ssh_host.is_none()
+            && self.profile_name.is_none()
+            && self.status.is_none()
+            && self.start_timestamp.is_none()
+            && self.end_timestamp.is_none()
+    }
+
+    /// エントリが条件に一致するか
+    pub fn matches(&self, entry: &BackupHistoryEntry) -> bool {
+        self.ssh_host.as_ref().is_none_or(|host| &entry.ssh_host == host)
+            && self.profile_name.as_ref().is_none_or(|name| entry.profile_name.as_ref() == Some(name))
+            && self.status.as_ref().is_none_or(|status| &entry.status == status)
+            && self.start_timestamp.is_none_or(|start| entry.timestamp >= start)
+            && self.end_timestamp.is_none_or(|end| entry.timestamp <= end)
+    }
+}
+
+/// 履歴データ
+///
+/// 件数などの統計は保存せず、必要なときにエントリから求める（保存した件数がエントリとずれないように）
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct BackupHistory {
     pub entries: Vec<BackupHistoryEntry>,
     pub last_updated: u64,
-    pub total_backups: usize,
-    pub successful_backups: usize,
-    pub failed_backups: usize,
+    /// 以前の形式で保存されていた件数（読み込み時の整合性の確認にのみ使い、保存しない）
+    #[serde(default, rename = "total_backups", skip_serializing)]
+    stored_total_backups: Option<usize>,
+    #[serde(default, rename = "successful_backups", skip_serializing)]
+    stored_successful_backups: Option<usize>,
+    #[serde(default, rename = "failed_backups", skip_serializing)]
+    stored_failed_backups: Option<usize>,
 }
 
-impl Default for BackupHistory {
-    fn default() -> Self {
-        Self {
-            entries: Vec::new(),
-            last_updated: 0,
-            total_backups: 0,
-            successful_backups: 0,
-            failed_backups: 0,
-        }
+/// エントリから求めた件数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HistoryCounts {
+    pub total: usize,
+    pub successful: usize,
+    pub failed: usize,
+    /// 一部のファイルの転送に失敗して完了した件数
+    pub partial: usize,
+}
+
+impl BackupHistory {
+    /// エントリの件数（総数・成功・失敗・一部失敗）
+    pub fn counts(&self) -> HistoryCounts {
+        self.entries.iter().fold(
+            HistoryCounts { total: self.entries.len(), ..Default::default() },
+            |mut counts, entry| {
+                match entry.status {
+                    BackupStatus::Success => counts.successful += 1,
+                    BackupStatus::Failed => counts.failed += 1,
+                    BackupStatus::PartialSuccess => counts.partial += 1,
+                    BackupStatus::Cancelled => {}
+                }
+                counts
+            },
+        )
+    }
+
+    /// 以前の形式で保存されていた件数がエントリと一致するか（件数が保存されていなければ一致とみなす）
+    fn stored_counts_match(&self) -> bool {
+        let counts = self.counts();
+        [
+            (self.stored_total_backups, counts.total),
+            (self.stored_successful_backups, counts.successful),
+            (self.stored_failed_backups, counts.failed),
+        ]
+        .iter()
+        .all(|(stored, actual)| stored.is_none_or(|stored| stored == *actual))
     }
 }
 
 pub struct BackupHistoryManager {
     history_path: PathBuf,
+    /// 履歴ファイルの署名（HMAC-SHA256、Base64）の保存先
+    signature_path: PathBuf,
+    /// 署名用の鍵の保存先
+    key_path: PathBuf,
+    /// 署名用の鍵（設定の暗号化キーと同じ方法で生成・保存）
+    signing_key: [u8; 32],
 }
 
 impl BackupHistoryManager {
     pub fn new() -> Result<Self> {
-        let config_dir = dirs::config_dir()
-            .ok_or_else(|| anyhow!("設定ディレクトリの取得に失敗しました"))?
-            .join("kyosho-backup");
+        // 設定ディレクトリを取得（存在しない場合は作成）
+        let config_dir = data_dir::data_dir()?;
+
+        let history_path = config_dir.join("backup_history.json");
+        let signature_path = config_dir.join("backup_history.json.sig");
+
+        // 署名用の鍵を初めて作成する場合、既存の履歴は署名導入前のものとして署名する
+        let key_path = config_dir.join("history_key.dat");
+        let is_new_key = !key_path.exists();
+        let signing_key = config_manager::load_or_create_key(&key_path)?;
+
+        let manager = Self {
+            history_path,
+            signature_path,
+            key_path,
+            signing_key,
+        };
 
-        // 設定ディレクトリを作成
-        fs::create_dir_all(&config_dir)?;
+        if is_new_key && manager.history_path.exists() && !manager.signature_path.exists() {
+            let json = fs::read(&manager.history_path)
+                .map_err(|e| anyhow!("履歴データの読み込みに失敗しました: {}", e))?;
+            manager.write_signature(&json)?;
+        }
 
-        Ok(Self {
-            history_path: config_dir.join("backup_history.json"),
-        })
+        Ok(manager)
     }
 
     /// バックアップエントリを追加
-    pub fn add_backup_entry(&self, entry: BackupHistoryEntry) -> Result<()> {
+    pub fn add_backup_entry(&self, mut entry: BackupHistoryEntry) -> Result<()> {
         let mut history = self.load_history()?;
 
-        // 統計を更新
-        history.entries.push(entry.clone());
-        history.total_backups += 1;
-        history.last_updated = self.current_timestamp();
-
-        match entry.status {
-            BackupStatus::Success => history.successful_backups += 1,
-            BackupStatus::Failed => history.failed_backups += 1,
-            _ => {}
+        // 暗号化のパスフレーズは保存しない（書き出されないが、読み込み済みの履歴にも残さない）
+        if let Some(options) = entry.options.as_mut() {
+            options.encryption_passphrase = None;
         }
 
+        history.entries.push(entry);
+        history.last_updated = self.current_timestamp();
+
         // 最新100件のみ保持（メモリとディスク使用量を制限）
         if history.entries.len() > 100 {
             history.entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
@@ -121,53 +269,43 @@ impl BackupHistoryManager {
         Ok(sorted_entries)
     }
 
-    /// 統計情報を取得
-    pub fn get_statistics(&self) -> Result<BackupStatistics> {
+    /// 指定したリモートパスの最後に成功したバックアップの開始時刻を取得（未実行ならNone）
+    pub fn last_successful_backup_timestamp(&self, remote_path: &str) -> Result<Option<u64>> {
         let history = self.load_history()?;
 
-        let total_files_transferred: usize = history.entries.iter()
-            .map(|entry| entry.transferred_files)
-            .sum();
+        Ok(history
+            .entries
+            .iter()
+            .filter(|entry| entry.status == BackupStatus::Success && entry.remote_path == remote_path)
+            .map(|entry| entry.timestamp)
+            .max())
+    }
 
-        let total_time_spent: u64 = history.entries.iter()
-            .map(|entry| entry.elapsed_seconds)
-            .sum();
+    /// IDで履歴エントリを取得
+    pub fn get_entry(&self, entry_id: &str) -> Result<Option<BackupHistoryEntry>> {
+        let history = self.load_history()?;
+        Ok(history.entries.into_iter().find(|entry| entry.id == entry_id))
+    }
 
-        let avg_files_per_backup = if history.total_backups > 0 {
-            total_files_transferred as f64 / history.total_backups as f64
-        } else {
-            0.0
-        };
+    /// 最新の履歴エントリを取得（状態を問わない）
+    pub fn latest_entry(&self) -> Result<Option<BackupHistoryEntry>> {
+        let history = self.load_history()?;
+        Ok(history.entries.into_iter().max_by_key(|entry| entry.timestamp))
+    }
 
-        let avg_time_per_backup = if history.total_backups > 0 {
-            total_time_spent as f64 / history.total_backups as f64
-        } else {
-            0.0
-        };
+    /// 履歴ファイルのパス
+    pub fn history_path(&self) -> &Path {
+        &self.history_path
+    }
 
-        let success_rate = if history.total_backups > 0 {
-            (history.successful_backups as f64 / history.total_backups as f64) * 100.0
-        } else {
-            0.0
-        };
+    /// 履歴ファイルの署名のパス
+    pub fn signature_path(&self) -> &Path {
+        &self.signature_path
+    }
 
-        // 最後のバックアップ日時
-        let last_backup_timestamp = history.entries.iter()
-            .map(|entry| entry.timestamp)
-            .max()
-            .unwrap_or(0);
-
-        Ok(BackupStatistics {
-            total_backups: history.total_backups,
-            successful_backups: history.successful_backups,
-            failed_backups: history.failed_backups,
-            success_rate,
-            total_files_transferred,
-            total_time_spent,
-            avg_files_per_backup,
-            avg_time_per_backup,
-            last_backup_timestamp,
-        })
+    /// 署名用の鍵のパス
+    pub fn key_path(&self) -> &Path {
+        &self.key_path
     }
 
     /// 履歴を削除
@@ -177,6 +315,39 @@ impl BackupHistoryManager {
         Ok(())
     }
 
+    /// サイトの表示確認の結果をエントリに記録（IDで指定。見つからない場合は false）
+    pub fn add_smoke_test(&self, entry_id: &str, result: SmokeTestResult) -> Result<bool> {
+        let mut history = self.load_history()?;
+
+        let Some(entry) = history.entries.iter_mut().find(|entry| entry.id == entry_id) else {
+            return Ok(false);
+        };
+        entry.smoke_tests.push(result);
+
+        history.last_updated = self.current_timestamp();
+        self.save_history(&history)?;
+        Ok(true)
+    }
+
+    /// プロファイル名の変更に合わせて、そのプロファイルで実行したエントリの記録を書き換え、件数を返す
+    pub fn rename_profile(&self, old_name: &str, new_name: &str) -> Result<usize> {
+        let mut history = self.load_history()?;
+
+        let mut renamed = 0;
+        for entry in &mut history.entries {
+            if entry.profile_name.as_deref() == Some(old_name) {
+                entry.profile_name = Some(new_name.to_string());
+                renamed += 1;
+            }
+        }
+
+        if renamed > 0 {
+            history.last_updated = self.current_timestamp();
+            self.save_history(&history)?;
+        }
+        Ok(renamed)
+    }
+
     /// バックアップエントリを削除（IDで指定）
     pub fn delete_backup_entry(&self, entry_id: &str) -> Result<bool> {
         let mut history = self.load_history()?;
@@ -185,8 +356,7 @@ impl BackupHistoryManager {
         history.entries.retain(|entry| entry.id != entry_id);
 
         if history.entries.len() < initial_len {
-            // 統計を再計算
-            self.recalculate_statistics(&mut history);
+            history.last_updated = self.current_timestamp();
             self.save_history(&history)?;
             Ok(true)
         } else {
@@ -194,6 +364,29 @@ impl BackupHistoryManager {
         }
     }
 
+    /// 条件に一致するエントリをまとめて削除し、削除件数を返す
+    ///
+    /// 誤って全件削除しないよう、条件が1つも指定されていない場合はエラーにする
+    /// （全件削除は `clear_history` を使用）
+    pub fn delete_entries_matching(&self, query: &HistoryQuery) -> Result<usize> {
+        if query.is_empty() {
+            return Err(anyhow!("削除条件が指定されていません。全件削除する場合は履歴のクリアを使用してください"));
+        }
+
+        let mut history = self.load_history()?;
+        let initial_len = history.entries.len();
+
+        history.entries.retain(|entry| !query.matches(entry));
+
+        let deleted = initial_len - history.entries.len();
+        if deleted > 0 {
+            history.last_updated = self.current_timestamp();
+            self.save_history(&history)?;
+        }
+
+        Ok(deleted)
+    }
+
     /// 現在のタイムスタンプを取得（Unix秒）
     fn current_timestamp(&self) -> u64 {
         std::time::SystemTime::now()
@@ -202,56 +395,57 @@ impl BackupHistoryManager {
             .as_secs()
     }
 
-    /// 履歴を保存
+    /// 履歴を保存（署名も更新）
+    ///
+    /// 書き込み途中で中断しても履歴ファイルが壊れないよう、一時ファイルに書いてから置き換える。
+    /// 新しい署名は履歴を置き換える前に `.sig.new` に書いておき、履歴と署名の置き換えの間で
+    /// 中断した場合は読み込み時にそちらで検証する（改ざんと誤認して退避しないため）
     fn save_history(&self, history: &BackupHistory) -> Result<()> {
         let json = serde_json::to_string_pretty(history)
             .map_err(|e| anyhow!("履歴データのシリアライズに失敗しました: {}", e))?;
 
-        fs::write(&self.history_path, json)
+        let temp_path = self.history_path.with_extension("json.tmp");
+        fs::write(&temp_path, &json)
             .map_err(|e| anyhow!("履歴データの保存に失敗しました: {}", e))?;
 
-        Ok(())
+        let pending_path = self.pending_signature_path();
+        fs::write(&pending_path, self.encoded_signature(json.as_bytes()))
+            .map_err(|e| anyhow!("履歴データの署名の保存に失敗しました: {}", e))?;
+
+        fs::rename(&temp_path, &self.history_path)
+            .map_err(|e| anyhow!("履歴データの保存に失敗しました: {}", e))?;
+        fs::rename(&pending_path, &self.signature_path)
+            .map_err(|e| anyhow!("履歴データの署名の保存に失敗しました: {}", e))
     }
 
     /// 履歴を読み込み
+    ///
+    /// 署名が一致しない場合は既定値に戻さず、履歴と署名を退避して `HistoryIntegrityError` を返す
     fn load_history(&self) -> Result<BackupHistory> {
         if !self.history_path.exists() {
             return Ok(BackupHistory::default());
         }
 
-        let json = fs::read_to_string(&self.history_path)
+        let json = fs::read(&self.history_path)
             .map_err(|e| anyhow!("履歴データの読み込みに失敗しました: {}", e))?;
 
-        let history = serde_json::from_str(&json)
+        self.verify_signature(&json)?;
+        self.parse_history(&json)
+    }
+
+    /// 履歴データをパース
+    fn parse_history(&self, json: &[u8]) -> Result<BackupHistory> {
+        let history: BackupHistory = serde_json::from_slice(json)
             .map_err(|e| anyhow!("履歴データのパースに失敗しました: {}", e))?;
 
-        Ok(history)
-    }
+        // 以前の形式の件数がずれていても、統計はエントリから求めるため記録だけ残す
+        if !history.stored_counts_match() {
+            tracing::warn!("保存されていた履歴の件数がエントリと一致しません（統計はエントリから求めます）");
+        }
 
-    /// 統計を再計算
-    fn recalculate_statistics(&self, history: &mut BackupHistory) {
-        history.total_backups = history.entries.len();
-        history.successful_backups = history.entries.iter()
-            .filter(|entry| matches!(entry.status, BackupStatus::Success))
-            .count();
-        history.failed_backups = history.entries.iter()
-            .filter(|entry| matches!(entry.status, BackupStatus::Failed))
-            .count();
-        history.last_updated = self.current_timestamp();
+        Ok(history)
     }
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct BackupStatistics {
-    pub total_backups: usize,
-    pub successful_backups: usize,
-    pub failed_backups: usize,
-    pub success_rate: f64,
-    pub total_files_transferred: usize,
-    pub total_time_spent: u64,
-    pub avg_files_per_backup: f64,
-    pub avg_time_per_backup: f64,
-    pub last_backup_timestamp: u64,
 }
 
 /// ユニークIDを生成（バックアップエントリ用）
@@ -274,4 +468,135 @@ pub fn generate_backup_id() -> String {
     };
 
     format!("backup_{}_{}", timestamp, random_suffix)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn manager_in(dir: &TempDir) -> BackupHistoryManager {
+        BackupHistoryManager {
+            history_path: dir.path().join("backup_history.json"),
+            signature_path: dir.path().join("backup_history.json.sig"),
+            key_path: dir.path().join("history_key.dat"),
+            signing_key: [1u8; 32],
+        }
+    }
+
+    fn entry(id: &str, timestamp: u64, status: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "timestamp": timestamp,
+            "remote_path": "/home/user/public_html",
+            "local_path": "/backup/public_html",
+            "transferred_files": 10,
+            "elapsed_seconds": 5,
+            "status": status,
+            "message": "",
+            "ssh_host": "sv1.example.com",
+            "ssh_user": "user"
+        })
+    }
+
+    /// 手動で編集した履歴（署名なし）: 重複したID・逆順・ずれた件数を含む
+    fn hand_edited_history() -> String {
+        serde_json::json!({
+            "entries": [
+                entry("b", 200, "Failed"),
+                entry("a", 100, "Success"),
+                entry("b", 300, "Success"),
+            ],
+            "last_updated": 300,
+            "total_backups": 5,
+            "successful_backups": 5,
+            "failed_backups": 0
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn added_entry_does_not_keep_the_encryption_passphrase() {
+        let dir = TempDir::new("history-passphrase");
+        let manager = manager_in(&dir);
+        let mut added: BackupHistoryEntry = serde_json::from_value(entry("a", 100, "Success")).unwrap();
+        added.options = Some(BackupOptions {
+            encrypt: true,
+            encryption_passphrase: Some(crate::backup_crypto::Passphrase::new("correct horse".to_string())),
+            ..BackupOptions::default()
+        });
+
+        manager.add_backup_entry(added).unwrap();
+
+        assert!(!fs::read_to_string(manager.history_path()).unwrap().contains("correct horse"));
+        let saved = manager.get_entry("a").unwrap().unwrap();
+        let options = saved.options.unwrap();
+        assert!(options.encrypt);
+        assert!(options.encryption_passphrase.is_none());
+    }
+
+    #[test]
+    fn repair_keeps_unsigned_history_until_the_user_confirms() {
+        let dir = TempDir::new("history-repair-unsigned");
+        let manager = manager_in(&dir);
+        let json = hand_edited_history();
+        fs::write(manager.history_path(), &json).unwrap();
+
+        let error = manager.repair_history(false).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<HistoryIntegrityError>(),
+            Some(HistoryIntegrityError::UnsignedChanges { .. })
+        ));
+        // 退避せず、そのまま残す
+        assert_eq!(fs::read_to_string(manager.history_path()).unwrap(), json);
+        assert!(!manager.signature_path().exists());
+    }
+
+    #[test]
+    fn repair_resigns_unsigned_history_after_confirmation() {
+        let dir = TempDir::new("history-repair-resign");
+        let manager = manager_in(&dir);
+        fs::write(manager.history_path(), hand_edited_history()).unwrap();
+
+        let report = manager.repair_history(true).unwrap();
+        assert!(report.resigned);
+        assert_eq!(report.entries_before, 3);
+        assert_eq!(report.entries_after, 2);
+        assert_eq!(report.duplicates_removed, 1);
+        assert!(report.reordered);
+        assert!(report.statistics_corrected);
+        assert_eq!(report.successful_backups, 2);
+        assert_eq!(report.failed_backups, 0);
+
+        // 再署名した履歴は通常の読み込みで検証できる
+        let history = manager.load_history().unwrap();
+        let ids: Vec<(&str, u64)> = history.entries.iter().map(|e| (e.id.as_str(), e.timestamp)).collect();
+        assert_eq!(ids, [("a", 100), ("b", 300)]);
+    }
+
+    #[test]
+    fn repair_of_signed_history_does_not_report_a_resign() {
+        let dir = TempDir::new("history-repair-signed");
+        let manager = manager_in(&dir);
+        let history: BackupHistory = serde_json::from_str(&hand_edited_history()).unwrap();
+        manager.save_history(&history).unwrap();
+
+        let report = manager.repair_history(false).unwrap();
+        assert!(!report.resigned);
+        assert_eq!(report.entries_after, 2);
+    }
+
+    #[test]
+    fn load_quarantines_history_with_a_mismatched_signature() {
+        let dir = TempDir::new("history-quarantine");
+        let manager = manager_in(&dir);
+        manager.save_history(&BackupHistory::default()).unwrap();
+        fs::write(manager.history_path(), hand_edited_history()).unwrap();
+
+        let error = manager.load_history().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<HistoryIntegrityError>(),
+            Some(HistoryIntegrityError::SignatureMismatch { .. })
+        ));
+        assert!(!manager.history_path().exists());
+    }
+}
@@ -0,0 +1,92 @@
+use super::*;
+
+/// 履歴の修復結果
+#[derive(Debug, Serialize)]
+pub struct HistoryRepairReport {
+    /// 修復前のエントリ数
+    pub entries_before: usize,
+    /// 修復後のエントリ数
+    pub entries_after: usize,
+    /// IDが重複していたため削除したエントリ数
+    pub duplicates_removed: usize,
+    /// タイムスタンプ順に並べ替えたか
+    pub reordered: bool,
+    /// 以前の形式で保存されていた件数がエントリと一致していなかったか
+    pub statistics_corrected: bool,
+    /// 署名が一致しなかった履歴をユーザーの確認のうえ再署名したか
+    pub resigned: bool,
+    pub total_backups: usize,
+    pub successful_backups: usize,
+    pub failed_backups: usize,
+}
+
+impl BackupHistoryManager {
+    /// 履歴ファイルを整理・検証して書き直し、修正内容を返す
+    ///
+    /// 同じIDのエントリは最も新しいものだけを残し、タイムスタンプ順（古い順）に並べ替える。
+    /// 以前の形式で保存されていた件数は書き出さない。修正がなくても署名ごと書き直す。
+    ///
+    /// 署名が一致しない履歴は退避せず `HistoryIntegrityError::UnsignedChanges` を返す。
+    /// ユーザーが内容を確認して `resign` を指定した場合のみ、そのまま再署名して修復する
+    pub fn repair_history(&self, resign: bool) -> Result<HistoryRepairReport> {
+        let (mut history, resigned) = self.load_history_for_repair(resign)?;
+        let entries_before = history.entries.len();
+
+        let was_sorted = history.entries.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp);
+        history.entries.sort_by_key(|entry| entry.timestamp);
+
+        // 後ろ（新しい方）から見て、最初に出てきたIDだけを残す
+        let mut seen_ids = HashSet::new();
+        let mut deduped: Vec<BackupHistoryEntry> = history.entries
+            .drain(..)
+            .rev()
+            .filter(|entry| seen_ids.insert(entry.id.clone()))
+            .collect();
+        deduped.reverse();
+        history.entries = deduped;
+
+        let statistics_corrected = !history.stored_counts_match();
+        history.last_updated = self.current_timestamp();
+
+        self.save_history(&history)?;
+        let counts = history.counts();
+
+        let report = HistoryRepairReport {
+            entries_before,
+            entries_after: history.entries.len(),
+            duplicates_removed: entries_before - history.entries.len(),
+            reordered: !was_sorted,
+            statistics_corrected,
+            resigned,
+            total_backups: counts.total,
+            successful_backups: counts.successful,
+            failed_backups: counts.failed,
+        };
+        tracing::info!("履歴データを修復しました: {:?}", report);
+
+        Ok(report)
+    }
+
+    /// 修復のために履歴を読み込み、署名が一致せず再署名することになったかを合わせて返す
+    ///
+    /// `load_history` と違い、署名が一致しなくても退避しない
+    fn load_history_for_repair(&self, resign: bool) -> Result<(BackupHistory, bool)> {
+        if !self.history_path.exists() {
+            return Ok((BackupHistory::default(), false));
+        }
+
+        let json = fs::read(&self.history_path)
+            .map_err(|e| anyhow!("履歴データの読み込みに失敗しました: {}", e))?;
+
+        let signed = self.signature_matches(&self.signature_path, &json)
+            || self.signature_matches(&self.pending_signature_path(), &json);
+        if !signed {
+            if !resign {
+                return Err(HistoryIntegrityError::UnsignedChanges { history_path: self.history_path.clone() }.into());
+            }
+            tracing::warn!("署名が一致しない履歴をユーザーの確認のうえ再署名します: {}", self.history_path.display());
+        }
+
+        Ok((self.parse_history(&json)?, !signed))
+    }
+}
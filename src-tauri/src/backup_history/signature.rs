@@ -0,0 +1,85 @@
+use super::*;
+
+type HmacSha256 = Hmac<Sha256>;
+
+impl BackupHistoryManager {
+    /// 履歴データのHMACを計算
+    fn compute_signature(&self, data: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.signing_key)
+            .expect("HMACは任意の長さの鍵を受け付ける");
+        mac.update(data);
+        mac
+    }
+
+    /// 履歴データの署名（Base64）
+    pub(super) fn encoded_signature(&self, data: &[u8]) -> String {
+        general_purpose::STANDARD.encode(self.compute_signature(data).finalize().into_bytes())
+    }
+
+    /// 履歴データの署名を保存
+    pub(super) fn write_signature(&self, data: &[u8]) -> Result<()> {
+        fs::write(&self.signature_path, self.encoded_signature(data))
+            .map_err(|e| anyhow!("履歴データの署名の保存に失敗しました: {}", e))
+    }
+
+    /// 保存途中の新しい署名の置き場所
+    pub(super) fn pending_signature_path(&self) -> PathBuf {
+        self.signature_path.with_extension("sig.new")
+    }
+
+    /// 署名ファイルが履歴データと一致するか
+    pub(super) fn signature_matches(&self, path: &Path, data: &[u8]) -> bool {
+        fs::read_to_string(path).is_ok_and(|encoded| {
+            general_purpose::STANDARD
+                .decode(encoded.trim())
+                .is_ok_and(|signature| self.compute_signature(data).verify_slice(&signature).is_ok())
+        })
+    }
+
+    /// 履歴データの署名を検証し、一致しない場合は履歴を退避する
+    pub(super) fn verify_signature(&self, data: &[u8]) -> Result<()> {
+        let pending_path = self.pending_signature_path();
+
+        if self.signature_path.exists() && self.signature_matches(&self.signature_path, data) {
+            if pending_path.exists() {
+                // 履歴を置き換える前に中断した保存の残り（履歴は以前のまま）
+                let _ = fs::remove_file(&pending_path);
+            }
+            return Ok(());
+        }
+
+        // 履歴を置き換えた後、署名を置き換える前に中断した場合は保存途中の署名で検証して完了させる
+        if pending_path.exists() && self.signature_matches(&pending_path, data) {
+            fs::rename(&pending_path, &self.signature_path)
+                .map_err(|e| anyhow!("履歴データの署名の保存に失敗しました: {}", e))?;
+            tracing::info!("中断していた履歴の保存を完了しました");
+            return Ok(());
+        }
+
+        let missing = !self.signature_path.exists();
+        let quarantined_path = self.quarantine()?;
+        if missing {
+            return Err(HistoryIntegrityError::MissingSignature { quarantined_path }.into());
+        }
+        Err(HistoryIntegrityError::SignatureMismatch { quarantined_path }.into())
+    }
+
+    /// 検証に失敗した履歴と署名を退避し、退避先の履歴ファイルのパスを返す
+    ///
+    /// 退避後は履歴が空の状態から記録を再開する
+    fn quarantine(&self) -> Result<PathBuf> {
+        let suffix = format!("quarantined-{}", self.current_timestamp());
+        let quarantined_path = self.history_path.with_extension(format!("json.{}", suffix));
+
+        fs::rename(&self.history_path, &quarantined_path)
+            .map_err(|e| anyhow!("履歴データの退避に失敗しました: {}", e))?;
+        if self.signature_path.exists() {
+            fs::rename(&self.signature_path, self.signature_path.with_extension(format!("sig.{}", suffix)))
+                .map_err(|e| anyhow!("履歴データの署名の退避に失敗しました: {}", e))?;
+        }
+        let _ = fs::remove_file(self.pending_signature_path());
+
+        tracing::warn!("履歴データの署名検証に失敗したため退避しました: {}", quarantined_path.display());
+        Ok(quarantined_path)
+    }
+}
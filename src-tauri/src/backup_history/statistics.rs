@@ -0,0 +1,167 @@
+use super::*;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupStatistics {
+    pub total_backups: usize,
+    pub successful_backups: usize,
+    pub failed_backups: usize,
+    /// 一部のファイルの転送に失敗して完了したバックアップの数
+    #[serde(default)]
+    pub partial_backups: usize,
+    pub success_rate: f64,
+    pub total_files_transferred: usize,
+    pub total_bytes_transferred: u64,
+    pub total_time_spent: u64,
+    pub avg_files_per_backup: f64,
+    pub avg_time_per_backup: f64,
+    pub avg_throughput_mbps: f64,
+    pub last_backup_timestamp: u64,
+    /// 失敗の原因別の件数（保持している履歴の範囲）
+    pub failures_by_kind: HashMap<BackupErrorKind, usize>,
+}
+
+/// バックアップ先（ホストとリモートパス）ごとの統計情報
+#[derive(Debug, Serialize)]
+pub struct TargetStatistics {
+    pub ssh_host: String,
+    pub remote_path: String,
+    pub total_backups: usize,
+    pub successful_backups: usize,
+    pub failed_backups: usize,
+    pub partial_backups: usize,
+    pub success_rate: f64,
+    pub total_bytes_transferred: u64,
+    pub last_backup_timestamp: u64,
+    /// 最後のバックアップの結果
+    pub last_status: BackupStatus,
+    /// 最後に成功したバックアップの日時（成功したことがなければNone）
+    pub last_success_timestamp: Option<u64>,
+}
+
+impl BackupHistoryManager {
+    /// 統計情報を取得（保持している履歴のエントリから求める）
+    pub fn get_statistics(&self) -> Result<BackupStatistics> {
+        let history = self.load_history()?;
+        let counts = history.counts();
+
+        let total_files_transferred: usize = history.entries.iter()
+            .map(|entry| entry.transferred_files)
+            .sum();
+
+        let total_time_spent: u64 = history.entries.iter()
+            .map(|entry| entry.elapsed_seconds)
+            .sum();
+
+        let total_bytes_transferred: u64 = history.entries.iter()
+            .map(|entry| entry.transferred_bytes)
+            .sum();
+
+        let avg_files_per_backup = if counts.total > 0 {
+            total_files_transferred as f64 / counts.total as f64
+        } else {
+            0.0
+        };
+
+        let avg_time_per_backup = if counts.total > 0 {
+            total_time_spent as f64 / counts.total as f64
+        } else {
+            0.0
+        };
+
+        // 平均スループット（MB/s）
+        let avg_throughput_mbps = if total_time_spent > 0 {
+            total_bytes_transferred as f64 / total_time_spent as f64 / (1024.0 * 1024.0)
+        } else {
+            0.0
+        };
+
+        // 失敗の原因別の件数（分類のない失敗は「その他」に含める）
+        let mut failures_by_kind: HashMap<BackupErrorKind, usize> = HashMap::new();
+        for entry in history.entries.iter().filter(|entry| entry.status == BackupStatus::Failed) {
+            *failures_by_kind
+                .entry(entry.error_kind.unwrap_or(BackupErrorKind::Other))
+                .or_default() += 1;
+        }
+
+        let success_rate = if counts.total > 0 {
+            (counts.successful as f64 / counts.total as f64) * 100.0
+        } else {
+            0.0
+        };
+
+        // 最後のバックアップ日時
+        let last_backup_timestamp = history.entries.iter()
+            .map(|entry| entry.timestamp)
+            .max()
+            .unwrap_or(0);
+
+        Ok(BackupStatistics {
+            total_backups: counts.total,
+            successful_backups: counts.successful,
+            failed_backups: counts.failed,
+            partial_backups: counts.partial,
+            success_rate,
+            total_files_transferred,
+            total_bytes_transferred,
+            total_time_spent,
+            avg_files_per_backup,
+            avg_time_per_backup,
+            avg_throughput_mbps,
+            last_backup_timestamp,
+            failures_by_kind,
+        })
+    }
+
+    /// バックアップ先（ホストとリモートパス）ごとの統計情報を取得
+    ///
+    /// 保持している履歴のエントリを集計する。最後のバックアップが新しい順
+    pub fn get_statistics_grouped(&self) -> Result<Vec<TargetStatistics>> {
+        let history = self.load_history()?;
+
+        let mut groups: HashMap<(String, String), TargetStatistics> = HashMap::new();
+        for entry in &history.entries {
+            let stats = groups
+                .entry((entry.ssh_host.clone(), entry.remote_path.clone()))
+                .or_insert_with(|| TargetStatistics {
+                    ssh_host: entry.ssh_host.clone(),
+                    remote_path: entry.remote_path.clone(),
+                    total_backups: 0,
+                    successful_backups: 0,
+                    failed_backups: 0,
+                    partial_backups: 0,
+                    success_rate: 0.0,
+                    total_bytes_transferred: 0,
+                    last_backup_timestamp: 0,
+                    last_status: entry.status.clone(),
+                    last_success_timestamp: None,
+                });
+
+            stats.total_backups += 1;
+            stats.total_bytes_transferred += entry.transferred_bytes;
+            match entry.status {
+                BackupStatus::Success => {
+                    stats.successful_backups += 1;
+                    stats.last_success_timestamp = stats.last_success_timestamp.max(Some(entry.timestamp));
+                }
+                BackupStatus::Failed => stats.failed_backups += 1,
+                BackupStatus::PartialSuccess => stats.partial_backups += 1,
+                BackupStatus::Cancelled => {}
+            }
+            if entry.timestamp >= stats.last_backup_timestamp {
+                stats.last_backup_timestamp = entry.timestamp;
+                stats.last_status = entry.status.clone();
+            }
+        }
+
+        let mut grouped: Vec<TargetStatistics> = groups
+            .into_values()
+            .map(|mut stats| {
+                stats.success_rate = (stats.successful_backups as f64 / stats.total_backups as f64) * 100.0;
+                stats
+            })
+            .collect();
+        grouped.sort_by(|a, b| b.last_backup_timestamp.cmp(&a.last_backup_timestamp));
+
+        Ok(grouped)
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use std::time::Duration;
+use tauri_plugin_shell::process::CommandEvent;
+use tauri_plugin_shell::ShellExt;
+
+use crate::ssh_client::{BackupConfig, BackupControl};
+
+/// 履歴に残すフックの出力の上限（文字数、標準出力・標準エラー出力それぞれ）
+const HOOK_OUTPUT_MAX_CHARS: usize = 4000;
+
+/// フックの実行時間の上限の既定値（秒）
+pub const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 600;
+
+/// フックの実行中にキャンセルを確認する間隔
+const HOOK_CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// バックアップの前後に実行するフックの種類
+#[derive(Debug, Clone, Copy)]
+pub enum HookKind {
+    Pre,
+    Post,
+}
+
+impl HookKind {
+    fn label(self) -> &'static str {
+        match self {
+            HookKind::Pre => "実行前フック",
+            HookKind::Post => "実行後フック",
+        }
+    }
+}
+
+/// フックの実行結果
+#[derive(Debug)]
+pub struct HookOutput {
+    pub kind: HookKind,
+    /// 終了コード（シグナルで終了した場合はNone）
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl HookOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// 履歴のメッセージに追記する形式にまとめる
+    pub fn summary(&self) -> String {
+        let exit_code = self.exit_code.map_or_else(|| "不明".to_string(), |code| code.to_string());
+        let mut summary = format!("[{}] 終了コード: {}", self.kind.label(), exit_code);
+        for (label, output) in [("標準出力", &self.stdout), ("標準エラー出力", &self.stderr)] {
+            let output = output.trim();
+            if !output.is_empty() {
+                summary.push_str(&format!("\n{}:\n{}", label, truncate(output)));
+            }
+        }
+        summary
+    }
+}
+
+/// プロファイルのフックをシェルで実行する（完了まで待つ）
+///
+/// プロファイルの接続先・フォルダを環境変数で渡す。実行後フックにはバックアップの成否
+/// （`KYOSHO_BACKUP_STATUS` に `success` / `failed`）も渡す
+///
+/// プロファイルの `hook_timeout_secs`（未指定は [`DEFAULT_HOOK_TIMEOUT_SECS`]）を過ぎた場合と、
+/// `control` を渡していてバックアップがキャンセルされた場合は、フックが起動したプロセスごと停止してエラーを返す
+pub async fn run_hook(
+    app_handle: &tauri::AppHandle,
+    kind: HookKind,
+    command: &str,
+    profile: &BackupConfig,
+    local_folder: &str,
+    succeeded: Option<bool>,
+    control: Option<&BackupControl>,
+) -> Result<HookOutput> {
+    let (shell, shell_args) = if cfg!(windows) { ("cmd", ["/C"]) } else { ("sh", ["-c"]) };
+
+    let mut envs = vec![
+        ("KYOSHO_PROFILE_NAME", profile.name.clone()),
+        ("KYOSHO_SSH_HOST", profile.ssh.hostname.clone()),
+        ("KYOSHO_SSH_USER", profile.ssh.username.clone()),
+        ("KYOSHO_REMOTE_FOLDER", profile.remote_folder.clone()),
+        ("KYOSHO_LOCAL_FOLDER", local_folder.to_string()),
+    ];
+    if let Some(succeeded) = succeeded {
+        envs.push(("KYOSHO_BACKUP_STATUS", if succeeded { "success" } else { "failed" }.to_string()));
+    }
+
+    tracing::info!("{}を実行します: {}", kind.label(), profile.name);
+    let (mut events, child) = app_handle
+        .shell()
+        .command(shell)
+        .args(shell_args)
+        .arg(command)
+        .envs(envs)
+        .set_process_group(true)
+        .spawn()
+        .with_context(|| format!("{}の実行に失敗しました", kind.label()))?;
+
+    let timeout_secs = profile.hook_timeout_secs.unwrap_or(DEFAULT_HOOK_TIMEOUT_SECS);
+    let deadline = tokio::time::sleep(Duration::from_secs(timeout_secs));
+    tokio::pin!(deadline);
+    let mut cancel_poll = tokio::time::interval(HOOK_CANCEL_POLL_INTERVAL);
+
+    let mut output = HookOutput {
+        kind,
+        exit_code: None,
+        stdout: String::new(),
+        stderr: String::new(),
+    };
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(CommandEvent::Stdout(line)) => push_line(&mut output.stdout, &line),
+                Some(CommandEvent::Stderr(line)) => push_line(&mut output.stderr, &line),
+                Some(CommandEvent::Terminated(payload)) => output.exit_code = payload.code,
+                Some(_) => {}
+                None => break,
+            },
+            _ = &mut deadline => {
+                if let Err(e) = child.kill() {
+                    tracing::warn!("{}を停止できませんでした: {}", kind.label(), e);
+                }
+                anyhow::bail!("{}が {} 秒以内に終わらなかったため停止しました\n{}", kind.label(), timeout_secs, output.summary());
+            }
+            _ = cancel_poll.tick(), if control.is_some() => {
+                if control.is_some_and(|control| control.is_cancelled()) {
+                    if let Err(e) = child.kill() {
+                        tracing::warn!("{}を停止できませんでした: {}", kind.label(), e);
+                    }
+                    anyhow::bail!("バックアップがキャンセルされたため{}を停止しました", kind.label());
+                }
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+fn push_line(output: &mut String, line: &[u8]) {
+    output.push_str(&String::from_utf8_lossy(line));
+    output.push('\n');
+}
+
+/// 長すぎる出力は末尾を残して切り詰める（エラーは最後に出ることが多いため）
+fn truncate(output: &str) -> String {
+    let char_count = output.chars().count();
+    if char_count <= HOOK_OUTPUT_MAX_CHARS {
+        return output.to_string();
+    }
+
+    let tail: String = output.chars().skip(char_count - HOOK_OUTPUT_MAX_CHARS).collect();
+    format!("…（先頭 {} 文字を省略）\n{}", char_count - HOOK_OUTPUT_MAX_CHARS, tail)
+}
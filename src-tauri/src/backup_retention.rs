@@ -0,0 +1,184 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::archiver;
+use crate::path_template::DatedFolderPattern;
+use crate::ssh_client::SshClient;
+
+/// 古いバックアップフォルダの削除結果
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RetentionReport {
+    /// 削除したフォルダ
+    pub deleted_folders: Vec<String>,
+    /// 削除して空いた容量（バイト）
+    pub freed_bytes: u64,
+    /// 残したバックアップフォルダの数（今回のバックアップを含む）
+    pub kept_folders: usize,
+}
+
+impl RetentionReport {
+    /// 結果の要約（バックアップ完了のメッセージに追加する）
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "古いバックアップの削除: {}件（{:.1} MB） / 保持: {}件",
+            self.deleted_folders.len(),
+            self.freed_bytes as f64 / (1024.0 * 1024.0),
+            self.kept_folders
+        );
+        for folder in &self.deleted_folders {
+            summary.push_str(&format!("\n  削除: {}", folder));
+        }
+        summary
+    }
+}
+
+/// 日付入りのバックアップフォルダを新しい順に `keep` 件残し、それより古いものを削除する
+///
+/// 保存先テンプレートの形式に一致する名前のフォルダだけを対象にし、それ以外のフォルダや
+/// シンボリックリンクには触れない。今回のバックアップ先（`current`）は古くても削除しない。
+/// 保存先として許可されたフォルダ（`allowed_roots`）の外にあるフォルダも削除しない。
+/// `keep` が0の場合は1とみなす
+pub fn prune_dated_backups(
+    pattern: &DatedFolderPattern,
+    keep: usize,
+    current: &Path,
+    allowed_roots: &[PathBuf],
+) -> Result<RetentionReport> {
+    let keep = keep.max(1);
+    let current = current.canonicalize().unwrap_or_else(|_| current.to_path_buf());
+
+    let entries = std::fs::read_dir(&pattern.parent)
+        .with_context(|| format!("保存先の親フォルダの読み取りに失敗しました: {:?}", pattern.parent))?;
+
+    let mut folders: Vec<(chrono::NaiveDateTime, String, PathBuf)> = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("保存先の親フォルダの読み取りに失敗しました: {:?}", pattern.parent))?;
+        // シンボリックリンクはたどらない
+        if !entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            continue;
+        }
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if let Some(date) = pattern.parse(&name) {
+            folders.push((date, name, entry.path()));
+        }
+    }
+
+    // 新しい順（同じ日時は名前の逆順）
+    folders.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| b.1.cmp(&a.1)));
+
+    let mut report = RetentionReport::default();
+    for (index, (_, _, path)) in folders.into_iter().enumerate() {
+        let is_current = path.canonicalize().is_ok_and(|path| path == current);
+        if index < keep || is_current {
+            report.kept_folders += 1;
+            continue;
+        }
+
+        if let Err(e) = SshClient::check_allowed_backup_root(&path, allowed_roots) {
+            tracing::warn!("古いバックアップを削除しません: {:#}", e);
+            report.kept_folders += 1;
+            continue;
+        }
+
+        // 深い階層が MAX_PATH を超えても削除できるよう、拡張長パスで扱う（Windows のみ）
+        let extended_path = SshClient::extended_length_path(&path);
+        let mut files = Vec::new();
+        if let Err(e) = archiver::collect_files(&extended_path, &mut files) {
+            tracing::warn!("削除するフォルダのサイズの計算に失敗: {:?}: {:#}", path, e);
+        }
+        let size: u64 = files.iter().map(|(_, size)| size).sum();
+
+        match std::fs::remove_dir_all(&extended_path) {
+            Ok(()) => {
+                tracing::info!("保持数を超えた古いバックアップを削除: {:?}", path);
+                report.freed_bytes += size;
+                report.deleted_folders.push(path.to_string_lossy().to_string());
+            }
+            Err(e) => tracing::warn!("古いバックアップの削除に失敗: {:?}: {}", path, e),
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path_template::{PathTemplateContext, PathTemplateSettings};
+    use crate::test_support::TempDir;
+    use chrono::TimeZone;
+
+    fn pattern(parent: &Path) -> DatedFolderPattern {
+        let context = PathTemplateContext {
+            host: "sv1.example.jp",
+            user: "user",
+            remote_folder: "/home/user/example.com/public_html",
+            now: chrono::Local.with_ymd_and_hms(2024, 5, 6, 7, 8, 9).unwrap(),
+        };
+        let template = format!("{}/site-{{date}}", parent.display());
+        DatedFolderPattern::from_template(&template, &context, &PathTemplateSettings::default()).unwrap()
+    }
+
+    /// 日付入りのフォルダと、削除してはいけないものを作る
+    fn create_backups(dir: &TempDir) {
+        for date in ["2024-05-01", "2024-05-02", "2024-05-03", "2024-05-04", "2024-05-05"] {
+            dir.write(&format!("site-{}/index.html", date), b"backup");
+        }
+        dir.write("other-2024-04-01/index.html", b"unrelated");
+        dir.write("site-notes/readme.txt", b"unrelated");
+        dir.write("site-2024-04-02", b"file, not a folder");
+    }
+
+    #[test]
+    fn keeps_newest_and_current_and_ignores_unrelated_entries() {
+        let dir = TempDir::new("retention");
+        create_backups(&dir);
+        let current = dir.path().join("site-2024-05-01");
+
+        let report = prune_dated_backups(&pattern(dir.path()), 2, &current, &[]).unwrap();
+
+        assert_eq!(report.kept_folders, 3);
+        assert_eq!(report.deleted_folders.len(), 2);
+        assert_eq!(report.freed_bytes, 12);
+        for kept in ["site-2024-05-05", "site-2024-05-04", "site-2024-05-01", "other-2024-04-01", "site-notes", "site-2024-04-02"] {
+            assert!(dir.path().join(kept).exists(), "{} が削除されました", kept);
+        }
+        for deleted in ["site-2024-05-02", "site-2024-05-03"] {
+            assert!(!dir.path().join(deleted).exists(), "{} が残っています", deleted);
+        }
+    }
+
+    #[test]
+    fn keep_zero_still_keeps_the_newest() {
+        let dir = TempDir::new("retention");
+        create_backups(&dir);
+
+        let report = prune_dated_backups(&pattern(dir.path()), 0, &dir.path().join("site-2024-05-05"), &[]).unwrap();
+
+        assert_eq!(report.kept_folders, 1);
+        assert!(dir.path().join("site-2024-05-05").exists());
+        assert!(!dir.path().join("site-2024-05-04").exists());
+    }
+
+    #[test]
+    fn does_not_delete_outside_allowed_roots() {
+        let dir = TempDir::new("retention");
+        let allowed = TempDir::new("retention-allowed");
+        create_backups(&dir);
+
+        let report = prune_dated_backups(
+            &pattern(dir.path()),
+            1,
+            &dir.path().join("site-2024-05-05"),
+            &[allowed.path().to_path_buf()],
+        )
+        .unwrap();
+
+        assert!(report.deleted_folders.is_empty());
+        assert_eq!(report.kept_folders, 5);
+        assert!(dir.path().join("site-2024-05-01").exists());
+    }
+}
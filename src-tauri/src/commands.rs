@@ -0,0 +1,478 @@
+// Tauri コマンド（画面から呼び出す処理）
+//
+// 画面の機能ごとにモジュールを分け、複数のモジュールで使う接続設定・バックアップ結果の記録などはここに置く
+
+pub mod app;
+pub mod backup;
+pub mod batch;
+pub mod connection;
+pub mod control;
+pub mod history;
+pub mod pin;
+pub mod remote;
+pub mod settings;
+
+use crate::{AppState, CachedDomains};
+use crate::{archiver, backup_crypto, backup_diff, backup_hooks, backup_retention, data_dir, diagnostics, disk_space, logger, partial_files, path_template, profile_check, smoke_test, ssh_client, ssh_key};
+use crate::ssh_client::{SshClient, SshConfig, SshAlgorithms, SshAuthMethod, BackupOptions, DirectoryPage, RemoteDirectoryListing, RemoteTree, ConnectionDiagnostics, IncrementalEstimate, MysqlDumpResult, ServerTime, BackupControl, PhaseTimings, DEFAULT_CONNECT_TIMEOUT_SECS};
+use crate::config_manager::{ConfigManager, AppSettings, SettingsIntegrity};
+use crate::auth_manager::{AuthManager, AuthStatus};
+use crate::ssh_key::{KeySecurityReport, StoredPrivateKey};
+use crate::backup_error::ClassifiedError;
+use crate::profile_check::ProfileValidationReport;
+use crate::backup_diff::BackupDiff;
+use crate::backup_crypto::{DecryptReport, Passphrase};
+use crate::partial_files::{CleanPartialFilesReport, PartialFile};
+use crate::backup_hooks::HookKind;
+use crate::backup_checkpoint::BackupCheckpoint;
+use crate::smoke_test::SmokeTestResult;
+use crate::backup_retention::RetentionReport;
+use crate::progress_events::MULTI_BACKUP_PROGRESS_EVENT;
+use crate::path_template::PathTemplateContext;
+use crate::backup_history::{BackupHistoryManager, BackupHistoryEntry, BackupStatus, BackupStatistics, TargetStatistics, HistoryQuery, HistoryRepairReport, generate_backup_id};
+use tauri::{State, Emitter};
+use std::sync::{Mutex, Arc, atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// 前回以降の更新のみ転送する際に、サーバーとローカルの時刻のずれとして許容する秒数
+const SINCE_LAST_BACKUP_TOLERANCE_SECS: u64 = 300;
+
+/// バックアップの実行中を示すガード（破棄時に実行中の状態を解除する）
+///
+/// 同時に実行すると一時停止・キャンセルの状態や進捗・履歴が混ざるため、実行中は新しいバックアップを拒否する
+struct BackupRunGuard<'a>(&'a AtomicBool);
+
+impl<'a> BackupRunGuard<'a> {
+    fn acquire(running: &'a AtomicBool) -> Result<Self, String> {
+        running
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .map_err(|_| "既にバックアップ実行中です".to_string())?;
+        Ok(Self(running))
+    }
+}
+
+impl Drop for BackupRunGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+// X-Server固定設定
+const XSERVER_HOST: &str = "sv8187.xserver.jp";
+
+const XSERVER_PORT: u16 = 10022;
+
+const XSERVER_USER: &str = "funnybooth";
+
+// X-Server接続用のSSH設定を作成
+fn xserver_ssh_config(key_path: String, connect_timeout_secs: Option<u64>) -> SshConfig {
+    SshConfig {
+        hostname: XSERVER_HOST.to_string(),
+        port: XSERVER_PORT,
+        username: XSERVER_USER.to_string(),
+        key_path,
+        connect_timeout_secs: connect_timeout_secs.unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS),
+        prefer_ipv6: false,
+        additional_key_paths: Vec::new(),
+        jump_host: None,
+        algorithms: SshAlgorithms::default(),
+        connect_retries: 0,
+        per_attempt_timeout_secs: None,
+        auth_method: SshAuthMethod::Key,
+    }
+}
+
+/// 名前でプロファイルを読み込む
+fn load_profile(state: &State<'_, AppState>, profile_name: &str) -> Result<ssh_client::BackupConfig, String> {
+    let settings = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?
+        .load_settings()
+        .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?;
+    settings.backup_configs
+        .into_iter()
+        .find(|config| config.name == profile_name)
+        .ok_or_else(|| format!("プロファイルが見つかりません: {}", profile_name))
+}
+
+/// プロファイルで計測したサーバーの時計のずれを、前回以降の更新の判定に使う
+fn apply_clock_skew(options: &mut BackupOptions, profile: Option<&ssh_client::BackupConfig>, remote_folder: &str) {
+    if let Some(skew) = profile.and_then(|profile| profile.clock_skew_secs) {
+        options.clock_skew_secs = skew;
+    }
+    if options.modified_since.is_some() && options.clock_skew_secs.abs() > ssh_client::CLOCK_SKEW_WARNING_SECS {
+        tracing::warn!("サーバーの時計が {} 秒ずれているため、前回以降の更新の判定をずらします: {}", options.clock_skew_secs, remote_folder);
+    }
+}
+
+/// プロファイルで設定したファイルサイズの上限を、オプションで指定していなければ使う
+fn apply_profile_max_file_size(options: &mut BackupOptions, profile: Option<&ssh_client::BackupConfig>) {
+    if options.max_file_size.is_none() {
+        options.max_file_size = profile.and_then(|profile| profile.max_file_size);
+    }
+}
+
+/// 経由する踏み台サーバー（指定がなければプロファイルの設定を使う。履歴からの再実行など）
+fn resolve_jump_host(jump_host: Option<SshConfig>, profile: Option<&ssh_client::BackupConfig>) -> Option<Box<SshConfig>> {
+    jump_host
+        .map(Box::new)
+        .or_else(|| profile.and_then(|profile| profile.ssh.jump_host.clone()))
+}
+
+/// X-Server に接続する設定を作る
+///
+/// 踏み台サーバーは `jump_host` の指定を優先し、なければプロファイルの設定を使う。
+/// プロファイルの設定に保存した鍵があれば、鍵ファイルの代わりに使う
+fn profile_ssh_config(
+    key_path: String,
+    connect_timeout_secs: Option<u64>,
+    jump_host: Option<SshConfig>,
+    profile: Option<&ssh_client::BackupConfig>,
+) -> SshConfig {
+    let mut config = xserver_ssh_config(key_path, connect_timeout_secs);
+    config.jump_host = resolve_jump_host(jump_host, profile);
+    apply_stored_key(&mut config, profile.and_then(|profile| profile.stored_private_key.clone()));
+    config
+}
+
+/// 設定に保存した鍵があれば、鍵ファイルの代わりに使う
+fn apply_stored_key(config: &mut SshConfig, key: Option<StoredPrivateKey>) {
+    if let Some(key) = key {
+        config.auth_method = SshAuthMethod::StoredKey(key);
+    }
+}
+
+/// 探索の開始時に中断フラグを下ろし、クライアントに渡すフラグを返す
+fn start_discovery(state: &State<'_, AppState>) -> Arc<AtomicBool> {
+    state.discovery_cancel.store(false, Ordering::SeqCst);
+    state.discovery_cancel.clone()
+}
+
+/// フックを実行するプロファイルを読み込む
+///
+/// フックが設定されているのに設定でフックの実行が許可されていない場合はエラー
+fn load_hook_profile(state: &State<'_, AppState>, profile_name: &str) -> Result<ssh_client::BackupConfig, String> {
+    let settings = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?
+        .load_settings()
+        .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?;
+
+    let profile = settings.backup_configs
+        .into_iter()
+        .find(|config| config.name == profile_name)
+        .ok_or_else(|| format!("プロファイルが見つかりません: {}", profile_name))?;
+
+    if (profile.pre_hook.is_some() || profile.post_hook.is_some()) && !settings.allow_hooks {
+        return Err(format!(
+            "プロファイル「{}」にフックが設定されていますが、設定でフックの実行が許可されていません",
+            profile_name
+        ));
+    }
+
+    Ok(profile)
+}
+
+/// バックアップオプションのうちアプリ設定で決まる項目（転送方式・進捗通知の細かさ・常に含める隠しファイル）を反映
+fn apply_app_settings(state: &State<'_, AppState>, options: &mut BackupOptions) -> Result<(), String> {
+    let settings = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?
+        .load_settings()
+        .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?;
+
+    options.transfer_backend = settings.transfer_backend;
+    options.progress_granularity = settings.progress_granularity;
+    options.always_include = settings.always_include;
+    options.allow_tar_fallback = settings.allow_tar_fallback;
+    options.allowed_backup_roots = settings.allowed_backup_roots.iter().map(std::path::PathBuf::from).collect();
+    Ok(())
+}
+
+/// 前回以降の更新のみ転送する場合、履歴から基準時刻を求めてオプションに設定
+///
+/// 初回（成功した履歴がない）場合はすべてのファイルを転送する
+fn apply_since_last_backup(state: &State<'_, AppState>, remote_folder: &str, options: &mut BackupOptions) -> Result<(), String> {
+    if !options.since_last_backup {
+        return Ok(());
+    }
+
+    let last_timestamp = state.backup_history_manager.lock()
+        .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?
+        .last_successful_backup_timestamp(remote_folder)
+        .map_err(|e| format!("履歴の取得に失敗しました: {}", e))?;
+
+    // 時刻のずれで更新を取りこぼさないよう、基準時刻を少し早める
+    options.modified_since = last_timestamp
+        .map(|timestamp| timestamp.saturating_sub(SINCE_LAST_BACKUP_TOLERANCE_SECS));
+
+    match options.modified_since {
+        Some(since) => tracing::info!("{} 以降に更新されたファイルのみ転送します: {}", since, remote_folder),
+        None => tracing::info!("前回のバックアップがないため全ファイルを転送します: {}", remote_folder),
+    }
+    Ok(())
+}
+
+/// 保存先パスのプレースホルダー（{date} {datetime} {host} {user} {domain}）を展開
+fn expand_local_folder(
+    state: &State<'_, AppState>,
+    local_folder: &str,
+    host: &str,
+    user: &str,
+    remote_folder: &str,
+) -> Result<String, String> {
+    let settings = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?
+        .load_settings()
+        .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?;
+
+    let context = PathTemplateContext {
+        host,
+        user,
+        remote_folder,
+        now: chrono::Local::now(),
+    };
+
+    path_template::expand_local_path(local_folder, &context, &settings.path_template)
+        .map_err(|e| format!("保存先パスの展開に失敗しました: {}", e))
+}
+
+/// 保存先テンプレートに一致する日付入りのフォルダを新しい順に `keep` 件残し、古いものを削除する
+fn prune_old_backups(
+    state: &State<'_, AppState>,
+    local_folder_template: &str,
+    remote_folder: &str,
+    local_folder: &str,
+    keep: usize,
+) -> Result<RetentionReport, String> {
+    let settings = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?
+        .load_settings()
+        .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?;
+
+    let context = PathTemplateContext {
+        host: XSERVER_HOST,
+        user: XSERVER_USER,
+        remote_folder,
+        now: chrono::Local::now(),
+    };
+    let pattern = path_template::DatedFolderPattern::from_template(local_folder_template, &context, &settings.path_template)
+        .map_err(|e| format!("保存先の形式を確認できません: {}", e))?;
+
+    // 今回のバックアップ先と同じ親フォルダでなければ、無関係なフォルダを消さないよう何もしない
+    if std::path::Path::new(local_folder).parent() != Some(pattern.parent.as_path()) {
+        return Err(format!(
+            "今回の保存先が日付入りフォルダの親フォルダ（{}）の直下にありません: {}",
+            pattern.parent.display(),
+            local_folder
+        ));
+    }
+
+    let allowed_roots: Vec<std::path::PathBuf> = settings.allowed_backup_roots.iter().map(std::path::PathBuf::from).collect();
+    backup_retention::prune_dated_backups(&pattern, keep, std::path::Path::new(local_folder), &allowed_roots)
+        .map_err(|e| e.to_string())
+}
+
+/// キャンセルしたバックアップで、中断までに転送した分（履歴に記録する）
+#[derive(Debug, Default)]
+struct InterruptedTransfer {
+    transferred_files: usize,
+    transferred_bytes: u64,
+    phase_timings: Option<PhaseTimings>,
+    failed_files: usize,
+}
+
+impl InterruptedTransfer {
+    /// 最後に通知した進捗から取り出す（キャンセル時に表示していた転送量と合わせる）
+    fn from_last_progress(last_progress: &Mutex<Option<ssh_client::BackupProgress>>) -> Self {
+        last_progress
+            .lock()
+            .ok()
+            .and_then(|last| last.as_ref().map(|progress| Self {
+                transferred_files: progress.transferred_files,
+                transferred_bytes: progress.transferred_bytes,
+                phase_timings: progress.phase_timings.clone(),
+                failed_files: progress.failed_files,
+            }))
+            .unwrap_or_default()
+    }
+}
+
+/// バックアップ結果の文字列から転送ファイル数を取り出す
+fn parse_transferred_files(result: &str) -> usize {
+    result
+        .split("転送ファイル数:")
+        .nth(1)
+        .and_then(|s| s.split('\n').next())
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// バックアップ履歴に保存（失敗してもバックアップ自体は失敗扱いにしない）
+fn save_history_entry(state: &State<'_, AppState>, entry: BackupHistoryEntry) {
+    if let Ok(history_manager) = state.backup_history_manager.lock() {
+        if let Err(e) = history_manager.add_backup_entry(entry) {
+            tracing::error!("履歴保存エラー: {}", e);
+        }
+    }
+}
+
+/// 実行中のバックアップ（一括バックアップではジョブ）の対象と開始時刻（履歴の記録に使う）
+struct BackupRun<'a> {
+    remote_folder: &'a str,
+    local_folder: &'a str,
+    key_path: &'a str,
+    profile_name: &'a Option<String>,
+    /// 開始日時（Unix秒）
+    timestamp: u64,
+    start_time: Instant,
+}
+
+impl BackupRun<'_> {
+    /// 転送量などを記録していない履歴エントリ（結果に応じた項目は呼び出し側で設定する）
+    fn history_entry(&self, status: BackupStatus, message: String, options: BackupOptions) -> BackupHistoryEntry {
+        BackupHistoryEntry {
+            id: generate_backup_id(),
+            timestamp: self.timestamp,
+            remote_path: self.remote_folder.to_string(),
+            local_path: self.local_folder.to_string(),
+            transferred_files: 0,
+            transferred_bytes: 0,
+            elapsed_seconds: self.start_time.elapsed().as_secs(),
+            status,
+            message,
+            ssh_host: XSERVER_HOST.to_string(),
+            ssh_user: XSERVER_USER.to_string(),
+            profile_name: self.profile_name.clone(),
+            archive_path: None,
+            phase_timings: None,
+            options: Some(options),
+            key_path: Some(self.key_path.to_string()),
+            error_kind: None,
+            timed_out_file: None,
+            smoke_tests: Vec::new(),
+            failed_files: 0,
+        }
+    }
+}
+
+/// バックアップ後に作成したアーカイブ
+struct ArchivedBackup {
+    path: String,
+    /// アーカイブ後にバックアップフォルダを削除できなかった理由（アーカイブは作成済み）
+    cleanup_error: Option<String>,
+}
+
+/// 設定でアーカイブ化が有効な場合、バックアップ先フォルダをアーカイブに圧縮
+///
+/// 作成したアーカイブを返す（無効な場合はNone）
+fn archive_backup_if_enabled(
+    state: &State<'_, AppState>,
+    app_handle: &tauri::AppHandle,
+    local_folder: &str,
+) -> Result<Option<ArchivedBackup>> {
+    let settings = state.config_manager.lock()
+        .map_err(|e| anyhow::anyhow!("設定管理のロックに失敗しました: {}", e))?
+        .load_settings()?;
+
+    if !settings.archive_after_backup {
+        return Ok(None);
+    }
+    let allowed_roots: Vec<std::path::PathBuf> = settings.allowed_backup_roots.iter().map(std::path::PathBuf::from).collect();
+
+    let source_dir = std::path::Path::new(local_folder);
+    let archive_dir = match &settings.archive_directory {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => source_dir
+            .parent()
+            .map(|p| p.to_path_buf())
+            .ok_or_else(|| anyhow::anyhow!("アーカイブの保存先を決定できません: {}", local_folder))?,
+    };
+    SshClient::check_allowed_backup_root(&archive_dir, &allowed_roots)?;
+
+    let archive_path = archiver::create_archive(
+        source_dir,
+        &archive_dir,
+        settings.archive_format,
+        settings.progress_granularity,
+        |progress| {
+            state.progress_events.emit(app_handle, progress);
+        },
+    )?;
+
+    // 削除に失敗してもアーカイブは作成済みのため、アーカイブのパスとともに返す
+    let mut cleanup_error = None;
+    if settings.delete_after_archive {
+        let removed = SshClient::check_allowed_backup_root(source_dir, &allowed_roots)
+            .and_then(|_| std::fs::remove_dir_all(SshClient::extended_length_path(source_dir)).map_err(anyhow::Error::from));
+        if let Err(e) = removed {
+            tracing::warn!("アーカイブ後のバックアップフォルダ削除に失敗しました: {:?}: {}", source_dir, e);
+            cleanup_error = Some(format!(
+                "アーカイブ（{}）は作成しましたが、バックアップフォルダ {} の削除に失敗しました: {}",
+                archive_path.display(),
+                source_dir.display(),
+                e
+            ));
+        }
+    }
+
+    Ok(Some(ArchivedBackup {
+        path: archive_path.to_string_lossy().to_string(),
+        cleanup_error,
+    }))
+}
+
+/// PINを確認する（`required` の場合はPIN認証が設定されていなければ拒否する）
+fn require_pin(state: &State<'_, AppState>, pin: &str, required: bool) -> Result<(), String> {
+    let auth_manager = state.auth_manager.lock()
+        .map_err(|e| format!("認証管理のロックに失敗しました: {}", e))?;
+
+    let enabled = auth_manager.is_pin_enabled()
+        .map_err(|e| format!("PIN状態の確認に失敗しました: {}", e))?;
+    if !enabled {
+        return if required {
+            Err("この操作にはPIN認証の設定が必要です".to_string())
+        } else {
+            Ok(())
+        };
+    }
+
+    match auth_manager.verify_pin(pin) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("PINが正しくありません".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// 保存先として許可されたフォルダを読み込む（ローカルに書き込む・削除する操作の前に確認する）
+fn load_allowed_backup_roots(state: &State<'_, AppState>) -> Result<Vec<std::path::PathBuf>, String> {
+    Ok(state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?
+        .load_settings()
+        .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?
+        .allowed_backup_roots
+        .iter()
+        .map(std::path::PathBuf::from)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interrupted_transfer_uses_the_last_progress() {
+        let last_progress = Mutex::new(None);
+        let nothing = InterruptedTransfer::from_last_progress(&last_progress);
+        assert_eq!((nothing.transferred_files, nothing.transferred_bytes, nothing.failed_files), (0, 0, 0));
+
+        *last_progress.lock().unwrap() = Some(ssh_client::BackupProgress {
+            transferred_files: 12,
+            transferred_bytes: 4096,
+            failed_files: 1,
+            ..Default::default()
+        });
+        let interrupted = InterruptedTransfer::from_last_progress(&last_progress);
+        assert_eq!((interrupted.transferred_files, interrupted.transferred_bytes, interrupted.failed_files), (12, 4096, 1));
+        assert!(interrupted.phase_timings.is_none());
+    }
+}
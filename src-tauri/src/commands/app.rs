@@ -0,0 +1,108 @@
+use super::*;
+
+// Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
+#[tauri::command]
+pub fn greet(name: &str) -> String {
+    format!("Hello, {}! You've been greeted from Rust!", name)
+}
+
+#[tauri::command]
+pub async fn get_log_path() -> Result<String, String> {
+    logger::latest_log_path()
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| format!("ログファイルの取得に失敗しました: {}", e))
+}
+
+#[tauri::command]
+pub async fn open_log() -> Result<(), String> {
+    let path = logger::latest_log_path()
+        .map_err(|e| format!("ログファイルの取得に失敗しました: {}", e))?;
+
+    logger::open_in_default_app(&path)
+        .map_err(|e| format!("ログファイルを開けませんでした: {}", e))
+}
+
+/// サイトの表示確認（HTTP GET）。復元・アップロード後の確認用
+///
+/// `history_entry_id` を指定した場合は結果をその履歴に記録する
+#[tauri::command]
+pub async fn http_smoke_test(
+    state: State<'_, AppState>,
+    url: String,
+    expected_status: Option<u16>,
+    timeout_secs: Option<u64>,
+    history_entry_id: Option<String>,
+) -> Result<SmokeTestResult, String> {
+    // 記録先がない場合は確認の前に知らせる
+    if let Some(entry_id) = &history_entry_id {
+        state.backup_history_manager.lock()
+            .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?
+            .get_entry(entry_id)
+            .map_err(|e| format!("履歴の取得に失敗しました: {}", e))?
+            .ok_or_else(|| format!("履歴が見つかりません: {}", entry_id))?;
+    }
+
+    let timeout = Duration::from_secs(timeout_secs.unwrap_or(smoke_test::DEFAULT_SMOKE_TEST_TIMEOUT_SECS));
+    let result = smoke_test::run(&url, expected_status.unwrap_or(smoke_test::DEFAULT_EXPECTED_STATUS), timeout)
+        .await
+        .map_err(|e| format!("サイトの表示確認に失敗しました: {}", e))?;
+
+    if let Some(entry_id) = &history_entry_id {
+        state.backup_history_manager.lock()
+            .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?
+            .add_smoke_test(entry_id, result.clone())
+            .map_err(|e| format!("表示確認の結果の記録に失敗しました: {}", e))?;
+    }
+
+    Ok(result)
+}
+
+/// ログ・設定・履歴・認証の設定・実行環境を1つのzipファイルにまとめる（秘密情報は伏せ字にする）
+///
+/// 書き出し先はバックアップの保存先と同じく、許可されたフォルダの配下に限る
+#[tauri::command]
+pub async fn export_diagnostics(
+    state: State<'_, AppState>,
+    dest: String,
+) -> Result<String, String> {
+    let settings = {
+        let config_manager = state.config_manager.lock()
+            .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?;
+        config_manager.load_settings()
+            .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?
+    };
+    let dest = std::path::PathBuf::from(dest);
+    let allowed_roots: Vec<std::path::PathBuf> = settings.allowed_backup_roots.iter().map(std::path::PathBuf::from).collect();
+    SshClient::check_allowed_backup_root(&dest, &allowed_roots)
+        .map_err(|e| e.to_string())?;
+
+    let history = {
+        let history_manager = state.backup_history_manager.lock()
+            .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?;
+        history_manager.get_history()
+            .map_err(|e| format!("バックアップ履歴の取得に失敗しました: {}", e))?
+    };
+    let auth_policy = {
+        let auth_manager = state.auth_manager.lock()
+            .map_err(|e| format!("認証管理のロックに失敗しました: {}", e))?;
+        auth_manager.get_auth_policy()
+            .map_err(|e| format!("認証設定の取得に失敗しました: {}", e))?
+    };
+
+    let bundle = diagnostics::DiagnosticsBundle {
+        log_path: logger::latest_log_path().ok(),
+        settings: serde_json::to_value(&settings)
+            .map_err(|e| format!("設定のシリアライズに失敗しました: {}", e))?,
+        history: serde_json::to_value(&history)
+            .map_err(|e| format!("バックアップ履歴のシリアライズに失敗しました: {}", e))?,
+        auth_policy: serde_json::to_value(&auth_policy)
+            .map_err(|e| format!("認証設定のシリアライズに失敗しました: {}", e))?,
+        system: diagnostics::SystemInfo::current(),
+    };
+
+    diagnostics::write_bundle(&dest, bundle)
+        .map_err(|e| format!("診断情報の書き出しに失敗しました: {}", e))?;
+
+    tracing::info!("診断情報を書き出しました: {}", dest.display());
+    Ok(dest.to_string_lossy().to_string())
+}
@@ -0,0 +1,488 @@
+use super::*;
+
+// バックアップ結果構造体（backup-complete イベントでも送信）
+#[derive(Clone, Serialize)]
+pub struct BackupResult {
+    pub message: String,
+    pub transferred_files: usize,
+    pub transferred_bytes: u64,
+    pub elapsed_seconds: u64,
+    /// フェーズ別の所要時間
+    pub phase_timings: PhaseTimings,
+    /// 前回のバックアップへのハードリンクで済ませたファイル数
+    pub linked_files: usize,
+    /// スキップした特殊ファイルの数
+    pub skipped_special_files: usize,
+    /// オプションにより転送対象から除外したファイルの数
+    pub excluded_files: usize,
+    /// 作成したアーカイブのパス
+    pub archive_path: Option<String>,
+    /// 保持数を超えた古いバックアップフォルダの削除結果（保持数の設定がない場合はNone）
+    pub retention: Option<RetentionReport>,
+    /// 権限がないなどで読み取れずスキップしたリモートのフォルダ数
+    pub inaccessible_dirs: usize,
+    /// 転送に失敗してスキップしたファイル数（`continue_on_error` 有効時）
+    pub failed_files: usize,
+}
+
+// バックアップ失敗時に backup-error イベントで送信する内容
+#[derive(Clone, Serialize)]
+pub struct BackupErrorEvent {
+    pub message: String,
+    pub remote_folder: String,
+    pub local_folder: String,
+    pub elapsed_seconds: u64,
+    /// ユーザーのキャンセルによる中断か
+    pub cancelled: bool,
+}
+
+/// 書き込み速度の計測でサイズを省略した場合の既定値（MB）
+const DEFAULT_WRITE_BENCHMARK_MB: u64 = 100;
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn backup_xserver_folder(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    key_path: String,
+    remote_folder: String,
+    local_folder: String,
+    options: Option<BackupOptions>,
+    connect_timeout_secs: Option<u64>,
+    encryption_passphrase: Option<String>,
+    profile_name: Option<String>,
+    jump_host: Option<SshConfig>,
+) -> Result<BackupResult, String> {
+    let _running = BackupRunGuard::acquire(&state.backup_running)?;
+    let start_time = Instant::now();
+    let mut options = options.unwrap_or_default();
+    options.encryption_passphrase = encryption_passphrase.map(Passphrase::new);
+    options.profile_name = profile_name.clone();
+
+    apply_app_settings(&state, &mut options)?;
+    apply_since_last_backup(&state, &remote_folder, &mut options)?;
+
+    let local_folder_template = local_folder.clone();
+    let local_folder = expand_local_folder(&state, &local_folder, XSERVER_HOST, XSERVER_USER, &remote_folder)?;
+    let profile = match &profile_name {
+        Some(name) => Some(load_hook_profile(&state, name)?),
+        None => None,
+    };
+    apply_clock_skew(&mut options, profile.as_ref(), &remote_folder);
+    apply_profile_max_file_size(&mut options, profile.as_ref());
+
+    // 実行状態（一時停止・キャンセル）をリセット
+    state.backup_control.reset();
+
+    let mut ssh_config = xserver_ssh_config(key_path.clone(), connect_timeout_secs);
+    ssh_config.jump_host = resolve_jump_host(jump_host, profile.as_ref());
+    apply_stored_key(&mut ssh_config, profile.as_ref().and_then(|profile| profile.stored_private_key.clone()));
+
+    let mut client = SshClient::new(ssh_config);
+
+    let run = BackupRun {
+        remote_folder: &remote_folder,
+        local_folder: &local_folder,
+        key_path: &key_path,
+        profile_name: &profile_name,
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        start_time,
+    };
+
+    // 進捗レポート用のコールバック関数（最後の進捗は履歴記録用に保持）
+    let last_progress = Arc::new(Mutex::new(None::<ssh_client::BackupProgress>));
+    let progress_callback = backup_progress_callback(&state, &app_handle, last_progress.clone());
+
+    // 実行前フックが失敗した場合はバックアップせずに失敗として扱う
+    let mut hook_summaries = Vec::new();
+    let backup_outcome = async {
+        if let Some((profile, command)) = profile.as_ref().and_then(|p| Some((p, p.pre_hook.as_deref()?))) {
+            let output = backup_hooks::run_hook(&app_handle, HookKind::Pre, command, profile, &local_folder, None, Some(&state.backup_control)).await?;
+            if !output.success() {
+                return Err(anyhow::anyhow!("実行前フックが失敗したため中止しました\n{}", output.summary()));
+            }
+            hook_summaries.push(output.summary());
+        }
+        client.backup_folder_with_progress(&remote_folder, &local_folder, state.backup_control.clone(), &options, progress_callback).await
+    }.await;
+    state.progress_events.flush(&app_handle);
+
+    if let Some(summary) = run_post_hook(&app_handle, profile.as_ref(), &local_folder, backup_outcome.is_ok()).await {
+        hook_summaries.push(summary);
+    }
+
+    match backup_outcome {
+        Ok(result) => {
+            let keep_last_n_backups = profile.as_ref().and_then(|profile| profile.keep_last_n_backups);
+            let backup_result = complete_backup(&state, &app_handle, &run, &local_folder_template, keep_last_n_backups, &result, &last_progress, &hook_summaries);
+            save_history_entry(&state, completed_history_entry(&run, &backup_result, options));
+
+            let _ = app_handle.emit("backup-complete", &backup_result);
+
+            Ok(backup_result)
+        }
+        Err(e) => {
+            let cancelled = state.backup_control.is_cancelled();
+            let _ = app_handle.emit("backup-error", &BackupErrorEvent {
+                message: e.to_string(),
+                remote_folder: remote_folder.clone(),
+                local_folder: local_folder.clone(),
+                elapsed_seconds: start_time.elapsed().as_secs(),
+                cancelled,
+            });
+
+            save_history_entry(&state, failed_history_entry(&run, &e, cancelled, &last_progress, &hook_summaries, options));
+
+            Err(format!("X-Serverバックアップに失敗しました: {}", e))
+        }
+    }
+}
+
+/// 進捗を通知し、最後の進捗を `last_progress` に保持するコールバック
+fn backup_progress_callback(
+    state: &State<'_, AppState>,
+    app_handle: &tauri::AppHandle,
+    last_progress: Arc<Mutex<Option<ssh_client::BackupProgress>>>,
+) -> impl Fn(ssh_client::BackupProgress) + Send + Sync + 'static {
+    let app_handle = app_handle.clone();
+    let progress_events = state.progress_events.clone();
+    move |progress: ssh_client::BackupProgress| {
+        if let Ok(mut last) = last_progress.lock() {
+            *last = Some(progress.clone());
+        }
+        progress_events.emit(&app_handle, progress);
+    }
+}
+
+/// プロファイルの実行後フックを実行し、結果の要約を返す（フックがなければNone）
+///
+/// 実行後フックはバックアップの成否を問わず実行し、失敗しても警告にとどめる
+/// （実行前フックで止めたサービスの再開などに使うため、キャンセル後も停止しない）
+async fn run_post_hook(
+    app_handle: &tauri::AppHandle,
+    profile: Option<&ssh_client::BackupConfig>,
+    local_folder: &str,
+    succeeded: bool,
+) -> Option<String> {
+    let (profile, command) = profile.and_then(|p| Some((p, p.post_hook.as_deref()?)))?;
+    Some(match backup_hooks::run_hook(app_handle, HookKind::Post, command, profile, local_folder, Some(succeeded), None).await {
+        Ok(output) if output.success() => output.summary(),
+        Ok(output) => format!("警告: 実行後フックが失敗しました\n{}", output.summary()),
+        Err(e) => format!("警告: {}", e),
+    })
+}
+
+/// 完了したバックアップの後処理（アーカイブ化・古いバックアップの削除）を行い、結果をまとめる
+#[allow(clippy::too_many_arguments)]
+fn complete_backup(
+    state: &State<'_, AppState>,
+    app_handle: &tauri::AppHandle,
+    run: &BackupRun<'_>,
+    local_folder_template: &str,
+    keep_last_n_backups: Option<usize>,
+    result: &str,
+    last_progress: &Mutex<Option<ssh_client::BackupProgress>>,
+    hook_summaries: &[String],
+) -> BackupResult {
+    let elapsed_seconds = run.start_time.elapsed().as_secs();
+
+    // 転送バイト数・フェーズ別時間などは最後の進捗（バックアップ完了）から取得
+    let final_progress = last_progress
+        .lock()
+        .ok()
+        .and_then(|last| last.clone())
+        .unwrap_or_default();
+
+    let mut message = result.to_string();
+    let (archive_path, archiving_seconds) = archive_after_backup(state, app_handle, run.local_folder, &mut message);
+    let retention = prune_after_backup(state, run, local_folder_template, keep_last_n_backups, final_progress.failed_files, &mut message);
+    for summary in hook_summaries {
+        message.push_str(&format!("\n{}", summary));
+    }
+
+    let mut phase_timings = final_progress.phase_timings.unwrap_or_default();
+    phase_timings.archiving_seconds = archiving_seconds;
+    BackupResult {
+        message,
+        transferred_files: parse_transferred_files(result),
+        transferred_bytes: final_progress.transferred_bytes,
+        elapsed_seconds,
+        phase_timings,
+        linked_files: final_progress.linked_files,
+        skipped_special_files: final_progress.skipped_special_files,
+        excluded_files: final_progress.excluded_files,
+        archive_path,
+        retention,
+        inaccessible_dirs: final_progress.inaccessible_dirs,
+        failed_files: final_progress.failed_files,
+    }
+}
+
+/// 設定に応じてバックアップをアーカイブ化し、結果をメッセージに追記する
+///
+/// 作成したアーカイブのパスと、アーカイブ化にかかった秒数を返す
+fn archive_after_backup(
+    state: &State<'_, AppState>,
+    app_handle: &tauri::AppHandle,
+    local_folder: &str,
+    message: &mut String,
+) -> (Option<String>, f64) {
+    let archive_started = Instant::now();
+    let archive_result = archive_backup_if_enabled(state, app_handle, local_folder);
+    state.progress_events.flush(app_handle);
+    let archiving_seconds = archive_started.elapsed().as_secs_f64();
+    let archive_path = match archive_result {
+        Ok(archived) => archived.map(|archived| {
+            message.push_str(&format!("\nアーカイブ: {}", archived.path));
+            if let Some(e) = archived.cleanup_error {
+                message.push_str(&format!("\n警告: {}", e));
+            }
+            archived.path
+        }),
+        Err(e) => {
+            message.push_str(&format!("\n警告: アーカイブの作成に失敗しました: {}", e));
+            None
+        }
+    };
+    (archive_path, archiving_seconds)
+}
+
+/// 保持数が設定されていれば、古い日付入りのバックアップフォルダを削除し、結果をメッセージに追記する
+///
+/// 転送に失敗したファイルがある場合は、欠けたバックアップで古いものを置き換えないよう削除しない
+fn prune_after_backup(
+    state: &State<'_, AppState>,
+    run: &BackupRun<'_>,
+    local_folder_template: &str,
+    keep_last_n_backups: Option<usize>,
+    failed_files: usize,
+    message: &mut String,
+) -> Option<RetentionReport> {
+    match keep_last_n_backups {
+        Some(_) if failed_files > 0 => {
+            message.push_str("\n転送に失敗したファイルがあるため、古いバックアップは削除しません");
+            None
+        }
+        Some(keep) => match prune_old_backups(state, local_folder_template, run.remote_folder, run.local_folder, keep) {
+            Ok(report) => {
+                message.push_str(&format!("\n{}", report.summary()));
+                Some(report)
+            }
+            Err(e) => {
+                message.push_str(&format!("\n警告: 古いバックアップの削除に失敗しました: {}", e));
+                None
+            }
+        },
+        None => None,
+    }
+}
+
+/// 完了したバックアップの履歴エントリ
+fn completed_history_entry(run: &BackupRun<'_>, result: &BackupResult, options: BackupOptions) -> BackupHistoryEntry {
+    let status = if result.failed_files > 0 { BackupStatus::PartialSuccess } else { BackupStatus::Success };
+    BackupHistoryEntry {
+        transferred_files: result.transferred_files,
+        transferred_bytes: result.transferred_bytes,
+        elapsed_seconds: result.elapsed_seconds,
+        archive_path: result.archive_path.clone(),
+        phase_timings: Some(result.phase_timings.clone()),
+        failed_files: result.failed_files,
+        ..run.history_entry(status, result.message.clone(), options)
+    }
+}
+
+/// 失敗・キャンセルしたバックアップの履歴エントリ
+///
+/// キャンセルは一括バックアップと同じく中断までの転送量を記録する
+fn failed_history_entry(
+    run: &BackupRun<'_>,
+    error: &anyhow::Error,
+    cancelled: bool,
+    last_progress: &Mutex<Option<ssh_client::BackupProgress>>,
+    hook_summaries: &[String],
+    options: BackupOptions,
+) -> BackupHistoryEntry {
+    let (status, mut message, interrupted) = if cancelled {
+        (BackupStatus::Cancelled, error.to_string(), InterruptedTransfer::from_last_progress(last_progress))
+    } else {
+        (BackupStatus::Failed, format!("バックアップ失敗: {}", error), InterruptedTransfer::default())
+    };
+    for summary in hook_summaries {
+        message.push_str(&format!("\n{}", summary));
+    }
+    BackupHistoryEntry {
+        transferred_files: interrupted.transferred_files,
+        transferred_bytes: interrupted.transferred_bytes,
+        phase_timings: interrupted.phase_timings,
+        error_kind: if cancelled { None } else { ClassifiedError::kind_of(error) },
+        timed_out_file: if cancelled { None } else { ClassifiedError::timed_out_file_of(error) },
+        failed_files: interrupted.failed_files,
+        ..run.history_entry(status, message, options)
+    }
+}
+
+// 直近のバックアップ（成功・失敗・キャンセルを問わない）を同じ設定で再実行
+#[tauri::command]
+pub async fn repeat_last_backup(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    encryption_passphrase: Option<String>,
+) -> Result<BackupResult, String> {
+    let last_entry = state.backup_history_manager.lock()
+        .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?
+        .latest_entry()
+        .map_err(|e| format!("履歴の取得に失敗しました: {}", e))?
+        .ok_or_else(|| "再実行できるバックアップの履歴がありません".to_string())?;
+
+    // 秘密鍵を記録していない古い履歴は、同じリモートフォルダのプロファイルから補う
+    let key_path = match last_entry.key_path {
+        Some(key_path) => key_path,
+        None => state.config_manager.lock()
+            .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?
+            .load_settings()
+            .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?
+            .backup_configs
+            .into_iter()
+            .find(|config| config.remote_folder == last_entry.remote_path)
+            .map(|config| config.ssh.key_path)
+            .ok_or_else(|| "前回のバックアップで使用した秘密鍵が履歴に記録されていません".to_string())?,
+    };
+
+    // 前回の基準時刻は使わず、再実行時点の履歴から求め直す
+    let options = last_entry.options.map(|mut options| {
+        options.modified_since = None;
+        options
+    });
+
+    backup_xserver_folder(
+        state,
+        app_handle,
+        key_path,
+        last_entry.remote_path,
+        last_entry.local_path,
+        options,
+        None,
+        encryption_passphrase,
+        last_entry.profile_name,
+        None,
+    ).await
+}
+
+/// 中断したバックアップをチェックポイントから続きを実行
+///
+/// `checkpoint` はチェックポイントのファイル、またはそれを含む保存先フォルダ。前回最後に処理を終えた
+/// ディレクトリまでは飛ばし、転送途中だったファイルは `.kyosho-part` から再開する
+#[tauri::command]
+pub async fn resume_backup_from_checkpoint(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    checkpoint: String,
+    encryption_passphrase: Option<String>,
+) -> Result<BackupResult, String> {
+    let checkpoint = BackupCheckpoint::load(std::path::Path::new(&checkpoint))
+        .map_err(|e| format!("チェックポイントの読み込みに失敗しました: {}", e))?;
+    if checkpoint.ssh.hostname != XSERVER_HOST || checkpoint.ssh.username != XSERVER_USER {
+        return Err(format!("X-Server以外へのバックアップのチェックポイントからは再開できません: {}@{}",
+            checkpoint.ssh.username, checkpoint.ssh.hostname));
+    }
+
+    let mut options = checkpoint.options;
+    options.resume = true;
+    options.resume_after_dir = checkpoint.last_completed_dir.map(std::path::PathBuf::from);
+    // 前回の基準時刻は使わず、再開時点の履歴から求め直す
+    options.modified_since = None;
+
+    tracing::info!(
+        "チェックポイントから再開: {} -> {} ({})",
+        checkpoint.remote_path,
+        checkpoint.local_path,
+        options.resume_after_dir.as_ref().map(|dir| dir.display().to_string()).unwrap_or_else(|| "最初から".to_string())
+    );
+
+    backup_xserver_folder(
+        state,
+        app_handle,
+        checkpoint.ssh.key_path,
+        checkpoint.remote_path,
+        checkpoint.local_path,
+        Some(options),
+        Some(checkpoint.ssh.connect_timeout_secs),
+        encryption_passphrase,
+        checkpoint.profile_name,
+        checkpoint.ssh.jump_host.map(|jump_host| *jump_host),
+    ).await
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn backup_folder(
+    state: State<'_, AppState>,
+    hostname: String,
+    port: u16,
+    username: String,
+    key_path: String,
+    remote_folder: String,
+    local_folder: String,
+    connect_timeout_secs: Option<u64>,
+    prefer_ipv6: Option<bool>,
+    additional_key_paths: Option<Vec<String>>,
+    jump_host: Option<SshConfig>,
+    algorithms: Option<SshAlgorithms>,
+    connect_retries: Option<u32>,
+    per_attempt_timeout_secs: Option<u64>,
+    password: Option<String>,
+) -> Result<String, String> {
+    let _running = BackupRunGuard::acquire(&state.backup_running)?;
+    let ssh_config = SshConfig {
+        hostname,
+        port,
+        username,
+        key_path,
+        connect_timeout_secs: connect_timeout_secs.unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS),
+        prefer_ipv6: prefer_ipv6.unwrap_or(false),
+        additional_key_paths: additional_key_paths.unwrap_or_default(),
+        jump_host: jump_host.map(Box::new),
+        algorithms: algorithms.unwrap_or_default(),
+        connect_retries: connect_retries.unwrap_or(0),
+        per_attempt_timeout_secs,
+        auth_method: SshAuthMethod::from_password(password),
+    };
+
+    let local_folder = expand_local_folder(&state, &local_folder, &ssh_config.hostname, &ssh_config.username, &remote_folder)?;
+
+    let options = BackupOptions {
+        allowed_backup_roots: load_allowed_backup_roots(&state)?,
+        ..Default::default()
+    };
+
+    let mut client = SshClient::new(ssh_config);
+
+    match client.backup_folder_with_progress(&remote_folder, &local_folder, Arc::new(BackupControl::new()), &options, |_| {}).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("バックアップに失敗しました: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn check_local_free_space(path: String) -> Result<u64, String> {
+    disk_space::available_space(std::path::Path::new(&path))
+        .map_err(|e| format!("空き容量の確認に失敗しました: {}", e))
+}
+
+// 保存先への書き込み速度（MB/s）を計測（ディスク律速かネットワーク律速かの切り分け用）
+//
+// 計測用のファイルを書き込んで削除するため、バックアップと同じく許可されたフォルダの配下に限る
+#[tauri::command]
+pub async fn benchmark_local_write(state: State<'_, AppState>, path: String, size_mb: Option<u64>) -> Result<f64, String> {
+    SshClient::check_allowed_backup_root(std::path::Path::new(&path), &load_allowed_backup_roots(&state)?)
+        .map_err(|e| e.to_string())?;
+    tokio::task::spawn_blocking(move || {
+        disk_space::benchmark_write(std::path::Path::new(&path), size_mb.unwrap_or(DEFAULT_WRITE_BENCHMARK_MB))
+    })
+    .await
+    .map_err(|e| format!("書き込み速度の計測に失敗しました: {}", e))?
+    .map_err(|e| format!("書き込み速度の計測に失敗しました: {}", e))
+}
@@ -0,0 +1,644 @@
+use super::*;
+
+// 複数フォルダの一括バックアップのジョブ
+#[derive(Debug, Deserialize)]
+pub struct BackupJob {
+    pub remote_folder: String,
+    pub local_folder: String,
+}
+
+// ジョブごとの結果
+#[derive(Serialize)]
+pub struct BackupJobResult {
+    pub remote_folder: String,
+    pub local_folder: String,
+    pub success: bool,
+    /// ジョブ単位のキャンセルで中断・スキップしたか
+    pub cancelled: bool,
+    pub message: String,
+    pub transferred_files: usize,
+    pub transferred_bytes: u64,
+    pub elapsed_seconds: u64,
+}
+
+// 一括バックアップ全体の集計結果
+#[derive(Serialize)]
+pub struct MultiBackupResult {
+    pub jobs: Vec<BackupJobResult>,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// ジョブ単位のキャンセルで中断・スキップしたジョブ数
+    pub cancelled: usize,
+    /// 中止（エラー停止・キャンセル）により実行しなかったジョブ数
+    pub skipped: usize,
+    pub transferred_files: usize,
+    pub transferred_bytes: u64,
+    pub elapsed_seconds: u64,
+}
+
+// 一括バックアップ全体の進捗（multi-backup-progress イベントの内容）
+#[derive(Clone, Default, Serialize)]
+pub struct MultiBackupProgress {
+    /// 全ジョブの累計（実行中のジョブを含む）
+    pub transferred_files: usize,
+    pub transferred_bytes: u64,
+    /// 実行中のジョブ番号（1始まり）と総ジョブ数
+    pub job_index: usize,
+    pub job_count: usize,
+    pub elapsed_seconds: u64,
+    /// 全体の平均スループット（バイト/秒）
+    pub throughput_bytes_per_sec: Option<f64>,
+    /// 全体の残り時間の見込み（秒）。完了したジョブ数と実行中のジョブの進捗率から推定
+    pub eta_seconds: Option<u64>,
+    /// ジョブ単位のキャンセルで中断・スキップしたジョブ数
+    pub cancelled_jobs: usize,
+    pub running: bool,
+}
+
+/// 一括バックアップの進捗カウンタ（ジョブをまたいで累積し、進捗通知と取得コマンドで共有）
+#[derive(Default)]
+pub struct MultiBackupCounters {
+    /// 終了したジョブの累計
+    completed_files: AtomicUsize,
+    completed_bytes: AtomicU64,
+    /// 実行中のジョブの転送量と進捗率（0〜1 の f64 をビット列で保持）
+    current_files: AtomicUsize,
+    current_bytes: AtomicU64,
+    current_fraction: AtomicU64,
+    job_index: AtomicUsize,
+    job_count: AtomicUsize,
+    cancelled_jobs: AtomicUsize,
+    /// 一括バックアップの開始時刻（Unixミリ秒）
+    started_at_ms: AtomicU64,
+    running: AtomicBool,
+}
+
+impl MultiBackupCounters {
+    /// 新しい一括バックアップの開始時に前回の値を消す
+    fn start(&self, job_count: usize) {
+        self.completed_files.store(0, Ordering::Relaxed);
+        self.completed_bytes.store(0, Ordering::Relaxed);
+        self.current_files.store(0, Ordering::Relaxed);
+        self.current_bytes.store(0, Ordering::Relaxed);
+        self.current_fraction.store(0f64.to_bits(), Ordering::Relaxed);
+        self.job_index.store(0, Ordering::Relaxed);
+        self.job_count.store(job_count, Ordering::Relaxed);
+        self.cancelled_jobs.store(0, Ordering::Relaxed);
+        self.started_at_ms.store(current_unix_millis(), Ordering::Relaxed);
+        self.running.store(true, Ordering::Relaxed);
+    }
+
+    fn begin_job(&self, job_index: usize) {
+        self.current_files.store(0, Ordering::Relaxed);
+        self.current_bytes.store(0, Ordering::Relaxed);
+        self.current_fraction.store(0f64.to_bits(), Ordering::Relaxed);
+        self.job_index.store(job_index, Ordering::Relaxed);
+    }
+
+    fn update_job(&self, progress: &ssh_client::BackupProgress) {
+        self.current_files.store(progress.transferred_files, Ordering::Relaxed);
+        self.current_bytes.store(progress.transferred_bytes, Ordering::Relaxed);
+        if let Some(percent) = progress.percent_complete {
+            self.current_fraction.store((percent / 100.0).to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    /// 実行中のジョブの転送量を累計に移す（失敗したジョブの転送量も含める）
+    fn finish_job(&self) {
+        self.completed_files.fetch_add(self.current_files.swap(0, Ordering::Relaxed), Ordering::Relaxed);
+        self.completed_bytes.fetch_add(self.current_bytes.swap(0, Ordering::Relaxed), Ordering::Relaxed);
+        self.current_fraction.store(1f64.to_bits(), Ordering::Relaxed);
+    }
+
+    /// キャンセルしたジョブを終了扱いにする（中断までに転送した分は累計に含める）
+    fn cancel_job(&self) {
+        self.cancelled_jobs.fetch_add(1, Ordering::Relaxed);
+        self.finish_job();
+    }
+
+    fn finish(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> MultiBackupProgress {
+        let started_at_ms = self.started_at_ms.load(Ordering::Relaxed);
+        if started_at_ms == 0 {
+            return MultiBackupProgress::default();
+        }
+
+        let running = self.running.load(Ordering::Relaxed);
+        let transferred_files = self.completed_files.load(Ordering::Relaxed) + self.current_files.load(Ordering::Relaxed);
+        let transferred_bytes = self.completed_bytes.load(Ordering::Relaxed) + self.current_bytes.load(Ordering::Relaxed);
+        let job_index = self.job_index.load(Ordering::Relaxed);
+        let job_count = self.job_count.load(Ordering::Relaxed);
+        let elapsed = current_unix_millis().saturating_sub(started_at_ms) as f64 / 1000.0;
+
+        let throughput_bytes_per_sec = (elapsed > 0.0).then(|| transferred_bytes as f64 / elapsed);
+
+        // 全体の進捗 = (終了したジョブ数 + 実行中のジョブの進捗率) / 総ジョブ数
+        let current_fraction = f64::from_bits(self.current_fraction.load(Ordering::Relaxed)).clamp(0.0, 1.0);
+        let overall_fraction = if job_count > 0 {
+            (job_index.saturating_sub(1) as f64 + current_fraction) / job_count as f64
+        } else {
+            0.0
+        };
+        let eta_seconds = (running && overall_fraction > 0.0)
+            .then(|| (elapsed * (1.0 - overall_fraction) / overall_fraction) as u64);
+
+        MultiBackupProgress {
+            transferred_files,
+            transferred_bytes,
+            job_index,
+            job_count,
+            elapsed_seconds: elapsed as u64,
+            throughput_bytes_per_sec,
+            eta_seconds,
+            cancelled_jobs: self.cancelled_jobs.load(Ordering::Relaxed),
+            running,
+        }
+    }
+}
+
+/// 一括バックアップのジョブ単位のキャンセル要求（ジョブ番号は1始まり）
+///
+/// 実行中のジョブは共有の `BackupControl` をキャンセルして中断し、ジョブの終了後に
+/// 実行状態を戻して次のジョブに進む。ジョブの開始・終了と要求の受付は同じロックの中で行い、
+/// 中断の要求が次のジョブに及ばないようにする
+#[derive(Default)]
+pub struct BatchJobCancels {
+    inner: Mutex<BatchJobCancelState>,
+}
+
+#[derive(Default)]
+struct BatchJobCancelState {
+    /// 最後に開始したジョブ番号（一括バックアップ中でなければ0）
+    current_job: usize,
+    /// 最後に開始したジョブを転送中か
+    job_running: bool,
+    job_count: usize,
+    requested: std::collections::HashSet<usize>,
+    /// 全体のキャンセル（`cancel_backup`）が要求されたか
+    batch_cancelled: bool,
+}
+
+impl BatchJobCancels {
+    fn lock(&self) -> std::sync::MutexGuard<'_, BatchJobCancelState> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn start(&self, job_count: usize) {
+        *self.lock() = BatchJobCancelState { job_count, ..Default::default() };
+    }
+
+    /// ジョブの開始を記録し、開始前にキャンセルが要求されていたかを返す
+    fn begin_job(&self, job_index: usize) -> bool {
+        let mut inner = self.lock();
+        let requested = inner.requested.contains(&job_index);
+        inner.current_job = job_index;
+        inner.job_running = !requested;
+        requested
+    }
+
+    /// ジョブの終了時に、ジョブ単位のキャンセルが要求されていたかを返す
+    ///
+    /// 要求されていた場合は、次のジョブに進めるよう実行状態を戻す（全体のキャンセル中は戻さない）
+    fn finish_job(&self, job_index: usize, control: &BackupControl) -> bool {
+        let mut inner = self.lock();
+        inner.job_running = false;
+        if !inner.requested.contains(&job_index) || inner.batch_cancelled {
+            return false;
+        }
+        control.reset();
+        true
+    }
+
+    fn finish(&self) {
+        *self.lock() = BatchJobCancelState::default();
+    }
+
+    /// ジョブのキャンセルを受け付ける（Noneは転送中のジョブ）
+    ///
+    /// 転送中のジョブなら転送を中断し、まだ開始していないジョブなら開始時にスキップする
+    pub(super) fn cancel_job(&self, job_index: Option<usize>, control: &BackupControl) -> Result<usize, String> {
+        let mut inner = self.lock();
+        if inner.current_job == 0 {
+            return Err("実行中の一括バックアップがありません".to_string());
+        }
+
+        let job_index = match job_index {
+            Some(job_index) => job_index,
+            None if inner.job_running => inner.current_job,
+            None => return Err("転送中のジョブがありません".to_string()),
+        };
+        if job_index == 0 || job_index > inner.job_count {
+            return Err(format!("ジョブ番号が範囲外です: {}（1〜{}）", job_index, inner.job_count));
+        }
+        if job_index < inner.current_job || (job_index == inner.current_job && !inner.job_running) {
+            return Err(format!("ジョブ {} は既に終了しています", job_index));
+        }
+
+        inner.requested.insert(job_index);
+        if job_index == inner.current_job {
+            control.cancel();
+        }
+        Ok(job_index)
+    }
+
+    /// 全体のキャンセルを記録する（ジョブ単位のキャンセル後に実行状態を戻さないようにする）
+    pub(super) fn cancel_batch(&self) {
+        self.lock().batch_cancelled = true;
+    }
+}
+
+fn current_unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+// 実行中（または直前）の一括バックアップ全体の進捗を取得
+#[tauri::command]
+pub async fn get_multi_backup_progress(state: State<'_, AppState>) -> Result<MultiBackupProgress, String> {
+    Ok(state.multi_backup_counters.snapshot())
+}
+
+// 複数フォルダを1つのSSH接続で順番にバックアップ
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn backup_multiple_folders(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    key_path: String,
+    jobs: Vec<BackupJob>,
+    stop_on_error: Option<bool>,
+    options: Option<BackupOptions>,
+    connect_timeout_secs: Option<u64>,
+    encryption_passphrase: Option<String>,
+    profile_name: Option<String>,
+    jump_host: Option<SshConfig>,
+) -> Result<MultiBackupResult, String> {
+    let _running = BackupRunGuard::acquire(&state.backup_running)?;
+    let start_time = Instant::now();
+    let stop_on_error = stop_on_error.unwrap_or(false);
+    let mut options = options.unwrap_or_default();
+    options.encryption_passphrase = encryption_passphrase.map(Passphrase::new);
+    options.profile_name = profile_name.clone();
+
+    apply_app_settings(&state, &mut options)?;
+
+    state.backup_control.reset();
+
+    // 接続は最初のジョブで確立し、以降のジョブで再利用する
+    let profile = profile_name.as_deref().map(|name| load_hook_profile(&state, name)).transpose()?;
+    apply_profile_max_file_size(&mut options, profile.as_ref());
+    let mut ssh_config = xserver_ssh_config(key_path.clone(), connect_timeout_secs);
+    ssh_config.jump_host = resolve_jump_host(jump_host, profile.as_ref());
+    apply_stored_key(&mut ssh_config, profile.as_ref().and_then(|profile| profile.stored_private_key.clone()));
+    let mut client = SshClient::new(ssh_config);
+
+    run_batch_pre_hook(&state, &app_handle, profile.as_ref()).await?;
+
+    let job_count = jobs.len();
+    let counters = state.multi_backup_counters.clone();
+    counters.start(job_count);
+    state.batch_job_cancels.start(job_count);
+
+    let batch = BatchRun {
+        state: &state,
+        app_handle: &app_handle,
+        key_path: &key_path,
+        profile_name: &profile_name,
+        profile: profile.as_ref(),
+        job_count,
+    };
+    let mut summary = MultiBackupResult {
+        jobs: Vec::new(),
+        succeeded: 0,
+        failed: 0,
+        cancelled: 0,
+        skipped: 0,
+        transferred_files: 0,
+        transferred_bytes: 0,
+        elapsed_seconds: 0,
+    };
+
+    for (index, job) in jobs.into_iter().enumerate() {
+        if state.backup_control.is_cancelled() {
+            summary.skipped = job_count - index;
+            break;
+        }
+
+        let result = batch.run_job(&mut client, index + 1, job, &options).await;
+        let failed = !result.success && !result.cancelled;
+        summary.add_job(result);
+
+        if failed && stop_on_error {
+            summary.skipped = job_count - index - 1;
+            break;
+        }
+    }
+
+    counters.finish();
+    state.batch_job_cancels.finish();
+    let _ = app_handle.emit(MULTI_BACKUP_PROGRESS_EVENT, counters.snapshot());
+
+    run_batch_post_hook(&app_handle, profile.as_ref(), &summary).await;
+
+    summary.elapsed_seconds = start_time.elapsed().as_secs();
+    Ok(summary)
+}
+
+/// プロファイルの実行前フックを一括バックアップ全体の前に1回実行する（保存先はプロファイルのもの）
+///
+/// 実行前フックが失敗した場合はどのジョブも実行しない
+async fn run_batch_pre_hook(
+    state: &State<'_, AppState>,
+    app_handle: &tauri::AppHandle,
+    profile: Option<&ssh_client::BackupConfig>,
+) -> Result<(), String> {
+    let Some((profile, command)) = profile.and_then(|p| Some((p, p.pre_hook.as_deref()?))) else {
+        return Ok(());
+    };
+    let output = backup_hooks::run_hook(app_handle, HookKind::Pre, command, profile, &profile.local_folder, None, Some(&state.backup_control))
+        .await
+        .map_err(|e| format!("実行前フックの実行に失敗しました: {}", e))?;
+    if !output.success() {
+        return Err(format!("実行前フックが失敗したため中止しました\n{}", output.summary()));
+    }
+    tracing::info!("{}", output.summary());
+    Ok(())
+}
+
+/// プロファイルの実行後フックを一括バックアップ全体の後に1回実行する
+///
+/// 実行後フックはジョブの成否を問わず実行し、失敗しても警告にとどめる
+async fn run_batch_post_hook(
+    app_handle: &tauri::AppHandle,
+    profile: Option<&ssh_client::BackupConfig>,
+    summary: &MultiBackupResult,
+) {
+    let Some((profile, command)) = profile.and_then(|p| Some((p, p.post_hook.as_deref()?))) else {
+        return;
+    };
+    let succeeded = summary.failed == 0 && summary.cancelled == 0 && summary.skipped == 0;
+    match backup_hooks::run_hook(app_handle, HookKind::Post, command, profile, &profile.local_folder, Some(succeeded), None).await {
+        Ok(output) if output.success() => tracing::info!("{}", output.summary()),
+        Ok(output) => tracing::warn!("実行後フックが失敗しました\n{}", output.summary()),
+        Err(e) => tracing::warn!("{}", e),
+    }
+}
+
+impl MultiBackupResult {
+    /// ジョブの結果を集計に加える
+    fn add_job(&mut self, job: BackupJobResult) {
+        if job.cancelled {
+            self.cancelled += 1;
+        } else if job.success {
+            self.succeeded += 1;
+        } else {
+            self.failed += 1;
+        }
+        self.transferred_files += job.transferred_files;
+        self.transferred_bytes += job.transferred_bytes;
+        self.jobs.push(job);
+    }
+}
+
+/// 実行中の一括バックアップで、ジョブをまたいで共通の情報
+struct BatchRun<'a> {
+    state: &'a State<'a, AppState>,
+    app_handle: &'a tauri::AppHandle,
+    key_path: &'a str,
+    profile_name: &'a Option<String>,
+    profile: Option<&'a ssh_client::BackupConfig>,
+    job_count: usize,
+}
+
+impl BatchRun<'_> {
+    /// ジョブを1つ実行して結果を履歴に記録する（`job_index` は1始まり）
+    async fn run_job(&self, client: &mut SshClient, job_index: usize, mut job: BackupJob, options: &BackupOptions) -> BackupJobResult {
+        let job_start = Instant::now();
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let counters = &self.state.multi_backup_counters;
+        counters.begin_job(job_index);
+
+        // 開始前にキャンセルされたジョブは実行せずにキャンセルとして記録する
+        if self.state.batch_job_cancels.begin_job(job_index) {
+            return self.skip_cancelled_job(job, timestamp, job_start, options);
+        }
+
+        let last_progress = Arc::new(Mutex::new(None::<ssh_client::BackupProgress>));
+        let progress_callback = self.job_progress_callback(job_index, last_progress.clone());
+
+        // 保存先のプレースホルダーはジョブごとに展開（展開できない場合はそのジョブを失敗扱い）
+        match expand_local_folder(self.state, &job.local_folder, XSERVER_HOST, XSERVER_USER, &job.remote_folder) {
+            Ok(local_folder) => job.local_folder = local_folder,
+            Err(e) => {
+                self.state.batch_job_cancels.finish_job(job_index, &self.state.backup_control);
+                counters.finish_job();
+                return BackupJobResult {
+                    remote_folder: job.remote_folder,
+                    local_folder: job.local_folder,
+                    success: false,
+                    cancelled: false,
+                    message: e,
+                    transferred_files: 0,
+                    transferred_bytes: 0,
+                    elapsed_seconds: 0,
+                };
+            }
+        }
+
+        let job_options = self.job_options(options, &job.remote_folder);
+        let result = client.backup_folder_with_progress(
+            &job.remote_folder,
+            &job.local_folder,
+            self.state.backup_control.clone(),
+            &job_options,
+            progress_callback,
+        ).await;
+        self.state.progress_events.flush(self.app_handle);
+
+        let elapsed_seconds = job_start.elapsed().as_secs();
+
+        // ジョブ単位のキャンセルで中断した場合は、失敗ではなくキャンセルとして記録して次のジョブへ
+        // （キャンセルが間に合わずに終わった場合は、そのままの結果を記録する）
+        let job_cancelled = self.state.batch_job_cancels.finish_job(job_index, &self.state.backup_control);
+        let run = self.backup_run(&job, timestamp, job_start);
+        if job_cancelled && result.is_err() {
+            self.record_cancelled_job(&run, &last_progress, job_options, elapsed_seconds)
+        } else {
+            self.record_finished_job(&run, result, &last_progress, job_options, elapsed_seconds)
+        }
+    }
+
+    /// 履歴に記録するジョブの対象と開始時刻
+    fn backup_run<'b>(&'b self, job: &'b BackupJob, timestamp: u64, start_time: Instant) -> BackupRun<'b> {
+        BackupRun {
+            remote_folder: &job.remote_folder,
+            local_folder: &job.local_folder,
+            key_path: self.key_path,
+            profile_name: self.profile_name,
+            timestamp,
+            start_time,
+        }
+    }
+
+    /// 開始前にキャンセルされたジョブを、実行せずにキャンセルとして記録する
+    fn skip_cancelled_job(&self, job: BackupJob, timestamp: u64, job_start: Instant, options: &BackupOptions) -> BackupJobResult {
+        let counters = &self.state.multi_backup_counters;
+        counters.cancel_job();
+        let _ = self.app_handle.emit(MULTI_BACKUP_PROGRESS_EVENT, counters.snapshot());
+
+        let message = "ジョブがキャンセルされたため実行しませんでした".to_string();
+        let run = self.backup_run(&job, timestamp, job_start);
+        save_history_entry(self.state, BackupHistoryEntry {
+            elapsed_seconds: 0,
+            ..run.history_entry(BackupStatus::Cancelled, message.clone(), options.clone())
+        });
+
+        BackupJobResult {
+            remote_folder: job.remote_folder,
+            local_folder: job.local_folder,
+            success: false,
+            cancelled: true,
+            message,
+            transferred_files: 0,
+            transferred_bytes: 0,
+            elapsed_seconds: 0,
+        }
+    }
+
+    /// 進捗にジョブ番号を付けて通知し、全体の進捗も通知するコールバック（最後の進捗は履歴記録用に保持）
+    ///
+    /// 全体の進捗はジョブの進捗と一緒に間引き、受信確認の際に保持していた分を送る
+    fn job_progress_callback(
+        &self,
+        job_index: usize,
+        last_progress: Arc<Mutex<Option<ssh_client::BackupProgress>>>,
+    ) -> impl Fn(ssh_client::BackupProgress) + Send + Sync + 'static {
+        let app_handle = self.app_handle.clone();
+        let counters = self.state.multi_backup_counters.clone();
+        let progress_events = self.state.progress_events.clone();
+        let job_count = self.job_count;
+        move |mut progress: ssh_client::BackupProgress| {
+            progress.job_index = Some(job_index);
+            progress.job_count = Some(job_count);
+            counters.update_job(&progress);
+            if let Ok(mut last) = last_progress.lock() {
+                *last = Some(progress.clone());
+            }
+            progress_events.emit_with_batch(&app_handle, progress, counters.snapshot());
+        }
+    }
+
+    /// ジョブのオプション（前回以降の更新のみ転送する場合の基準時刻はジョブ（リモートフォルダ）ごとに異なる）
+    fn job_options(&self, options: &BackupOptions, remote_folder: &str) -> BackupOptions {
+        let mut job_options = options.clone();
+        if let Err(e) = apply_since_last_backup(self.state, remote_folder, &mut job_options) {
+            tracing::warn!("前回のバックアップ日時を取得できないため全ファイルを転送します: {}", e);
+            job_options.modified_since = None;
+        }
+        apply_clock_skew(&mut job_options, self.profile, remote_folder);
+        job_options
+    }
+
+    /// ジョブ単位のキャンセルで中断したジョブを履歴に記録し、結果を返す（中断までの転送量を記録する）
+    fn record_cancelled_job(
+        &self,
+        run: &BackupRun<'_>,
+        last_progress: &Mutex<Option<ssh_client::BackupProgress>>,
+        job_options: BackupOptions,
+        elapsed_seconds: u64,
+    ) -> BackupJobResult {
+        let counters = &self.state.multi_backup_counters;
+        counters.cancel_job();
+        let _ = self.app_handle.emit(MULTI_BACKUP_PROGRESS_EVENT, counters.snapshot());
+
+        let InterruptedTransfer { transferred_files, transferred_bytes, phase_timings, failed_files } =
+            InterruptedTransfer::from_last_progress(last_progress);
+
+        let message = "ジョブがキャンセルされました".to_string();
+        save_history_entry(self.state, BackupHistoryEntry {
+            transferred_files,
+            transferred_bytes,
+            elapsed_seconds,
+            phase_timings,
+            failed_files,
+            ..run.history_entry(BackupStatus::Cancelled, message.clone(), job_options)
+        });
+
+        BackupJobResult {
+            remote_folder: run.remote_folder.to_string(),
+            local_folder: run.local_folder.to_string(),
+            success: false,
+            cancelled: true,
+            message,
+            transferred_files,
+            transferred_bytes,
+            elapsed_seconds,
+        }
+    }
+
+    /// 完了・失敗したジョブを履歴に記録し、結果を返す
+    fn record_finished_job(
+        &self,
+        run: &BackupRun<'_>,
+        result: anyhow::Result<String>,
+        last_progress: &Mutex<Option<ssh_client::BackupProgress>>,
+        job_options: BackupOptions,
+        elapsed_seconds: u64,
+    ) -> BackupJobResult {
+        let timed_out_file = result.as_ref().err().and_then(ClassifiedError::timed_out_file_of);
+        let (success, message, transferred_files, transferred_bytes, phase_timings, error_kind, failed_files) = match result {
+            Ok(message) => {
+                let (transferred_bytes, phase_timings, failed_files) = last_progress
+                    .lock()
+                    .ok()
+                    .and_then(|last| last.as_ref().map(|progress| {
+                        (progress.transferred_bytes, progress.phase_timings.clone(), progress.failed_files)
+                    }))
+                    .unwrap_or_default();
+                let transferred_files = parse_transferred_files(&message);
+                (true, message, transferred_files, transferred_bytes, phase_timings, None, failed_files)
+            }
+            Err(e) => (false, format!("バックアップ失敗: {}", e), 0, 0, None, ClassifiedError::kind_of(&e), 0),
+        };
+        let status = match (success, failed_files) {
+            (false, _) => BackupStatus::Failed,
+            (true, 0) => BackupStatus::Success,
+            (true, _) => BackupStatus::PartialSuccess,
+        };
+
+        let counters = &self.state.multi_backup_counters;
+        counters.finish_job();
+        let _ = self.app_handle.emit(MULTI_BACKUP_PROGRESS_EVENT, counters.snapshot());
+
+        save_history_entry(self.state, BackupHistoryEntry {
+            transferred_files,
+            transferred_bytes,
+            elapsed_seconds,
+            phase_timings,
+            error_kind,
+            timed_out_file,
+            failed_files,
+            ..run.history_entry(status, message.clone(), job_options)
+        });
+
+        BackupJobResult {
+            remote_folder: run.remote_folder.to_string(),
+            local_folder: run.local_folder.to_string(),
+            success,
+            cancelled: false,
+            message,
+            transferred_files,
+            transferred_bytes,
+            elapsed_seconds,
+        }
+    }
+}
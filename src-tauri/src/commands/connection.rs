@@ -0,0 +1,257 @@
+use super::*;
+
+// 接続テスト＋ドメイン探索の結果構造体
+#[derive(Serialize)]
+pub struct ConnectAndDiscoverResult {
+    pub connection_message: String,
+    pub domains: Vec<String>,
+}
+
+#[tauri::command]
+pub async fn test_xserver_connection(
+    state: State<'_, AppState>,
+    key_path: String,
+    connect_timeout_secs: Option<u64>,
+    jump_host: Option<SshConfig>,
+    profile_name: Option<String>,
+) -> Result<String, String> {
+    let profile = profile_name.as_deref().map(|name| load_profile(&state, name)).transpose()?;
+    let config = profile_ssh_config(key_path, connect_timeout_secs, jump_host, profile.as_ref());
+
+    let mut client = SshClient::new(config);
+
+    match client.test_connection().await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("X-Server SSH接続テストに失敗しました: {}", e)),
+    }
+}
+
+// 鍵で認証できるかだけを確認（コマンドを実行しないため接続テストより速い）
+#[tauri::command]
+pub async fn test_auth_only(
+    state: State<'_, AppState>,
+    key_path: String,
+    connect_timeout_secs: Option<u64>,
+    profile_name: Option<String>,
+    jump_host: Option<SshConfig>,
+) -> Result<String, String> {
+    let profile = profile_name.as_deref().map(|name| load_profile(&state, name)).transpose()?;
+    let mut client = SshClient::new(profile_ssh_config(key_path, connect_timeout_secs, jump_host, profile.as_ref()));
+
+    client.test_auth_only().await
+        .map_err(|e| format!("X-Server SSH認証テストに失敗しました: {}", e))
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn test_ssh_connection(
+    hostname: String,
+    port: u16,
+    username: String,
+    key_path: String,
+    connect_timeout_secs: Option<u64>,
+    prefer_ipv6: Option<bool>,
+    additional_key_paths: Option<Vec<String>>,
+    jump_host: Option<SshConfig>,
+    algorithms: Option<SshAlgorithms>,
+    connect_retries: Option<u32>,
+    per_attempt_timeout_secs: Option<u64>,
+    password: Option<String>,
+) -> Result<String, String> {
+    let config = SshConfig {
+        hostname,
+        port,
+        username,
+        key_path,
+        connect_timeout_secs: connect_timeout_secs.unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS),
+        prefer_ipv6: prefer_ipv6.unwrap_or(false),
+        additional_key_paths: additional_key_paths.unwrap_or_default(),
+        jump_host: jump_host.map(Box::new),
+        algorithms: algorithms.unwrap_or_default(),
+        connect_retries: connect_retries.unwrap_or(0),
+        per_attempt_timeout_secs,
+        auth_method: SshAuthMethod::from_password(password),
+    };
+
+    let mut client = SshClient::new(config);
+
+    match client.test_connection().await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("SSH接続テストに失敗しました: {}", e)),
+    }
+}
+
+// 1回の認証で接続テストとドメイン探索をまとめて実行
+#[tauri::command]
+pub async fn connect_and_discover(
+    state: State<'_, AppState>,
+    key_path: String,
+    connect_timeout_secs: Option<u64>,
+    jump_host: Option<SshConfig>,
+    profile_name: Option<String>,
+) -> Result<ConnectAndDiscoverResult, String> {
+    let profile = profile_name.as_deref().map(|name| load_profile(&state, name)).transpose()?;
+    let config = profile_ssh_config(key_path, connect_timeout_secs, jump_host, profile.as_ref());
+
+    let mut client = SshClient::new(config).with_cancel_flag(start_discovery(&state));
+
+    let connection_message = client.test_connection().await
+        .map_err(|e| format!("X-Server SSH接続テストに失敗しました: {}", e))?;
+
+    // 同じセッションを再利用してドメインを探索
+    let domains = client.find_domains().await
+        .map_err(|e| format!("X-Serverドメイン探索に失敗しました: {}", e))?;
+
+    store_domain_cache(&state, &domains);
+
+    Ok(ConnectAndDiscoverResult {
+        connection_message,
+        domains,
+    })
+}
+
+#[tauri::command]
+pub async fn check_key_security(key_path: String) -> Result<KeySecurityReport, String> {
+    ssh_key::check_key_security(&key_path)
+        .map_err(|e| format!("秘密鍵のチェックに失敗しました: {}", e))
+}
+
+#[tauri::command]
+pub async fn convert_key_to_pem(key_path: String, passphrase: Option<String>) -> Result<String, String> {
+    ssh_key::convert_key_to_pem(&key_path, passphrase.as_deref())
+        .map_err(|e| format!("秘密鍵のPEM変換に失敗しました: {}", e))
+}
+
+#[tauri::command]
+pub async fn find_xserver_domains(
+    state: State<'_, AppState>,
+    key_path: String,
+    jump_host: Option<SshConfig>,
+) -> Result<Vec<String>, String> {
+    let mut config = xserver_ssh_config(key_path, None);
+    config.jump_host = jump_host.map(Box::new);
+
+    let mut client = SshClient::new(config).with_cancel_flag(start_discovery(&state));
+
+    match client.find_domains().await {
+        Ok(domains) => {
+            store_domain_cache(&state, &domains);
+            Ok(domains)
+        }
+        Err(e) => Err(format!("X-Serverドメイン探索に失敗しました: {}", e)),
+    }
+}
+
+// キャッシュ済みのドメイン一覧を取得（未探索または有効期間切れの場合はNone）
+#[tauri::command]
+pub async fn get_cached_domains(
+    state: State<'_, AppState>,
+) -> Result<Option<CachedDomains>, String> {
+    let ttl_secs = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?
+        .load_settings()
+        .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?
+        .domain_cache_ttl_secs;
+
+    let mut cache = state.domain_cache.lock()
+        .map_err(|e| format!("ドメインキャッシュのロックに失敗しました: {}", e))?;
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    // 有効期間切れのキャッシュは破棄する
+    if cache.as_ref().is_some_and(|cached| now.saturating_sub(cached.cached_at) > ttl_secs) {
+        *cache = None;
+    }
+
+    Ok(cache.clone())
+}
+
+// キャッシュを無視してドメインを再探索
+#[tauri::command]
+pub async fn refresh_domains(
+    state: State<'_, AppState>,
+    key_path: String,
+    jump_host: Option<SshConfig>,
+) -> Result<Vec<String>, String> {
+    find_xserver_domains(state, key_path, jump_host).await
+}
+
+/// 探索したドメイン一覧をキャッシュに保存
+fn store_domain_cache(state: &State<'_, AppState>, domains: &[String]) {
+    let cached_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    if let Ok(mut cache) = state.domain_cache.lock() {
+        *cache = Some(CachedDomains {
+            domains: domains.to_vec(),
+            cached_at,
+        });
+    }
+}
+
+// X-Serverへの接続を診断（合意したアルゴリズム・認証方法・使えるコマンドなど）
+#[tauri::command]
+pub async fn diagnose_connection(
+    state: State<'_, AppState>,
+    key_path: String,
+    jump_host: Option<SshConfig>,
+    profile_name: Option<String>,
+) -> Result<ConnectionDiagnostics, String> {
+    let profile = profile_name.as_deref().map(|name| load_profile(&state, name)).transpose()?;
+    let config = profile_ssh_config(key_path, None, jump_host, profile.as_ref());
+
+    let mut client = SshClient::new(config).with_cancel_flag(start_discovery(&state));
+
+    client
+        .diagnose_connection()
+        .await
+        .map_err(|e| format!("接続の診断に失敗しました: {}", e))
+}
+
+/// サーバーの時刻とローカルの時計とのずれを計測する
+///
+/// `profile_name` を指定した場合は、計測したずれをそのプロファイルに保存し、以降のバックアップで
+/// 前回以降の更新の判定に使う
+#[tauri::command]
+pub async fn get_server_time(
+    state: State<'_, AppState>,
+    key_path: String,
+    profile_name: Option<String>,
+    jump_host: Option<SshConfig>,
+) -> Result<ServerTime, String> {
+    let profile = profile_name.as_deref().map(|name| load_profile(&state, name)).transpose()?;
+    let mut client = SshClient::new(profile_ssh_config(key_path, None, jump_host, profile.as_ref()));
+
+    let server_time = client
+        .get_server_time()
+        .await
+        .map_err(|e| format!("サーバーの時刻の取得に失敗しました: {}", e))?;
+
+    if let Some(profile_name) = profile_name {
+        let config_manager = state.config_manager.lock()
+            .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?;
+        let mut settings = config_manager.load_settings()
+            .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?;
+        let profile = settings.backup_configs
+            .iter_mut()
+            .find(|config| config.name == profile_name)
+            .ok_or_else(|| format!("プロファイルが見つかりません: {}", profile_name))?;
+        profile.clock_skew_secs = Some(server_time.skew_seconds);
+        config_manager.save_settings(&settings)
+            .map_err(|e| format!("設定の保存に失敗しました: {}", e))?;
+    }
+
+    Ok(server_time)
+}
+
+// 実行中の接続テスト・ドメイン探索・ディレクトリ探索を中断
+#[tauri::command]
+pub async fn cancel_discovery(state: State<'_, AppState>) -> Result<(), String> {
+    state.discovery_cancel.store(true, Ordering::SeqCst);
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use super::*;
+
+#[tauri::command]
+pub async fn cancel_backup(state: State<'_, AppState>) -> Result<(), String> {
+    state.batch_job_cancels.cancel_batch();
+    state.backup_control.cancel();
+    Ok(())
+}
+
+// 一括バックアップの指定したジョブ（1始まり）だけをキャンセルし、残りのジョブは続ける
+#[tauri::command]
+pub async fn cancel_job(state: State<'_, AppState>, job_index: usize) -> Result<(), String> {
+    state.batch_job_cancels.cancel_job(Some(job_index), &state.backup_control)?;
+    Ok(())
+}
+
+// 一括バックアップで転送中のジョブだけをキャンセルし、次のジョブに進む（キャンセルしたジョブ番号を返す）
+#[tauri::command]
+pub async fn skip_current_job(state: State<'_, AppState>) -> Result<usize, String> {
+    state.batch_job_cancels.cancel_job(None, &state.backup_control)
+}
+
+// キャンセルに加え、今回のバックアップで新規作成したフォルダを削除する
+#[tauri::command]
+pub async fn cancel_backup_and_cleanup(state: State<'_, AppState>) -> Result<(), String> {
+    state.batch_job_cancels.cancel_batch();
+    state.backup_control.cancel_with_cleanup();
+    Ok(())
+}
+
+// バックアップ先のフォルダをOSのファイルマネージャー（Finder・エクスプローラーなど）で開く
+#[tauri::command]
+pub async fn open_backup_folder(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    path: String,
+) -> Result<(), String> {
+    open_folder_in_file_manager(&state, &app_handle, &path)
+}
+
+// 履歴エントリのバックアップ先のフォルダをOSのファイルマネージャーで開く
+#[tauri::command]
+pub async fn open_history_entry_folder(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    entry_id: String,
+) -> Result<(), String> {
+    let entry = state.backup_history_manager.lock()
+        .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?
+        .get_entry(&entry_id)
+        .map_err(|e| format!("履歴の取得に失敗しました: {}", e))?
+        .ok_or_else(|| format!("履歴が見つかりません: {}", entry_id))?;
+
+    open_folder_in_file_manager(&state, &app_handle, &entry.local_path)
+}
+
+/// フォルダが存在し、保存先として許可されたフォルダの配下にあることを確認してから開く
+fn open_folder_in_file_manager(state: &State<'_, AppState>, app_handle: &tauri::AppHandle, path: &str) -> Result<(), String> {
+    use tauri_plugin_shell::ShellExt;
+
+    let folder = std::path::Path::new(path)
+        .canonicalize()
+        .map_err(|e| format!("フォルダが見つかりません: {}: {}", path, e))?;
+    if !folder.is_dir() {
+        return Err(format!("フォルダではありません: {}", path));
+    }
+
+    SshClient::check_allowed_backup_root(&folder, &load_allowed_backup_roots(state)?)
+        .map_err(|e| e.to_string())?;
+
+    let file_manager = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+
+    // Windows の正規化したパス（\\?\ 付き）はエクスプローラーで開けないため、確認済みの元のパスを渡す。
+    // エクスプローラーは成功しても終了コードが0にならないため、終了を待たない
+    app_handle.shell()
+        .command(file_manager)
+        .arg(path)
+        .spawn()
+        .map_err(|e| format!("フォルダを開けませんでした: {}", e))?;
+    Ok(())
+}
+
+// フロントエンドが backup-progress イベントを処理したことを通知（保持している最新の進捗を送る）
+#[tauri::command]
+pub async fn ack_backup_progress(state: State<'_, AppState>, app_handle: tauri::AppHandle) -> Result<(), String> {
+    state.progress_events.acknowledge(&app_handle);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn pause_backup(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.backup_control.pause())
+}
+
+#[tauri::command]
+pub async fn resume_backup(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.backup_control.resume())
+}
+
+// バックアップ（一括バックアップを含む）を実行中か（開始ボタンの無効化などに使う）
+#[tauri::command]
+pub async fn is_any_backup_running(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.backup_running.load(Ordering::Acquire))
+}
+
+#[tauri::command]
+pub async fn is_backup_cancelled(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.backup_control.is_cancelled())
+}
@@ -0,0 +1,157 @@
+use super::*;
+
+#[tauri::command]
+pub async fn get_backup_history(
+    state: State<'_, AppState>,
+) -> Result<Vec<BackupHistoryEntry>, String> {
+    let history_manager = state.backup_history_manager.lock()
+        .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?;
+
+    history_manager.get_recent_history(50) // 最新50件を取得
+        .map_err(|e| format!("バックアップ履歴の取得に失敗しました: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_backup_statistics(
+    state: State<'_, AppState>,
+) -> Result<BackupStatistics, String> {
+    let history_manager = state.backup_history_manager.lock()
+        .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?;
+
+    history_manager.get_statistics()
+        .map_err(|e| format!("統計情報の取得に失敗しました: {}", e))
+}
+
+// バックアップ先（ホストとリモートパス）ごとの統計情報を取得
+#[tauri::command]
+pub async fn get_statistics_grouped(
+    state: State<'_, AppState>,
+) -> Result<Vec<TargetStatistics>, String> {
+    let history_manager = state.backup_history_manager.lock()
+        .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?;
+
+    history_manager.get_statistics_grouped()
+        .map_err(|e| format!("統計情報の取得に失敗しました: {}", e))
+}
+
+#[tauri::command]
+pub async fn clear_backup_history(
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let history_manager = state.backup_history_manager.lock()
+        .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?;
+
+    history_manager.clear_history()
+        .map_err(|e| format!("履歴のクリアに失敗しました: {}", e))
+}
+
+#[tauri::command]
+pub async fn delete_backup_entry(
+    state: State<'_, AppState>,
+    entry_id: String,
+) -> Result<bool, String> {
+    let history_manager = state.backup_history_manager.lock()
+        .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?;
+
+    history_manager.delete_backup_entry(&entry_id)
+        .map_err(|e| format!("履歴エントリの削除に失敗しました: {}", e))
+}
+
+#[tauri::command]
+pub async fn delete_history_matching(
+    state: State<'_, AppState>,
+    query: HistoryQuery,
+) -> Result<usize, String> {
+    let history_manager = state.backup_history_manager.lock()
+        .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?;
+
+    history_manager.delete_entries_matching(&query)
+        .map_err(|e| format!("履歴エントリの一括削除に失敗しました: {}", e))
+}
+
+// 履歴ファイルを整理・検証して書き直す（重複の削除・並べ替え・統計の再計算）
+//
+// 署名が一致しない履歴は、ユーザーが内容を確認して resign を指定した場合のみ再署名する
+#[tauri::command]
+pub async fn repair_history(
+    state: State<'_, AppState>,
+    resign: Option<bool>,
+) -> Result<HistoryRepairReport, String> {
+    let history_manager = state.backup_history_manager.lock()
+        .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?;
+
+    history_manager.repair_history(resign.unwrap_or(false))
+        .map_err(|e| format!("履歴の修復に失敗しました: {}", e))
+}
+
+// 2つのローカルバックアップの差分（追加・削除・変更）を取得
+#[tauri::command]
+pub async fn diff_backups(
+    path_a: String,
+    path_b: String,
+    compare_content: Option<bool>,
+) -> Result<BackupDiff, String> {
+    backup_diff::diff_backups(
+        std::path::Path::new(&path_a),
+        std::path::Path::new(&path_b),
+        compare_content.unwrap_or(false),
+    )
+    .map_err(|e| format!("バックアップの比較に失敗しました: {}", e))
+}
+
+// 暗号化したバックアップを別のフォルダに復号
+#[tauri::command]
+pub async fn decrypt_backup(
+    state: State<'_, AppState>,
+    path: String,
+    passphrase: String,
+    dest: String,
+) -> Result<DecryptReport, String> {
+    SshClient::check_allowed_backup_root(std::path::Path::new(&dest), &load_allowed_backup_roots(&state)?)
+        .map_err(|e| e.to_string())?;
+    tokio::task::spawn_blocking(move || {
+        backup_crypto::decrypt_backup(
+            &SshClient::extended_length_path(std::path::Path::new(&path)),
+            &Passphrase::new(passphrase),
+            &SshClient::extended_length_path(std::path::Path::new(&dest)),
+        )
+    })
+    .await
+    .map_err(|e| format!("バックアップの復号に失敗しました: {}", e))?
+    .map_err(|e| format!("バックアップの復号に失敗しました: {:#}", e))
+}
+
+// 中断したバックアップが残した一時ファイル（.kyosho-part など）を一覧
+#[tauri::command]
+pub async fn find_partial_files(
+    state: State<'_, AppState>,
+    local_folder: String,
+) -> Result<Vec<PartialFile>, String> {
+    let cancel_flag = start_discovery(&state);
+    tokio::task::spawn_blocking(move || {
+        partial_files::find_partial_files(std::path::Path::new(&local_folder), &cancel_flag)
+    })
+    .await
+    .map_err(|e| format!("一時ファイルの検索に失敗しました: {}", e))?
+    .map_err(|e| format!("一時ファイルの検索に失敗しました: {}", e))
+}
+
+// 中断したバックアップが残した一時ファイル（.kyosho-part など）を削除
+#[tauri::command]
+pub async fn clean_partial_files(
+    state: State<'_, AppState>,
+    local_folder: String,
+) -> Result<CleanPartialFilesReport, String> {
+    // 実行中のバックアップが書き込んでいる一時ファイルを消さないよう、バックアップと同時には行わない
+    let _running = BackupRunGuard::acquire(&state.backup_running)
+        .map_err(|_| "バックアップ実行中は一時ファイルを削除できません".to_string())?;
+    SshClient::check_allowed_backup_root(std::path::Path::new(&local_folder), &load_allowed_backup_roots(&state)?)
+        .map_err(|e| e.to_string())?;
+    let cancel_flag = start_discovery(&state);
+    tokio::task::spawn_blocking(move || {
+        partial_files::clean_partial_files(std::path::Path::new(&local_folder), &cancel_flag)
+    })
+    .await
+    .map_err(|e| format!("一時ファイルの削除に失敗しました: {}", e))?
+    .map_err(|e| format!("一時ファイルの削除に失敗しました: {}", e))
+}
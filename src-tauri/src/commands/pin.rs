@@ -0,0 +1,83 @@
+use super::*;
+
+// 設定済みのPINを変更する場合は、保護している操作を迂回できないよう現在のPINを求める
+#[tauri::command]
+pub async fn setup_pin(
+    state: State<'_, AppState>,
+    pin: String,
+    current_pin: Option<String>,
+) -> Result<(), String> {
+    let pin_enabled = state.auth_manager.lock()
+        .map_err(|e| format!("認証管理のロックに失敗しました: {}", e))?
+        .is_pin_enabled()
+        .map_err(|e| format!("PIN状態の確認に失敗しました: {}", e))?;
+    if pin_enabled {
+        let current_pin = current_pin.ok_or("PINを変更するには現在のPINが必要です")?;
+        require_pin(&state, &current_pin, true)?;
+    }
+    let auth_manager = state.auth_manager.lock()
+        .map_err(|e| format!("認証管理のロックに失敗しました: {}", e))?;
+
+    auth_manager.setup_pin(&pin)
+        .map_err(|e| format!("PIN設定に失敗しました: {}", e))
+}
+
+#[tauri::command]
+pub async fn verify_pin(
+    state: State<'_, AppState>,
+    pin: String,
+) -> Result<bool, String> {
+    let auth_manager = state.auth_manager.lock()
+        .map_err(|e| format!("認証管理のロックに失敗しました: {}", e))?;
+
+    auth_manager.verify_pin(&pin)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn is_pin_enabled(
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let auth_manager = state.auth_manager.lock()
+        .map_err(|e| format!("認証管理のロックに失敗しました: {}", e))?;
+
+    auth_manager.is_pin_enabled()
+        .map_err(|e| format!("PIN状態の確認に失敗しました: {}", e))
+}
+
+// PINを無効にすると保護している操作をPINなしで行えるため、現在のPINを求める
+#[tauri::command]
+pub async fn disable_pin(
+    state: State<'_, AppState>,
+    pin: String,
+) -> Result<(), String> {
+    require_pin(&state, &pin, true)?;
+    let auth_manager = state.auth_manager.lock()
+        .map_err(|e| format!("認証管理のロックに失敗しました: {}", e))?;
+
+    auth_manager.disable_pin()
+        .map_err(|e| format!("PIN無効化に失敗しました: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_lockout_remaining_minutes(
+    state: State<'_, AppState>,
+) -> Result<Option<u32>, String> {
+    let auth_manager = state.auth_manager.lock()
+        .map_err(|e| format!("認証管理のロックに失敗しました: {}", e))?;
+
+    auth_manager.get_lockout_remaining_minutes()
+        .map_err(|e| format!("ロックアウト状態の確認に失敗しました: {}", e))
+}
+
+// ロック画面の表示用に認証状態を取得（PINの検証・失敗回数の更新はしない）
+#[tauri::command]
+pub async fn get_auth_status(
+    state: State<'_, AppState>,
+) -> Result<AuthStatus, String> {
+    let auth_manager = state.auth_manager.lock()
+        .map_err(|e| format!("認証管理のロックに失敗しました: {}", e))?;
+
+    auth_manager.get_auth_status()
+        .map_err(|e| format!("認証状態の確認に失敗しました: {}", e))
+}
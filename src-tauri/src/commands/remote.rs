@@ -0,0 +1,318 @@
+use super::*;
+
+/// ディレクトリ一覧のページ取得で件数を省略した場合の既定値
+const DEFAULT_DIRECTORY_PAGE_SIZE: usize = 500;
+
+/// ディレクトリ一覧の続きを取得するために開いたままにしておくフォルダの数の上限（古いものから閉じる）
+const MAX_OPEN_DIRECTORY_LISTINGS: usize = 8;
+
+/// 続きが取得されないまま、開いたフォルダを閉じるまでの時間
+const DIRECTORY_LISTING_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// リモートのツリー取得で深さを省略した場合の既定値
+const DEFAULT_REMOTE_TREE_DEPTH: usize = 3;
+
+/// 転送量の見積もりで時間の上限を省略した場合の既定値（秒）
+const DEFAULT_ESTIMATE_TIME_BUDGET_SECS: u64 = 60;
+
+// X-Serverのホームディレクトリがあるファイルシステムの空き容量（クォータを考慮）を取得
+#[tauri::command]
+pub async fn get_xserver_free_space(
+    state: State<'_, AppState>,
+    key_path: String,
+    jump_host: Option<SshConfig>,
+    profile_name: Option<String>,
+) -> Result<u64, String> {
+    let profile = profile_name.as_deref().map(|name| load_profile(&state, name)).transpose()?;
+    let config = profile_ssh_config(key_path, None, jump_host, profile.as_ref());
+    let home_path = format!("/home/{}", config.username);
+
+    let mut client = SshClient::new(config);
+
+    client.get_remote_free_space(&home_path).await
+        .map_err(|e| format!("サーバーの空き容量の確認に失敗しました: {}", e))
+}
+
+#[tauri::command]
+pub async fn list_xserver_directories(
+    state: State<'_, AppState>,
+    key_path: String,
+    path: String,
+    jump_host: Option<SshConfig>,
+) -> Result<Vec<String>, String> {
+    let mut config = xserver_ssh_config(key_path, None);
+    config.jump_host = jump_host.map(Box::new);
+
+    let mut client = SshClient::new(config).with_cancel_flag(start_discovery(&state));
+
+    match client.list_remote_directories(&path).await {
+        Ok(dirs) => Ok(dirs),
+        Err(e) => Err(format!("X-Serverディレクトリ探索に失敗しました: {}", e)),
+    }
+}
+
+// X-Serverのディレクトリ一覧をページ単位で取得（エントリが非常に多いフォルダ向け）
+//
+// cursor を省略すると path を開いて最初のページを返す。続きがある場合は返した cursor を渡すと、
+// 開いたままのフォルダの続きから読み取る（path などの接続情報は使わない）
+#[tauri::command]
+pub async fn list_xserver_directories_paged(
+    state: State<'_, AppState>,
+    key_path: String,
+    path: String,
+    cursor: Option<String>,
+    limit: Option<usize>,
+    jump_host: Option<SshConfig>,
+) -> Result<DirectoryPage, String> {
+    let limit = limit.unwrap_or(DEFAULT_DIRECTORY_PAGE_SIZE);
+    if limit == 0 {
+        return Err("1ページの件数は1以上を指定してください".to_string());
+    }
+
+    let cancel_flag = start_discovery(&state);
+    let mut listing = match cursor {
+        Some(cursor) => state.directory_listings.take(&cursor)?,
+        None => {
+            let mut config = xserver_ssh_config(key_path, None);
+            config.jump_host = jump_host.map(Box::new);
+
+            SshClient::new(config)
+                .with_cancel_flag(cancel_flag)
+                .open_directory_listing(&path)
+                .await
+                .map_err(|e| format!("X-Serverディレクトリ探索に失敗しました: {}", e))?
+        }
+    };
+
+    let mut page = listing.next_page(limit)
+        .map_err(|e| format!("X-Serverディレクトリ探索に失敗しました: {}", e))?;
+    if page.has_more {
+        page.cursor = Some(state.directory_listings.keep(listing));
+    }
+    Ok(page)
+}
+
+/// ページ単位のディレクトリ一覧で、続きを取得するために開いたままのフォルダ（カーソルごと）
+///
+/// 続きが取得されないまま `DIRECTORY_LISTING_IDLE_TIMEOUT` が過ぎたものと、
+/// `MAX_OPEN_DIRECTORY_LISTINGS` を超えた古いものは閉じる
+#[derive(Default)]
+pub struct DirectoryListings {
+    inner: Mutex<std::collections::HashMap<String, (RemoteDirectoryListing, Instant)>>,
+}
+
+impl DirectoryListings {
+    fn lock(&self) -> std::sync::MutexGuard<'_, std::collections::HashMap<String, (RemoteDirectoryListing, Instant)>> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// カーソルの続きを取り出す（取り出したカーソルは使えなくなる）
+    fn take(&self, cursor: &str) -> Result<RemoteDirectoryListing, String> {
+        self.lock()
+            .remove(cursor)
+            .map(|(listing, _)| listing)
+            .ok_or_else(|| "ディレクトリ一覧の続きが見つかりません。時間が経って閉じられたため、最初から読み込み直してください".to_string())
+    }
+
+    /// 続きを取得できるよう保持し、新しいカーソルを返す
+    fn keep(&self, listing: RemoteDirectoryListing) -> String {
+        let mut listings = self.lock();
+        listings.retain(|_, (_, kept_at)| kept_at.elapsed() < DIRECTORY_LISTING_IDLE_TIMEOUT);
+        while listings.len() >= MAX_OPEN_DIRECTORY_LISTINGS {
+            let oldest = listings
+                .iter()
+                .min_by_key(|(_, (_, kept_at))| *kept_at)
+                .map(|(cursor, _)| cursor.clone());
+            match oldest {
+                Some(cursor) => listings.remove(&cursor),
+                None => break,
+            };
+        }
+
+        let cursor = format!("{:016x}", rand::random::<u64>());
+        listings.insert(cursor.clone(), (listing, Instant::now()));
+        cursor
+    }
+}
+
+// X-Serverのフォルダ構造をツリーで取得（バックアップ対象の選択用、読み取りのみ）
+#[tauri::command]
+pub async fn get_remote_tree(
+    state: State<'_, AppState>,
+    key_path: String,
+    root: String,
+    max_depth: Option<usize>,
+    jump_host: Option<SshConfig>,
+    profile_name: Option<String>,
+) -> Result<RemoteTree, String> {
+    let profile = profile_name.as_deref().map(|name| load_profile(&state, name)).transpose()?;
+    let config = profile_ssh_config(key_path, None, jump_host, profile.as_ref());
+
+    let mut client = SshClient::new(config).with_cancel_flag(start_discovery(&state));
+
+    client
+        .get_remote_tree(&root, max_depth.unwrap_or(DEFAULT_REMOTE_TREE_DEPTH))
+        .await
+        .map_err(|e| format!("リモートのフォルダ構造の取得に失敗しました: {}", e))
+}
+
+// 転送せずにバックアップと同じ判定で転送量を見積もる（前回以降の更新のみ・インデックス・参照バックアップなど）
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn estimate_incremental(
+    state: State<'_, AppState>,
+    key_path: String,
+    remote_folder: String,
+    local_folder: String,
+    options: Option<BackupOptions>,
+    time_budget_secs: Option<u64>,
+    profile_name: Option<String>,
+    jump_host: Option<SshConfig>,
+) -> Result<IncrementalEstimate, String> {
+    let mut options = options.unwrap_or_default();
+    apply_app_settings(&state, &mut options)?;
+    apply_since_last_backup(&state, &remote_folder, &mut options)?;
+    let profile = profile_name.as_deref().map(|name| load_profile(&state, name)).transpose()?;
+    apply_clock_skew(&mut options, profile.as_ref(), &remote_folder);
+    apply_profile_max_file_size(&mut options, profile.as_ref());
+
+    let local_folder = expand_local_folder(&state, &local_folder, XSERVER_HOST, XSERVER_USER, &remote_folder)?;
+
+    let config = profile_ssh_config(key_path, None, jump_host, profile.as_ref());
+
+    let mut client = SshClient::new(config).with_cancel_flag(start_discovery(&state));
+
+    let time_budget = Duration::from_secs(time_budget_secs.unwrap_or(DEFAULT_ESTIMATE_TIME_BUDGET_SECS));
+    client
+        .estimate_incremental(&remote_folder, &local_folder, &options, time_budget)
+        .await
+        .map_err(|e| format!("転送量の見積もりに失敗しました: {}", e))
+}
+
+/// リモートファイルの読み取りサイズの既定値（64KB）
+const READ_REMOTE_FILE_DEFAULT_MAX_BYTES: u64 = 64 * 1024;
+
+// サーバー上のテキストファイル（wp-config.php など）の先頭を読み取って確認
+#[tauri::command]
+pub async fn read_remote_file(
+    state: State<'_, AppState>,
+    key_path: String,
+    remote_path: String,
+    max_bytes: Option<u64>,
+    jump_host: Option<SshConfig>,
+    profile_name: Option<String>,
+) -> Result<String, String> {
+    let profile = profile_name.as_deref().map(|name| load_profile(&state, name)).transpose()?;
+    let mut client = SshClient::new(profile_ssh_config(key_path, None, jump_host, profile.as_ref()));
+
+    client.read_remote_file(&remote_path, max_bytes.unwrap_or(READ_REMOTE_FILE_DEFAULT_MAX_BYTES)).await
+        .map_err(|e| format!("リモートファイルの読み取りに失敗しました: {}", e))
+}
+
+/// データベースのダンプにかける時間の既定の上限（秒）
+const DEFAULT_MYSQL_DUMP_TIME_LIMIT_SECS: u64 = 600;
+
+// バックアップ前にサーバーでデータベースをダンプし、バックアップ対象のフォルダに書き出す
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn dump_remote_mysql(
+    state: State<'_, AppState>,
+    key_path: String,
+    db_name: String,
+    db_user: String,
+    db_pass: String,
+    remote_dump_path: String,
+    time_limit_secs: Option<u64>,
+    jump_host: Option<SshConfig>,
+    profile_name: Option<String>,
+) -> Result<MysqlDumpResult, String> {
+    let profile = profile_name.as_deref().map(|name| load_profile(&state, name)).transpose()?;
+    let mut client = SshClient::new(profile_ssh_config(key_path, None, jump_host, profile.as_ref()));
+    let time_limit = Duration::from_secs(time_limit_secs.unwrap_or(DEFAULT_MYSQL_DUMP_TIME_LIMIT_SECS));
+
+    client.dump_remote_mysql(&db_name, &db_user, &db_pass, &remote_dump_path, time_limit).await
+        .map_err(|e| format!("データベースのダンプに失敗しました: {}", e))
+}
+
+/// 既存のバックアップをリモートと照合する際に、既定で内容（SHA-256）まで比較するファイル数
+const VERIFY_DEFAULT_HASH_SAMPLES: usize = 5;
+
+// 既存のバックアップがリモートと一致しているかを、ファイルを再ダウンロードせずに確認
+//
+// サイズと更新日時で比較し、一致したファイルの一部はリモートで計算したSHA-256とも照合する
+// （暗号化したバックアップでは内容を照合できないため、サイズと更新日時のみ）
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn verify_backup(
+    state: State<'_, AppState>,
+    key_path: String,
+    remote_folder: String,
+    local_folder: String,
+    hash_samples: Option<usize>,
+    jump_host: Option<SshConfig>,
+    profile_name: Option<String>,
+    exclude_patterns: Option<Vec<String>>,
+) -> Result<BackupDiff, String> {
+    let profile = profile_name.as_deref().map(|name| load_profile(&state, name)).transpose()?;
+    let mut client = SshClient::new(profile_ssh_config(key_path, None, jump_host, profile.as_ref()));
+
+    // バックアップ時に除外したファイルを「追加」と扱わないよう、同じ除外設定で一覧を取得する
+    let mut options = BackupOptions {
+        exclude_patterns: exclude_patterns.unwrap_or_default(),
+        ..BackupOptions::default()
+    };
+    apply_app_settings(&state, &mut options)?;
+
+    let remote_files: std::collections::BTreeMap<String, backup_diff::FileInfo> = client
+        .list_remote_files(&remote_folder, &options)
+        .await
+        .map_err(|e| format!("リモートのファイル一覧の取得に失敗しました: {}", e))?
+        .into_iter()
+        .map(|file| (file.relative_path, backup_diff::FileInfo { size: file.size, mtime: file.mtime }))
+        .collect();
+
+    // 深い階層が MAX_PATH を超えても読めるよう、拡張長パスで扱う（Windows のみ）
+    let local_root = &SshClient::extended_length_path(std::path::Path::new(&local_folder));
+    let mut diff = backup_diff::diff_local_with_remote(local_root, &remote_files)
+        .map_err(|e| format!("バックアップの照合に失敗しました: {}", e))?;
+
+    // サイズ・更新日時が一致したファイルから等間隔に抜き出して内容を照合
+    let mismatched: std::collections::HashSet<&String> = diff.added.iter().chain(&diff.modified).collect();
+    let matched: Vec<String> = remote_files
+        .keys()
+        .filter(|relative| !mismatched.contains(relative))
+        .cloned()
+        .collect();
+    let samples = if diff.encrypted {
+        0
+    } else {
+        hash_samples.unwrap_or(VERIFY_DEFAULT_HASH_SAMPLES).min(matched.len())
+    };
+
+    let sampled: Vec<&String> = (0..samples).map(|i| &matched[i * matched.len() / samples]).collect();
+    let remote_path = |relative: &str| format!("{}/{}", remote_folder.trim_end_matches('/'), relative);
+    let remote_paths: Vec<String> = sampled.iter().map(|relative| remote_path(relative)).collect();
+
+    // サンプルごとにコマンドを実行すると往復が増えるため、まとめて計算する
+    let remote_hashes = if remote_paths.is_empty() {
+        std::collections::HashMap::new()
+    } else {
+        client.remote_sha256_many(&remote_paths).await
+            .map_err(|e| format!("リモートのハッシュ計算に失敗しました: {}", e))?
+    };
+
+    for relative in sampled {
+        let remote_hash = remote_hashes.get(&remote_path(relative))
+            .ok_or_else(|| format!("リモートのハッシュ計算に失敗しました: {}", relative))?;
+        let local_hash = backup_diff::file_hash_hex(&local_root.join(relative))
+            .map_err(|e| format!("ローカルのハッシュ計算に失敗しました: {}", e))?;
+
+        diff.hash_checked_count += 1;
+        if *remote_hash != local_hash {
+            diff.modified.push(relative.clone());
+            diff.unchanged_count -= 1;
+        }
+    }
+
+    Ok(diff)
+}
@@ -0,0 +1,317 @@
+use super::*;
+
+// アプリのデータファイルのパス（`get_data_paths` の結果）
+#[derive(Serialize)]
+pub struct DataPaths {
+    /// 設定・認証・履歴ファイルを保存するフォルダ
+    pub data_dir: String,
+    /// 暗号化された設定ファイル
+    pub config_path: String,
+    /// 設定の暗号化キー（OSのキーチェーンは使わず、常にファイルに保存する）
+    pub key_path: String,
+    pub auth_settings_path: String,
+    pub lockout_path: String,
+    pub history_path: String,
+    /// 履歴ファイルの改ざん検知用の署名
+    pub history_signature_path: String,
+    /// 履歴の署名用の鍵
+    pub history_key_path: String,
+}
+
+/// バックアッププロファイルの名前を変更し、そのプロファイルで実行した履歴の記録も書き換える
+///
+/// 書き換えた履歴のエントリ数を返す
+#[tauri::command]
+pub async fn rename_profile(
+    state: State<'_, AppState>,
+    old_name: String,
+    new_name: String,
+) -> Result<usize, String> {
+    let new_name = new_name.trim().to_string();
+    {
+        let config_manager = state.config_manager.lock()
+            .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?;
+        let mut settings = config_manager.load_settings()
+            .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?;
+        settings.rename_backup_config(&old_name, &new_name)
+            .map_err(|e| format!("プロファイル名の変更に失敗しました: {}", e))?;
+        config_manager.save_settings(&settings)
+            .map_err(|e| format!("設定の保存に失敗しました: {}", e))?;
+    }
+
+    state.backup_history_manager.lock()
+        .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?
+        .rename_profile(&old_name, &new_name)
+        .map_err(|e| format!("プロファイル名は変更しましたが、履歴の更新に失敗しました: {}", e))
+}
+
+// 設定を保存（`renamed_profiles` は名前を変更したプロファイルの「新しい名前 → 変更前の名前」）
+#[tauri::command]
+pub async fn save_settings(
+    state: State<'_, AppState>,
+    settings: AppSettings,
+    renamed_profiles: Option<std::collections::HashMap<String, String>>,
+    pin: Option<String>,
+) -> Result<(), String> {
+    // フックのコマンドは任意のコマンドを実行するため、PINを確認できた場合だけ変更できる
+    let hooks_unlocked = match &pin {
+        Some(pin) => {
+            require_pin(&state, pin, true)?;
+            true
+        }
+        None => false,
+    };
+    let config_manager = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?;
+
+    // 保存先の制限はフロントエンドから外せないよう、保存済みのものを引き継ぐ
+    let mut settings = settings;
+    let saved = config_manager.load_settings()
+        .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?;
+    settings.allowed_backup_roots = saved.allowed_backup_roots;
+    // フックの実行許可も同様に、PINを求める set_allow_hooks でだけ変更できる
+    settings.allow_hooks = saved.allow_hooks;
+
+    // 設定に保存した秘密鍵はフロントエンドに渡していないため、同じプロファイル（名前を変更した場合は
+    // 変更前の名前）から引き継ぐ。PINがない場合はフックのコマンドも保存済みのものを引き継ぐ
+    let renamed_profiles = renamed_profiles.unwrap_or_default();
+    for config in &mut settings.backup_configs {
+        let saved_name = renamed_profiles.get(&config.name).unwrap_or(&config.name);
+        let saved_config = saved.backup_configs.iter().find(|saved| &saved.name == saved_name);
+        config.stored_private_key = saved_config.and_then(|saved| saved.stored_private_key.clone());
+        if !hooks_unlocked {
+            keep_saved_hooks(config, saved_config);
+        }
+    }
+
+    config_manager.save_settings(&settings)
+        .map_err(|e| format!("設定の保存に失敗しました: {}", e))
+}
+
+/// プロファイルのフックのコマンドを保存済みのものに戻す（保存済みのプロファイルがなければフックなし）
+fn keep_saved_hooks(config: &mut ssh_client::BackupConfig, saved: Option<&ssh_client::BackupConfig>) {
+    let pre_hook = saved.and_then(|saved| saved.pre_hook.clone());
+    let post_hook = saved.and_then(|saved| saved.post_hook.clone());
+    if config.pre_hook != pre_hook || config.post_hook != post_hook {
+        tracing::warn!("PINが入力されていないため、フックの変更を保存しません: {}", config.name);
+    }
+    config.pre_hook = pre_hook;
+    config.post_hook = post_hook;
+}
+
+// フックの実行を許可・禁止する
+//
+// フックは任意のコマンドを実行するため、許可する場合はPIN認証を設定したうえでPINの入力を求める
+#[tauri::command]
+pub async fn set_allow_hooks(
+    state: State<'_, AppState>,
+    allow: bool,
+    pin: String,
+) -> Result<(), String> {
+    require_pin(&state, &pin, allow)?;
+    let config_manager = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?;
+    let mut settings = config_manager.load_settings()
+        .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?;
+    settings.allow_hooks = allow;
+    config_manager.save_settings(&settings)
+        .map_err(|e| format!("設定の保存に失敗しました: {}", e))
+}
+
+// バックアップの保存先として許可するフォルダを追加（正規化したパスを返す）
+//
+// 保存先の制限を画面から外せないよう、PIN認証を設定したうえでPINの入力を求める
+#[tauri::command]
+pub async fn add_allowed_backup_root(
+    state: State<'_, AppState>,
+    path: String,
+    pin: String,
+) -> Result<String, String> {
+    require_pin(&state, &pin, true)?;
+    let config_manager = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?;
+
+    config_manager.add_allowed_backup_root(std::path::Path::new(&path))
+        .map(|root| root.to_string_lossy().to_string())
+        .map_err(|e| format!("許可するフォルダの追加に失敗しました: {}", e))
+}
+
+#[tauri::command]
+pub async fn load_settings(
+    state: State<'_, AppState>,
+) -> Result<AppSettings, String> {
+    let config_manager = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?;
+
+    let mut settings = config_manager.load_settings()
+        .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?;
+
+    // 設定に保存した秘密鍵はフロントエンドに渡さない
+    for config in &mut settings.backup_configs {
+        config.stored_private_key = None;
+    }
+    Ok(settings)
+}
+
+/// 秘密鍵ファイルの内容をプロファイルの設定に取り込む（以降の接続では鍵ファイルを使わない）
+///
+/// 取り込んだ鍵は設定ファイルと一緒に暗号化して保存する。鍵の形式を返す
+#[tauri::command]
+pub async fn import_profile_key(
+    state: State<'_, AppState>,
+    profile_name: String,
+    key_path: String,
+) -> Result<String, String> {
+    let key = StoredPrivateKey::import(std::path::Path::new(&key_path))
+        .map_err(|e| format!("秘密鍵の取り込みに失敗しました: {}", e))?;
+    let format = key.format().label().to_string();
+
+    let config_manager = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?;
+    let mut settings = config_manager.load_settings()
+        .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?;
+    let profile = settings.backup_configs
+        .iter_mut()
+        .find(|config| config.name == profile_name)
+        .ok_or_else(|| format!("プロファイルが見つかりません: {}", profile_name))?;
+    profile.stored_private_key = Some(key);
+    config_manager.save_settings(&settings)
+        .map_err(|e| format!("設定の保存に失敗しました: {}", e))?;
+
+    tracing::info!("秘密鍵をプロファイルの設定に取り込みました: {} ({})", profile_name, format);
+    Ok(format)
+}
+
+/// プロファイルの設定に保存した秘密鍵を削除する（以降は鍵ファイルで認証する）
+///
+/// 保存した鍵がなかった場合は false を返す
+#[tauri::command]
+pub async fn remove_profile_key(
+    state: State<'_, AppState>,
+    profile_name: String,
+) -> Result<bool, String> {
+    let config_manager = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?;
+    let mut settings = config_manager.load_settings()
+        .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?;
+    let profile = settings.backup_configs
+        .iter_mut()
+        .find(|config| config.name == profile_name)
+        .ok_or_else(|| format!("プロファイルが見つかりません: {}", profile_name))?;
+    if profile.stored_private_key.take().is_none() {
+        return Ok(false);
+    }
+    config_manager.save_settings(&settings)
+        .map_err(|e| format!("設定の保存に失敗しました: {}", e))?;
+    Ok(true)
+}
+
+// 設定・認証・履歴ファイルの現在の保存先を取得
+#[tauri::command]
+pub async fn get_data_directory() -> Result<String, String> {
+    data_dir::data_dir()
+        .map(|dir| dir.to_string_lossy().to_string())
+        .map_err(|e| format!("保存先の取得に失敗しました: {}", e))
+}
+
+// 設定・鍵・認証・履歴ファイルの現在のパスを取得（読み取りのみ）
+#[tauri::command]
+pub async fn get_data_paths(state: State<'_, AppState>) -> Result<DataPaths, String> {
+    let config_manager = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?;
+    let auth_manager = state.auth_manager.lock()
+        .map_err(|e| format!("認証管理のロックに失敗しました: {}", e))?;
+    let history_manager = state.backup_history_manager.lock()
+        .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?;
+
+    let display = |path: &std::path::Path| path.to_string_lossy().to_string();
+    Ok(DataPaths {
+        data_dir: data_dir::data_dir()
+            .map(|dir| display(&dir))
+            .map_err(|e| format!("保存先の取得に失敗しました: {}", e))?,
+        config_path: display(config_manager.config_path()),
+        key_path: display(config_manager.key_path()),
+        auth_settings_path: display(auth_manager.config_path()),
+        lockout_path: display(auth_manager.lockout_path()),
+        history_path: display(history_manager.history_path()),
+        history_signature_path: display(history_manager.signature_path()),
+        history_key_path: display(history_manager.key_path()),
+    })
+}
+
+// 設定・認証・履歴ファイルの保存先を変更し、既存のファイルを移動する
+#[tauri::command]
+pub async fn set_data_directory(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<String, String> {
+    // 移動中に他のコマンドがファイルを書き換えないよう、すべての管理のロックを取ってから移動する
+    let mut config_manager = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?;
+    let mut auth_manager = state.auth_manager.lock()
+        .map_err(|e| format!("認証管理のロックに失敗しました: {}", e))?;
+    let mut history_manager = state.backup_history_manager.lock()
+        .map_err(|e| format!("履歴管理のロックに失敗しました: {}", e))?;
+
+    let old_log_dir = logger::log_dir()
+        .map_err(|e| format!("保存先の取得に失敗しました: {}", e))?;
+    let new_dir = data_dir::set_data_dir(std::path::Path::new(&path))
+        .map_err(|e| format!("保存先の変更に失敗しました: {}", e))?;
+
+    // 新しい保存先のファイルで管理を作り直す
+    *config_manager = ConfigManager::new()
+        .map_err(|e| format!("設定管理の初期化に失敗しました: {}", e))?;
+    *auth_manager = AuthManager::new()
+        .map_err(|e| format!("認証管理の初期化に失敗しました: {}", e))?;
+    *history_manager = BackupHistoryManager::new()
+        .map_err(|e| format!("履歴管理の初期化に失敗しました: {}", e))?;
+
+    // ログは移動できなくても設定の移動は完了しているため、失敗は記録だけ残す
+    if let Err(e) = logger::relocate_logs(&old_log_dir) {
+        tracing::warn!("ログの移動に失敗しました: {:#}", e);
+    }
+
+    Ok(new_dir.to_string_lossy().to_string())
+}
+
+// 設定の暗号化キーを新しいものに置き換える
+#[tauri::command]
+pub async fn rotate_encryption_key(
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let mut config_manager = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?;
+
+    config_manager.rotate_key()
+        .map_err(|e| format!("暗号化キーの更新に失敗しました: {}", e))
+}
+
+// 暗号化された設定ファイルを復号できるか確認（プロファイル数のみ返し、設定の内容は返さない）
+#[tauri::command]
+pub async fn verify_config_integrity(
+    state: State<'_, AppState>,
+) -> Result<SettingsIntegrity, String> {
+    let config_manager = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?;
+
+    config_manager.verify_settings()
+        .map_err(|e| e.to_string())
+}
+
+// 保存済みプロファイルでバックアップが実行できるかを検証
+#[tauri::command]
+pub async fn validate_profile(
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<ProfileValidationReport, String> {
+    let settings = state.config_manager.lock()
+        .map_err(|e| format!("設定管理のロックに失敗しました: {}", e))?
+        .load_settings()
+        .map_err(|e| format!("設定の読み込みに失敗しました: {}", e))?;
+
+    let config = settings
+        .take_backup_config(&name)
+        .ok_or_else(|| format!("プロファイルが見つかりません: {}", name))?;
+
+    Ok(profile_check::validate_profile(config).await)
+}
@@ -4,91 +4,212 @@ use aes_gcm::{
 };
 use anyhow::{Context, Result};
 use base64::{engine::general_purpose, Engine as _};
-use dirs;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
 
-use crate::ssh_client::BackupConfig;
+use crate::archiver::ArchiveFormat;
+use crate::data_dir;
+use crate::path_template::PathTemplateSettings;
+use crate::ssh_key::{wipe_bytes, wipe_string};
+use crate::ssh_client::{default_connect_timeout_secs, BackupConfig, ProgressGranularity, TransferBackend, DEFAULT_CONNECT_TIMEOUT_SECS};
+
+mod migration;
+
+pub use migration::migrate_settings;
+
+/// 現在の設定フォーマットのバージョン
+///
+/// 設定の構造を変更する場合はこの値を上げ、`migrate_settings` に移行処理を追加する
+pub const CURRENT_SETTINGS_VERSION: u32 = 3;
+
+fn current_settings_version() -> u32 {
+    CURRENT_SETTINGS_VERSION
+}
+
+/// 探索したドメイン一覧のキャッシュ有効期間の既定値（1時間）
+pub const DEFAULT_DOMAIN_CACHE_TTL_SECS: u64 = 3600;
+
+fn default_domain_cache_ttl_secs() -> u64 {
+    DEFAULT_DOMAIN_CACHE_TTL_SECS
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AppSettings {
+    #[serde(default = "current_settings_version")]
+    pub version: u32,
     pub backup_configs: Vec<BackupConfig>,
     pub default_local_backup_path: Option<String>,
     pub auto_backup_enabled: bool,
     pub auto_backup_interval_hours: u32,
+    /// SSH接続タイムアウトの既定値（秒）
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    /// バックアップ完了後にアーカイブを作成するか
+    #[serde(default)]
+    pub archive_after_backup: bool,
+    #[serde(default)]
+    pub archive_format: ArchiveFormat,
+    /// アーカイブの保存先（未指定の場合はバックアップ先の親ディレクトリ）
+    #[serde(default)]
+    pub archive_directory: Option<String>,
+    /// アーカイブ作成後に元のバックアップフォルダを削除するか
+    #[serde(default)]
+    pub delete_after_archive: bool,
+    /// フォルダの転送方式（SFTP / rsync）
+    #[serde(default)]
+    pub transfer_backend: TransferBackend,
+    /// 進捗通知の間隔・バイト数閾値
+    #[serde(default)]
+    pub progress_granularity: ProgressGranularity,
+    /// サーバーでSFTPが使えない場合に、`tar` をSSHで実行してフォルダを転送する
+    #[serde(default)]
+    pub allow_tar_fallback: bool,
+    /// プロファイルの実行前・実行後フック（任意のコマンド）の実行を許可する
+    #[serde(default)]
+    pub allow_hooks: bool,
+    /// 探索したドメイン一覧をキャッシュする秒数
+    #[serde(default = "default_domain_cache_ttl_secs")]
+    pub domain_cache_ttl_secs: u64,
+    /// 隠しファイルでも常にバックアップする名前（`.htaccess` などの完全一致、または `*.ini` 形式の拡張子）
+    #[serde(default)]
+    pub always_include: Vec<String>,
+    /// 保存先パスの日付プレースホルダーの書式・基準ディレクトリ
+    #[serde(default)]
+    pub path_template: PathTemplateSettings,
+    /// バックアップの保存先として許可するフォルダ（空の場合は制限しない）
+    ///
+    /// `add_allowed_backup_root` でのみ追加し、設定の保存では書き換えない
+    #[serde(default)]
+    pub allowed_backup_roots: Vec<String>,
+}
+
+impl AppSettings {
+    /// 名前でバックアッププロファイルを取り出す
+    pub fn take_backup_config(self, name: &str) -> Option<BackupConfig> {
+        self.backup_configs.into_iter().find(|config| config.name == name)
+    }
+
+    /// バックアッププロファイルの名前を変更する
+    ///
+    /// 新しい名前は前後の空白を除いて空でなく、他のプロファイルと重複しないこと
+    pub fn rename_backup_config(&mut self, old_name: &str, new_name: &str) -> Result<()> {
+        let new_name = new_name.trim();
+        if new_name.is_empty() {
+            return Err(anyhow::anyhow!("新しいプロファイル名を入力してください"));
+        }
+        if new_name != old_name && self.backup_configs.iter().any(|config| config.name == new_name) {
+            return Err(anyhow::anyhow!("同じ名前のプロファイルが既にあります: {}", new_name));
+        }
+
+        let profile = self.backup_configs
+            .iter_mut()
+            .find(|config| config.name == old_name)
+            .with_context(|| format!("プロファイルが見つかりません: {}", old_name))?;
+        profile.name = new_name.to_string();
+        Ok(())
+    }
 }
 
 impl Default for AppSettings {
     fn default() -> Self {
         Self {
+            version: CURRENT_SETTINGS_VERSION,
             backup_configs: Vec::new(),
             default_local_backup_path: None,
             auto_backup_enabled: false,
             auto_backup_interval_hours: 24,
+            connect_timeout_secs: DEFAULT_CONNECT_TIMEOUT_SECS,
+            archive_after_backup: false,
+            archive_format: ArchiveFormat::default(),
+            archive_directory: None,
+            delete_after_archive: false,
+            transfer_backend: TransferBackend::default(),
+            progress_granularity: ProgressGranularity::default(),
+            allow_tar_fallback: false,
+            allow_hooks: false,
+            domain_cache_ttl_secs: DEFAULT_DOMAIN_CACHE_TTL_SECS,
+            always_include: Vec::new(),
+            path_template: PathTemplateSettings::default(),
+            allowed_backup_roots: Vec::new(),
         }
     }
 }
 
 pub struct ConfigManager {
     config_path: PathBuf,
+    key_path: PathBuf,
     encryption_key: [u8; 32],
+    /// 起動時に鍵ファイルがなく、新しい鍵を作成した
+    key_created: bool,
+}
+
+/// 設定ファイルの復号の確認結果（秘密情報は含めない）
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsIntegrity {
+    /// 設定ファイルがあるか（ない場合は既定の設定を使う）
+    pub settings_exist: bool,
+    /// 保存されていた設定の形式のバージョン（移行前）
+    pub version: u32,
+    pub profile_count: usize,
+}
+
+/// 設定ファイルを復号できなかった原因
+///
+/// 鍵と設定ファイルのどちらをバックアップから戻せばよいか分かるよう、原因ごとに分ける
+#[derive(Debug, Error)]
+pub enum SettingsIntegrityError {
+    #[error("暗号化キーが見つかりません。キーのバックアップを元に戻してください: {}", .key_path.display())]
+    MissingKey { key_path: PathBuf },
+
+    #[error("起動時に暗号化キーが見つからなかったため、新しいキーが作成されています。以前のキーのバックアップを元に戻してからアプリを再起動してください: {}", .key_path.display())]
+    KeyRecreated { key_path: PathBuf },
+
+    #[error("設定ファイルを復号できません。ファイルが破損しているか、別のキーで暗号化されています。設定ファイルのバックアップを元に戻してください: {}", .config_path.display())]
+    CorruptCiphertext { config_path: PathBuf },
+
+    #[error("設定ファイルは復号できましたが、内容を読み取れません: {reason}")]
+    InvalidContent { reason: String },
+
+    #[error("設定ファイルの読み取りに失敗しました: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 impl ConfigManager {
     pub fn new() -> Result<Self> {
-        // アプリケーション設定ディレクトリを取得
-        let config_dir = dirs::config_dir()
-            .context("設定ディレクトリの取得に失敗しました")?
-            .join("kyosho-backup");
-
-        // ディレクトリが存在しない場合は作成
-        fs::create_dir_all(&config_dir)
-            .context("設定ディレクトリの作成に失敗しました")?;
+        // アプリケーション設定ディレクトリを取得（存在しない場合は作成）
+        let config_dir = data_dir::data_dir()?;
 
         let config_path = config_dir.join("settings.enc");
 
         // 暗号化キーの生成/読み取り
         let key_path = config_dir.join("key.dat");
-        let encryption_key = if key_path.exists() {
-            fs::read(&key_path)
-                .context("暗号化キーの読み取りに失敗しました")?
-                .try_into()
-                .map_err(|_| anyhow::anyhow!("無効な暗号化キーファイル"))?
-        } else {
-            let key = Aes256Gcm::generate_key(&mut rand::thread_rng());
-            fs::write(&key_path, &key)
-                .context("暗号化キーの保存に失敗しました")?;
-            key.into()
-        };
+
+        // 前回の鍵のローテーションが中断されていれば復旧
+        recover_interrupted_rotation(&config_path, &key_path)?;
+
+        let key_created = !key_path.exists();
+        let encryption_key = load_or_create_key(&key_path)?;
 
         Ok(Self {
             config_path,
+            key_path,
             encryption_key,
+            key_created,
         })
     }
 
     /// 設定を暗号化して保存
     pub fn save_settings(&self, settings: &AppSettings) -> Result<()> {
-        // JSONにシリアライズ
-        let json_data = serde_json::to_vec(settings)
+        // JSONにシリアライズ（保存した秘密鍵を含むため、暗号化したら平文は消す）
+        let mut json_data = serde_json::to_vec(settings)
             .context("設定のシリアライズに失敗しました")?;
 
-        // AES-256-GCMで暗号化
-        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.encryption_key));
-        let nonce = Aes256Gcm::generate_nonce(&mut rand::thread_rng());
-
-        let ciphertext = cipher
-            .encrypt(&nonce, json_data.as_ref())
-            .map_err(|e| anyhow::anyhow!("暗号化に失敗しました: {}", e))?;
-
-        // Nonce + Ciphertextの形式で保存
-        let mut encrypted_data = Vec::new();
-        encrypted_data.extend_from_slice(&nonce);
-        encrypted_data.extend_from_slice(&ciphertext);
-
-        // Base64エンコードして保存
-        let encoded_data = general_purpose::STANDARD.encode(encrypted_data);
+        let encoded_data = encrypt_data(&self.encryption_key, &json_data);
+        wipe_bytes(&mut json_data);
+        let encoded_data = encoded_data?;
         fs::write(&self.config_path, encoded_data)
             .context("暗号化された設定ファイルの保存に失敗しました")?;
 
@@ -102,33 +223,133 @@ impl ConfigManager {
             return Ok(AppSettings::default());
         }
 
-        // Base64デコード
         let encoded_data = fs::read_to_string(&self.config_path)
             .context("暗号化された設定ファイルの読み取りに失敗しました")?;
 
-        let encrypted_data = general_purpose::STANDARD
-            .decode(encoded_data.trim())
-            .context("Base64デコードに失敗しました")?;
+        let mut decrypted_data = decrypt_data(&self.encryption_key, &encoded_data)?;
 
-        if encrypted_data.len() < 12 {
-            return Err(anyhow::anyhow!("無効な暗号化データです"));
+        // 旧バージョンの設定を現在の形式に移行してからデシリアライズ
+        // （保存した秘密鍵を含むため、復号した平文と途中の値は使い終わったら消す）
+        let raw_settings = serde_json::from_slice::<serde_json::Value>(&decrypted_data);
+        wipe_bytes(&mut decrypted_data);
+        let raw_settings = raw_settings.context("設定のデシリアライズに失敗しました")?;
+        let mut migrated = migrate_settings(raw_settings)?;
+
+        let settings = AppSettings::deserialize(&migrated);
+        wipe_json(&mut migrated);
+        settings.context("設定のデシリアライズに失敗しました")
+    }
+
+    /// 設定ファイルを読み込んで復号できるか確認し、プロファイル数を返す
+    ///
+    /// 設定ファイルがない場合は成功として扱う。復号できない場合は、鍵がない・鍵が作り直された・
+    /// 設定ファイルが壊れている（または別の鍵で暗号化されている）のいずれかを返す
+    pub fn verify_settings(&self) -> std::result::Result<SettingsIntegrity, SettingsIntegrityError> {
+        if !self.config_path.exists() {
+            return Ok(SettingsIntegrity {
+                settings_exist: false,
+                version: CURRENT_SETTINGS_VERSION,
+                profile_count: 0,
+            });
+        }
+
+        let encoded_data = fs::read_to_string(&self.config_path)?;
+        if !self.key_path.exists() {
+            return Err(SettingsIntegrityError::MissingKey { key_path: self.key_path.clone() });
+        }
+
+        // AES-GCM では鍵の誤りと暗号文の破損を区別できないため、起動時に鍵を作り直したかで判断する
+        let mut decrypted_data = decrypt_data(&self.encryption_key, &encoded_data).map_err(|_| {
+            if self.key_created {
+                SettingsIntegrityError::KeyRecreated { key_path: self.key_path.clone() }
+            } else {
+                SettingsIntegrityError::CorruptCiphertext { config_path: self.config_path.clone() }
+            }
+        })?;
+
+        let invalid = |e: &dyn std::fmt::Display| SettingsIntegrityError::InvalidContent { reason: e.to_string() };
+        let raw_settings = serde_json::from_slice::<serde_json::Value>(&decrypted_data);
+        wipe_bytes(&mut decrypted_data);
+        let raw_settings = raw_settings.map_err(|e| invalid(&e))?;
+        // versionフィールドがない設定はv1（`migrate_settings` と同じ扱い）
+        let version = raw_settings.get("version").and_then(|v| v.as_u64()).unwrap_or(1) as u32;
+        let mut migrated = migrate_settings(raw_settings).map_err(|e| invalid(&e))?;
+        let settings = AppSettings::deserialize(&migrated);
+        wipe_json(&mut migrated);
+        let settings = settings.map_err(|e| invalid(&e))?;
+
+        Ok(SettingsIntegrity {
+            settings_exist: true,
+            version,
+            profile_count: settings.backup_configs.len(),
+        })
+    }
+
+    /// バックアップの保存先として許可するフォルダを追加し、正規化したパスを返す
+    ///
+    /// 存在するフォルダのみ追加できる。既に登録済みの場合は何もしない
+    pub fn add_allowed_backup_root(&self, path: &Path) -> Result<PathBuf> {
+        let root = path
+            .canonicalize()
+            .with_context(|| format!("フォルダが見つかりません: {}", path.display()))?;
+        if !root.is_dir() {
+            return Err(anyhow::anyhow!("フォルダではありません: {}", root.display()));
+        }
+
+        let mut settings = self.load_settings()?;
+        let root_str = root.to_string_lossy().to_string();
+        if !settings.allowed_backup_roots.contains(&root_str) {
+            settings.allowed_backup_roots.push(root_str);
+            self.save_settings(&settings)?;
+        }
+
+        Ok(root)
+    }
+
+    /// 新しい暗号化キーを生成し、設定を再暗号化する
+    ///
+    /// 新しい鍵と再暗号化した設定を一時ファイルに書き出してから、鍵→設定の順に置き換える。
+    /// 置き換えの途中で中断した場合は次回起動時に `recover_interrupted_rotation` で復旧する
+    pub fn rotate_key(&mut self) -> Result<()> {
+        let new_key: [u8; 32] = Aes256Gcm::generate_key(&mut rand::thread_rng()).into();
+        let new_config_path = pending_path(&self.config_path);
+        let new_key_path = pending_path(&self.key_path);
+
+        // 現在の鍵で復号した内容をそのまま新しい鍵で暗号化
+        if self.config_path.exists() {
+            let encoded_data = fs::read_to_string(&self.config_path)
+                .context("暗号化された設定ファイルの読み取りに失敗しました")?;
+            let mut decrypted_data = decrypt_data(&self.encryption_key, &encoded_data)?;
+            let reencrypted = encrypt_data(&new_key, &decrypted_data);
+            wipe_bytes(&mut decrypted_data);
+            let reencrypted = reencrypted?;
+            write_synced(&new_config_path, reencrypted.as_bytes())
+                .context("再暗号化した設定の保存に失敗しました")?;
         }
 
-        // NonceとCiphertextを分離
-        let (nonce_bytes, ciphertext) = encrypted_data.split_at(12);
-        let nonce = Nonce::from_slice(nonce_bytes);
+        write_synced(&new_key_path, &new_key)
+            .context("新しい暗号化キーの保存に失敗しました")?;
+
+        fs::rename(&new_key_path, &self.key_path)
+            .context("暗号化キーの置き換えに失敗しました")?;
+        if new_config_path.exists() {
+            fs::rename(&new_config_path, &self.config_path)
+                .context("設定ファイルの置き換えに失敗しました")?;
+        }
 
-        // 復号化
-        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.encryption_key));
-        let decrypted_data = cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| anyhow::anyhow!("復号化に失敗しました: {}", e))?;
+        self.encryption_key = new_key;
+        self.key_created = false;
+        Ok(())
+    }
 
-        // JSONデシリアライズ
-        let settings: AppSettings = serde_json::from_slice(&decrypted_data)
-            .context("設定のデシリアライズに失敗しました")?;
+    /// 暗号化された設定ファイルのパス
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
 
-        Ok(settings)
+    /// 暗号化キーのパス（OSのキーチェーンは使わず、常にファイルに保存する）
+    pub fn key_path(&self) -> &Path {
+        &self.key_path
     }
 
     /// 設定ファイルが存在するかチェック
@@ -144,4 +365,299 @@ impl ConfigManager {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// 鍵ファイルから32バイトの鍵を読み取る（存在しない場合はランダムに生成して保存）
+pub fn load_or_create_key(key_path: &Path) -> Result<[u8; 32]> {
+    if key_path.exists() {
+        return fs::read(key_path)
+            .context("暗号化キーの読み取りに失敗しました")?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("無効な暗号化キーファイル"));
+    }
+
+    let key = Aes256Gcm::generate_key(&mut rand::thread_rng());
+    fs::write(key_path, &key)
+        .context("暗号化キーの保存に失敗しました")?;
+    Ok(key.into())
+}
+
+/// AES-256-GCMで暗号化し、Nonce + Ciphertext をBase64エンコードした文字列を返す
+pub fn encrypt_data(key: &[u8; 32], data: &[u8]) -> Result<String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut rand::thread_rng());
+
+    let ciphertext = cipher
+        .encrypt(&nonce, data)
+        .map_err(|e| anyhow::anyhow!("暗号化に失敗しました: {}", e))?;
+
+    // Nonce + Ciphertextの形式で保存
+    let mut encrypted_data = Vec::new();
+    encrypted_data.extend_from_slice(&nonce);
+    encrypted_data.extend_from_slice(&ciphertext);
+
+    Ok(general_purpose::STANDARD.encode(encrypted_data))
+}
+
+/// `encrypt_data` で暗号化した文字列を復号
+pub fn decrypt_data(key: &[u8; 32], encoded_data: &str) -> Result<Vec<u8>> {
+    // Base64デコード
+    let encrypted_data = general_purpose::STANDARD
+        .decode(encoded_data.trim())
+        .context("Base64デコードに失敗しました")?;
+
+    if encrypted_data.len() < 12 {
+        return Err(anyhow::anyhow!("無効な暗号化データです"));
+    }
+
+    // NonceとCiphertextを分離
+    let (nonce_bytes, ciphertext) = encrypted_data.split_at(12);
+    let nonce = Nonce::from_slice(nonce_bytes);
+
+    // 復号化
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    cipher
+        .decrypt(nonce, ciphertext)
+        .map_err(|e| anyhow::anyhow!("復号化に失敗しました: {}", e))
+}
+
+/// 設定のJSONに含まれる文字列を0で上書きする（保存した秘密鍵などがメモリに残らないように）
+fn wipe_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(text) => wipe_string(std::mem::take(text)),
+        serde_json::Value::Array(items) => items.iter_mut().for_each(wipe_json),
+        serde_json::Value::Object(map) => map.values_mut().for_each(wipe_json),
+        _ => {}
+    }
+}
+
+/// 鍵のローテーション中に書き出す一時ファイルのパス（`<ファイル名>.new`）
+fn pending_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".new");
+    path.with_file_name(name)
+}
+
+/// ファイルを書き込み、ディスクへの反映まで待つ
+fn write_synced(path: &Path, data: &[u8]) -> Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// 中断された鍵のローテーションを復旧
+///
+/// - 新しい鍵の一時ファイルが残っている: 置き換え前に中断したため一時ファイルを破棄（旧状態のまま）
+/// - 設定の一時ファイルだけが残っている: 鍵の置き換え後に中断したため設定の置き換えを完了する
+fn recover_interrupted_rotation(config_path: &Path, key_path: &Path) -> Result<()> {
+    let new_config_path = pending_path(config_path);
+    let new_key_path = pending_path(key_path);
+
+    if new_key_path.exists() {
+        fs::remove_file(&new_key_path)
+            .context("中断した鍵のローテーションの破棄に失敗しました")?;
+        if new_config_path.exists() {
+            fs::remove_file(&new_config_path)
+                .context("中断した鍵のローテーションの破棄に失敗しました")?;
+        }
+    } else if new_config_path.exists() {
+        fs::rename(&new_config_path, config_path)
+            .context("中断した鍵のローテーションの復旧に失敗しました")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn manager_in(dir: &TempDir) -> ConfigManager {
+        let key_path = dir.path().join("key.dat");
+        let encryption_key = load_or_create_key(&key_path).unwrap();
+        ConfigManager {
+            config_path: dir.path().join("settings.enc"),
+            key_path,
+            encryption_key,
+            key_created: false,
+        }
+    }
+
+    fn write_raw_settings(manager: &ConfigManager, value: &serde_json::Value) {
+        let encoded = encrypt_data(&manager.encryption_key, value.to_string().as_bytes()).unwrap();
+        fs::write(&manager.config_path, encoded).unwrap();
+    }
+
+    /// バージョン管理導入前（v1）の設定: versionもプロファイル名もない
+    fn v1_settings() -> serde_json::Value {
+        serde_json::json!({
+            "backup_configs": [
+                {
+                    "ssh": {
+                        "hostname": "sv1.example.com",
+                        "port": 10022,
+                        "username": "user1",
+                        "key_path": "/home/user/.ssh/id_rsa"
+                    },
+                    "remote_folder": "/home/user1/example.com/public_html",
+                    "local_folder": "/backup/example.com"
+                },
+                {
+                    "ssh": {
+                        "hostname": "sv2.example.com",
+                        "port": 22,
+                        "username": "user2",
+                        "key_path": "/home/user/.ssh/id_ed25519"
+                    },
+                    "remote_folder": "/home/user2",
+                    "local_folder": "/backup/user2"
+                }
+            ],
+            "default_local_backup_path": "/backup",
+            "auto_backup_enabled": true,
+            "auto_backup_interval_hours": 12
+        })
+    }
+
+    #[test]
+    fn loads_v1_settings_and_migrates_to_current_version() {
+        let dir = TempDir::new("settings-v1");
+        let manager = manager_in(&dir);
+        write_raw_settings(&manager, &v1_settings());
+
+        let settings = manager.load_settings().unwrap();
+
+        assert_eq!(settings.version, CURRENT_SETTINGS_VERSION);
+        assert_eq!(settings.default_local_backup_path.as_deref(), Some("/backup"));
+        assert!(settings.auto_backup_enabled);
+        assert_eq!(settings.auto_backup_interval_hours, 12);
+        assert_eq!(settings.connect_timeout_secs, DEFAULT_CONNECT_TIMEOUT_SECS);
+
+        let names: Vec<&str> = settings.backup_configs.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["プロファイル1", "プロファイル2"]);
+        let first = &settings.backup_configs[0];
+        assert_eq!(first.ssh.hostname, "sv1.example.com");
+        assert_eq!(first.ssh.port, 10022);
+        assert_eq!(first.remote_folder, "/home/user1/example.com/public_html");
+        assert_eq!(first.local_folder, "/backup/example.com");
+
+        // 移行後に保存し直しても同じ内容で読める
+        manager.save_settings(&settings).unwrap();
+        let reloaded = manager.load_settings().unwrap();
+        assert_eq!(reloaded.version, CURRENT_SETTINGS_VERSION);
+        assert_eq!(reloaded.backup_configs.len(), 2);
+        assert_eq!(reloaded.backup_configs[1].name, "プロファイル2");
+    }
+
+    #[test]
+    fn verify_reports_the_version_before_migration() {
+        let dir = TempDir::new("settings-v1-verify");
+        let manager = manager_in(&dir);
+        write_raw_settings(&manager, &v1_settings());
+
+        let integrity = manager.verify_settings().unwrap();
+        assert!(integrity.settings_exist);
+        assert_eq!(integrity.version, 1);
+        assert_eq!(integrity.profile_count, 2);
+    }
+
+    fn settings_with_profile(name: &str) -> AppSettings {
+        let mut value = v1_settings();
+        value["backup_configs"][0]["name"] = serde_json::Value::from(name);
+        AppSettings::deserialize(migrate_settings(value).unwrap()).unwrap()
+    }
+
+    /// 保存済みのファイルから開き直す（起動時と同じく中断したローテーションを復旧する）
+    fn reopen(dir: &TempDir) -> ConfigManager {
+        let config_path = dir.path().join("settings.enc");
+        let key_path = dir.path().join("key.dat");
+        recover_interrupted_rotation(&config_path, &key_path).unwrap();
+        manager_in(dir)
+    }
+
+    #[test]
+    fn rotate_key_reencrypts_settings_under_a_new_key() {
+        let dir = TempDir::new("rotate-key");
+        let mut manager = manager_in(&dir);
+        manager.save_settings(&settings_with_profile("本番サイト")).unwrap();
+        let old_key = manager.encryption_key;
+
+        manager.rotate_key().unwrap();
+
+        assert_ne!(manager.encryption_key, old_key);
+        assert_eq!(fs::read(manager.key_path()).unwrap(), manager.encryption_key);
+        assert!(!pending_path(manager.config_path()).exists());
+        assert!(!pending_path(manager.key_path()).exists());
+
+        // 古い鍵では復号できず、新しい鍵で同じ内容を読める
+        let encoded = fs::read_to_string(manager.config_path()).unwrap();
+        assert!(decrypt_data(&old_key, &encoded).is_err());
+        let settings = manager.load_settings().unwrap();
+        assert_eq!(settings.backup_configs[0].name, "本番サイト");
+        assert_eq!(settings.backup_configs.len(), 2);
+
+        let reopened = reopen(&dir);
+        assert_eq!(reopened.load_settings().unwrap().backup_configs[0].name, "本番サイト");
+    }
+
+    #[test]
+    fn rotate_key_without_settings_only_replaces_the_key() {
+        let dir = TempDir::new("rotate-key-empty");
+        let mut manager = manager_in(&dir);
+        let old_key = manager.encryption_key;
+
+        manager.rotate_key().unwrap();
+
+        assert_ne!(manager.encryption_key, old_key);
+        assert!(!manager.settings_exist());
+        assert_eq!(manager.load_settings().unwrap().backup_configs.len(), 0);
+    }
+
+    #[test]
+    fn recovery_discards_a_rotation_interrupted_before_the_key_swap() {
+        let dir = TempDir::new("rotate-key-before-swap");
+        let manager = manager_in(&dir);
+        manager.save_settings(&settings_with_profile("本番サイト")).unwrap();
+
+        // 新しい鍵と再暗号化した設定を書き出したところで中断
+        let new_key = [7u8; 32];
+        let settings_json = serde_json::to_vec(&manager.load_settings().unwrap()).unwrap();
+        let reencrypted = encrypt_data(&new_key, &settings_json).unwrap();
+        write_synced(&pending_path(manager.config_path()), reencrypted.as_bytes()).unwrap();
+        write_synced(&pending_path(manager.key_path()), &new_key).unwrap();
+
+        let reopened = reopen(&dir);
+        assert_eq!(reopened.encryption_key, manager.encryption_key);
+        assert!(!pending_path(reopened.config_path()).exists());
+        assert!(!pending_path(reopened.key_path()).exists());
+        assert_eq!(reopened.load_settings().unwrap().backup_configs[0].name, "本番サイト");
+    }
+
+    #[test]
+    fn recovery_completes_a_rotation_interrupted_after_the_key_swap() {
+        let dir = TempDir::new("rotate-key-after-swap");
+        let manager = manager_in(&dir);
+        manager.save_settings(&settings_with_profile("本番サイト")).unwrap();
+
+        // 鍵を置き換えたが、設定ファイルを置き換える前に中断
+        let new_key = [9u8; 32];
+        let settings_json = serde_json::to_vec(&manager.load_settings().unwrap()).unwrap();
+        let reencrypted = encrypt_data(&new_key, &settings_json).unwrap();
+        write_synced(&pending_path(manager.config_path()), reencrypted.as_bytes()).unwrap();
+        write_synced(manager.key_path(), &new_key).unwrap();
+
+        let reopened = reopen(&dir);
+        assert_eq!(reopened.encryption_key, new_key);
+        assert!(!pending_path(reopened.config_path()).exists());
+        assert_eq!(reopened.load_settings().unwrap().backup_configs[0].name, "本番サイト");
+    }
+
+    #[test]
+    fn rejects_settings_from_a_newer_version() {
+        let mut value = v1_settings();
+        value["version"] = serde_json::Value::from(CURRENT_SETTINGS_VERSION + 1);
+        assert!(migrate_settings(value).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use super::*;
+
+/// 保存されている設定JSONを現在のバージョンの形式に移行
+///
+/// versionフィールドがない設定はv1（バージョン管理導入前）として扱う
+pub fn migrate_settings(mut value: serde_json::Value) -> Result<serde_json::Value> {
+    let mut version = value
+        .get("version")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(1) as u32;
+
+    if version > CURRENT_SETTINGS_VERSION {
+        return Err(anyhow::anyhow!(
+            "より新しいバージョンのアプリで保存された設定です (v{})。アプリを更新してください",
+            version
+        ));
+    }
+
+    while version < CURRENT_SETTINGS_VERSION {
+        value = match version {
+            1 => migrate_v1_to_v2(value)?,
+            2 => migrate_v2_to_v3(value)?,
+            _ => return Err(anyhow::anyhow!("未対応の設定バージョンです (v{})", version)),
+        };
+        version += 1;
+    }
+
+    Ok(value)
+}
+
+/// v1 → v2: versionフィールドを追加
+fn migrate_v1_to_v2(mut value: serde_json::Value) -> Result<serde_json::Value> {
+    let object = value
+        .as_object_mut()
+        .context("設定データの形式が不正です")?;
+    object.insert("version".to_string(), serde_json::Value::from(2u32));
+    Ok(value)
+}
+
+/// v2 → v3: バックアッププロファイルに名前を追加（未設定のものは連番で命名）
+fn migrate_v2_to_v3(mut value: serde_json::Value) -> Result<serde_json::Value> {
+    let object = value
+        .as_object_mut()
+        .context("設定データの形式が不正です")?;
+
+    if let Some(configs) = object.get_mut("backup_configs").and_then(|v| v.as_array_mut()) {
+        for (index, config) in configs.iter_mut().enumerate() {
+            if let Some(config) = config.as_object_mut() {
+                let has_name = config
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .is_some_and(|name| !name.is_empty());
+                if !has_name {
+                    config.insert("name".to_string(), serde_json::Value::from(format!("プロファイル{}", index + 1)));
+                }
+            }
+        }
+    }
+
+    object.insert("version".to_string(), serde_json::Value::from(3u32));
+    Ok(value)
+}
@@ -0,0 +1,195 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 設定・認証・履歴ファイルの保存先を指定する環境変数（`set_data_directory` より優先）
+pub const DATA_DIR_ENV: &str = "KYOSHO_BACKUP_DATA_DIR";
+
+/// 変更後の保存先を記録するファイル（既定の保存先に置く）
+const LOCATION_FILE_NAME: &str = "data_location.txt";
+
+/// 保存先の変更時に移動するファイル
+const DATA_FILE_NAMES: &[&str] = &[
+    "settings.enc",
+    "key.dat",
+    "auth_settings.json",
+    "lockout_info.json",
+    "backup_history.json",
+    "backup_history.json.sig",
+    "history_key.dat",
+];
+
+/// 保存先の変更時に合わせて移動する、書き込み途中の一時ファイル
+///
+/// 鍵のローテーションや履歴の保存が中断した場合に残り、次回の起動・読み込み時の復旧に使う
+const PENDING_FILE_NAMES: &[&str] = &[
+    "settings.enc.new",
+    "key.dat.new",
+    "backup_history.json.tmp",
+    "backup_history.json.sig.new",
+];
+
+/// 署名の検証に失敗して退避した履歴と署名（`backup_history.json.quarantined-<時刻>` など）
+const QUARANTINED_FILE_PREFIX: &str = "backup_history.json.";
+const QUARANTINED_FILE_MARKER: &str = "quarantined-";
+
+/// 既定の保存先（設定ディレクトリ配下の kyosho-backup）
+pub fn default_data_dir() -> Result<PathBuf> {
+    Ok(dirs::config_dir()
+        .context("設定ディレクトリの取得に失敗しました")?
+        .join("kyosho-backup"))
+}
+
+/// 設定・認証・履歴ファイルの保存先を取得し、ディレクトリがなければ作成する
+///
+/// 環境変数 `KYOSHO_BACKUP_DATA_DIR`、`set_data_dir` で記録した保存先、既定の保存先の順に使う
+pub fn data_dir() -> Result<PathBuf> {
+    let dir = resolve_data_dir()?;
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("設定ディレクトリの作成に失敗しました: {:?}", dir))?;
+    Ok(dir)
+}
+
+fn resolve_data_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os(DATA_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+
+    let default_dir = default_data_dir()?;
+    match fs::read_to_string(default_dir.join(LOCATION_FILE_NAME)) {
+        Ok(location) if !location.trim().is_empty() => Ok(PathBuf::from(location.trim())),
+        _ => Ok(default_dir),
+    }
+}
+
+/// 保存先を変更し、現在の保存先にあるファイルを移動する
+///
+/// 変更後の保存先は既定の保存先に記録する（既定の保存先に書き込めない環境では環境変数を使う）。
+/// 移動先に同名のファイルがある場合は、上書きせずにエラーにする
+pub fn set_data_dir(new_dir: &Path) -> Result<PathBuf> {
+    if std::env::var_os(DATA_DIR_ENV).is_some_and(|dir| !dir.is_empty()) {
+        return Err(anyhow::anyhow!(
+            "環境変数 {} で保存先が指定されているため変更できません",
+            DATA_DIR_ENV
+        ));
+    }
+    if !new_dir.is_absolute() {
+        return Err(anyhow::anyhow!("保存先は絶対パスで指定してください: {:?}", new_dir));
+    }
+
+    fs::create_dir_all(new_dir)
+        .with_context(|| format!("保存先の作成に失敗しました: {:?}", new_dir))?;
+    let new_dir = new_dir.canonicalize()
+        .with_context(|| format!("保存先の確認に失敗しました: {:?}", new_dir))?;
+
+    let current_dir = data_dir()?;
+    if current_dir.canonicalize().is_ok_and(|current| current == new_dir) {
+        return Ok(new_dir);
+    }
+
+    // 途中で失敗して一部だけ移動した状態にならないよう、先に衝突を確認する
+    let names = files_to_move(&current_dir);
+    for name in &names {
+        if new_dir.join(name).exists() {
+            return Err(anyhow::anyhow!(
+                "移動先に同名のファイルがあります: {:?}",
+                new_dir.join(name)
+            ));
+        }
+    }
+
+    for name in &names {
+        move_file(&current_dir.join(name), &new_dir.join(name))?;
+    }
+
+    // 既定の保存先に戻す場合は記録を消す
+    let default_dir = default_data_dir()?;
+    let location_path = default_dir.join(LOCATION_FILE_NAME);
+    if default_dir.canonicalize().is_ok_and(|default| default == new_dir) {
+        if location_path.exists() {
+            fs::remove_file(&location_path)
+                .context("保存先の記録の削除に失敗しました")?;
+        }
+    } else {
+        fs::create_dir_all(&default_dir)
+            .context("設定ディレクトリの作成に失敗しました")?;
+        fs::write(&location_path, new_dir.to_string_lossy().as_bytes())
+            .context("保存先の記録に失敗しました")?;
+    }
+
+    tracing::info!("データの保存先を変更しました: {:?} -> {:?}", current_dir, new_dir);
+    Ok(new_dir)
+}
+
+/// 保存先にある、移動するファイルの名前（設定・認証・履歴と、書き込み途中・退避したもの）
+///
+/// ログは書き込み中のため、`logger::relocate_logs` で別に移動する
+fn files_to_move(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = DATA_FILE_NAMES
+        .iter()
+        .chain(PENDING_FILE_NAMES)
+        .filter(|name| dir.join(name).exists())
+        .map(|name| name.to_string())
+        .collect();
+
+    if let Ok(entries) = fs::read_dir(dir) {
+        names.extend(
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| {
+                    name.strip_prefix(QUARANTINED_FILE_PREFIX)
+                        .is_some_and(|rest| rest.contains(QUARANTINED_FILE_MARKER))
+                }),
+        );
+    }
+
+    names
+}
+
+/// ファイルを移動する（別ボリュームへの移動はコピーしてから削除）
+pub(crate) fn move_file(source: &Path, destination: &Path) -> Result<()> {
+    if fs::rename(source, destination).is_ok() {
+        return Ok(());
+    }
+
+    fs::copy(source, destination)
+        .with_context(|| format!("ファイルのコピーに失敗しました: {:?} -> {:?}", source, destination))?;
+    fs::remove_file(source)
+        .with_context(|| format!("移動元のファイルの削除に失敗しました: {:?}", source))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn moves_pending_and_quarantined_files_with_the_data_files() {
+        let dir = TempDir::new("data-dir-files");
+        for name in [
+            "settings.enc",
+            "key.dat.new",
+            "backup_history.json.sig.new",
+            "backup_history.json.quarantined-1700000000",
+            "backup_history.json.sig.quarantined-1700000000",
+            "unrelated.txt",
+            "kyosho-key-123",
+        ] {
+            dir.write(name, b"");
+        }
+        dir.write("logs/backup.2026-01-01.log", b"");
+
+        let mut names = files_to_move(dir.path());
+        names.sort();
+        assert_eq!(names, [
+            "backup_history.json.quarantined-1700000000",
+            "backup_history.json.sig.new",
+            "backup_history.json.sig.quarantined-1700000000",
+            "key.dat.new",
+            "settings.enc",
+        ]);
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 伏せ字にした値の表示
+const REDACTED: &str = "***";
+
+/// 名前にこれらを含むキーの値は伏せ字にする（大文字・小文字は区別しない）
+const SENSITIVE_KEY_PARTS: &[&str] = &["password", "passphrase", "secret", "token", "hash", "private_key"];
+
+/// 任意のコマンドを含むため値を伏せ字にするキー（認証情報が書かれていることがある）
+const REDACTED_KEYS: &[&str] = &["pre_hook", "post_hook"];
+
+/// 診断情報に含める実行環境の情報
+#[derive(Debug, Serialize)]
+pub struct SystemInfo {
+    pub app_version: &'static str,
+    pub os: &'static str,
+    pub os_family: &'static str,
+    pub arch: &'static str,
+    pub exported_at: String,
+}
+
+impl SystemInfo {
+    pub fn current() -> Self {
+        Self {
+            app_version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            os_family: std::env::consts::FAMILY,
+            arch: std::env::consts::ARCH,
+            exported_at: chrono::Local::now().to_rfc3339(),
+        }
+    }
+}
+
+/// 診断情報としてまとめる内容（設定などは書き出す前に伏せ字にする）
+pub struct DiagnosticsBundle {
+    pub log_path: Option<PathBuf>,
+    pub settings: Value,
+    pub history: Value,
+    pub auth_policy: Value,
+    pub system: SystemInfo,
+}
+
+/// 秘密情報にあたる値を伏せ字にする（鍵ファイルのパスなどはそのまま残す）
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if is_sensitive_key(key) {
+                    if !value.is_null() {
+                        *value = Value::String(REDACTED.to_string());
+                    }
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+fn is_sensitive_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    REDACTED_KEYS.contains(&key.as_str())
+        || SENSITIVE_KEY_PARTS.iter().any(|part| key.contains(part))
+}
+
+/// 診断情報を1つのzipファイルに書き出す
+pub fn write_bundle(dest: &Path, mut bundle: DiagnosticsBundle) -> Result<()> {
+    redact(&mut bundle.settings);
+    redact(&mut bundle.history);
+    redact(&mut bundle.auth_policy);
+
+    let file = File::create(dest)
+        .with_context(|| format!("診断情報ファイルの作成に失敗しました: {}", dest.display()))?;
+    let mut writer = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .large_file(true);
+
+    let json_entries = [
+        ("settings.json", bundle.settings),
+        ("history.json", bundle.history),
+        ("auth_policy.json", bundle.auth_policy),
+        ("system.json", serde_json::to_value(&bundle.system)?),
+    ];
+    for (name, value) in json_entries {
+        writer
+            .start_file(name, options)
+            .with_context(|| format!("診断情報への追加に失敗: {}", name))?;
+        serde_json::to_writer_pretty(&mut writer, &value)
+            .with_context(|| format!("診断情報への書き込みに失敗: {}", name))?;
+        writer.write_all(b"\n")?;
+    }
+
+    // ログがまだない場合もそれ以外の情報は書き出す
+    match bundle.log_path {
+        Some(log_path) => {
+            let name = log_path
+                .file_name()
+                .map(|name| format!("logs/{}", name.to_string_lossy()))
+                .unwrap_or_else(|| "logs/latest.log".to_string());
+            writer
+                .start_file(name.as_str(), options)
+                .with_context(|| format!("診断情報への追加に失敗: {}", name))?;
+            let mut source = File::open(&log_path)
+                .with_context(|| format!("ファイルのオープンに失敗: {:?}", log_path))?;
+            std::io::copy(&mut source, &mut writer)
+                .with_context(|| format!("診断情報への書き込みに失敗: {}", name))?;
+        }
+        None => tracing::warn!("ログファイルがないため診断情報に含めません"),
+    }
+
+    writer.finish().context("診断情報の書き込み完了に失敗しました")?;
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// 空き容量チェック時に見積もりサイズへ上乗せする余裕分（100MB）
+pub const DISK_SPACE_MARGIN_BYTES: u64 = 100 * 1024 * 1024;
+
+/// 書き込み速度の計測で書き込むサイズの上限（MB）
+pub const WRITE_BENCHMARK_MAX_MB: u64 = 1024;
+
+/// 書き込み速度の計測用の一時ファイル名の接頭辞（後ろにプロセスIDと `.tmp` が付く）
+pub const WRITE_BENCHMARK_FILE_PREFIX: &str = ".kyosho-write-benchmark-";
+
+/// 書き込み速度の計測で1回に書き込むサイズ（1MB）
+const WRITE_BENCHMARK_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// 指定パスが属するボリュームの利用可能な空き容量（バイト）を取得
+///
+/// パスがまだ存在しない場合（これから作成するバックアップ先など）は、
+/// 存在する最も近い親ディレクトリで判定する
+pub fn available_space(path: &Path) -> Result<u64> {
+    let existing = path
+        .ancestors()
+        .find(|p| p.exists())
+        .with_context(|| format!("空き容量を確認できるパスが見つかりません: {:?}", path))?;
+
+    platform_available_space(existing)
+}
+
+/// 指定ディレクトリに一時ファイルを書き込んで同期し、書き込み速度（MB/s）を計測する
+///
+/// 保存先がネットワークドライブなどで遅いのか、SSHの転送が遅いのかの切り分けに使う。
+/// サイズは上限で頭打ちにし、空き容量が足りない場合は書き込まずにエラーにする。
+/// 一時ファイルはエラー時も含めて削除する
+pub fn benchmark_write(dir: &Path, size_mb: u64) -> Result<f64> {
+    if !dir.is_dir() {
+        return Err(anyhow::anyhow!("保存先のフォルダが見つかりません: {:?}", dir));
+    }
+
+    let size_mb = size_mb.clamp(1, WRITE_BENCHMARK_MAX_MB);
+    let size_bytes = size_mb * WRITE_BENCHMARK_CHUNK_SIZE as u64;
+
+    let available = available_space(dir)?;
+    if available < size_bytes + DISK_SPACE_MARGIN_BYTES {
+        return Err(anyhow::anyhow!(
+            "空き容量が不足しているため計測できません（必要: {} バイト / 空き: {} バイト）",
+            size_bytes + DISK_SPACE_MARGIN_BYTES,
+            available
+        ));
+    }
+
+    let temp_file = TempFile(dir.join(format!("{}{}.tmp", WRITE_BENCHMARK_FILE_PREFIX, std::process::id())));
+    let mut file = std::fs::File::create(&temp_file.0)
+        .with_context(|| format!("計測用ファイルの作成に失敗しました: {:?}", temp_file.0))?;
+
+    // 圧縮・重複排除されるファイルシステムで速くなりすぎないよう、0以外の値で埋める
+    let chunk: Vec<u8> = (0..WRITE_BENCHMARK_CHUNK_SIZE).map(|i| (i % 251) as u8).collect();
+
+    let started = Instant::now();
+    for _ in 0..size_mb {
+        file.write_all(&chunk)
+            .context("計測用ファイルの書き込みに失敗しました")?;
+    }
+    file.sync_all()
+        .context("計測用ファイルの同期に失敗しました")?;
+    let elapsed = started.elapsed().as_secs_f64();
+
+    Ok(size_mb as f64 / elapsed.max(f64::EPSILON))
+}
+
+/// スコープを抜けるときに削除する一時ファイル
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(unix)]
+fn platform_available_space(path: &Path) -> Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .context("パスにNUL文字が含まれています")?;
+
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("空き容量の取得に失敗しました: {:?}", path));
+    }
+
+    // プラットフォームによりフィールドの型が異なるためキャストで揃える
+    #[allow(clippy::unnecessary_cast)]
+    let available = stat.f_bavail as u64 * stat.f_frsize as u64;
+    Ok(available)
+}
+
+#[cfg(windows)]
+fn platform_available_space(path: &Path) -> Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let mut available: u64 = 0;
+
+    let ret = unsafe {
+        GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, std::ptr::null_mut(), std::ptr::null_mut())
+    };
+    if ret == 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("空き容量の取得に失敗しました: {:?}", path));
+    }
+
+    Ok(available)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn platform_available_space(_path: &Path) -> Result<u64> {
+    Err(anyhow::anyhow!("このプラットフォームでは空き容量の取得に対応していません"))
+}
@@ -0,0 +1,218 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::transfer_index;
+
+/// 同時に実行するハッシュ計算数の上限（指定がない場合はCPU数まで。指定した場合もこれを超えない）
+const MAX_HASH_JOBS: usize = 4;
+
+/// 転送後のチェックサム照合の結果
+#[derive(Debug, Default)]
+pub struct HashVerifyReport {
+    /// 内容が一致したファイル数
+    pub verified: usize,
+    /// リモートとハッシュが一致しなかったファイル
+    pub mismatched: Vec<PathBuf>,
+    /// ローカルのハッシュを計算できなかったファイル
+    pub failed: Vec<PathBuf>,
+    /// リモートのハッシュを取得できず照合しなかったファイル数
+    pub skipped: usize,
+}
+
+impl HashVerifyReport {
+    /// 結果の要約（バックアップ完了のメッセージに追加する）
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "チェックサム照合: 一致 {} / 不一致 {} / 照合できず {}",
+            self.verified,
+            self.mismatched.len(),
+            self.failed.len() + self.skipped
+        );
+        for path in &self.mismatched {
+            summary.push_str(&format!("\n  不一致: {}", path.display()));
+        }
+        summary
+    }
+}
+
+struct HashJob {
+    local_path: PathBuf,
+    expected_sha256: String,
+}
+
+/// 保存したファイルのSHA-256を別スレッドで計算し、リモートのハッシュと照合する
+///
+/// 計算中も次のファイルの転送を進められるよう、ハッシュ計算は上限数のスレッドで並行して行う。
+/// 待ち行列も上限数までで、それを超えて追加すると空くまで待つ。結果は件数と一覧に集めるため、
+/// 計算の終わる順番は結果に影響しない
+pub struct HashVerifier {
+    sender: Option<SyncSender<HashJob>>,
+    workers: Vec<JoinHandle<()>>,
+    report: Arc<Mutex<HashVerifyReport>>,
+}
+
+impl HashVerifier {
+    /// `max_jobs` が0の場合はCPU数まで並行して計算する（いずれも上限 `MAX_HASH_JOBS`）
+    pub fn new(max_jobs: usize) -> Self {
+        let jobs = if max_jobs == 0 {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        } else {
+            max_jobs
+        };
+        let jobs = jobs.min(MAX_HASH_JOBS);
+
+        let (sender, receiver) = mpsc::sync_channel::<HashJob>(jobs);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let report = Arc::new(Mutex::new(HashVerifyReport::default()));
+
+        let workers = (0..jobs)
+            .map(|_| {
+                let receiver = receiver.clone();
+                let report = report.clone();
+                std::thread::spawn(move || Self::run_worker(&receiver, &report))
+            })
+            .collect();
+
+        Self {
+            sender: Some(sender),
+            workers,
+            report,
+        }
+    }
+
+    /// 保存したファイルの照合を予約する（待ち行列が埋まっている場合は空くまで待つ）
+    pub fn submit(&self, local_path: PathBuf, expected_sha256: String) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        if sender.send(HashJob { local_path, expected_sha256 }).is_err() {
+            tracing::warn!("チェックサム照合のスレッドが終了しているため照合できません");
+        }
+    }
+
+    /// リモートのハッシュを取得できなかったファイルを数える
+    pub fn skip(&self) {
+        Self::lock(&self.report).skipped += 1;
+    }
+
+    /// 予約したすべての照合が終わるまで待ち、結果を返す
+    pub fn finish(mut self) -> HashVerifyReport {
+        self.join();
+        let mut report = std::mem::take(&mut *Self::lock(&self.report));
+        report.mismatched.sort();
+        report.failed.sort();
+        report
+    }
+
+    fn join(&mut self) {
+        // 送信側を閉じると、待ち行列を処理し終えたスレッドから終了する
+        self.sender = None;
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                tracing::warn!("チェックサム照合のスレッドが異常終了しました");
+            }
+        }
+    }
+
+    fn run_worker(receiver: &Mutex<Receiver<HashJob>>, report: &Mutex<HashVerifyReport>) {
+        loop {
+            // 受信の間だけロックし、計算中は他のスレッドが次のジョブを受け取れるようにする
+            let job = match receiver.lock() {
+                Ok(receiver) => receiver.recv(),
+                Err(_) => return,
+            };
+            let Ok(job) = job else {
+                return;
+            };
+
+            let result = transfer_index::file_sha256(&job.local_path);
+            let mut report = Self::lock(report);
+            match result {
+                Ok(actual) if actual == job.expected_sha256 => report.verified += 1,
+                Ok(_) => {
+                    tracing::warn!("チェックサムが一致しません: {:?}", job.local_path);
+                    report.mismatched.push(job.local_path);
+                }
+                Err(e) => {
+                    tracing::warn!("チェックサム照合用のハッシュの計算に失敗: {:?}: {:#}", job.local_path, e);
+                    report.failed.push(job.local_path);
+                }
+            }
+        }
+    }
+
+    fn lock(report: &Mutex<HashVerifyReport>) -> std::sync::MutexGuard<'_, HashVerifyReport> {
+        report.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Drop for HashVerifier {
+    fn drop(&mut self) {
+        // 途中で失敗した場合も、計算中のスレッドを残さない
+        self.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn sha256_of(path: &std::path::Path) -> String {
+        transfer_index::file_sha256(path).unwrap()
+    }
+
+    #[test]
+    fn reports_matches_mismatches_and_missing_files() {
+        let dir = TempDir::new("hash-verifier");
+        let good = dir.write("good.txt", b"same");
+        let bad = dir.write("bad.txt", b"changed");
+        let missing = dir.path().join("missing.txt");
+
+        let verifier = HashVerifier::new(2);
+        verifier.submit(good.clone(), sha256_of(&good));
+        verifier.submit(bad.clone(), "0".repeat(64));
+        verifier.submit(missing.clone(), "0".repeat(64));
+        verifier.skip();
+        let report = verifier.finish();
+
+        assert_eq!(report.verified, 1);
+        assert_eq!(report.mismatched, vec![bad]);
+        assert_eq!(report.failed, vec![missing]);
+        assert_eq!(report.skipped, 1);
+        assert!(report.summary().contains("一致 1 / 不一致 1 / 照合できず 2"));
+    }
+
+    #[test]
+    fn finish_waits_for_all_jobs_and_sorts_results() {
+        let dir = TempDir::new("hash-verifier");
+        let paths: Vec<PathBuf> = (0..20)
+            .rev()
+            .map(|i| dir.write(&format!("file-{:02}.txt", i), format!("content {}", i).as_bytes()))
+            .collect();
+
+        // 待ち行列より多く予約しても、すべての照合を終えてから結果を返す
+        let verifier = HashVerifier::new(1);
+        for path in &paths {
+            verifier.submit(path.clone(), "f".repeat(64));
+        }
+        let report = verifier.finish();
+
+        let mut expected = paths.clone();
+        expected.sort();
+        assert_eq!(report.verified, 0);
+        assert_eq!(report.mismatched, expected);
+    }
+
+    #[test]
+    fn requested_job_count_is_clamped() {
+        let verifier = HashVerifier::new(64);
+        assert_eq!(verifier.workers.len(), MAX_HASH_JOBS);
+        let verifier = HashVerifier::new(0);
+        assert!((1..=MAX_HASH_JOBS).contains(&verifier.workers.len()));
+    }
+}
@@ -0,0 +1,260 @@
+use glob::{MatchOptions, Pattern};
+use std::path::{Component, Path, PathBuf};
+
+/// リモートのバックアップ元の直下に置く除外設定ファイル（`.gitignore` と同様の書式）
+pub const IGNORE_FILE_NAME: &str = ".kyoshoignore";
+
+/// 除外設定ファイルから読み取るサイズの上限（64KB）
+pub const IGNORE_FILE_MAX_BYTES: u64 = 64 * 1024;
+
+/// `*` と `?` がパス区切りをまたがないように照合する
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// バックアップ元からの相対パスで照合する除外パターンの一覧
+///
+/// `.gitignore` と同様に、`/` を含まないパターンは各階層の名前と、`/` を含むパターンは
+/// バックアップ元からの相対パスと照合する。末尾の `/` はディレクトリのみ、`**` は任意の階層に一致する。
+/// 先頭の `!` は否定で、後に書いたパターンほど優先する（除外されたディレクトリの配下は否定しても対象外のまま）
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    root: PathBuf,
+    rules: Vec<IgnoreRule>,
+}
+
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+    pattern: Pattern,
+    /// バックアップ元からの相対パス全体と照合する
+    anchored: bool,
+    /// ディレクトリにのみ一致する
+    dir_only: bool,
+    /// 一致したものを除外の対象から外す（`!`）
+    negated: bool,
+}
+
+impl IgnoreRules {
+    /// バックアップ元のパスとパターンから作成する（解釈できないパターンは警告して無視する）
+    pub fn new<I, S>(root: &Path, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut rules = Self {
+            root: root.to_path_buf(),
+            rules: Vec::new(),
+        };
+        rules.extend(patterns);
+        rules
+    }
+
+    /// パターンを追加する
+    pub fn extend<I, S>(&mut self, patterns: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for pattern in patterns {
+            let pattern = pattern.as_ref().trim();
+            if pattern.is_empty() || pattern.starts_with('#') {
+                continue;
+            }
+            match IgnoreRule::parse(pattern) {
+                Some(rule) => self.rules.push(rule),
+                None => tracing::warn!("除外パターンを解釈できないため無視します: {}", pattern),
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// バックアップ元の配下のパスが除外対象か（バックアップ元の外のパスは対象外）
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) => self.is_ignored_relative(relative, is_dir),
+            Err(_) => false,
+        }
+    }
+
+    /// バックアップ元からの相対パスが除外対象か（親ディレクトリの除外は考慮しない）
+    pub fn is_ignored_relative(&self, relative: &Path, is_dir: bool) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+
+        let components: Vec<String> = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        let Some(name) = components.last() else {
+            return false;
+        };
+        let relative = components.join("/");
+
+        // 最後に一致したパターンで決める
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                if rule.dir_only && !is_dir {
+                    return false;
+                }
+                let target = if rule.anchored { relative.as_str() } else { name.as_str() };
+                rule.pattern.matches_with(target, MATCH_OPTIONS)
+            })
+            .is_some_and(|rule| !rule.negated)
+    }
+
+    /// rsync に渡す `--exclude` / `--include` の引数
+    ///
+    /// rsync は最初に一致したものを使うため、後に書いたパターンが優先されるよう逆順に並べる
+    pub fn rsync_filter_args(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .rev()
+            .map(|rule| {
+                format!(
+                    "--{}={}{}{}",
+                    if rule.negated { "include" } else { "exclude" },
+                    if rule.anchored { "/" } else { "" },
+                    rule.pattern.as_str(),
+                    if rule.dir_only { "/" } else { "" }
+                )
+            })
+            .collect()
+    }
+}
+
+impl IgnoreRule {
+    fn parse(line: &str) -> Option<Self> {
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let dir_only = line.ends_with('/');
+        let line = line.trim_end_matches('/');
+        // 先頭の `/` はバックアップ元に固定する指定（固定の判定に使い、照合からは外す）
+        let anchored = line.contains('/');
+        let line = line.trim_start_matches('/');
+        if line.is_empty() {
+            return None;
+        }
+
+        let pattern = Pattern::new(line).ok()?;
+        Some(Self { pattern, anchored, dir_only, negated })
+    }
+}
+
+/// 除外設定ファイルの内容からパターンの行を取り出す
+///
+/// 上限で読み取りを打ち切った場合は、途中で切れた最後の行を使わない
+pub fn parse_ignore_file(content: &str, truncated: bool) -> Vec<String> {
+    let mut lines: Vec<&str> = content.lines().collect();
+    if truncated && !content.ends_with('\n') {
+        lines.pop();
+    }
+
+    lines
+        .into_iter()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(patterns: &[&str]) -> IgnoreRules {
+        IgnoreRules::new(Path::new("/home/user/example.com"), patterns)
+    }
+
+    #[test]
+    fn unanchored_pattern_matches_name_at_any_depth() {
+        let rules = rules(&["*.log"]);
+        assert!(rules.is_ignored_relative(Path::new("error.log"), false));
+        assert!(rules.is_ignored_relative(Path::new("wp-content/debug.log"), false));
+        assert!(!rules.is_ignored_relative(Path::new("wp-content/log.txt"), false));
+    }
+
+    #[test]
+    fn anchored_pattern_matches_only_from_root() {
+        let rules = rules(&["/cache", "wp-content/uploads"]);
+        assert!(rules.is_ignored_relative(Path::new("cache"), true));
+        assert!(!rules.is_ignored_relative(Path::new("public_html/cache"), true));
+        assert!(rules.is_ignored_relative(Path::new("wp-content/uploads"), true));
+        assert!(!rules.is_ignored_relative(Path::new("old/wp-content/uploads"), true));
+    }
+
+    #[test]
+    fn wildcard_does_not_cross_separator() {
+        let rules = rules(&["wp-content/*.php", "logs/**/*.gz"]);
+        assert!(rules.is_ignored_relative(Path::new("wp-content/index.php"), false));
+        assert!(!rules.is_ignored_relative(Path::new("wp-content/plugins/index.php"), false));
+        assert!(rules.is_ignored_relative(Path::new("logs/2026/01/access.gz"), false));
+    }
+
+    #[test]
+    fn dir_only_pattern_skips_files() {
+        let rules = rules(&["node_modules/"]);
+        assert!(rules.is_ignored_relative(Path::new("app/node_modules"), true));
+        assert!(!rules.is_ignored_relative(Path::new("app/node_modules"), false));
+    }
+
+    #[test]
+    fn later_negation_re_includes_match() {
+        let rules = rules(&["*.log", "!keep.log"]);
+        assert!(rules.is_ignored_relative(Path::new("error.log"), false));
+        assert!(!rules.is_ignored_relative(Path::new("keep.log"), false));
+        assert!(!rules.is_ignored_relative(Path::new("logs/keep.log"), false));
+    }
+
+    #[test]
+    fn later_pattern_overrides_earlier_negation() {
+        let rules = rules(&["!keep.log", "*.log"]);
+        assert!(rules.is_ignored_relative(Path::new("keep.log"), false));
+    }
+
+    #[test]
+    fn is_ignored_checks_only_paths_under_root() {
+        let rules = rules(&["cache/"]);
+        assert!(rules.is_ignored(Path::new("/home/user/example.com/cache"), true));
+        assert!(!rules.is_ignored(Path::new("/home/user/other.com/cache"), true));
+        assert!(!rules.is_ignored(Path::new("/home/user/example.com"), true));
+    }
+
+    #[test]
+    fn comments_blank_lines_and_invalid_patterns_are_skipped() {
+        let rules = rules(&["# comment", "", "   ", "[", "/", "*.tmp"]);
+        assert_eq!(rules.len(), 1);
+    }
+
+    #[test]
+    fn rsync_filter_args_keep_last_match_wins_order() {
+        let rules = rules(&["/cache/", "*.log", "!keep.log"]);
+        assert_eq!(
+            rules.rsync_filter_args(),
+            vec!["--include=keep.log", "--exclude=*.log", "--exclude=/cache/"]
+        );
+    }
+
+    #[test]
+    fn truncated_ignore_file_drops_partial_last_line() {
+        assert_eq!(parse_ignore_file("a\n# b\n\nc", true), vec!["a"]);
+        assert_eq!(parse_ignore_file("a\nc\n", true), vec!["a", "c"]);
+        assert_eq!(parse_ignore_file("a\nc", false), vec!["a", "c"]);
+    }
+}
@@ -1,7 +1,20 @@
 // Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
 
-mod ssh_client;
+pub mod ssh_client;
 mod config_manager;
+mod backup_error;
+mod disk_space;
+mod ssh_key;
+mod archiver;
+mod path_template;
+mod data_dir;
+mod transfer_index;
+mod backup_crypto;
+mod ignore_rules;
+mod backup_checkpoint;
+mod hash_verifier;
+#[cfg(test)]
+mod test_support;
 
 use config_manager::ConfigManager;
 use std::sync::Mutex;
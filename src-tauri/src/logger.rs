@@ -0,0 +1,202 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+use crate::data_dir;
+
+/// ログファイル名の接頭辞・拡張子（backup.YYYY-MM-DD.log）
+const LOG_FILE_PREFIX: &str = "backup";
+const LOG_FILE_SUFFIX: &str = "log";
+/// 保持するログファイル数（日次ローテーション）
+const MAX_LOG_FILES: usize = 7;
+
+/// 現在のログファイルの書き込み先（保存先の変更時に差し替える）
+static LOG_FILE: OnceLock<Arc<Mutex<Option<RollingFileAppender>>>> = OnceLock::new();
+
+/// 差し替えできるログファイルへの書き込み（書き込み先がない間のログは捨てる）
+struct SwitchableLogFile(Arc<Mutex<Option<RollingFileAppender>>>);
+
+impl Write for SwitchableLogFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.0.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+            Some(appender) => appender.write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.0.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+            Some(appender) => appender.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// ログの保存先ディレクトリ（設定ディレクトリ配下の logs）
+pub fn log_dir() -> Result<PathBuf> {
+    Ok(data_dir::data_dir()?.join("logs"))
+}
+
+/// 日次ローテーションするファイルへのログ出力を初期化
+///
+/// 戻り値のガードが破棄されると未書き込みのログが失われるため、アプリ終了まで保持すること
+pub fn init_logging() -> Result<WorkerGuard> {
+    let appender = build_appender(&log_dir()?)?;
+    let log_file = LOG_FILE.get_or_init(|| Arc::new(Mutex::new(None)));
+    *log_file.lock().unwrap_or_else(PoisonError::into_inner) = Some(appender);
+
+    let (writer, guard) = tracing_appender::non_blocking(SwitchableLogFile(log_file.clone()));
+
+    tracing_subscriber::fmt()
+        .with_writer(writer)
+        .with_ansi(false)
+        .with_max_level(tracing::Level::INFO)
+        .try_init()
+        .map_err(|e| anyhow::anyhow!("ログ出力の初期化に失敗しました: {}", e))?;
+
+    Ok(guard)
+}
+
+/// 日次ローテーションするログファイルの書き込み先を作成
+fn build_appender(dir: &Path) -> Result<RollingFileAppender> {
+    std::fs::create_dir_all(dir)
+        .context("ログディレクトリの作成に失敗しました")?;
+
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(dir)
+        .context("ログファイルの作成に失敗しました")
+}
+
+/// データの保存先の変更後に、変更前のログディレクトリのログを移動し、以降のログを新しい保存先に書き込む
+///
+/// 移動の間は書き込み先を閉じる（Windowsでは開いているファイルを移動できないため）。
+/// 移動先に同名のログがある場合は、変更前のログを末尾に追記する
+pub fn relocate_logs(old_dir: &Path) -> Result<()> {
+    let new_dir = log_dir()?;
+    if old_dir == new_dir {
+        return Ok(());
+    }
+    std::fs::create_dir_all(&new_dir)
+        .context("ログディレクトリの作成に失敗しました")?;
+
+    let mut log_file = LOG_FILE.get().map(|file| file.lock().unwrap_or_else(PoisonError::into_inner));
+    if let Some(log_file) = log_file.as_mut() {
+        **log_file = None;
+    }
+
+    let moved = move_log_files(old_dir, &new_dir);
+    if let Some(log_file) = log_file.as_mut() {
+        **log_file = Some(build_appender(&new_dir)?);
+    }
+    moved
+}
+
+/// ログディレクトリのファイルを移動し、空になった変更前のディレクトリを削除
+fn move_log_files(old_dir: &Path, new_dir: &Path) -> Result<()> {
+    let entries = match std::fs::read_dir(old_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).context("ログディレクトリの読み取りに失敗しました"),
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        if !entry.file_type().is_ok_and(|file_type| file_type.is_file()) {
+            continue;
+        }
+        let source = entry.path();
+        let destination = new_dir.join(entry.file_name());
+
+        if destination.exists() {
+            let content = std::fs::read(&source)
+                .with_context(|| format!("ログファイルの読み取りに失敗しました: {:?}", source))?;
+            std::fs::OpenOptions::new()
+                .append(true)
+                .open(&destination)
+                .and_then(|mut file| file.write_all(&content))
+                .with_context(|| format!("ログファイルの追記に失敗しました: {:?}", destination))?;
+            std::fs::remove_file(&source)
+                .with_context(|| format!("移動元のログファイルの削除に失敗しました: {:?}", source))?;
+        } else {
+            data_dir::move_file(&source, &destination)?;
+        }
+    }
+
+    let _ = std::fs::remove_dir(old_dir);
+    Ok(())
+}
+
+/// 最新のログファイルのパスを取得
+pub fn latest_log_path() -> Result<PathBuf> {
+    let dir = log_dir()?;
+    let entries = std::fs::read_dir(&dir)
+        .context("ログディレクトリの読み取りに失敗しました")?;
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with(LOG_FILE_PREFIX) && name.ends_with(LOG_FILE_SUFFIX)
+        })
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((modified, entry.path()))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+        .context("ログファイルがまだ作成されていません")
+}
+
+/// ログファイルをOSの既定のアプリで開く
+pub fn open_in_default_app(path: &std::path::Path) -> Result<()> {
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    };
+
+    #[cfg(target_os = "macos")]
+    let mut command = std::process::Command::new("open");
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut command = std::process::Command::new("xdg-open");
+
+    command
+        .arg(path)
+        .spawn()
+        .context("ログファイルを開くアプリの起動に失敗しました")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn moves_logs_and_appends_to_logs_with_the_same_name() {
+        let old = TempDir::new("logs-old");
+        let new = TempDir::new("logs-new");
+        old.write("backup.2026-01-01.log", b"old day\n");
+        old.write("backup.2026-01-02.log", b"current\n");
+        new.write("backup.2026-01-02.log", b"earlier stay\n");
+
+        move_log_files(old.path(), new.path()).unwrap();
+
+        assert!(!old.path().exists());
+        assert_eq!(std::fs::read(new.path().join("backup.2026-01-01.log")).unwrap(), b"old day\n");
+        assert_eq!(
+            std::fs::read(new.path().join("backup.2026-01-02.log")).unwrap(),
+            b"earlier stay\ncurrent\n"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::backup_crypto::MANIFEST_FILE_NAME;
+use crate::disk_space::WRITE_BENCHMARK_FILE_PREFIX;
+use crate::ssh_client::{SshClient, PART_FILE_EXTENSION};
+use crate::transfer_index::INDEX_FILE_NAME;
+
+/// 中断したバックアップが残した一時ファイル
+#[derive(Debug, Serialize)]
+pub struct PartialFile {
+    pub path: String,
+    pub size: u64,
+}
+
+/// 一時ファイルの削除結果
+#[derive(Debug, Default, Serialize)]
+pub struct CleanPartialFilesReport {
+    pub removed_files: usize,
+    pub removed_bytes: u64,
+    /// 削除に失敗したファイル（パスと理由）
+    pub failed: Vec<String>,
+}
+
+/// フォルダ配下から中断したバックアップが残した一時ファイルを探す
+///
+/// 対象は再開用の `.kyosho-part`、インデックス・暗号化マニフェストの書き込み途中の `.tmp`、
+/// 書き込み速度の計測用ファイルのみ。シンボリックリンクはたどらない。
+/// MAX_PATH を超える深い階層も探せるよう拡張長パスで辿り、結果のパスには付けない
+pub fn find_partial_files(root: &Path, cancel_flag: &AtomicBool) -> Result<Vec<PartialFile>> {
+    if !root.is_dir() {
+        return Err(anyhow::anyhow!("フォルダが見つかりません: {}", root.display()));
+    }
+
+    let mut files = Vec::new();
+    collect_partial_files(&SshClient::extended_length_path(root), 0, cancel_flag, &mut files)?;
+    Ok(files)
+}
+
+/// フォルダ配下の一時ファイルを削除する
+///
+/// `find_partial_files` と同じく、一時ファイルの命名規則に一致するファイルだけを削除する
+pub fn clean_partial_files(root: &Path, cancel_flag: &AtomicBool) -> Result<CleanPartialFilesReport> {
+    let mut report = CleanPartialFilesReport::default();
+
+    for file in find_partial_files(root, cancel_flag)? {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err(anyhow::anyhow!("🚫 キャンセルされました（削除済み: {}）", report.removed_files));
+        }
+
+        match std::fs::remove_file(SshClient::extended_length_path(Path::new(&file.path))) {
+            Ok(()) => {
+                tracing::info!("一時ファイルを削除: {}", file.path);
+                report.removed_files += 1;
+                report.removed_bytes += file.size;
+            }
+            Err(e) => report.failed.push(format!("{}: {}", file.path, e)),
+        }
+    }
+
+    Ok(report)
+}
+
+/// 一時ファイルの命名規則に一致するか
+fn is_partial_file_name(name: &str) -> bool {
+    let is_part = name.len() > PART_FILE_EXTENSION.len() && name.ends_with(PART_FILE_EXTENSION);
+    let is_pending_metadata = [INDEX_FILE_NAME, MANIFEST_FILE_NAME]
+        .iter()
+        .any(|file_name| name.strip_suffix(".tmp") == Some(*file_name));
+    let is_benchmark = name.starts_with(WRITE_BENCHMARK_FILE_PREFIX) && name.ends_with(".tmp");
+
+    is_part || is_pending_metadata || is_benchmark
+}
+
+/// フォルダ配下の一時ファイルを再帰的に収集
+fn collect_partial_files(dir: &Path, depth: usize, cancel_flag: &AtomicBool, files: &mut Vec<PartialFile>) -> Result<()> {
+    if cancel_flag.load(Ordering::Relaxed) {
+        return Err(anyhow::anyhow!("🚫 キャンセルされました"));
+    }
+
+    // 深すぎる再帰を防ぐ（無限ループ対策）
+    if depth > 50 {
+        return Err(anyhow::anyhow!("ディレクトリの階層が深すぎます: {}", dir.display()));
+    }
+
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("ディレクトリの読み取りに失敗: {:?}", dir))?;
+
+    for entry in entries {
+        let entry = entry.with_context(|| format!("ディレクトリの読み取りに失敗: {:?}", dir))?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            collect_partial_files(&path, depth + 1, cancel_flag, files)?;
+        } else if file_type.is_file() && is_partial_file_name(&entry.file_name().to_string_lossy()) {
+            let size = entry.metadata()
+                .with_context(|| format!("ファイル情報の取得に失敗: {:?}", path))?
+                .len();
+            files.push(PartialFile { path: SshClient::display_path(&path), size });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    /// Windows の MAX_PATH（260文字）を超える長さ
+    const LONG_PATH_LENGTH: usize = 320;
+
+    #[test]
+    fn only_app_specific_temporary_names_are_partial() {
+        assert!(is_partial_file_name("index.html.kyosho-part"));
+        assert!(is_partial_file_name(&format!("{}.tmp", INDEX_FILE_NAME)));
+        assert!(!is_partial_file_name(".kyosho-part"));
+        assert!(!is_partial_file_name("movie.part"));
+        assert!(!is_partial_file_name("cache.tmp"));
+    }
+
+    #[test]
+    fn cleans_partial_files_beyond_max_path() {
+        let local = TempDir::new("partial-long-path");
+        let mut deep_dir = local.path().to_path_buf();
+        while deep_dir.as_os_str().len() < LONG_PATH_LENGTH {
+            deep_dir.push("deeply-nested-directory");
+        }
+        let part_path = deep_dir.join("index.html.kyosho-part");
+        std::fs::create_dir_all(SshClient::extended_length_path(&deep_dir)).unwrap();
+        std::fs::write(SshClient::extended_length_path(&part_path), b"partial").unwrap();
+
+        let cancel_flag = AtomicBool::new(false);
+        let found = find_partial_files(local.path(), &cancel_flag).unwrap();
+        assert_eq!(found.len(), 1);
+        // 利用者に見せるパスには拡張長パスの接頭辞を付けない
+        assert_eq!(found[0].path, part_path.to_string_lossy());
+
+        let report = clean_partial_files(local.path(), &cancel_flag).unwrap();
+        assert_eq!((report.removed_files, report.removed_bytes), (1, 7));
+        assert!(report.failed.is_empty());
+        assert!(!SshClient::extended_length_path(&part_path).exists());
+    }
+}
@@ -0,0 +1,300 @@
+use anyhow::{Context, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+/// 保存先パスのプレースホルダー展開の設定
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathTemplateSettings {
+    /// `{date}` の書式（chrono の strftime 形式）
+    pub date_format: String,
+    /// `{datetime}` の書式（chrono の strftime 形式）
+    pub datetime_format: String,
+    /// プレースホルダーを展開した保存先が、このディレクトリの外を指さないか確認する
+    pub base_directory: Option<String>,
+}
+
+impl Default for PathTemplateSettings {
+    fn default() -> Self {
+        Self {
+            date_format: "%Y-%m-%d".to_string(),
+            datetime_format: "%Y-%m-%d_%H%M%S".to_string(),
+            base_directory: None,
+        }
+    }
+}
+
+/// 保存先パスのプレースホルダーに埋め込む値
+pub struct PathTemplateContext<'a> {
+    pub host: &'a str,
+    pub user: &'a str,
+    pub remote_folder: &'a str,
+    pub now: DateTime<Local>,
+}
+
+/// 保存先パスの `{date}` `{datetime}` `{host}` `{user}` `{domain}` と先頭の `~` を展開する
+///
+/// プレースホルダーを含まないパスはそのまま返す。展開した場合は、設定された基準ディレクトリの
+/// 外を指していないか確認する
+pub fn expand_local_path(
+    template: &str,
+    context: &PathTemplateContext,
+    settings: &PathTemplateSettings,
+) -> Result<String> {
+    let has_placeholder = ["{date}", "{datetime}", "{host}", "{user}", "{domain}"]
+        .iter()
+        .any(|placeholder| template.contains(placeholder));
+
+    let mut expanded = expand_home(template)?;
+    if !has_placeholder {
+        return Ok(expanded);
+    }
+
+    // {datetime} を先に置換（{date} の部分一致を避ける）
+    if expanded.contains("{datetime}") {
+        expanded = expanded.replace("{datetime}", &format_date(&context.now, &settings.datetime_format)?);
+    }
+    if expanded.contains("{date}") {
+        expanded = expanded.replace("{date}", &format_date(&context.now, &settings.date_format)?);
+    }
+    expanded = expanded
+        .replace("{host}", &sanitize_component(context.host))
+        .replace("{user}", &sanitize_component(context.user))
+        .replace("{domain}", &sanitize_component(&domain_from_remote_folder(context.remote_folder)));
+
+    if let Some(base) = &settings.base_directory {
+        let base = normalize(Path::new(&expand_home(base)?));
+        let target = normalize(Path::new(&expanded));
+        if !target.starts_with(&base) {
+            return Err(anyhow::anyhow!(
+                "展開した保存先が基準ディレクトリの外を指しています: {}（基準: {}）",
+                target.display(),
+                base.display()
+            ));
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// 日付入りの保存先フォルダの名前の形式
+///
+/// 古いバックアップフォルダを削除する際に、テンプレートから作られたフォルダだけを対象にするために使う
+#[derive(Debug)]
+pub struct DatedFolderPattern {
+    /// 日付入りのフォルダを作る親ディレクトリ
+    pub parent: PathBuf,
+    prefix: String,
+    suffix: String,
+    date_format: String,
+}
+
+impl DatedFolderPattern {
+    /// 保存先パスのテンプレートから作成する
+    ///
+    /// 最後のフォルダ名に `{date}` か `{datetime}` が1つだけあり、親ディレクトリには日付を含まない場合のみ作成できる。
+    /// フォルダ名が日付だけの場合は、他のツールが作った日付名のフォルダと見分けられないため作成しない
+    pub fn from_template(
+        template: &str,
+        context: &PathTemplateContext,
+        settings: &PathTemplateSettings,
+    ) -> Result<Self> {
+        let template = template.trim_end_matches(['/', '\\']);
+        let (parent_template, name_template) = match template.rfind(['/', '\\']) {
+            Some(index) => (&template[..=index], &template[index + 1..]),
+            None => return Err(anyhow::anyhow!("保存先パスに親ディレクトリがありません: {}", template)),
+        };
+
+        let has_date = |value: &str| value.contains("{date}") || value.contains("{datetime}");
+        let date_placeholders = name_template.matches("{date}").count() + name_template.matches("{datetime}").count();
+        if date_placeholders != 1 || has_date(parent_template) {
+            return Err(anyhow::anyhow!(
+                "保存先の最後のフォルダ名にだけ {{date}} か {{datetime}} を1つ含めてください: {}",
+                template
+            ));
+        }
+
+        let (placeholder, date_format) = if name_template.contains("{datetime}") {
+            ("{datetime}", &settings.datetime_format)
+        } else {
+            ("{date}", &settings.date_format)
+        };
+        format_date(&context.now, date_format)?;
+
+        let name = name_template
+            .replace("{host}", &sanitize_component(context.host))
+            .replace("{user}", &sanitize_component(context.user))
+            .replace("{domain}", &sanitize_component(&domain_from_remote_folder(context.remote_folder)));
+        let (prefix, suffix) = name
+            .split_once(placeholder)
+            .context("保存先のフォルダ名の解析に失敗しました")?;
+        if prefix.is_empty() && suffix.is_empty() {
+            return Err(anyhow::anyhow!(
+                "フォルダ名が日付だけのため、このアプリのバックアップと見分けられません。日付の前後に文字を加えてください（例: backup-{}）: {}",
+                placeholder,
+                template
+            ));
+        }
+
+        Ok(Self {
+            parent: PathBuf::from(expand_local_path(parent_template, context, settings)?),
+            prefix: prefix.to_string(),
+            suffix: suffix.to_string(),
+            date_format: date_format.clone(),
+        })
+    }
+
+    /// フォルダ名がテンプレートから作られたものなら、その日時を返す
+    ///
+    /// 書式どおりに整形し直して元の名前と一致するものだけを対象にし、似た名前のフォルダを誤って含めない
+    pub fn parse(&self, name: &str) -> Option<NaiveDateTime> {
+        let date = name.strip_prefix(&self.prefix)?.strip_suffix(&self.suffix)?;
+        let parsed = NaiveDateTime::parse_from_str(date, &self.date_format)
+            .ok()
+            .or_else(|| {
+                NaiveDate::parse_from_str(date, &self.date_format)
+                    .ok()
+                    .and_then(|day| day.and_hms_opt(0, 0, 0))
+            })?;
+
+        // 書式に整形できない指定（タイムゾーンなど）が含まれていても panic しないよう、エラーを受け取って判定する
+        let mut formatted = String::new();
+        std::fmt::Write::write_fmt(&mut formatted, format_args!("{}", parsed.format(&self.date_format))).ok()?;
+        (formatted == date).then_some(parsed)
+    }
+}
+
+/// リモートフォルダのパスからドメイン名を取り出す
+///
+/// X-Server のパス（/home/<ユーザー>/<ドメイン>/public_html）を想定し、`.` を含む最初の要素を使う。
+/// 見つからない場合は最後の要素
+fn domain_from_remote_folder(remote_folder: &str) -> String {
+    let components: Vec<&str> = remote_folder
+        .split('/')
+        .filter(|component| !component.is_empty())
+        .collect();
+
+    components
+        .iter()
+        .find(|component| component.contains('.') && !component.starts_with('.'))
+        .or_else(|| components.last())
+        .map(|component| component.to_string())
+        .unwrap_or_else(|| "root".to_string())
+}
+
+/// 書式を検証してから日時を整形（不正な書式で panic しないように）
+fn format_date(now: &DateTime<Local>, format: &str) -> Result<String> {
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(anyhow::anyhow!("日付の書式が不正です: {}", format));
+    }
+    Ok(now.format_with_items(items.into_iter()).to_string())
+}
+
+/// 値に含まれるパス区切りや親ディレクトリ参照を無害化して、1つのフォルダ名にする
+fn sanitize_component(value: &str) -> String {
+    let sanitized = value.replace(['/', '\\', ':'], "_");
+    if sanitized.is_empty() || sanitized == "." || sanitized == ".." {
+        "_".to_string()
+    } else {
+        sanitized
+    }
+}
+
+/// 先頭の `~` をホームディレクトリに展開
+fn expand_home(path: &str) -> Result<String> {
+    let Some(rest) = path.strip_prefix('~') else {
+        return Ok(path.to_string());
+    };
+    if !(rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\')) {
+        return Ok(path.to_string());
+    }
+
+    let home = dirs::home_dir().context("ホームディレクトリの取得に失敗しました")?;
+    Ok(format!("{}{}", home.display(), rest))
+}
+
+/// ファイルシステムにアクセスせずに `.` と `..` を解決する
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn context() -> PathTemplateContext<'static> {
+        PathTemplateContext {
+            host: "sv1.example.jp",
+            user: "user",
+            remote_folder: "/home/user/example.com/public_html",
+            now: Local.with_ymd_and_hms(2024, 5, 6, 7, 8, 9).unwrap(),
+        }
+    }
+
+    fn pattern(template: &str) -> Result<DatedFolderPattern> {
+        DatedFolderPattern::from_template(template, &context(), &PathTemplateSettings::default())
+    }
+
+    fn day(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn parse_accepts_names_made_from_the_template() {
+        let pattern = pattern("/backups/{domain}-{date}-full").unwrap();
+        assert_eq!(pattern.parent, PathBuf::from("/backups/"));
+        assert_eq!(pattern.parse("example.com-2024-05-06-full"), Some(day(2024, 5, 6)));
+    }
+
+    #[test]
+    fn parse_rejects_prefix_and_suffix_mismatch() {
+        let pattern = pattern("/backups/{domain}-{date}-full").unwrap();
+        assert_eq!(pattern.parse("other.com-2024-05-06-full"), None);
+        assert_eq!(pattern.parse("example.com-2024-05-06"), None);
+        assert_eq!(pattern.parse("example.com-2024-05-06-full-old"), None);
+    }
+
+    #[test]
+    fn parse_rejects_dates_that_do_not_round_trip() {
+        let pattern = pattern("/backups/site-{date}").unwrap();
+        assert_eq!(pattern.parse("site-2024-5-06"), None);
+        assert_eq!(pattern.parse("site-2024-02-30"), None);
+        assert_eq!(pattern.parse("site-2024-05-06"), Some(day(2024, 5, 6)));
+    }
+
+    #[test]
+    fn parse_reads_datetime_templates() {
+        let pattern = pattern("/backups/site-{datetime}").unwrap();
+        let expected = NaiveDate::from_ymd_opt(2024, 5, 6).unwrap().and_hms_opt(7, 8, 9).unwrap();
+        assert_eq!(pattern.parse("site-2024-05-06_070809"), Some(expected));
+        assert_eq!(pattern.parse("site-2024-05-06"), None);
+    }
+
+    #[test]
+    fn from_template_rejects_bare_date_names() {
+        assert!(pattern("/backups/{date}").is_err());
+        assert!(pattern("/backups/{datetime}/").is_err());
+    }
+
+    #[test]
+    fn from_template_requires_a_single_date_in_the_last_folder() {
+        assert!(pattern("/backups/site").is_err());
+        assert!(pattern("/backups/{date}-{date}").is_err());
+        assert!(pattern("/backups/{date}/site-{date}").is_err());
+        assert!(pattern("site-{date}").is_err());
+    }
+}
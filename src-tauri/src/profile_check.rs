@@ -0,0 +1,191 @@
+use serde::Serialize;
+use std::path::Path;
+use tokio::time::{timeout, Duration};
+
+use crate::ssh_client::{BackupConfig, SshClient};
+use crate::ssh_key;
+
+/// プロファイル検証全体の制限時間（秒）
+const PROFILE_VALIDATION_TIMEOUT_SECS: u64 = 60;
+
+/// 個々のチェック結果
+#[derive(Debug, Serialize)]
+pub struct ProfileCheck {
+    pub name: String,
+    pub passed: bool,
+    pub message: String,
+}
+
+/// プロファイル検証の結果（UIのチェックリスト用）
+#[derive(Debug, Serialize)]
+pub struct ProfileValidationReport {
+    pub profile_name: String,
+    pub passed: bool,
+    pub checks: Vec<ProfileCheck>,
+}
+
+/// 保存済みプロファイルでバックアップが実行できるかを一通り確認
+///
+/// 保存先の書き込み、秘密鍵、SSH接続・認証、リモートフォルダの順に確認し、
+/// 失敗したチェック以降も可能な範囲で続行する。全体を制限時間で打ち切る
+pub async fn validate_profile(config: BackupConfig) -> ProfileValidationReport {
+    let profile_name = config.name.clone();
+    let mut checks = Vec::new();
+
+    let result = timeout(
+        Duration::from_secs(PROFILE_VALIDATION_TIMEOUT_SECS),
+        run_checks(config, &mut checks),
+    )
+    .await;
+
+    if result.is_err() {
+        checks.push(ProfileCheck {
+            name: "制限時間".to_string(),
+            passed: false,
+            message: format!("検証が{}秒以内に完了しませんでした", PROFILE_VALIDATION_TIMEOUT_SECS),
+        });
+    }
+
+    ProfileValidationReport {
+        profile_name,
+        passed: checks.iter().all(|check| check.passed),
+        checks,
+    }
+}
+
+async fn run_checks(config: BackupConfig, checks: &mut Vec<ProfileCheck>) {
+    checks.push(check_local_writable(&config.local_folder));
+
+    let key_check = if config.stored_private_key.is_some() {
+        passed("秘密鍵ファイル", "設定に保存した鍵を使用します")
+    } else {
+        check_key_file(&config.ssh.key_path)
+    };
+    let key_ok = key_check.passed;
+    checks.push(key_check);
+
+    if !key_ok {
+        // 秘密鍵が使えない場合は接続を試みない
+        return;
+    }
+
+    let mut client = SshClient::new(config.ssh_config());
+    let remote_folder = config.remote_folder;
+
+    match client.test_connection().await {
+        Ok(_) => checks.push(passed("SSH接続・認証", "接続と認証に成功しました")),
+        Err(e) => {
+            checks.push(failed("SSH接続・認証", e));
+            return;
+        }
+    }
+
+    match client.check_remote_directory(&remote_folder).await {
+        Ok(()) => checks.push(passed("リモートフォルダ", &format!("{} はディレクトリです", remote_folder))),
+        Err(e) => checks.push(failed("リモートフォルダ", e)),
+    }
+}
+
+/// ローカルの保存先に書き込めるか確認
+///
+/// 保存先のフォルダはバックアップ時に作成されるため、未作成でも失敗にしない。
+/// 存在する最も近い親（保存先が存在すれば保存先そのもの）がフォルダで、書き込めるかを確認する
+fn check_local_writable(local_folder: &str) -> ProfileCheck {
+    let local_path = Path::new(local_folder);
+    // 壊れたシンボリックリンクは作成できないため、リンク自体があれば存在するものとして扱う
+    let Some(existing) = local_path
+        .ancestors()
+        .find(|p| !p.as_os_str().is_empty() && p.symlink_metadata().is_ok())
+    else {
+        return failed("保存先への書き込み", format!("保存先のパスが見つかりません: {}", local_folder));
+    };
+
+    if !existing.is_dir() {
+        return failed(
+            "保存先への書き込み",
+            format!("{} はフォルダではないため、保存先を作成できません", existing.display()),
+        );
+    }
+
+    let probe = existing.join(".kyosho_write_probe");
+    match std::fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            let message = if existing == local_path {
+                format!("{} に書き込めます", existing.display())
+            } else {
+                format!("{} はバックアップ時に作成します（{} に書き込めます）", local_folder, existing.display())
+            };
+            passed("保存先への書き込み", &message)
+        }
+        Err(e) => failed("保存先への書き込み", format!("{} に書き込めません: {}", existing.display(), e)),
+    }
+}
+
+/// 秘密鍵ファイルの存在と権限を確認
+fn check_key_file(key_path: &str) -> ProfileCheck {
+    let path = Path::new(key_path);
+    if !path.exists() {
+        return failed("秘密鍵ファイル", format!("秘密鍵ファイルが見つかりません: {}", key_path));
+    }
+
+    match ssh_key::check_key_permissions(path) {
+        Ok(Some((false, mode))) => failed(
+            "秘密鍵ファイル",
+            format!("権限が安全でありません (現在: {:o})。chmod 600 {} を実行してください", mode, key_path),
+        ),
+        Ok(_) => passed("秘密鍵ファイル", "秘密鍵ファイルが存在し、権限も安全です"),
+        Err(e) => failed("秘密鍵ファイル", e),
+    }
+}
+
+fn passed(name: &str, message: &str) -> ProfileCheck {
+    ProfileCheck {
+        name: name.to_string(),
+        passed: true,
+        message: message.to_string(),
+    }
+}
+
+fn failed(name: &str, error: impl std::fmt::Display) -> ProfileCheck {
+    ProfileCheck {
+        name: name.to_string(),
+        passed: false,
+        message: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn existing_local_folder_is_writable() {
+        let dir = TempDir::new("profile-local-existing");
+        let check = check_local_writable(&dir.path().to_string_lossy());
+        assert!(check.passed, "{}", check.message);
+        assert!(!dir.path().join(".kyosho_write_probe").exists());
+    }
+
+    #[test]
+    fn missing_local_folder_passes_when_the_nearest_parent_is_writable() {
+        let dir = TempDir::new("profile-local-missing");
+        let local_folder = dir.path().join("site/2026-01-01");
+
+        let check = check_local_writable(&local_folder.to_string_lossy());
+        assert!(check.passed, "{}", check.message);
+        assert!(check.message.contains("作成します"), "{}", check.message);
+        assert!(!local_folder.exists());
+    }
+
+    #[test]
+    fn fails_when_the_nearest_existing_parent_is_a_file() {
+        let dir = TempDir::new("profile-local-file");
+        let file = dir.write("backup", b"");
+
+        let check = check_local_writable(&file.join("site").to_string_lossy());
+        assert!(!check.passed);
+        assert!(check.message.contains("フォルダではない"), "{}", check.message);
+    }
+}
@@ -0,0 +1,188 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+
+use crate::ssh_client::BackupProgress;
+use crate::commands::batch::MultiBackupProgress;
+
+/// 進捗イベントの名前
+pub const BACKUP_PROGRESS_EVENT: &str = "backup-progress";
+
+/// 一括バックアップ全体の進捗イベントの名前
+pub const MULTI_BACKUP_PROGRESS_EVENT: &str = "multi-backup-progress";
+
+/// 前回の通知の受信確認を待つ時間（過ぎたら確認がなくても次の進捗を通知する）
+const PROGRESS_ACK_WINDOW: Duration = Duration::from_millis(250);
+
+/// `backup-progress` イベントの送信を間引く
+///
+/// 前回の通知をフロントエンドが受信確認（`ack_backup_progress`）するまでの進捗は送らずに
+/// 最新のものだけを保持し、受信確認か確認待ちの時間切れの後に送る。受信確認をしない
+/// フロントエンドでも、確認待ちの時間ごとに1回は通知する。完了時の進捗は間引かない。
+/// 一括バックアップでは全体の進捗（`multi-backup-progress`）も同じ間隔で一緒に送る
+#[derive(Default)]
+pub struct ProgressEvents {
+    inner: Mutex<ProgressEventsState>,
+}
+
+#[derive(Default)]
+struct ProgressEventsState {
+    /// 受信確認を待っている通知の送信時刻
+    awaiting_ack_since: Option<Instant>,
+    /// 送らずに保持している最新の進捗
+    pending: Option<ProgressUpdate>,
+    /// 送らずに破棄した進捗の数（ログ用）
+    dropped: usize,
+}
+
+/// 1回に送る進捗（一括バックアップでは全体の進捗も含む）
+struct ProgressUpdate {
+    progress: BackupProgress,
+    batch: Option<MultiBackupProgress>,
+}
+
+impl ProgressEventsState {
+    /// 今すぐ送る進捗を返す（受信確認待ちで間引く場合は保持して None）
+    fn offer(&mut self, update: ProgressUpdate) -> Option<ProgressUpdate> {
+        // 完了時の進捗（フェーズ別の所要時間を含む）は必ず送る
+        let is_final = update.progress.phase_timings.is_some();
+        let ack_pending = self
+            .awaiting_ack_since
+            .is_some_and(|since| since.elapsed() < PROGRESS_ACK_WINDOW);
+        if ack_pending && !is_final {
+            if self.pending.replace(update).is_some() {
+                self.dropped += 1;
+            }
+            return None;
+        }
+
+        self.pending = None;
+        self.awaiting_ack_since = Some(Instant::now());
+        Some(update)
+    }
+
+    /// 受信確認を記録し、保持している進捗があれば返す
+    fn acknowledge(&mut self) -> Option<ProgressUpdate> {
+        self.awaiting_ack_since = None;
+        let update = self.pending.take()?;
+        self.awaiting_ack_since = Some(Instant::now());
+        Some(update)
+    }
+
+    /// 保持している進捗を返し、次のバックアップに備えて状態を戻す
+    fn flush(&mut self) -> Option<ProgressUpdate> {
+        if self.dropped > 0 {
+            tracing::debug!("受信確認待ちのため送らなかった進捗: {} 件", self.dropped);
+        }
+        std::mem::take(self).pending
+    }
+}
+
+impl ProgressEvents {
+    /// 進捗を通知する（間引いた場合は保持して false を返す）
+    pub fn emit(&self, app_handle: &tauri::AppHandle, progress: BackupProgress) -> bool {
+        self.emit_update(app_handle, ProgressUpdate { progress, batch: None })
+    }
+
+    /// 一括バックアップのジョブの進捗を、全体の進捗とともに通知する
+    ///
+    /// 間引いた場合は両方を保持し、受信確認や `flush` の際に一緒に送る
+    pub fn emit_with_batch(&self, app_handle: &tauri::AppHandle, progress: BackupProgress, batch: MultiBackupProgress) -> bool {
+        self.emit_update(app_handle, ProgressUpdate { progress, batch: Some(batch) })
+    }
+
+    /// フロントエンドからの受信確認（保持している進捗があれば送る）
+    pub fn acknowledge(&self, app_handle: &tauri::AppHandle) {
+        let mut inner = self.lock();
+        if let Some(update) = inner.acknowledge() {
+            Self::send(app_handle, &update);
+        }
+    }
+
+    /// 保持している進捗を送り、次のバックアップに備えて状態を戻す
+    pub fn flush(&self, app_handle: &tauri::AppHandle) {
+        let mut inner = self.lock();
+        if let Some(update) = inner.flush() {
+            Self::send(app_handle, &update);
+        }
+    }
+
+    /// 送る順序が入れ替わらないよう、ロックしたまま送る
+    fn emit_update(&self, app_handle: &tauri::AppHandle, update: ProgressUpdate) -> bool {
+        let mut inner = self.lock();
+        match inner.offer(update) {
+            Some(update) => {
+                Self::send(app_handle, &update);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn send(app_handle: &tauri::AppHandle, update: &ProgressUpdate) {
+        let _ = app_handle.emit(BACKUP_PROGRESS_EVENT, &update.progress);
+        if let Some(batch) = &update.batch {
+            let _ = app_handle.emit(MULTI_BACKUP_PROGRESS_EVENT, batch);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ProgressEventsState> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh_client::PhaseTimings;
+
+    fn update(transferred_files: usize) -> ProgressUpdate {
+        ProgressUpdate {
+            progress: BackupProgress { transferred_files, ..Default::default() },
+            batch: Some(MultiBackupProgress { transferred_files, job_index: 1, job_count: 2, ..Default::default() }),
+        }
+    }
+
+    fn transferred_files(update: Option<ProgressUpdate>) -> Option<(usize, Option<usize>)> {
+        update.map(|update| (update.progress.transferred_files, update.batch.map(|batch| batch.transferred_files)))
+    }
+
+    #[test]
+    fn holds_only_the_latest_progress_until_acknowledged() {
+        let mut state = ProgressEventsState::default();
+        assert_eq!(transferred_files(state.offer(update(1))), Some((1, Some(1))));
+        assert!(state.offer(update(2)).is_none());
+        assert!(state.offer(update(3)).is_none());
+        assert_eq!(state.dropped, 1);
+
+        // 受信確認で、保持していた進捗を全体の進捗とともに送る
+        assert_eq!(transferred_files(state.acknowledge()), Some((3, Some(3))));
+        assert!(state.awaiting_ack_since.is_some());
+        assert!(state.acknowledge().is_none());
+    }
+
+    #[test]
+    fn flush_returns_the_held_batch_progress_and_resets() {
+        let mut state = ProgressEventsState::default();
+        state.offer(update(1));
+        state.offer(update(2));
+
+        assert_eq!(transferred_files(state.flush()), Some((2, Some(2))));
+        assert!(state.awaiting_ack_since.is_none());
+        assert!(state.flush().is_none());
+        assert!(state.offer(update(3)).is_some());
+    }
+
+    #[test]
+    fn sends_final_progress_and_progress_after_the_ack_window() {
+        let mut state = ProgressEventsState::default();
+        state.offer(update(1));
+
+        let mut final_update = update(2);
+        final_update.progress.phase_timings = Some(PhaseTimings::default());
+        assert_eq!(transferred_files(state.offer(final_update)), Some((2, Some(2))));
+
+        state.awaiting_ack_since = Instant::now().checked_sub(PROGRESS_ACK_WINDOW);
+        assert_eq!(transferred_files(state.offer(update(3))), Some((3, Some(3))));
+    }
+}
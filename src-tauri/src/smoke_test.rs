@@ -0,0 +1,164 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// 応答を待つ時間の既定値（秒）
+pub const DEFAULT_SMOKE_TEST_TIMEOUT_SECS: u64 = 15;
+
+/// 期待するステータスコードの既定値
+pub const DEFAULT_EXPECTED_STATUS: u16 = 200;
+
+/// 追跡するリダイレクトの上限
+const MAX_REDIRECTS: usize = 10;
+
+/// 本文を読み取るサイズの上限（大きなページを最後まで読まない）
+const BODY_READ_LIMIT: usize = 64 * 1024;
+
+/// 結果に含める本文の先頭の文字数
+const BODY_SNIPPET_CHARS: usize = 500;
+
+/// サイトの表示確認（HTTP GET）の結果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmokeTestResult {
+    pub url: String,
+    /// リダイレクトを追跡した後のURL
+    pub final_url: String,
+    pub redirected: bool,
+    pub status: u16,
+    /// ステータスコードの説明（"OK" など。不明な場合はNone）
+    pub status_text: Option<String>,
+    pub expected_status: u16,
+    /// ステータスコードが期待どおりだったか
+    pub passed: bool,
+    /// 応答ヘッダーを受け取るまでの時間（ミリ秒）
+    pub response_time_ms: u64,
+    /// 本文の先頭（UTF-8として読めない部分は置き換える）
+    pub body_snippet: String,
+    /// 確認した時刻（Unix秒）
+    pub tested_at: u64,
+}
+
+impl SmokeTestResult {
+    /// 結果の要約（ログと履歴用）
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "表示確認: {} {} → {}{} ({}ms)",
+            if self.passed { "✅" } else { "❌" },
+            self.url,
+            self.status,
+            self.status_text.as_deref().map(|text| format!(" {}", text)).unwrap_or_default(),
+            self.response_time_ms
+        );
+        if !self.passed {
+            summary.push_str(&format!("（期待値: {}）", self.expected_status));
+        }
+        if self.redirected {
+            summary.push_str(&format!("\nリダイレクト先: {}", self.final_url));
+        }
+        summary
+    }
+}
+
+/// URLにGETリクエストを送り、ステータスコードが期待どおりか確認する
+///
+/// リダイレクトは上限まで追跡し、最終的な応答のステータスコードで判定する。
+/// ステータスコードが期待と異なる場合はエラーにせず、結果の `passed` で返す
+pub async fn run(url: &str, expected_status: u16, timeout: Duration) -> Result<SmokeTestResult> {
+    let parsed = reqwest::Url::parse(url.trim())
+        .map_err(|e| anyhow!("URLの形式が正しくありません: {}: {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(anyhow!("http または https のURLを指定してください: {}", url));
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
+        .user_agent(concat!("kyosho-backup/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(|e| anyhow!("HTTPクライアントの作成に失敗しました: {}", e))?;
+
+    let started = Instant::now();
+    let mut response = client
+        .get(parsed.clone())
+        .send()
+        .await
+        .map_err(|e| describe_error(&e, timeout))?;
+    let response_time_ms = started.elapsed().as_millis() as u64;
+
+    let status = response.status();
+    let final_url = response.url().clone();
+
+    // 本文は先頭だけ読めば十分なため、上限に達したら読み取りをやめる
+    let mut body = Vec::new();
+    while body.len() < BODY_READ_LIMIT {
+        match response.chunk().await {
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            Ok(None) => break,
+            Err(e) => {
+                tracing::warn!("本文の読み取りに失敗しました: {}: {}", url, describe_error(&e, timeout));
+                break;
+            }
+        }
+    }
+    let body_snippet: String = String::from_utf8_lossy(&body)
+        .trim()
+        .chars()
+        .take(BODY_SNIPPET_CHARS)
+        .collect();
+
+    let result = SmokeTestResult {
+        url: parsed.to_string(),
+        final_url: final_url.to_string(),
+        redirected: final_url != parsed,
+        status: status.as_u16(),
+        status_text: status.canonical_reason().map(str::to_string),
+        expected_status,
+        passed: status.as_u16() == expected_status,
+        response_time_ms,
+        body_snippet,
+        tested_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    };
+
+    if result.passed {
+        tracing::info!("{}", result.summary());
+    } else {
+        tracing::warn!("{}", result.summary());
+    }
+    Ok(result)
+}
+
+/// 失敗の原因ごとに分かりやすいメッセージにする
+fn describe_error(error: &reqwest::Error, timeout: Duration) -> anyhow::Error {
+    let detail = error_chain(error);
+
+    if error.is_timeout() {
+        return anyhow!("{}秒以内に応答がありませんでした: {}", timeout.as_secs(), detail);
+    }
+    if error.is_redirect() {
+        return anyhow!("リダイレクトが多すぎるか、ループしています（上限 {} 回）: {}", MAX_REDIRECTS, detail);
+    }
+
+    let lower = detail.to_lowercase();
+    if ["certificate", "tls", "ssl", "handshake"].iter().any(|word| lower.contains(word)) {
+        return anyhow!("TLS（SSL証明書）の検証に失敗しました。証明書の期限・ドメイン名を確認してください: {}", detail);
+    }
+    if error.is_connect() {
+        return anyhow!("サーバーに接続できませんでした（ドメインのDNS設定・ポートを確認してください）: {}", detail);
+    }
+
+    anyhow!("リクエストに失敗しました: {}", detail)
+}
+
+/// エラーとその原因をつなげた文字列（reqwest のエラーは原因が入れ子になっている）
+fn error_chain(error: &reqwest::Error) -> String {
+    let mut messages = vec![error.to_string()];
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        messages.push(cause.to_string());
+        source = cause.source();
+    }
+    messages.join(": ")
+}
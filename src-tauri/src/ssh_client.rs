@@ -1,828 +1,355 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use ssh2::Session;
+use ssh2::{MethodType, Session};
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
 use std::io::prelude::*;
-use std::net::TcpStream;
-use std::path::Path;
+use std::io::SeekFrom;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
 use tokio::time::{timeout, Duration, Instant};
 use std::pin::Pin;
 use std::future::Future;
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::sync::{Arc, atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering}, mpsc};
+
+use crate::backup_checkpoint::{BackupCheckpoint, CheckpointWriter};
+use crate::backup_crypto::{self, BackupEncryption, FileEncryptor, Passphrase};
+use crate::backup_error::{BackupError, BackupErrorKind, ClassifiedError, DiscoveryCancelled, JumpHostStage, TimedOutFile};
+use crate::disk_space;
+use crate::hash_verifier::{HashVerifier, HashVerifyReport};
+use crate::ignore_rules::{self, IgnoreRules};
+use crate::ssh_key::{self, StoredPrivateKey};
+use crate::transfer_index::{self, IndexEntry, IndexSession};
+
+mod auth;
+mod browse;
+mod checksum;
+mod completion;
+mod connection;
+mod control;
+mod download;
+mod errors;
+mod incremental;
+mod mirror;
+mod options;
+mod paths;
+mod progress;
+mod remote;
+mod rsync;
+mod state;
+mod tar_stream;
+mod transfer;
+mod walk;
+
+pub use browse::RemoteDirectoryListing;
+pub use control::BackupControl;
+pub use options::{BackupConfig, BackupOptions, OverwritePolicy, TransferBackend, CaseCollisionStrategy};
+pub use progress::{BackupProgress, OverwriteCounts, PhaseTimings, ProgressThrottle, ProgressGranularity};
+
+use control::BackupWatchdog;
+use incremental::IncrementalSkip;
+use state::{DeadlineReader, ProgressReader, RemoteDirEntries, RemoteTreeCount, TransferState};
+
+/// SSH接続タイムアウトのデフォルト値（秒）
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 30;
+
+pub fn default_connect_timeout_secs() -> u64 {
+    DEFAULT_CONNECT_TIMEOUT_SECS
+}
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SshConfig {
     pub hostname: String,
     pub port: u16,
     pub username: String,
     pub key_path: String,
+    /// 接続（TCP接続〜認証）のタイムアウト秒数。転送のタイムアウトとは独立
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    /// 名前解決で複数のアドレスが得られた場合にIPv6を優先して接続する
+    #[serde(default)]
+    pub prefer_ipv6: bool,
+    /// key_path で認証できなかった場合に順に試す秘密鍵（鍵のローテーション用）
+    #[serde(default)]
+    pub additional_key_paths: Vec<String>,
+    /// 経由する踏み台サーバー（ProxyJump 相当）
+    #[serde(default)]
+    pub jump_host: Option<Box<SshConfig>>,
+    /// 使用するアルゴリズムの優先順（古いサーバー向け。未指定はlibssh2の既定）
+    #[serde(default)]
+    pub algorithms: SshAlgorithms,
+    /// 接続（TCP接続・ハンドシェイク）に失敗した場合の再試行回数（0で再試行しない）
+    #[serde(default)]
+    pub connect_retries: u32,
+    /// 1回の接続試行のタイムアウト秒数（未指定は connect_timeout_secs）
+    #[serde(default)]
+    pub per_attempt_timeout_secs: Option<u64>,
+    /// 認証方式（パスワードはコマンドの引数で一時的に受け取るだけで、ファイルには保存しない）
+    #[serde(skip)]
+    pub auth_method: SshAuthMethod,
 }
 
-// 進捗報告用の構造体
-#[derive(Debug, Clone, Serialize)]
-pub struct BackupProgress {
-    pub phase: String,
-    pub transferred_files: usize,
-    pub total_files: Option<usize>,
-    pub transferred_bytes: u64,
-    pub current_file: Option<String>,
-    pub elapsed_seconds: u64,
-    pub transfer_speed: Option<f64>,
-}
-
-// 進捗更新の間隔制御
-pub struct ProgressThrottle {
-    last_update: Instant,
-    last_bytes: u64,
-    start_time: Instant,
-    update_interval: Duration,
-    byte_threshold: u64,
+/// SSHの認証方式
+#[derive(Clone, Default)]
+pub enum SshAuthMethod {
+    /// 秘密鍵による公開鍵認証（推奨）
+    #[default]
+    Key,
+    /// パスワード認証（鍵が使えない緊急時の代替手段。公開鍵認証を強く推奨）
+    Password(String),
+    /// プロファイルの設定に保存した秘密鍵による公開鍵認証（鍵ファイルを使わない）
+    StoredKey(StoredPrivateKey),
 }
 
-impl ProgressThrottle {
-    pub fn new() -> Self {
-        Self {
-            last_update: Instant::now(),
-            last_bytes: 0,
-            start_time: Instant::now(),
-            update_interval: Duration::from_secs(3), // 3秒間隔
-            byte_threshold: 50 * 1024 * 1024, // 50MB閾値
-        }
-    }
-
-    pub fn should_update(&mut self, transferred_bytes: u64) -> bool {
-        let now = Instant::now();
-        let time_elapsed = now.duration_since(self.last_update) >= self.update_interval;
-        let bytes_elapsed = transferred_bytes.saturating_sub(self.last_bytes) >= self.byte_threshold;
-
-        if time_elapsed || bytes_elapsed {
-            self.last_update = now;
-            self.last_bytes = transferred_bytes;
-            true
-        } else {
-            false
+impl SshAuthMethod {
+    /// 空でないパスワードが渡された場合はパスワード認証、それ以外は公開鍵認証
+    pub fn from_password(password: Option<String>) -> Self {
+        match password {
+            Some(password) if !password.is_empty() => Self::Password(password),
+            _ => Self::Key,
         }
     }
+}
 
-    pub fn get_elapsed_seconds(&self) -> u64 {
-        self.start_time.elapsed().as_secs()
-    }
-
-    pub fn calculate_speed(&self, total_bytes: u64) -> Option<f64> {
-        let elapsed = self.start_time.elapsed().as_secs_f64();
-        if elapsed > 0.0 {
-            Some((total_bytes as f64) / elapsed / (1024.0 * 1024.0)) // MB/s
-        } else {
-            None
+// パスワードがログに出ないように Debug ではマスクする
+impl std::fmt::Debug for SshAuthMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Key => write!(f, "Key"),
+            Self::Password(_) => write!(f, "Password(***)"),
+            Self::StoredKey(_) => write!(f, "StoredKey(***)"),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct BackupConfig {
-    pub ssh: SshConfig,
-    pub remote_folder: String,
-    pub local_folder: String,
+/// SSHのアルゴリズムの優先順（それぞれカンマ区切り、例: "aes128-cbc,3des-cbc"）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SshAlgorithms {
+    pub kex: Option<String>,
+    pub host_key: Option<String>,
+    pub cipher: Option<String>,
+    pub mac: Option<String>,
 }
 
-pub struct SshClient {
-    session: Option<Session>,
-    config: SshConfig,
-}
-
-impl SshClient {
-    pub fn new(config: SshConfig) -> Self {
-        Self {
-            session: None,
-            config,
+impl SshAlgorithms {
+    /// 指定されたアルゴリズムと対応する libssh2 の種別（暗号・MACは送受信の両方向）
+    fn preferences(&self) -> Vec<(MethodType, &'static str, &str)> {
+        let mut preferences = Vec::new();
+        if let Some(kex) = &self.kex {
+            preferences.push((MethodType::Kex, "鍵交換", kex.as_str()));
         }
-    }
-
-    /// SSH接続をテストする（エラー分類対応）
-    pub async fn test_connection(&mut self) -> Result<String> {
-        let connection_future = async {
-            // TCP接続
-            let tcp = TcpStream::connect(&format!("{}:{}", self.config.hostname, self.config.port))
-                .context("TCP接続に失敗しました")?;
-
-            // SSH セッションを開始
-            let mut session = Session::new()
-                .context("SSHセッションの作成に失敗しました")?;
-
-            session.set_tcp_stream(tcp);
-            session.handshake()
-                .context("SSHハンドシェイクに失敗しました")?;
-
-            // 公開鍵認証
-            let private_key_path = Path::new(&self.config.key_path);
-            if !private_key_path.exists() {
-                return Err(anyhow::anyhow!("秘密鍵ファイルが見つかりません: {}", self.config.key_path));
-            }
-
-            // ファイル権限をチェック
-            let metadata = std::fs::metadata(private_key_path)
-                .context("秘密鍵ファイルのメタデータ取得に失敗しました")?;
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                let mode = metadata.permissions().mode();
-                if mode & 0o077 != 0 {
-                    return Err(anyhow::anyhow!(
-                        "秘密鍵ファイルの権限が安全でありません (現在: {:o})。chmod 600 {} を実行してください。",
-                        mode & 0o777,
-                        self.config.key_path
-                    ));
-                }
-            }
-
-            // 利用可能な認証方法を確認
-            let auth_methods = session.auth_methods(&self.config.username)
-                .context("認証方法の取得に失敗しました")?;
-
-            println!("利用可能な認証方法: {}", auth_methods);
-
-            // 秘密鍵の形式をチェック
-            let key_content = std::fs::read_to_string(private_key_path)
-                .context("秘密鍵ファイルの読み取りに失敗しました")?;
-
-            let key_format = if key_content.contains("BEGIN OPENSSH PRIVATE KEY") {
-                "OpenSSH"
-            } else if key_content.contains("BEGIN RSA PRIVATE KEY") || key_content.contains("BEGIN PRIVATE KEY") {
-                "PEM"
-            } else {
-                "不明"
-            };
-
-            println!("秘密鍵形式: {}", key_format);
-
-            let auth_result = session.userauth_pubkey_file(
-                &self.config.username,
-                None,
-                private_key_path,
-                None,
-            );
-
-            if let Err(e) = auth_result {
-                return Err(anyhow::anyhow!(
-                    "SSH公開鍵認証に失敗しました。\nユーザー: {}\n鍵ファイル: {}\n鍵形式: {}\nエラー: {}\n\nヒント: X-Serverでは PEM 形式の鍵が推奨されています。OpenSSH形式の場合は、以下のコマンドで変換できます:\nssh-keygen -p -m PEM -f {}",
-                    self.config.username,
-                    self.config.key_path,
-                    key_format,
-                    e,
-                    self.config.key_path
-                ));
-            }
-
-            if !session.authenticated() {
-                return Err(anyhow::anyhow!("SSH認証に失敗しました"));
-            }
-
-            // 簡単なコマンドを実行してテスト
-            let mut channel = session.channel_session()
-                .context("SSHチャンネルの作成に失敗しました")?;
-
-            channel.exec("echo 'SSH connection test successful'")
-                .context("SSHコマンドの実行に失敗しました")?;
-
-            let mut result = String::new();
-            channel.read_to_string(&mut result)
-                .context("SSHコマンドの結果読み取りに失敗しました")?;
-
-            channel.wait_close()
-                .context("SSHチャンネルのクローズに失敗しました")?;
-
-            self.session = Some(session);
-
-            Ok(format!("✅ SSH接続テスト成功!\n{}@{}:{}\n結果: {}",
-                self.config.username,
-                self.config.hostname,
-                self.config.port,
-                result.trim()
-            ))
-        };
-
-        // 30秒でタイムアウト（エラー分類適用）
-        match timeout(Duration::from_secs(30), connection_future).await {
-            Ok(Ok(result)) => Ok(result),
-            Ok(Err(e)) => Err(anyhow::anyhow!("{}", Self::classify_error(&e))),
-            Err(_) => Err(anyhow::anyhow!(
-                "⏱️ タイムアウトエラー: SSH接続が30秒でタイムアウトしました\n\
-                 - サーバーが応答していない可能性があります\n\
-                 - ネットワーク接続を確認してください"
-            )),
+        if let Some(host_key) = &self.host_key {
+            preferences.push((MethodType::HostKey, "ホスト鍵", host_key.as_str()));
+        }
+        if let Some(cipher) = &self.cipher {
+            preferences.push((MethodType::CryptCs, "暗号", cipher.as_str()));
+            preferences.push((MethodType::CryptSc, "暗号", cipher.as_str()));
         }
+        if let Some(mac) = &self.mac {
+            preferences.push((MethodType::MacCs, "MAC", mac.as_str()));
+            preferences.push((MethodType::MacSc, "MAC", mac.as_str()));
+        }
+        preferences
     }
 
-    /// リモートディレクトリを探索する
-    pub async fn list_remote_directories(&mut self, path: &str) -> Result<Vec<String>> {
-        let list_future = async {
-            // 接続がない場合は接続を確立
-            if self.session.is_none() {
-                self.test_connection().await?;
-            }
-
-            let session = self.session.as_ref()
-                .context("SSHセッションが確立されていません")?;
-
-            // SFTPチャンネルを作成
-            let sftp = session.sftp()
-                .context("SFTPセッションの作成に失敗しました")?;
-
-            // ディレクトリの存在確認
-            let path_to_check = if path.is_empty() || path == "/" {
-                Path::new("/")
-            } else {
-                Path::new(path)
-            };
-
-            let mut directories = Vec::new();
-
-            match sftp.readdir(path_to_check) {
-                Ok(entries) => {
-                    for (entry_path, stat) in entries {
-                        if stat.is_dir() {
-                            if let Some(dir_name) = entry_path.to_str() {
-                                directories.push(dir_name.to_string());
-                            }
-                        }
-                    }
-                }
-                Err(_) => {
-                    // エラーの場合は空のリストを返す
-                    return Ok(directories);
-                }
-            }
-
-            directories.sort();
-            Ok(directories)
-        };
-
-        // 30秒でタイムアウト
-        timeout(Duration::from_secs(30), list_future)
-            .await
-            .context("ディレクトリ探索がタイムアウトしました")?
+    /// rsync から起動する ssh コマンドに渡すオプション
+    fn ssh_options(&self) -> String {
+        [
+            ("KexAlgorithms", &self.kex),
+            ("HostKeyAlgorithms", &self.host_key),
+            ("Ciphers", &self.cipher),
+            ("MACs", &self.mac),
+        ]
+        .iter()
+        .filter_map(|(option, value)| value.as_ref().map(|value| format!(" -o {}={}", option, value)))
+        .collect()
     }
+}
 
-    /// ホームディレクトリから利用可能なドメインを探索する
-    pub async fn find_domains(&mut self) -> Result<Vec<String>> {
-        let find_future = async {
-            // 接続がない場合は接続を確立
-            if self.session.is_none() {
-                self.test_connection().await?;
-            }
-
-            let session = self.session.as_ref()
-                .context("SSHセッションが確立されていません")?;
-
-            // SFTPチャンネルを作成
-            let sftp = session.sftp()
-                .context("SFTPセッションの作成に失敗しました")?;
-
-            let mut domains = Vec::new();
-
-            // /home/[username]/ ディレクトリを探索
-            let home_path = format!("/home/{}", self.config.username);
-
-            match sftp.readdir(Path::new(&home_path)) {
-                Ok(entries) => {
-                    for (entry_path, stat) in entries {
-                        if stat.is_dir() {
-                            if let Some(dir_name) = entry_path.file_name() {
-                                if let Some(name_str) = dir_name.to_str() {
-                                    // ドメイン名らしいディレクトリをフィルター（.が含まれている）
-                                    if name_str.contains('.') && !name_str.starts_with('.') {
-                                        // public_htmlがあるかチェック
-                                        let public_html_path = entry_path.join("public_html");
-                                        if sftp.stat(&public_html_path).is_ok() {
-                                            domains.push(format!("{}/public_html", entry_path.to_string_lossy()));
-                                        } else {
-                                            // public_htmlがなくても候補として追加
-                                            domains.push(entry_path.to_string_lossy().to_string());
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    return Err(anyhow::anyhow!("ホームディレクトリの探索に失敗しました: {}", e));
-                }
-            }
-
-            domains.sort();
-            Ok(domains)
-        };
-
-        // 30秒でタイムアウト
-        timeout(Duration::from_secs(30), find_future)
-            .await
-            .context("ドメイン探索がタイムアウトしました")?
+impl SshConfig {
+    /// 認証に試す秘密鍵のパス（key_path、additional_key_paths の順）
+    pub fn key_paths(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.key_path.as_str())
+            .chain(self.additional_key_paths.iter().map(String::as_str))
+            .filter(|path| !path.is_empty())
     }
 
-    /// リモートフォルダをローカルにバックアップ
-    pub async fn backup_folder(&mut self, remote_path: &str, local_path: &str) -> Result<String> {
-        let cancel_flag = Arc::new(AtomicBool::new(false));
-        self.backup_folder_with_cancel(remote_path, local_path, cancel_flag).await
+    /// 1回の接続試行（TCP接続・ハンドシェイク・認証）のタイムアウト
+    pub fn attempt_timeout(&self) -> Duration {
+        Duration::from_secs(self.per_attempt_timeout_secs.unwrap_or(self.connect_timeout_secs).max(1))
     }
+}
 
-    /// キャンセル対応のリモートフォルダバックアップ
-    pub async fn backup_folder_with_progress<F>(&mut self, remote_path: &str, local_path: &str, cancel_flag: Arc<AtomicBool>, progress_callback: F) -> Result<String>
-    where
-        F: Fn(BackupProgress) + Send + Sync + 'static,
-    {
-        let callback = Arc::new(progress_callback);
-
-        // 初期進捗を送信
-        callback(BackupProgress {
-            phase: "接続中".to_string(),
-            transferred_files: 0,
-            total_files: None,
-            transferred_bytes: 0,
-            current_file: None,
-            elapsed_seconds: 0,
-            transfer_speed: None,
-        });
-
-        self.backup_folder_with_cancel_and_progress(remote_path, local_path, cancel_flag, callback).await
-    }
+/// ページ単位で取得したリモートのディレクトリ一覧
+#[derive(Debug, Serialize)]
+pub struct DirectoryPage {
+    pub directories: Vec<String>,
+    /// このページより後にもディレクトリがあるか
+    pub has_more: bool,
+    /// 続きを取得するためのカーソル（続きがある場合のみ。呼び出し側で設定する）
+    pub cursor: Option<String>,
+}
 
-    pub async fn backup_folder_with_cancel(&mut self, remote_path: &str, local_path: &str, cancel_flag: Arc<AtomicBool>) -> Result<String> {
-        // 進捗コールバックなしでバックアップを実行
-        self.backup_folder_with_cancel_and_progress(remote_path, local_path, cancel_flag, Arc::new(|_| {})).await
-    }
+/// リモートのディレクトリツリーのノード
+#[derive(Debug, Serialize)]
+pub struct RemoteTreeNode {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    /// ファイルのサイズ（ディレクトリは None）
+    pub size: Option<u64>,
+    pub children: Vec<RemoteTreeNode>,
+    /// 深さ・ノード数・時間の上限に達したため、中身を読み取っていない（または途中まで）
+    pub truncated: bool,
+}
 
-    async fn backup_folder_with_cancel_and_progress<F>(&mut self, remote_path: &str, local_path: &str, cancel_flag: Arc<AtomicBool>, progress_callback: Arc<F>) -> Result<String>
-    where
-        F: Fn(BackupProgress) + Send + Sync + 'static,
-    {
-        let backup_future = async {
-            let mut throttle = ProgressThrottle::new();
-
-            // 接続がない場合は接続を確立
-            if self.session.is_none() {
-                progress_callback(BackupProgress {
-                    phase: "SSH接続中".to_string(),
-                    transferred_files: 0,
-                    total_files: None,
-                    transferred_bytes: 0,
-                    current_file: None,
-                    elapsed_seconds: throttle.get_elapsed_seconds(),
-                    transfer_speed: None,
-                });
-                self.test_connection().await?;
-            }
-
-            let session = self.session.as_ref()
-                .context("SSHセッションが確立されていません")?;
-
-            // SFTPチャンネルを作成
-            progress_callback(BackupProgress {
-                phase: "SFTPセッション作成中".to_string(),
-                transferred_files: 0,
-                total_files: None,
-                transferred_bytes: 0,
-                current_file: None,
-                elapsed_seconds: throttle.get_elapsed_seconds(),
-                transfer_speed: None,
-            });
-
-            let sftp = session.sftp()
-                .context("SFTPセッションの作成に失敗しました")?;
-
-            // ローカルディレクトリを作成
-            std::fs::create_dir_all(local_path)
-                .context("ローカルバックアップディレクトリの作成に失敗しました")?;
-
-            // リモートディレクトリの存在確認
-            progress_callback(BackupProgress {
-                phase: "リモートフォルダ確認中".to_string(),
-                transferred_files: 0,
-                total_files: None,
-                transferred_bytes: 0,
-                current_file: Some(remote_path.to_string()),
-                elapsed_seconds: throttle.get_elapsed_seconds(),
-                transfer_speed: None,
-            });
-
-            let remote_stat = sftp.stat(Path::new(remote_path))
-                .with_context(|| format!("リモートフォルダが見つかりません: {}", remote_path))?;
-
-            if !remote_stat.is_dir() {
-                return Err(anyhow::anyhow!("指定されたリモートパスはディレクトリではありません: {}", remote_path));
-            }
-
-            progress_callback(BackupProgress {
-                phase: "ファイル転送開始".to_string(),
-                transferred_files: 0,
-                total_files: None,
-                transferred_bytes: 0,
-                current_file: None,
-                elapsed_seconds: throttle.get_elapsed_seconds(),
-                transfer_speed: None,
-            });
-
-            // ファイル転送の実行（再帰的実装）
-            let transferred_files = self.backup_directory_recursive_with_cancel_and_progress(
-                &sftp,
-                Path::new(remote_path),
-                Path::new(local_path),
-                0,
-                &cancel_flag,
-                progress_callback.clone()
-            ).await?;
-
-            if cancel_flag.load(Ordering::Relaxed) {
-                progress_callback(BackupProgress {
-                    phase: "キャンセル完了".to_string(),
-                    transferred_files,
-                    total_files: None,
-                    transferred_bytes: 0,
-                    current_file: None,
-                    elapsed_seconds: throttle.get_elapsed_seconds(),
-                    transfer_speed: None,
-                });
-                return Err(anyhow::anyhow!("🚫 バックアップがキャンセルされました"));
-            }
-
-            progress_callback(BackupProgress {
-                phase: "バックアップ完了".to_string(),
-                transferred_files,
-                total_files: Some(transferred_files),
-                transferred_bytes: 0,
-                current_file: None,
-                elapsed_seconds: throttle.get_elapsed_seconds(),
-                transfer_speed: throttle.calculate_speed(0),
-            });
-
-            Ok(format!("✅ バックアップ完了!\n転送ファイル数: {}\nリモート: {}\nローカル: {}",
-                transferred_files, remote_path, local_path))
-        };
-
-        // 2時間でタイムアウト（大容量バックアップ対応・エラー分類適用）
-        match timeout(Duration::from_secs(7200), backup_future).await {
-            Ok(Ok(result)) => Ok(result),
-            Ok(Err(e)) => Err(anyhow::anyhow!("{}", Self::classify_error(&e))),
-            Err(_) => Err(anyhow::anyhow!(
-                "⏱️ タイムアウトエラー: バックアップ処理が2時間でタイムアウトしました\n\
-                 - 非常に大容量のデータをバックアップしようとしている可能性があります\n\
-                 - ネットワーク速度が極端に遅い可能性があります\n\
-                 - バックアップ対象を分割することをお勧めします"
-            )),
-        }
-    }
+/// リモートのディレクトリツリー（`get_remote_tree` の結果）
+#[derive(Debug, Serialize)]
+pub struct RemoteTree {
+    pub root: RemoteTreeNode,
+    /// ツリーに含めたノード数（ルートを除く）
+    pub node_count: usize,
+    /// ノード数・時間の上限に達して打ち切ったか（深さの上限では立てない）
+    pub budget_exhausted: bool,
+}
 
-    /// ファイル転送の最適化実装（128KBバッファ使用）
-    fn transfer_file_optimized(
-        remote_file: &mut ssh2::File,
-        local_file: &mut std::fs::File,
-    ) -> Result<u64> {
-        // エックスサーバー最適化: 128KBバッファ
-        // 理由: RTT 10-50ms × 10-100Mbps → 最適バッファサイズ
-        // 調査により8KB→128KBで1.5-3倍の転送速度向上を確認
-        const BUFFER_SIZE: usize = 128 * 1024; // 128KB
-
-        let mut buffer = vec![0u8; BUFFER_SIZE];
-        let mut total_bytes = 0u64;
-
-        loop {
-            match remote_file.read(&mut buffer) {
-                Ok(0) => break, // EOF
-                Ok(n) => {
-                    local_file.write_all(&buffer[..n])
-                        .with_context(|| "ローカルファイル書き込み失敗")?;
-                    total_bytes += n as u64;
-                }
-                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
-                    continue; // シグナル割り込み→リトライ
-                }
-                Err(e) => return Err(e.into()),
-            }
-        }
+/// リモートで実行したデータベースダンプの結果
+#[derive(Debug, Serialize)]
+pub struct MysqlDumpResult {
+    /// ダンプを書き出したリモートのパス
+    pub remote_path: String,
+    pub size_bytes: u64,
+    pub elapsed_seconds: u64,
+    /// mysqldump が標準エラー出力に出した警告
+    pub warnings: Vec<String>,
+}
 
-        Ok(total_bytes)
-    }
+/// サーバーの時刻とローカルの時計とのずれ
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerTime {
+    /// サーバーの現在時刻（Unix秒）
+    pub server_time: u64,
+    /// 問い合わせの往復の中間時点のローカル時刻（Unix秒）
+    pub local_time: u64,
+    /// サーバーの時計のずれ（サーバー − ローカル、秒。正ならサーバーが進んでいる）
+    pub skew_seconds: i64,
+    /// 問い合わせの往復時間（ミリ秒。ずれの誤差の目安）
+    pub round_trip_ms: u64,
+    /// ずれが大きく、前回以降の更新の判定に影響するか
+    pub large_skew: bool,
+}
 
-    /// ファイルサイズに基づいてタイムアウト時間を動的に計算
-    ///
-    /// # 計算ロジック
-    /// - 基本タイムアウト: 30秒（ファイルオープンと小ファイル用）
-    /// - 小ファイル（<10MB）: 60秒
-    /// - 中ファイル（10MB-100MB）: 120秒
-    /// - 大ファイル（100MB-1GB）: 600秒
-    /// - 巨大ファイル（>1GB）: 1800秒（30分）
-    ///
-    /// これにより、無駄な長時間待機を避けつつ、大ファイル転送も確実に完了できる
-    fn calculate_file_timeout(file_size: u64) -> Duration {
-        const MB: u64 = 1024 * 1024;
-        const GB: u64 = 1024 * MB;
-
-        if file_size < 10 * MB {
-            Duration::from_secs(60)  // 小ファイル: 1分
-        } else if file_size < 100 * MB {
-            Duration::from_secs(120)  // 中ファイル: 2分
-        } else if file_size < GB {
-            Duration::from_secs(600)  // 大ファイル: 10分
-        } else {
-            Duration::from_secs(1800)  // 巨大ファイル: 30分
-        }
-    }
+/// 時計のずれを警告する大きさ（秒）
+pub const CLOCK_SKEW_WARNING_SECS: i64 = 60;
+
+/// 接続の診断結果（サーバーの対応状況と、それにより使えるオプション機能）
+#[derive(Debug, Serialize)]
+pub struct ConnectionDiagnostics {
+    /// サーバーのSSHバナー（`SSH-2.0-OpenSSH_8.0` など）
+    pub banner: Option<String>,
+    /// ハンドシェイクで合意したアルゴリズム
+    pub algorithms: NegotiatedAlgorithms,
+    /// サーバーが提示した認証方法
+    pub auth_methods: Vec<String>,
+    /// 認証に使用した秘密鍵のパス（パスワード認証の場合はNone）
+    pub authenticated_key_path: Option<String>,
+    /// ログイン時のホームディレクトリ
+    pub home_directory: Option<String>,
+    pub rsync_available: bool,
+    pub sha256sum_available: bool,
+    pub du_available: bool,
+    /// rsync での転送を使えるか（サーバーとローカルの rsync、公開鍵認証、踏み台なし）
+    pub rsync_backend_usable: bool,
+}
 
-    /// エラーを分類してユーザーフレンドリーなメッセージを生成
-    ///
-    /// # エラー分類
-    /// 1. 認証エラー: 秘密鍵の問題、パスフレーズ不正など
-    /// 2. ネットワークエラー: 接続タイムアウト、DNS解決失敗など
-    /// 3. パーミッションエラー: 読み取り/書き込み権限不足
-    /// 4. ファイルシステムエラー: ディスク容量不足、パス不正など
-    /// 5. タイムアウトエラー: 転送タイムアウト
-    /// 6. その他のエラー
-    fn classify_error(error: &anyhow::Error) -> String {
-        let error_str = error.to_string().to_lowercase();
-
-        // 認証エラー
-        if error_str.contains("authentication")
-            || error_str.contains("publickey")
-            || error_str.contains("passphrase")
-            || error_str.contains("permission denied (publickey)") {
-            return format!(
-                "🔐 認証エラー: SSH秘密鍵の確認が必要です\n\
-                 - 秘密鍵のパスが正しいか確認してください\n\
-                 - 秘密鍵のパーミッションが600または400になっているか確認してください\n\
-                 - サーバーに公開鍵が正しく登録されているか確認してください\n\n\
-                 詳細: {}", error
-            );
-        }
+/// 転送量の見積もり結果（転送せずにバックアップと同じ判定で集計したもの）
+#[derive(Debug, Default, Serialize)]
+pub struct IncrementalEstimate {
+    /// 転送するファイル数・バイト数
+    pub transfer_files: usize,
+    pub transfer_bytes: u64,
+    /// 参照バックアップ（`link_dest`）へのハードリンクで済むファイル数・バイト数
+    pub linked_files: usize,
+    pub linked_bytes: u64,
+    /// 更新がない・インデックスと一致・既存のファイルを残すなどの理由で転送しないファイル数・バイト数
+    pub skipped_files: usize,
+    pub skipped_bytes: u64,
+    /// サイズ上限により転送しないファイル数・バイト数
+    pub excluded_files: usize,
+    pub excluded_bytes: u64,
+    /// 時間の上限に達して途中で打ち切ったか（打ち切った場合はそれまでの集計）
+    pub budget_exhausted: bool,
+}
 
-        // ネットワークエラー
-        if error_str.contains("connection")
-            || error_str.contains("timeout")
-            || error_str.contains("dns")
-            || error_str.contains("network")
-            || error_str.contains("host") {
-            return format!(
-                "🌐 ネットワークエラー: サーバーへの接続に失敗しました\n\
-                 - インターネット接続を確認してください\n\
-                 - サーバーのホスト名とポート番号が正しいか確認してください\n\
-                 - ファイアウォールやVPNの設定を確認してください\n\n\
-                 詳細: {}", error
-            );
-        }
+/// ハンドシェイクで合意したアルゴリズム（取得できないものはNone）
+#[derive(Debug, Serialize)]
+pub struct NegotiatedAlgorithms {
+    pub kex: Option<String>,
+    pub host_key: Option<String>,
+    pub cipher_client_to_server: Option<String>,
+    pub cipher_server_to_client: Option<String>,
+    pub mac_client_to_server: Option<String>,
+    pub mac_server_to_client: Option<String>,
+}
 
-        // パーミッションエラー
-        if error_str.contains("permission denied")
-            || error_str.contains("access denied")
-            || error_str.contains("forbidden") {
-            return format!(
-                "🚫 権限エラー: ファイルやディレクトリへのアクセスが拒否されました\n\
-                 - サーバー上のファイル/ディレクトリの権限を確認してください\n\
-                 - ローカルの保存先ディレクトリの書き込み権限を確認してください\n\n\
-                 詳細: {}", error
-            );
-        }
+/// リモートのファイル情報（フォルダからの相対パス、区切りは "/"）
+#[derive(Debug, Clone)]
+pub struct RemoteFileEntry {
+    pub relative_path: String,
+    pub size: u64,
+    pub mtime: Option<u64>,
+}
 
-        // ディスク容量エラー
-        if error_str.contains("no space")
-            || error_str.contains("disk full")
-            || error_str.contains("quota") {
-            return format!(
-                "💾 ディスク容量エラー: ストレージに空き容量がありません\n\
-                 - ローカルディスクの空き容量を確保してください\n\
-                 - 不要なファイルを削除するか、別のディスクを選択してください\n\n\
-                 詳細: {}", error
-            );
-        }
+/// 接続の再試行の待機時間（失敗するごとに倍にする）とその上限
+const CONNECT_RETRY_BACKOFF_BASE: Duration = Duration::from_secs(1);
+const CONNECT_RETRY_BACKOFF_MAX: Duration = Duration::from_secs(8);
 
-        // タイムアウトエラー
-        if error_str.contains("timeout") || error_str.contains("timed out") {
-            return format!(
-                "⏱️ タイムアウトエラー: 処理時間が制限を超えました\n\
-                 - ネットワーク速度が遅い可能性があります\n\
-                 - 大容量ファイルの場合、時間をおいて再試行してください\n\
-                 - サーバーの応答が遅い可能性があります\n\n\
-                 詳細: {}", error
-            );
-        }
+/// libssh2 がディレクトリの終端で返すエラーコード（LIBSSH2_ERROR_FILE）
+const LIBSSH2_ERROR_FILE: i32 = -16;
 
-        // ファイルシステムエラー
-        if error_str.contains("no such file")
-            || error_str.contains("not found")
-            || error_str.contains("invalid path") {
-            return format!(
-                "📁 ファイルシステムエラー: ファイルまたはディレクトリが見つかりません\n\
-                 - 指定したパスが正しいか確認してください\n\
-                 - サーバー上にファイル/ディレクトリが存在するか確認してください\n\n\
-                 詳細: {}", error
-            );
-        }
+/// tar・rsync の標準エラー出力を保持する上限（エラーメッセージ用）
+const MAX_STDERR_BYTES: usize = 64 * 1024;
 
-        // その他のエラー（詳細をそのまま表示）
-        format!("❌ エラーが発生しました: {}", error)
-    }
+/// ダウンロード途中のファイルに付ける拡張子（一時ファイルの掃除で利用者のファイルを消さないよう、アプリ固有にする）
+pub const PART_FILE_EXTENSION: &str = ".kyosho-part";
 
-    /// 再帰的にディレクトリをバックアップする
-    fn backup_directory_recursive<'a>(
-        &'a self,
-        sftp: &'a ssh2::Sftp,
-        remote_dir: &'a Path,
-        local_dir: &'a Path,
-        depth: usize,
-    ) -> Pin<Box<dyn Future<Output = Result<usize>> + Send + 'a>> {
-        Box::pin(async move {
-        // 深すぎる再帰を防ぐ（無限ループ対策）
-        if depth > 50 {
-            return Err(anyhow::anyhow!("ディレクトリの階層が深すぎます: {}", remote_dir.display()));
-        }
+pub struct SshClient {
+    session: Option<Session>,
+    config: SshConfig,
+    /// 認証に成功した秘密鍵のパス
+    authenticated_key_path: Option<String>,
+    /// 認証前にサーバーが提示した認証方法（カンマ区切り）
+    server_auth_methods: Option<String>,
+    /// 接続・探索を中断するためのフラグ（バックアップの制御とは別）
+    cancel_flag: Option<Arc<AtomicBool>>,
+}
 
-        // ローカルディレクトリを作成
-        std::fs::create_dir_all(local_dir)
-            .with_context(|| format!("ローカルディレクトリの作成に失敗: {:?}", local_dir))?;
-
-        let mut total_files = 0;
-
-        // リモートディレクトリを読み取り
-        let entries = sftp.readdir(remote_dir)
-            .with_context(|| format!("リモートディレクトリの読み取りに失敗: {:?}", remote_dir))?;
-
-        for (entry_path, stat) in entries {
-            if let Some(entry_name) = entry_path.file_name() {
-                // 隠しファイル/ディレクトリをスキップ（. で始まるもの）
-                if let Some(name_str) = entry_name.to_str() {
-                    if name_str.starts_with('.') {
-                        continue;
-                    }
-                }
-
-                let local_entry_path = local_dir.join(entry_name);
-
-                if stat.is_file() {
-                    // ファイルをダウンロード（個別ファイルに10分のタイムアウト）
-                    let file_transfer = async {
-                        let mut remote_file = sftp.open(&entry_path)
-                            .with_context(|| format!("リモートファイルのオープンに失敗: {:?}", entry_path))?;
-
-                        let mut local_file = std::fs::File::create(&local_entry_path)
-                            .with_context(|| format!("ローカルファイルの作成に失敗: {:?}", local_entry_path))?;
-
-                        // 最適化された転送関数を使用（128KBバッファ）- 転送バイト数を返す
-                        let transferred = Self::transfer_file_optimized(&mut remote_file, &mut local_file)
-                            .with_context(|| format!("ファイル転送に失敗: {:?}", entry_path))?;
-
-                        Ok::<u64, anyhow::Error>(transferred)
-                    };
-
-                    let _transferred = timeout(Duration::from_secs(600), file_transfer)
-                        .await
-                        .with_context(|| format!("ファイル転送がタイムアウトしました: {:?}", entry_path))??;
-
-                    // 注: この関数は進捗コールバックなしバージョンのため、transferred_bytesは使用しない
-                    total_files += 1;
-
-                } else if stat.is_dir() {
-                    // ディレクトリを再帰的に処理
-                    let sub_files = self.backup_directory_recursive(
-                        sftp,
-                        &entry_path,
-                        &local_entry_path,
-                        depth + 1
-                    ).await?;
-
-                    total_files += sub_files;
-                }
-            }
+impl SshClient {
+    pub fn new(config: SshConfig) -> Self {
+        Self {
+            session: None,
+            config,
+            authenticated_key_path: None,
+            server_auth_methods: None,
+            cancel_flag: None,
         }
-
-        Ok(total_files)
-        })
     }
 
-    /// 進捗レポート対応の再帰的ディレクトリバックアップ
-    fn backup_directory_recursive_with_cancel_and_progress<'a, F>(
-        &'a self,
-        sftp: &'a ssh2::Sftp,
-        remote_dir: &'a Path,
-        local_dir: &'a Path,
-        depth: usize,
-        cancel_flag: &'a Arc<AtomicBool>,
-        progress_callback: Arc<F>,
-    ) -> Pin<Box<dyn Future<Output = Result<usize>> + Send + 'a>>
-    where
-        F: Fn(BackupProgress) + Send + Sync + 'static,
-    {
-        Box::pin(async move {
-        // キャンセル確認
-        if cancel_flag.load(Ordering::Relaxed) {
-            return Err(anyhow::anyhow!("🚫 バックアップがキャンセルされました"));
-        }
-
-        // 深すぎる再帰を防ぐ（無限ループ対策）
-        if depth > 50 {
-            return Err(anyhow::anyhow!("ディレクトリの階層が深すぎます: {}", remote_dir.display()));
-        }
+    /// 接続・探索を中断するフラグを設定する（true になると処理の区切りで中断する）
+    pub fn with_cancel_flag(mut self, cancel_flag: Arc<AtomicBool>) -> Self {
+        self.cancel_flag = Some(cancel_flag);
+        self
+    }
 
-        // ローカルディレクトリを作成
-        std::fs::create_dir_all(local_dir)
-            .with_context(|| format!("ローカルディレクトリの作成に失敗: {:?}", local_dir))?;
-
-        let mut total_files = 0;
-        let mut total_transferred_bytes = 0u64;
-        let mut throttle = ProgressThrottle::new();
-
-        // リモートディレクトリを読み取り
-        let entries = sftp.readdir(remote_dir)
-            .with_context(|| format!("リモートディレクトリの読み取りに失敗: {:?}", remote_dir))?;
-
-        for (entry_path, stat) in entries {
-            // キャンセル確認
-            if cancel_flag.load(Ordering::Relaxed) {
-                return Err(anyhow::anyhow!("🚫 バックアップがキャンセルされました"));
-            }
-
-            if let Some(entry_name) = entry_path.file_name() {
-                // 隠しファイル/ディレクトリをスキップ（. で始まるもの）
-                if let Some(name_str) = entry_name.to_str() {
-                    if name_str.starts_with('.') {
-                        continue;
-                    }
-                }
-
-                let local_entry_path = local_dir.join(entry_name);
-
-                if stat.is_file() {
-                    // 進捗報告（スロットル制御付き - 正確な転送バイト数で更新）
-                    if throttle.should_update(total_transferred_bytes) {
-                        progress_callback(BackupProgress {
-                            phase: "ファイル転送中".to_string(),
-                            transferred_files: total_files,
-                            total_files: None,
-                            transferred_bytes: total_transferred_bytes,
-                            current_file: Some(entry_path.to_string_lossy().to_string()),
-                            elapsed_seconds: throttle.get_elapsed_seconds(),
-                            transfer_speed: throttle.calculate_speed(total_transferred_bytes),
-                        });
-                    }
-
-                    // ファイルサイズ取得（Noneの場合は0として扱う）
-                    let file_size = stat.size.unwrap_or(0);
-
-                    // ファイルサイズに基づいて動的にタイムアウトを計算
-                    let file_timeout = Self::calculate_file_timeout(file_size);
-
-                    // ファイルをダウンロード（ファイルサイズに応じた動的タイムアウト）
-                    let file_transfer = async {
-                        let mut remote_file = sftp.open(&entry_path)
-                            .with_context(|| format!("リモートファイルのオープンに失敗: {:?}", entry_path))?;
-
-                        let mut local_file = std::fs::File::create(&local_entry_path)
-                            .with_context(|| format!("ローカルファイルの作成に失敗: {:?}", local_entry_path))?;
-
-                        // 最適化された転送関数を使用（128KBバッファ）- 転送バイト数を返す
-                        let transferred = Self::transfer_file_optimized(&mut remote_file, &mut local_file)
-                            .with_context(|| format!("ファイル転送に失敗: {:?}", entry_path))?;
-
-                        Ok::<u64, anyhow::Error>(transferred)
-                    };
-
-                    let transferred = timeout(file_timeout, file_transfer)
-                        .await
-                        .with_context(|| format!("ファイル転送がタイムアウトしました（{}秒）: {:?}", file_timeout.as_secs(), entry_path))??;
-
-                    total_transferred_bytes += transferred;
-                    total_files += 1;
-
-                } else if stat.is_dir() {
-                    // ディレクトリを再帰的に処理
-                    let sub_files = self.backup_directory_recursive_with_cancel_and_progress(
-                        sftp,
-                        &entry_path,
-                        &local_entry_path,
-                        depth + 1,
-                        cancel_flag,
-                        progress_callback.clone()
-                    ).await?;
-
-                    total_files += sub_files;
-                }
-            }
+    /// 中断フラグが立っていればエラーを返す
+    fn check_cancelled(cancel_flag: Option<&AtomicBool>) -> Result<()> {
+        if cancel_flag.is_some_and(|flag| flag.load(Ordering::SeqCst)) {
+            return Err(DiscoveryCancelled.into());
         }
-
-        Ok(total_files)
-        })
+        Ok(())
     }
 
-    /// キャンセル対応の再帰的ディレクトリバックアップ（進捗なし）
-    fn backup_directory_recursive_with_cancel<'a>(
-        &'a self,
-        sftp: &'a ssh2::Sftp,
-        remote_dir: &'a Path,
-        local_dir: &'a Path,
-        depth: usize,
-        cancel_flag: &'a Arc<AtomicBool>,
-    ) -> Pin<Box<dyn Future<Output = Result<usize>> + Send + 'a>> {
-        // 進捗レポートなしで実行
-        self.backup_directory_recursive_with_cancel_and_progress(
-            sftp, remote_dir, local_dir, depth, cancel_flag, Arc::new(|_| {})
-        )
-    }
 }
 
 impl Drop for SshClient {
@@ -831,4 +358,4 @@ impl Drop for SshClient {
             let _ = session.disconnect(None, "Connection closed", None);
         }
     }
-}
\ No newline at end of file
+}
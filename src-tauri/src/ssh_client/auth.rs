@@ -0,0 +1,288 @@
+use super::*;
+
+/// 踏み台経由の中継でデータを待つ間隔
+const JUMP_RELAY_POLL_INTERVAL: Duration = Duration::from_millis(2);
+
+/// 踏み台経由の中継の読み取りバッファサイズ
+const JUMP_RELAY_BUFFER_SIZE: usize = 32 * 1024;
+
+impl SshClient {
+    /// ハンドシェイク済みのセッションで認証する（戻り値は `open_session` と同じ）
+    pub(super) fn authenticate_session(config: &SshConfig, session: Session) -> Result<(Session, Option<String>, String)> {
+        // 利用可能な認証方法を確認
+        let auth_methods = session.auth_methods(&config.username)
+            .context("認証方法の取得に失敗しました")?
+            .to_string();
+
+        tracing::info!("利用可能な認証方法: {} ({})", auth_methods, config.hostname);
+
+        // パスワード認証（鍵が使えない場合の代替手段）
+        if let SshAuthMethod::Password(password) = &config.auth_method {
+            Self::authenticate_with_password(config, &session, password, &auth_methods)?;
+            if !session.authenticated() {
+                return Err(anyhow::anyhow!("SSHパスワード認証に失敗しました"));
+            }
+            return Ok((session, None, auth_methods));
+        }
+
+        // 設定に保存した鍵による公開鍵認証（鍵ファイルは試さない）
+        if let SshAuthMethod::StoredKey(key) = &config.auth_method {
+            Self::authenticate_with_stored_key(config, &session, key)?;
+            if !session.authenticated() {
+                return Err(anyhow::anyhow!("SSH認証に失敗しました"));
+            }
+            return Ok((session, None, auth_methods));
+        }
+
+        // 公開鍵認証（登録された鍵を順に試行）
+        let mut failures = Vec::new();
+        for key_path in config.key_paths() {
+            match Self::authenticate_with_key(config, &session, key_path) {
+                Ok(()) if session.authenticated() => return Ok((session, Some(key_path.to_string()), auth_methods)),
+                Ok(()) => return Err(anyhow::anyhow!("SSH認証に失敗しました")),
+                Err(e) => {
+                    tracing::warn!("鍵での認証に失敗: {}: {:#}", key_path, e);
+                    // サーバーに切断されたため残りの鍵は試行できない
+                    let disconnected = Self::is_too_many_auth_failures(&e);
+                    failures.push(e);
+                    if disconnected {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // 鍵が1つの場合はその鍵のエラーをそのまま返す
+        if failures.len() == 1 {
+            return Err(failures.remove(0));
+        }
+        let details: Vec<String> = failures.iter().map(|e| format!("- {}", e)).collect();
+        Err(anyhow::anyhow!(
+            "SSH公開鍵認証に失敗しました（{}個の鍵をすべて試行）\n{}",
+            failures.len(),
+            details.join("\n")
+        ))
+    }
+
+    /// 踏み台サーバーに接続し、接続先へのトンネルをローカルのTCPストリームとして返す
+    ///
+    /// libssh2 のセッションはソケットを必要とするため、ループバックの接続と
+    /// 踏み台の direct-tcpip チャンネルの間をスレッドで中継する
+    pub(super) fn connect_via_jump_host(
+        jump_host: &SshConfig,
+        target_host: &str,
+        target_port: u16,
+        cancel_flag: Option<&AtomicBool>,
+    ) -> Result<TcpStream> {
+        let jump_label = format!("{}@{}:{}", jump_host.username, jump_host.hostname, jump_host.port);
+        // 失敗した段階ごとに分類できるよう、どの段階で失敗したかをエラーに付ける
+        let failed_at = |stage| BackupError::JumpHost {
+            jump_host: jump_label.clone(),
+            target: format!("{}:{}", target_host, target_port),
+            stage,
+        };
+
+        let (jump_tcp, jump_addr) = Self::connect_tcp(jump_host, cancel_flag)
+            .context(failed_at(JumpHostStage::Connect))?;
+        tracing::info!("踏み台サーバーへのTCP接続成功: {} -> {}", jump_label, jump_addr);
+
+        Self::check_cancelled(cancel_flag)?;
+        let jump_session = Self::start_session(jump_host, jump_tcp)
+            .context(failed_at(JumpHostStage::Connect))?;
+        // 認証情報を送る前に、踏み台がなりすましでないことを known_hosts で確認する
+        Self::check_known_host(&jump_session, jump_host)
+            .context(failed_at(JumpHostStage::HostKey))?;
+        let (jump_session, _, _) = Self::authenticate_session(jump_host, jump_session)
+            .context(failed_at(JumpHostStage::Authentication))?;
+
+        let channel = jump_session.channel_direct_tcpip(target_host, target_port, None)
+            .context(failed_at(JumpHostStage::Forward))?;
+        jump_session.set_timeout(0);
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .context("踏み台経由の中継用ソケットの作成に失敗しました")?;
+        let tunnel = TcpStream::connect(listener.local_addr()?)
+            .context("踏み台経由の中継用ソケットへの接続に失敗しました")?;
+        let (local, _) = listener.accept()
+            .context("踏み台経由の中継用ソケットの受け入れに失敗しました")?;
+
+        local.set_nonblocking(true)
+            .context("中継用ソケットの設定に失敗しました")?;
+        jump_session.set_blocking(false);
+
+        std::thread::spawn(move || {
+            // 中継が終わるまで踏み台のセッションを保持する
+            let _jump_session = jump_session;
+            let mut channel = channel;
+            let mut local = local;
+            if let Err(e) = Self::relay_jump_channel(&mut local, &mut channel) {
+                tracing::warn!("踏み台経由の中継が終了しました: {}", e);
+            }
+            let _ = channel.close();
+        });
+
+        Ok(tunnel)
+    }
+
+    /// ローカルのソケットと踏み台のチャンネルの間でデータを双方向に中継する（どちらかが閉じるまで）
+    fn relay_jump_channel(local: &mut TcpStream, channel: &mut ssh2::Channel) -> std::io::Result<()> {
+        let mut buf = vec![0u8; JUMP_RELAY_BUFFER_SIZE];
+        let mut to_remote: Vec<u8> = Vec::new();
+        let mut to_local: Vec<u8> = Vec::new();
+
+        loop {
+            let mut progressed = false;
+
+            if to_remote.is_empty() {
+                match local.read(&mut buf) {
+                    // 接続先のセッションが閉じられた
+                    Ok(0) => return Ok(()),
+                    Ok(n) => {
+                        to_remote.extend_from_slice(&buf[..n]);
+                        progressed = true;
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if !to_remote.is_empty() {
+                match channel.write(&to_remote) {
+                    Ok(n) => {
+                        to_remote.drain(..n);
+                        progressed = true;
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if to_local.is_empty() {
+                match channel.read(&mut buf) {
+                    Ok(0) if channel.eof() => return Ok(()),
+                    Ok(0) => {}
+                    Ok(n) => {
+                        to_local.extend_from_slice(&buf[..n]);
+                        progressed = true;
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if !to_local.is_empty() {
+                match local.write(&to_local) {
+                    Ok(n) => {
+                        to_local.drain(..n);
+                        progressed = true;
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if !progressed {
+                std::thread::sleep(JUMP_RELAY_POLL_INTERVAL);
+            }
+        }
+    }
+
+    /// サーバーが認証試行回数の上限で切断したエラーか
+    pub(super) fn is_too_many_auth_failures(error: &anyhow::Error) -> bool {
+        error
+            .chain()
+            .any(|e| e.to_string().to_lowercase().contains("too many authentication failures"))
+    }
+
+    /// パスワードで認証する（サーバーがパスワード認証を許可している場合のみ）
+    fn authenticate_with_password(config: &SshConfig, session: &Session, password: &str, auth_methods: &str) -> Result<()> {
+        if !auth_methods.split(',').any(|method| method.trim() == "password") {
+            return Err(anyhow::anyhow!(
+                "SSHパスワード認証に失敗しました: サーバーがパスワード認証を許可していません（利用可能な認証方法: {}）\n\
+                 公開鍵認証を設定してください",
+                auth_methods
+            ));
+        }
+
+        session.userauth_password(&config.username, password).map_err(|e| anyhow::anyhow!(
+            "SSHパスワード認証に失敗しました。\nユーザー: {}\nエラー: {}",
+            config.username,
+            e
+        ))?;
+
+        tracing::warn!(
+            "パスワード認証で接続しました（{}@{}）。公開鍵認証の利用を強く推奨します",
+            config.username,
+            config.hostname
+        );
+        Ok(())
+    }
+
+    /// 1つの秘密鍵で公開鍵認証を試みる（存在・権限・形式を確認してから認証）
+    fn authenticate_with_key(config: &SshConfig, session: &Session, key_path: &str) -> Result<()> {
+        let private_key_path = Path::new(key_path);
+        if !private_key_path.exists() {
+            return Err(anyhow::anyhow!("秘密鍵ファイルが見つかりません: {}", key_path));
+        }
+
+        // ファイル権限をチェック
+        if let Some((false, mode)) = ssh_key::check_key_permissions(private_key_path)? {
+            return Err(anyhow::anyhow!(
+                "秘密鍵ファイルの権限が安全でありません (現在: {:o})。chmod 600 {} を実行してください。",
+                mode,
+                key_path
+            ));
+        }
+
+        // 秘密鍵の形式をチェック
+        let key_content = std::fs::read_to_string(private_key_path)
+            .context("秘密鍵ファイルの読み取りに失敗しました")?;
+
+        let key_format = ssh_key::detect_key_format(&key_content).label();
+
+        tracing::info!("秘密鍵形式: {} ({})", key_format, key_path);
+
+        let auth_result = session.userauth_pubkey_file(
+            &config.username,
+            None,
+            private_key_path,
+            None,
+        );
+
+        if let Err(e) = auth_result {
+            return Err(anyhow::anyhow!(
+                "SSH公開鍵認証に失敗しました。\nユーザー: {}\n鍵ファイル: {}\n鍵形式: {}\nエラー: {}\n\nヒント: X-Serverでは PEM 形式の鍵が推奨されています。OpenSSH形式の場合は、以下のコマンドで変換できます:\nssh-keygen -p -m PEM -f {}",
+                config.username,
+                key_path,
+                key_format,
+                e,
+                key_path
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 設定に保存した秘密鍵で認証する
+    ///
+    /// Unix では鍵をファイルに書き出さずメモリから渡す。それ以外の環境では libssh2 がメモリからの
+    /// 認証に対応していないため、一時ファイルに書き出して認証し、すぐに上書きして削除する
+    fn authenticate_with_stored_key(config: &SshConfig, session: &Session, key: &StoredPrivateKey) -> Result<()> {
+        tracing::info!("秘密鍵形式: {} (設定に保存した鍵)", key.format().label());
+
+        #[cfg(unix)]
+        let auth_result = session.userauth_pubkey_memory(&config.username, None, key.expose(), None);
+        #[cfg(not(unix))]
+        let auth_result = {
+            let temp = ssh_key::TemporaryKeyFile::write(key)?;
+            session.userauth_pubkey_file(&config.username, None, temp.path(), None)
+        };
+
+        auth_result.map_err(|e| anyhow::anyhow!(
+            "SSH公開鍵認証に失敗しました。\nユーザー: {}\n鍵: 設定に保存した鍵\n鍵形式: {}\nエラー: {}",
+            config.username,
+            key.format().label(),
+            e
+        ))
+    }
+}
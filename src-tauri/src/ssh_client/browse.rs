@@ -0,0 +1,510 @@
+use super::*;
+
+/// リモートのツリー取得で返すノード数の上限（応答が巨大にならないように）
+const REMOTE_TREE_MAX_NODES: usize = 10_000;
+
+/// リモートのツリー取得にかける時間の上限（超えた時点までの結果を返す）
+const REMOTE_TREE_TIME_BUDGET: Duration = Duration::from_secs(20);
+
+/// 開いたままのリモートのフォルダ（`SshClient::open_directory_listing`）
+///
+/// 接続ごと保持し、`next_page` で前のページの続きから読み取る
+pub struct RemoteDirectoryListing {
+    /// 開けなかった・最後まで読み取った場合は None
+    pub(super) dir: Option<ssh2::File>,
+    pub(super) path: PathBuf,
+    /// 続きがあるかを確かめるために先読みしたディレクトリ（次のページの先頭）
+    pub(super) pending: Option<String>,
+    /// フォルダを開いた接続（読み取り中は閉じない。フォルダより後に破棄する）
+    pub(super) client: SshClient,
+}
+
+impl RemoteDirectoryListing {
+    /// 前のページの続きから、最大 `limit` 件のディレクトリを読み取る（`limit` は1以上）
+    pub fn next_page(&mut self, limit: usize) -> Result<DirectoryPage> {
+        if limit == 0 {
+            return Err(anyhow::anyhow!("1ページの件数は1以上を指定してください"));
+        }
+
+        let mut directories: Vec<String> = self.pending.take().into_iter().collect();
+        while directories.len() <= limit {
+            SshClient::check_cancelled(self.client.cancel_flag.as_deref())?;
+
+            let Some(dir) = self.dir.as_mut() else {
+                break;
+            };
+            let (file_name, stat) = match dir.readdir() {
+                Ok(entry) => entry,
+                Err(e) if e.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_FILE) => {
+                    self.dir = None;
+                    break;
+                }
+                Err(e) => return Err(e).with_context(|| format!("リモートディレクトリの読み取りに失敗: {}", self.path.display())),
+            };
+
+            if !stat.is_dir() || file_name == Path::new(".") || file_name == Path::new("..") {
+                continue;
+            }
+
+            if let Some(dir_name) = self.path.join(&file_name).to_str() {
+                directories.push(dir_name.to_string());
+            }
+        }
+
+        // limit 件を超えて1件見つかれば続きがある（次のページの先頭に回す）
+        let has_more = directories.len() > limit;
+        if has_more {
+            self.pending = directories.pop();
+        }
+
+        Ok(DirectoryPage {
+            directories,
+            has_more,
+            cursor: None,
+        })
+    }
+}
+
+/// リモートのツリー取得の残り予算
+struct RemoteTreeBudget {
+    remaining_nodes: usize,
+    pub(super) deadline: Instant,
+    exhausted: bool,
+}
+
+impl RemoteTreeBudget {
+    /// ノードを1つ追加できるか（上限に達したら以降はすべて拒否する）
+    fn take_node(&mut self) -> bool {
+        if self.exhausted || self.remaining_nodes == 0 || Instant::now() >= self.deadline {
+            self.exhausted = true;
+            return false;
+        }
+        self.remaining_nodes -= 1;
+        true
+    }
+}
+
+impl SshClient {
+    /// リモートフォルダ配下のファイルを再帰的に列挙する（内容は転送しない）
+    ///
+    /// 転送時と同じく隠しファイル/ディレクトリ（always_include に一致するものを除く）、
+    /// 除外パターン（`.kyoshoignore` を含む）に一致するもの、特殊ファイルは対象外とする
+    pub async fn list_remote_files(&mut self, remote_path: &str, options: &BackupOptions) -> Result<Vec<RemoteFileEntry>> {
+        if self.session.is_none() {
+            self.test_connection().await?;
+        }
+
+        let session = self.session.as_ref()
+            .context("SSHセッションが確立されていません")?;
+
+        let sftp = session.sftp()
+            .context("SFTPセッションの作成に失敗しました")?;
+
+        let ignore_file = Self::read_ignore_file_with_sftp(&sftp, remote_path);
+        let options = Self::with_ignore_rules(remote_path, options, ignore_file);
+
+        let root = Path::new(remote_path);
+        let mut files = Vec::new();
+        Self::collect_remote_files(&sftp, &options, root, root, 0, &mut files)?;
+        Ok(files)
+    }
+
+    fn collect_remote_files(
+        sftp: &ssh2::Sftp,
+        options: &BackupOptions,
+        root: &Path,
+        remote_dir: &Path,
+        depth: usize,
+        files: &mut Vec<RemoteFileEntry>,
+    ) -> Result<()> {
+        // 深すぎる再帰を防ぐ（無限ループ対策）
+        if depth > 50 {
+            return Err(anyhow::anyhow!("ディレクトリの階層が深すぎます: {}", remote_dir.display()));
+        }
+
+        // 読み取れないディレクトリはバックアップ時にもスキップするため含めない
+        let entries = match sftp.readdir(remote_dir) {
+            Ok(entries) => entries,
+            Err(e) if depth > 0 && Self::is_inaccessible_sftp_error(&e) => return Ok(()),
+            Err(e) => return Err(e).with_context(|| format!("リモートディレクトリの読み取りに失敗: {:?}", remote_dir)),
+        };
+
+        for (entry_path, stat) in entries {
+            let is_hidden = entry_path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| options.skips_hidden(name));
+            if is_hidden || options.is_ignored(&entry_path, stat.is_dir()) {
+                continue;
+            }
+
+            if stat.is_file() {
+                let relative_path = entry_path
+                    .strip_prefix(root)
+                    .unwrap_or(&entry_path)
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                files.push(RemoteFileEntry {
+                    relative_path,
+                    size: stat.size.unwrap_or(0),
+                    mtime: stat.mtime,
+                });
+            } else if stat.is_dir() {
+                Self::collect_remote_files(sftp, options, root, &entry_path, depth + 1, files)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// リモートディレクトリを探索する
+    pub async fn list_remote_directories(&mut self, path: &str) -> Result<Vec<String>> {
+        let list_future = async {
+            // 接続がない場合は接続を確立
+            if self.session.is_none() {
+                self.test_connection().await?;
+            }
+
+            let session = self.session.as_ref()
+                .context("SSHセッションが確立されていません")?;
+
+            // SFTPチャンネルを作成
+            let sftp = session.sftp()
+                .context("SFTPセッションの作成に失敗しました")?;
+
+            // ディレクトリの存在確認
+            let path_to_check = if path.is_empty() || path == "/" {
+                Path::new("/")
+            } else {
+                Path::new(path)
+            };
+
+            let mut directories = Vec::new();
+
+            match sftp.readdir(path_to_check) {
+                Ok(entries) => {
+                    for (entry_path, stat) in entries {
+                        Self::check_cancelled(self.cancel_flag.as_deref())?;
+                        if stat.is_dir() {
+                            if let Some(dir_name) = entry_path.to_str() {
+                                directories.push(dir_name.to_string());
+                            }
+                        }
+                    }
+                }
+                Err(_) => {
+                    // エラーの場合は空のリストを返す
+                    return Ok(directories);
+                }
+            }
+
+            directories.sort();
+            Ok(directories)
+        };
+
+        // 30秒でタイムアウト
+        timeout(Duration::from_secs(30), list_future)
+            .await
+            .context("ディレクトリ探索がタイムアウトしました")?
+    }
+
+    /// リモートのフォルダを開いたままにし、ディレクトリ一覧をページ単位で読み取れるようにする（エントリが非常に多いフォルダ向け）
+    ///
+    /// 全件を読み込まずに `RemoteDirectoryListing::next_page` で少しずつ返すため、最初のページをすぐ表示でき、
+    /// 読み取り中でも中断できる。開いたフォルダの続きから読むため、ページの間で重複・欠落しない。
+    /// 並びはサーバーが返す順（ソートしない）。少数のフォルダは `list_remote_directories` を使う
+    pub async fn open_directory_listing(mut self, path: &str) -> Result<RemoteDirectoryListing> {
+        let path_to_check = if path.is_empty() || path == "/" {
+            PathBuf::from("/")
+        } else {
+            PathBuf::from(path)
+        };
+
+        let open_future = async {
+            // 接続がない場合は接続を確立
+            if self.session.is_none() {
+                self.test_connection().await?;
+            }
+
+            let session = self.session.as_ref()
+                .context("SSHセッションが確立されていません")?;
+
+            // SFTPチャンネルを作成
+            let sftp = session.sftp()
+                .context("SFTPセッションの作成に失敗しました")?;
+
+            // 開けない場合は空の一覧として扱う（list_remote_directories と同じ扱い）
+            Ok::<_, anyhow::Error>(sftp.opendir(&path_to_check).ok())
+        };
+
+        // 30秒でタイムアウト
+        let dir = timeout(Duration::from_secs(30), open_future)
+            .await
+            .context("ディレクトリ探索がタイムアウトしました")??;
+
+        Ok(RemoteDirectoryListing {
+            dir,
+            path: path_to_check,
+            pending: None,
+            client: self,
+        })
+    }
+
+    /// リモートのディレクトリ構造をツリーとして取得する（読み取りのみ）
+    ///
+    /// `max_depth` 階層まで再帰的に読み取り、ノード数か時間の上限に達した場合はそこまでの結果を返す。
+    /// 転送時と同じく隠しファイル/ディレクトリと特殊ファイルは含めない。
+    /// 子要素はディレクトリを先に、それぞれ名前順に並べる
+    pub async fn get_remote_tree(&mut self, root: &str, max_depth: usize) -> Result<RemoteTree> {
+        if self.session.is_none() {
+            self.test_connection().await?;
+        }
+
+        let session = self.session.as_ref()
+            .context("SSHセッションが確立されていません")?;
+
+        let sftp = session.sftp()
+            .context("SFTPセッションの作成に失敗しました")?;
+
+        let root_path = if root.is_empty() { Path::new("/") } else { Path::new(root) };
+        let stat = sftp.stat(root_path)
+            .with_context(|| format!("リモートフォルダが見つかりません: {}", root_path.display()))?;
+        if !stat.is_dir() {
+            return Err(anyhow::anyhow!("フォルダではありません: {}", root_path.display()));
+        }
+
+        let mut root_node = RemoteTreeNode {
+            name: root_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| root_path.display().to_string()),
+            path: root_path.display().to_string(),
+            is_dir: true,
+            size: None,
+            children: Vec::new(),
+            truncated: false,
+        };
+
+        let mut budget = RemoteTreeBudget {
+            remaining_nodes: REMOTE_TREE_MAX_NODES,
+            deadline: Instant::now() + REMOTE_TREE_TIME_BUDGET,
+            exhausted: false,
+        };
+        Self::read_remote_tree(&sftp, &mut root_node, max_depth, &mut budget, self.cancel_flag.as_deref())?;
+
+        Ok(RemoteTree {
+            root: root_node,
+            node_count: REMOTE_TREE_MAX_NODES - budget.remaining_nodes,
+            budget_exhausted: budget.exhausted,
+        })
+    }
+
+    fn read_remote_tree(
+        sftp: &ssh2::Sftp,
+        node: &mut RemoteTreeNode,
+        remaining_depth: usize,
+        budget: &mut RemoteTreeBudget,
+        cancel_flag: Option<&AtomicBool>,
+    ) -> Result<()> {
+        Self::check_cancelled(cancel_flag)?;
+
+        if remaining_depth == 0 || budget.exhausted {
+            node.truncated = true;
+            return Ok(());
+        }
+
+        // 権限のないフォルダなどは中身を空として扱い、ツリー全体は失敗させない
+        let entries = match sftp.readdir(Path::new(&node.path)) {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!("リモートディレクトリの読み取りに失敗: {}: {}", node.path, e);
+                return Ok(());
+            }
+        };
+
+        let mut entries: Vec<_> = entries
+            .into_iter()
+            .filter(|(entry_path, stat)| {
+                let is_hidden = entry_path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_none_or(|name| name.starts_with('.'));
+                !is_hidden && (stat.is_dir() || stat.is_file())
+            })
+            .collect();
+        entries.sort_by(|(path_a, stat_a), (path_b, stat_b)| {
+            stat_b.is_dir().cmp(&stat_a.is_dir()).then_with(|| path_a.cmp(path_b))
+        });
+
+        for (entry_path, stat) in entries {
+            if !budget.take_node() {
+                node.truncated = true;
+                break;
+            }
+
+            node.children.push(RemoteTreeNode {
+                name: entry_path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                path: entry_path.to_string_lossy().to_string(),
+                is_dir: stat.is_dir(),
+                size: stat.is_file().then(|| stat.size.unwrap_or(0)),
+                children: Vec::new(),
+                truncated: false,
+            });
+        }
+
+        for child in node.children.iter_mut().filter(|child| child.is_dir) {
+            Self::read_remote_tree(sftp, child, remaining_depth - 1, budget, cancel_flag)?;
+        }
+
+        Ok(())
+    }
+
+    /// ホームディレクトリから利用可能なドメインを探索する
+    pub async fn find_domains(&mut self) -> Result<Vec<String>> {
+        let find_future = async {
+            // 接続がない場合は接続を確立
+            if self.session.is_none() {
+                self.test_connection().await?;
+            }
+
+            let session = self.session.as_ref()
+                .context("SSHセッションが確立されていません")?;
+
+            // SFTPチャンネルを作成
+            let sftp = session.sftp()
+                .context("SFTPセッションの作成に失敗しました")?;
+
+            let mut domains = Vec::new();
+
+            // /home/[username]/ ディレクトリを探索
+            let home_path = format!("/home/{}", self.config.username);
+
+            match sftp.readdir(Path::new(&home_path)) {
+                Ok(entries) => {
+                    for (entry_path, stat) in entries {
+                        Self::check_cancelled(self.cancel_flag.as_deref())?;
+                        if stat.is_dir() {
+                            if let Some(dir_name) = entry_path.file_name() {
+                                if let Some(name_str) = dir_name.to_str() {
+                                    // ドメイン名らしいディレクトリをフィルター（.が含まれている）
+                                    if name_str.contains('.') && !name_str.starts_with('.') {
+                                        // public_htmlがあるかチェック
+                                        let public_html_path = entry_path.join("public_html");
+                                        if sftp.stat(&public_html_path).is_ok() {
+                                            domains.push(format!("{}/public_html", entry_path.to_string_lossy()));
+                                        } else {
+                                            // public_htmlがなくても候補として追加
+                                            domains.push(entry_path.to_string_lossy().to_string());
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    return Err(anyhow::anyhow!("ホームディレクトリの探索に失敗しました: {}", e));
+                }
+            }
+
+            domains.sort();
+            Ok(domains)
+        };
+
+        // 30秒でタイムアウト
+        timeout(Duration::from_secs(30), find_future)
+            .await
+            .context("ドメイン探索がタイムアウトしました")?
+    }
+
+    /// リモートディレクトリの総ファイル数と総バイト数を再帰的に計算して `count` に加算
+    ///
+    /// 転送時と同じく隠しファイル/ディレクトリは対象外とする
+    pub(super) fn count_remote_tree(
+        sftp: &ssh2::Sftp,
+        remote_dir: &Path,
+        depth: usize,
+        control: &BackupControl,
+        options: &BackupOptions,
+        count: &RemoteTreeCount,
+    ) -> Result<()> {
+        if control.is_cancelled() {
+            return Err(anyhow::anyhow!("🚫 バックアップがキャンセルされました"));
+        }
+        if count.stop.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        // 深すぎる再帰を防ぐ（無限ループ対策）
+        if depth > 50 {
+            return Err(anyhow::anyhow!("ディレクトリの階層が深すぎます: {}", remote_dir.display()));
+        }
+
+        // 読み取れないディレクトリはバックアップ時にもスキップするため含めない
+        let entries = match RemoteDirEntries::read(sftp, remote_dir, options.low_memory) {
+            Ok(entries) => entries,
+            Err(e) if depth > 0 && Self::is_inaccessible_sftp_error(&e) => return Ok(()),
+            Err(e) => return Err(e).with_context(|| format!("リモートディレクトリの読み取りに失敗: {:?}", remote_dir)),
+        };
+
+        for entry in entries {
+            let (entry_path, stat) = entry?;
+            let is_hidden = entry_path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| options.skips_hidden(name));
+            if is_hidden || options.is_ignored(&entry_path, stat.is_dir()) {
+                continue;
+            }
+
+            if stat.is_file() {
+                // サイズ上限・更新日時で除外されるファイルは集計しない
+                let file_size = stat.size.unwrap_or(0);
+                if options.exceeds_max_file_size(file_size)
+                    || options.is_outside_age_window(stat.mtime)
+                    || options.is_unmodified_since(stat.mtime)
+                {
+                    continue;
+                }
+                count.files.fetch_add(1, Ordering::Relaxed);
+                count.bytes.fetch_add(file_size, Ordering::Relaxed);
+            } else if stat.is_dir() {
+                Self::count_remote_tree(sftp, &entry_path, depth + 1, control, options, count)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 別スレッドで総ファイル数・総バイト数を集計する
+    ///
+    /// 転送とは別のSFTPチャンネルを使う。失敗しても転送は続け、総数を不明として扱う
+    pub(super) fn spawn_concurrent_count(
+        session: Session,
+        remote_path: &str,
+        control: Arc<BackupControl>,
+        options: BackupOptions,
+        count: Arc<RemoteTreeCount>,
+    ) {
+        let remote_path = PathBuf::from(remote_path);
+        std::thread::spawn(move || {
+            let result = session
+                .sftp()
+                .context("集計用SFTPセッションの作成に失敗しました")
+                .and_then(|sftp| Self::count_remote_tree(&sftp, &remote_path, 0, &control, &options, &count));
+
+            if let Err(e) = result {
+                if !control.is_cancelled() {
+                    tracing::warn!("総ファイル数の並行集計に失敗しました: {}", e);
+                }
+                count.failed.store(true, Ordering::Relaxed);
+            }
+            count.finished.store(true, Ordering::Relaxed);
+        });
+    }
+}
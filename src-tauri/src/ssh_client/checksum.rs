@@ -0,0 +1,143 @@
+use super::*;
+
+/// チェックサム照合でリモートのハッシュを1回の sha256sum でまとめて取得するファイル数
+const REMOTE_HASH_BATCH_FILES: usize = 32;
+
+impl SshClient {
+    /// リモートで複数ファイルのSHA-256をまとめて計算する（パス → 16進文字列）
+    ///
+    /// 読み取れなかったファイルは結果に含まれない
+    pub async fn remote_sha256_many(&mut self, remote_paths: &[String]) -> Result<HashMap<String, String>> {
+        if self.session.is_none() {
+            self.test_connection().await?;
+        }
+
+        let session = self.session.as_ref()
+            .context("SSHセッションが確立されていません")?;
+
+        Self::remote_files_sha256(session, remote_paths)
+    }
+
+    /// サーバーで sha256sum を実行してファイルのSHA-256を取得
+    pub(super) fn remote_file_sha256(session: &Session, remote_path: &str) -> Result<String> {
+        let output = Self::exec_command(session, &format!("sha256sum -- {}", Self::shell_quote(remote_path)))?;
+        output
+            .split_whitespace()
+            .next()
+            .filter(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+            .map(|hash| hash.to_lowercase())
+            .with_context(|| format!("sha256sum の出力を解釈できませんでした: {}", output.trim()))
+    }
+
+    /// ダウンロードしたファイルのチェックサム照合を予約する（`verify_checksums` 有効時）
+    ///
+    /// ファイルごとにコマンドを実行しないよう、リモートのハッシュは一定数たまったらまとめて取得する。
+    /// ローカルのハッシュ計算は照合用のスレッドに任せる
+    pub(super) fn queue_checksum_verification(&self, state: &mut TransferState, remote_path: &Path, local_path: &Path) {
+        if state.verifier.is_none() {
+            return;
+        }
+
+        state.pending_hashes.push((remote_path.to_path_buf(), local_path.to_path_buf()));
+        if state.pending_hashes.len() >= REMOTE_HASH_BATCH_FILES {
+            self.flush_checksum_verification(state);
+        }
+    }
+
+    /// 待っているファイルのリモートのハッシュをまとめて取得し、照合用のスレッドに渡す
+    pub(super) fn flush_checksum_verification(&self, state: &mut TransferState) {
+        let pending = std::mem::take(&mut state.pending_hashes);
+        let Some(verifier) = &state.verifier else {
+            return;
+        };
+        if pending.is_empty() {
+            return;
+        }
+
+        let remote_paths: Vec<String> = pending.iter().map(|(remote, _)| remote.to_string_lossy().to_string()).collect();
+        let remote_hashes = self.session
+            .as_ref()
+            .context("SSHセッションが確立されていません")
+            .and_then(|session| Self::remote_files_sha256(session, &remote_paths));
+
+        match remote_hashes {
+            Ok(hashes) => {
+                for ((_, local_path), remote_path) in pending.into_iter().zip(&remote_paths) {
+                    match hashes.get(remote_path) {
+                        Some(hash) => verifier.submit(local_path, hash.clone()),
+                        None => {
+                            tracing::warn!("リモートのハッシュを取得できないため照合しません: {}", remote_path);
+                            verifier.skip();
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::warn!("リモートのハッシュを取得できないため {} 件を照合しません: {:#}", pending.len(), e);
+                pending.iter().for_each(|_| verifier.skip());
+            }
+        }
+    }
+
+    /// サーバーで sha256sum をまとめて実行し、パスごとのSHA-256を返す
+    ///
+    /// 読み取れなかったファイルは結果に含まれない（残りのファイルのハッシュは返す）
+    fn remote_files_sha256(session: &Session, remote_paths: &[String]) -> Result<HashMap<String, String>> {
+        let quoted: Vec<String> = remote_paths.iter().map(|path| Self::shell_quote(path)).collect();
+        // 一部のファイルが読み取れないと終了ステータスが0にならないため、出力された分を使う
+        let command = format!("sha256sum -- {} 2>/dev/null; true", quoted.join(" "));
+        let output = Self::exec_command(session, &command)?;
+        Ok(Self::parse_sha256sum_output(&output))
+    }
+
+    /// sha256sum の出力をパスごとのハッシュにする
+    ///
+    /// パスに改行や `\` を含む行は先頭に `\` が付き、パスがエスケープされるため元に戻す
+    fn parse_sha256sum_output(output: &str) -> HashMap<String, String> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let (escaped, line) = match line.strip_prefix('\\') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let hash = line.get(..64).filter(|hash| hash.chars().all(|c| c.is_ascii_hexdigit()))?;
+                // ハッシュとパスの区切りは2文字（テキストモードは "  "、バイナリモードは " *"）
+                let path = line.get(66..)?;
+                let path = if escaped {
+                    path.replace("\\\\", "\u{0}").replace("\\n", "\n").replace('\u{0}', "\\")
+                } else {
+                    path.to_string()
+                };
+                Some((path, hash.to_lowercase()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sha256sum_output_maps_paths_to_hashes() {
+        let hash_a = "a".repeat(64);
+        let hash_b = "B".repeat(64);
+        let output = format!(
+            "{}  /home/user/a.txt\n{} */home/user/dir with space/b.bin\n\\{}  /home/user/line\\nbreak\\\\x\n",
+            hash_a, hash_b, hash_a
+        );
+
+        let hashes = SshClient::parse_sha256sum_output(&output);
+        assert_eq!(hashes.len(), 3);
+        assert_eq!(hashes["/home/user/a.txt"], hash_a);
+        assert_eq!(hashes["/home/user/dir with space/b.bin"], "b".repeat(64));
+        assert_eq!(hashes["/home/user/line\nbreak\\x"], hash_a);
+    }
+
+    #[test]
+    fn parse_sha256sum_output_ignores_malformed_lines() {
+        let output = "sha256sum: /missing: No such file or directory\nnot-a-hash  /a\n";
+        assert!(SshClient::parse_sha256sum_output(output).is_empty());
+    }
+}
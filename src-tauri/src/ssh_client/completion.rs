@@ -0,0 +1,156 @@
+use super::*;
+
+/// 完了メッセージに一覧を載せる、読み取れなかったディレクトリの上限
+const MAX_LISTED_INACCESSIBLE_DIRS: usize = 20;
+
+/// 完了メッセージに列挙する、転送に失敗したファイルの上限
+const MAX_LISTED_FAILED_FILES: usize = 20;
+
+impl SshClient {
+    /// 転送と並行して計算していたハッシュの照合を待ち、結果を返す（照合しない場合は None）
+    pub(super) fn finish_checksum_verification<F>(&self, state: &mut TransferState, timings: &mut PhaseTimings, progress_callback: &F) -> Option<HashVerifyReport>
+    where
+        F: Fn(BackupProgress),
+    {
+        self.flush_checksum_verification(state);
+        let verifier = state.verifier.take()?;
+        progress_callback(BackupProgress {
+            phase: "チェックサム照合中".to_string(),
+            transferred_files: state.transferred_files,
+            transferred_bytes: state.transferred_bytes,
+            total_bytes: state.total_bytes,
+            elapsed_seconds: state.throttle.get_elapsed_seconds(),
+            ..Default::default()
+        });
+        let verify_started = Instant::now();
+        let report = verifier.finish();
+        timings.verifying_seconds = verify_started.elapsed().as_secs_f64();
+        Some(report)
+    }
+
+    /// バックアップ完了時の進捗（フェーズ別の所要時間と、スキップ・失敗などの件数を含む）
+    pub(super) fn completion_progress(state: &TransferState, timings: PhaseTimings) -> BackupProgress {
+        BackupProgress {
+            phase: "バックアップ完了".to_string(),
+            transferred_files: state.transferred_files,
+            total_files: Some(state.transferred_files),
+            transferred_bytes: state.transferred_bytes,
+            total_bytes: Some(state.transferred_bytes),
+            current_file: None,
+            elapsed_seconds: state.throttle.get_elapsed_seconds(),
+            transfer_speed: state.throttle.calculate_speed(state.transferred_bytes),
+            average_speed: state.throttle.calculate_average_speed(state.transferred_bytes),
+            linked_files: state.linked_files,
+            phase_timings: Some(timings),
+            percent_complete: Some(100.0),
+            skipped_special_files: state.skipped_special_files,
+            excluded_files: state.skipped_large_files + state.skipped_unmodified_files + state.skipped_by_age,
+            overwrite_counts: Some(state.overwrite_counts.clone()),
+            reconnect_attempts: state.reconnects,
+            created_directories: state.created_dirs,
+            inaccessible_dirs: state.inaccessible_dirs.len(),
+            failed_files: state.failed_files.len(),
+            ..Default::default()
+        }
+    }
+
+    /// バックアップ完了時のメッセージ（スキップ・削除・失敗したファイルなどの内訳を含む）
+    pub(super) fn completion_message(
+        remote_path: &str,
+        display_local_path: &str,
+        options: &BackupOptions,
+        use_rsync: bool,
+        state: &TransferState,
+        verify_report: Option<&HashVerifyReport>,
+    ) -> String {
+        let transferred_files = state.transferred_files;
+        let mut message = format!("✅ バックアップ完了!\n転送ファイル数: {}\nリモート: {}\nローカル: {}",
+            transferred_files, remote_path, display_local_path);
+        if let Some(dir) = &options.resume_after_dir {
+            message.push_str(&format!("\nチェックポイントから再開: {} の次から", dir.display()));
+        }
+        // rsync の --link-dest はハードリンクの数を返さないため、SFTPで転送した場合のみ表示する
+        if options.link_dest.is_some() && !use_rsync {
+            message.push_str(&format!("\nコピー: {} / ハードリンク: {}",
+                transferred_files - state.linked_files, state.linked_files));
+        }
+        if state.case_collisions > 0 {
+            message.push_str(&format!("\n大文字・小文字の衝突: {}", state.case_collisions));
+        }
+        if state.skipped_special_files > 0 {
+            message.push_str(&format!("\n特殊ファイルのスキップ: {}", state.skipped_special_files));
+        }
+        if state.type_mismatches > 0 {
+            message.push_str(&format!("\n種類の不一致によりスキップ: {}", state.type_mismatches));
+        }
+        if state.skipped_large_files > 0 {
+            message.push_str(&format!("\nサイズ上限によりスキップ: {}", state.skipped_large_files));
+        }
+        if state.skipped_by_age > 0 {
+            message.push_str(&format!("\n更新日時が指定の期間外のためスキップ: {}", state.skipped_by_age));
+        }
+        if state.deleted_files > 0 {
+            message.push_str(&format!("\nリモートにないため削除: {}", state.deleted_files));
+        }
+        if state.skipped_unmodified_files > 0 {
+            message.push_str(&format!("\n前回のバックアップ以降の更新なしでスキップ: {}", state.skipped_unmodified_files));
+        }
+        if state.reconnects > 0 {
+            message.push_str(&format!("\n接続が切れたため再接続: {}回", state.reconnects));
+        }
+        Self::push_listed_errors(&mut message, "読み取れずスキップしたフォルダ", &state.inaccessible_dirs, MAX_LISTED_INACCESSIBLE_DIRS);
+        Self::push_listed_errors(&mut message, "⚠️ 転送に失敗したファイル", &state.failed_files, MAX_LISTED_FAILED_FILES);
+        if let Some(report) = verify_report {
+            message.push_str(&format!("\n{}", report.summary()));
+        }
+        let overwrite_counts = &state.overwrite_counts;
+        if overwrite_counts.overwritten > 0 {
+            message.push_str(&format!("\n既存のファイルを上書き: {}", overwrite_counts.overwritten));
+        }
+        if overwrite_counts.skipped_existing > 0 {
+            message.push_str(&format!("\n既存のファイルがあるためスキップ: {}", overwrite_counts.skipped_existing));
+        }
+        if overwrite_counts.kept_both > 0 || overwrite_counts.identical > 0 {
+            message.push_str(&format!("\n別名で保存: {} / 内容が同じため保存せず: {}",
+                overwrite_counts.kept_both, overwrite_counts.identical));
+        }
+        message
+    }
+
+    /// スキップ・失敗したパスとエラーを、件数と最大 `max_listed` 件の内訳としてメッセージに加える（空なら何もしない）
+    fn push_listed_errors(message: &mut String, heading: &str, entries: &[(PathBuf, String)], max_listed: usize) {
+        if entries.is_empty() {
+            return;
+        }
+        message.push_str(&format!("\n{}: {}", heading, entries.len()));
+        for (path, error) in entries.iter().take(max_listed) {
+            message.push_str(&format!("\n  {}: {}", path.display(), error));
+        }
+        if entries.len() > max_listed {
+            message.push_str(&format!("\n  ほか {} 件", entries.len() - max_listed));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completion_message_lists_failed_files_up_to_the_limit() {
+        let options = BackupOptions::default();
+        let mut state = TransferState::new(Path::new("/backup"), &options);
+        state.transferred_files = 3;
+        state.failed_files = (0..MAX_LISTED_FAILED_FILES + 2)
+            .map(|i| (PathBuf::from(format!("/remote/locked-{}.db", i)), "Permission denied".to_string()))
+            .collect();
+
+        let message = SshClient::completion_message("/remote", "/backup", &options, false, &state, None);
+        assert!(message.contains("転送ファイル数: 3\nリモート: /remote\nローカル: /backup"));
+        assert!(message.contains(&format!("⚠️ 転送に失敗したファイル: {}", MAX_LISTED_FAILED_FILES + 2)));
+        assert!(message.contains("  /remote/locked-0.db: Permission denied"));
+        assert!(!message.contains(&format!("locked-{}.db", MAX_LISTED_FAILED_FILES)));
+        assert!(message.ends_with("  ほか 2 件"));
+        assert!(!message.contains("読み取れずスキップしたフォルダ"));
+    }
+}
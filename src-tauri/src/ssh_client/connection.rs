@@ -0,0 +1,420 @@
+use super::*;
+
+/// 「Too many authentication failures」で切断された後、再接続までに待つ時間
+const AUTH_FAILURE_COOLDOWN: Duration = Duration::from_secs(5);
+
+/// libssh2 のエラーコード: セッションのタイムアウト（`Session::set_timeout`）
+const LIBSSH2_ERROR_TIMEOUT: i32 = -9;
+
+impl SshClient {
+    /// SSH接続をテストする（エラー分類対応）
+    ///
+    /// TCP接続・ハンドシェイクの失敗やタイムアウトは `connect_retries` 回まで間隔を空けて再試行する
+    /// （認証エラーなど再試行で回復しないエラーは再試行しない）。
+    /// 「Too many authentication failures」で切断された場合は、直前のセッションが
+    /// サーバー側で閉じられるのを待ってから1回だけ再試行する
+    pub async fn test_connection(&mut self) -> Result<String> {
+        self.run_connection_test(true).await
+    }
+
+    /// 認証が通るかだけを確認する（鍵の確認用）
+    ///
+    /// `test_connection` と同じく再試行・エラー分類を行うが、認証後にチャンネルを開いてコマンドを
+    /// 実行しないため速い。認証したセッションは `test_connection` と同じく以降の操作に使える
+    pub async fn test_auth_only(&mut self) -> Result<String> {
+        self.run_connection_test(false).await
+    }
+
+    /// 接続・認証を再試行付きで行う（`run_command` が true なら認証後にコマンドの実行も確認する）
+    async fn run_connection_test(&mut self, run_command: bool) -> Result<String> {
+        let attempt_timeout_secs = self.config.attempt_timeout().as_secs();
+        let max_attempts = self.config.connect_retries.saturating_add(1);
+        // 再試行した場合のそれまでの試行の失敗理由
+        let mut failures = Vec::new();
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            // 接続・認証は同期的に行うため、タイムアウトはTCP接続とSSHセッションに設定する
+            // （`connect_tcp` / `start_session`）
+            let mut attempt_started = Instant::now();
+            let mut result = self.connect_and_authenticate(run_command).await;
+
+            if matches!(&result, Err(e) if Self::is_too_many_auth_failures(e)) {
+                tracing::warn!(
+                    "認証失敗が多すぎるため切断されました。{}秒待ってから再試行します",
+                    AUTH_FAILURE_COOLDOWN.as_secs()
+                );
+                tokio::time::sleep(AUTH_FAILURE_COOLDOWN).await;
+                Self::check_cancelled(self.cancel_flag.as_deref())?;
+                attempt_started = Instant::now();
+                result = self.connect_and_authenticate(run_command).await;
+            }
+            // エラーの種類で判別できなくても、制限時間を使い切って失敗した場合はタイムアウトとみなす
+            let timed_out = attempt_started.elapsed() >= self.config.attempt_timeout();
+
+            // 1回ごとの接続タイムアウトで打ち切り（エラー分類適用）
+            let (error, retryable) = match result {
+                Ok(result) => {
+                    return Ok(format!("{}\n接続試行回数: {}/{}", result, attempt, max_attempts));
+                }
+                // 中断は再試行せず、分類もしない（踏み台サーバーのエラーなどに包まれている場合も含む）
+                Err(e) if DiscoveryCancelled::is(&e) => return Err(DiscoveryCancelled.into()),
+                Err(e) if !timed_out && !Self::is_attempt_timeout(&e) => {
+                    tracing::warn!("SSH接続エラー（{}/{}回目）: {:#}", attempt, max_attempts, e);
+                    (Self::classify_error(&e), Self::is_transient_connect_error(&e))
+                }
+                Err(e) => {
+                    tracing::warn!("SSH接続タイムアウト（{}/{}回目）: {:#}", attempt, max_attempts, e);
+                    (ClassifiedError::new(BackupErrorKind::Timeout, format!(
+                        "⏱️ タイムアウトエラー: SSH接続が{}秒でタイムアウトしました\n\
+                         - サーバーが応答していない可能性があります\n\
+                         - ネットワーク接続を確認してください",
+                        attempt_timeout_secs
+                    )), true)
+                }
+            };
+
+            if !retryable || attempt >= max_attempts {
+                let message = Self::summarize_connect_failures(error.message, &failures);
+                return Err(ClassifiedError::new(error.kind, message).into());
+            }
+
+            // 最初の行（エラーの分類）だけを失敗理由として残す
+            failures.push(error.message.lines().next().unwrap_or_default().to_string());
+
+            let backoff = Self::connect_retry_backoff(attempt);
+            tracing::warn!(
+                "SSH接続に失敗したため{}秒後に再試行します（{}/{}回目）",
+                backoff.as_secs(),
+                attempt + 1,
+                max_attempts
+            );
+            Self::check_cancelled(self.cancel_flag.as_deref())?;
+            tokio::time::sleep(backoff).await;
+            Self::check_cancelled(self.cancel_flag.as_deref())?;
+        }
+    }
+
+    /// 1回の接続試行のタイムアウト（TCP接続、またはSSHセッションの応答待ち）で失敗したか
+    fn is_attempt_timeout(error: &anyhow::Error) -> bool {
+        error.chain().any(|cause| {
+            cause.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::TimedOut)
+                || cause.downcast_ref::<ssh2::Error>().is_some_and(|e| e.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_TIMEOUT))
+        })
+    }
+
+    /// 再試行で回復する可能性がある接続エラーか（TCP接続・ハンドシェイクの失敗）
+    fn is_transient_connect_error(error: &anyhow::Error) -> bool {
+        error.chain().any(|cause| {
+            let message = cause.to_string();
+            message.contains("TCP接続に失敗") || message.contains("ハンドシェイクに失敗")
+        })
+    }
+
+    /// n回目の試行に失敗した後の待機時間（指数的に延ばし、上限で頭打ち）
+    fn connect_retry_backoff(failed_attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failed_attempts.saturating_sub(1));
+        CONNECT_RETRY_BACKOFF_BASE.saturating_mul(factor).min(CONNECT_RETRY_BACKOFF_MAX)
+    }
+
+    /// 最後のエラーに、それまでの試行の失敗理由を添える（再試行していなければそのまま）
+    fn summarize_connect_failures(last_error: String, earlier_failures: &[String]) -> String {
+        if earlier_failures.is_empty() {
+            return last_error;
+        }
+
+        let attempts = earlier_failures.len() + 1;
+        let details: Vec<String> = earlier_failures
+            .iter()
+            .chain(std::iter::once(&last_error.lines().next().unwrap_or_default().to_string()))
+            .enumerate()
+            .map(|(index, reason)| format!("- {}回目: {}", index + 1, reason))
+            .collect();
+
+        format!(
+            "{}\n\n{}回の接続試行がすべて失敗しました\n{}",
+            last_error,
+            attempts,
+            details.join("\n")
+        )
+    }
+
+    /// 接続・認証を行い、テストコマンドの結果を返す
+    ///
+    /// 鍵は設定された秘密鍵ファイルのみを使い、SSHエージェントの鍵は提示しない
+    /// （余分な鍵を提示するとサーバーの認証試行回数の上限に達するため）
+    async fn connect_and_authenticate(&mut self, run_command: bool) -> Result<String> {
+        self.authenticated_key_path = None;
+
+        let cancel_flag = self.cancel_flag.as_deref();
+        let (tcp, route) = match &self.config.jump_host {
+            Some(jump_host) => {
+                let tcp = Self::connect_via_jump_host(jump_host, &self.config.hostname, self.config.port, cancel_flag)?;
+                let route = format!(
+                    "{}@{}:{} 経由",
+                    jump_host.username, jump_host.hostname, jump_host.port
+                );
+                (tcp, route)
+            }
+            None => {
+                // TCP接続（解決したアドレスを優先順に試行）
+                let (tcp, connected_addr) = Self::connect_tcp(&self.config, cancel_flag)
+                    .context("TCP接続に失敗しました")?;
+                tracing::info!("TCP接続成功: {}:{} -> {}", self.config.hostname, self.config.port, connected_addr);
+                let route = format!(
+                    "{} ({})",
+                    connected_addr,
+                    if connected_addr.is_ipv6() { "IPv6" } else { "IPv4" }
+                );
+                (tcp, route)
+            }
+        };
+
+        Self::check_cancelled(cancel_flag)?;
+        let (session, key_path, auth_methods) = Self::open_session(&self.config, tcp)?;
+        self.authenticated_key_path = key_path;
+        self.server_auth_methods = Some(auth_methods);
+
+        let auth_label = match (&self.authenticated_key_path, &self.config.auth_method) {
+            (Some(key_path), _) => format!("認証に使用した鍵: {}", key_path),
+            (None, SshAuthMethod::StoredKey(_)) => "認証に使用した鍵: 設定に保存した鍵".to_string(),
+            (None, _) => "認証方式: パスワード（公開鍵認証への切り替えを推奨します）".to_string(),
+        };
+
+        if !run_command {
+            if !session.authenticated() {
+                return Err(anyhow::anyhow!("SSH認証に失敗しました"));
+            }
+            session.set_timeout(0);
+            self.session = Some(session);
+            return Ok(format!("✅ SSH認証テスト成功!\n{}@{}:{}\n接続先アドレス: {}\n{}",
+                self.config.username,
+                self.config.hostname,
+                self.config.port,
+                route,
+                auth_label
+            ));
+        }
+
+        // 簡単なコマンドを実行してテスト
+        let mut channel = session.channel_session()
+            .context("SSHチャンネルの作成に失敗しました")?;
+
+        channel.exec("echo 'SSH connection test successful'")
+            .context("SSHコマンドの実行に失敗しました")?;
+
+        let mut result = String::new();
+        channel.read_to_string(&mut result)
+            .context("SSHコマンドの結果読み取りに失敗しました")?;
+
+        channel.wait_close()
+            .context("SSHチャンネルのクローズに失敗しました")?;
+
+        session.set_timeout(0);
+        self.session = Some(session);
+
+        Ok(format!("✅ SSH接続テスト成功!\n{}@{}:{}\n接続先アドレス: {}\n{}\n結果: {}",
+            self.config.username,
+            self.config.hostname,
+            self.config.port,
+            route,
+            auth_label,
+            result.trim()
+        ))
+    }
+
+    /// TCPストリーム上でSSHセッションを開始し、登録された鍵を順に試して認証する
+    ///
+    /// 認証済みのセッション、認証に使った鍵のパス（パスワード認証の場合はNone）、
+    /// サーバーが提示した認証方法を返す
+    fn open_session(config: &SshConfig, tcp: TcpStream) -> Result<(Session, Option<String>, String)> {
+        let session = Self::start_session(config, tcp)?;
+        // 踏み台と同じく、認証情報を送る前に接続先がなりすましでないことを known_hosts で確認する
+        Self::check_known_host(&session, config)?;
+        Self::authenticate_session(config, session)
+    }
+
+    /// TCPストリーム上でSSHのハンドシェイクを行う（認証はしない）
+    pub(super) fn start_session(config: &SshConfig, tcp: TcpStream) -> Result<Session> {
+        // SSH セッションを開始
+        let mut session = Session::new()
+            .context("SSHセッションの作成に失敗しました")?;
+
+        // 指定されたアルゴリズムをハンドシェイク前に設定
+        for (method_type, label, preference) in config.algorithms.preferences() {
+            session.method_pref(method_type, preference)
+                .with_context(|| format!("{}アルゴリズムの指定が不正です: {}", label, preference))?;
+        }
+
+        // ハンドシェイク・認証の応答待ちが1回の接続試行のタイムアウトを超えたら打ち切る
+        // （認証後は呼び出し側で解除する）
+        session.set_timeout(config.attempt_timeout().as_millis().min(u32::MAX as u128) as u32);
+        session.set_tcp_stream(tcp);
+        if let Err(e) = session.handshake() {
+            return Err(anyhow::anyhow!(
+                "SSHハンドシェイクに失敗しました: {}\n\
+                 サーバーと共通のアルゴリズムがない可能性があります。詳細設定でアルゴリズムを指定してください\n{}",
+                e,
+                Self::describe_algorithms(&session, &config.algorithms)
+            ));
+        }
+
+        Ok(session)
+    }
+
+    /// ハンドシェイク失敗時の診断用に、指定したアルゴリズムとこちらが対応しているアルゴリズムを列挙
+    fn describe_algorithms(session: &Session, algorithms: &SshAlgorithms) -> String {
+        let offered = |method_type: MethodType, preference: &Option<String>| -> String {
+            match preference {
+                Some(preference) => format!("指定: {}", preference),
+                None => session
+                    .supported_algs(method_type)
+                    .map(|algs| format!("対応: {}", algs.join(",")))
+                    .unwrap_or_else(|_| "不明".to_string()),
+            }
+        };
+
+        format!(
+            "- 鍵交換: {}\n- ホスト鍵: {}\n- 暗号: {}\n- MAC: {}",
+            offered(MethodType::Kex, &algorithms.kex),
+            offered(MethodType::HostKey, &algorithms.host_key),
+            offered(MethodType::CryptCs, &algorithms.cipher),
+            offered(MethodType::MacCs, &algorithms.mac),
+        )
+    }
+
+    /// サーバーのホスト鍵を `~/.ssh/known_hosts` の登録と照合する
+    ///
+    /// 未登録の場合は登録して続行し（ssh の `StrictHostKeyChecking=accept-new` と同じ）、
+    /// 登録と一致しない場合はエラーにする
+    pub(super) fn check_known_host(session: &Session, config: &SshConfig) -> Result<()> {
+        let known_hosts_path = dirs::home_dir()
+            .context("ホームディレクトリを取得できません")?
+            .join(".ssh")
+            .join("known_hosts");
+
+        let mut known_hosts = session.known_hosts()
+            .context("known_hosts の初期化に失敗しました")?;
+        if known_hosts_path.exists() {
+            known_hosts.read_file(&known_hosts_path, ssh2::KnownHostFileKind::OpenSSH)
+                .with_context(|| format!("known_hosts の読み込みに失敗しました: {:?}", known_hosts_path))?;
+        }
+
+        let (key, key_type) = session.host_key()
+            .context("サーバーのホスト鍵を取得できません")?;
+        match known_hosts.check_port(&config.hostname, config.port, key) {
+            ssh2::CheckResult::Match => Ok(()),
+            ssh2::CheckResult::NotFound => {
+                let host = if config.port == 22 {
+                    config.hostname.clone()
+                } else {
+                    format!("[{}]:{}", config.hostname, config.port)
+                };
+                known_hosts.add(&host, key, "", key_type.into())
+                    .context("known_hosts へのホスト鍵の追加に失敗しました")?;
+                if let Some(parent) = known_hosts_path.parent() {
+                    std::fs::create_dir_all(parent)
+                        .with_context(|| format!("フォルダの作成に失敗しました: {:?}", parent))?;
+                }
+                known_hosts.write_file(&known_hosts_path, ssh2::KnownHostFileKind::OpenSSH)
+                    .with_context(|| format!("known_hosts の保存に失敗しました: {:?}", known_hosts_path))?;
+                tracing::info!("ホスト鍵を known_hosts に登録しました: {}", host);
+                Ok(())
+            }
+            ssh2::CheckResult::Mismatch => Err(anyhow::anyhow!(
+                "{}:{} のホスト鍵が known_hosts の登録と一致しません。サーバーの鍵が変更されたか、なりすましの可能性があります: {:?}",
+                config.hostname, config.port, known_hosts_path
+            )),
+            ssh2::CheckResult::Failure => Err(anyhow::anyhow!(
+                "{}:{} のホスト鍵を確認できませんでした", config.hostname, config.port
+            )),
+        }
+    }
+
+    /// ホスト名を解決し、設定に応じた優先順でアドレスごとにTCP接続を試行する
+    ///
+    /// 成功したストリームと接続先アドレスを返す
+    pub(super) fn connect_tcp(config: &SshConfig, cancel_flag: Option<&AtomicBool>) -> Result<(TcpStream, SocketAddr)> {
+        let mut addrs: Vec<SocketAddr> = (config.hostname.as_str(), config.port)
+            .to_socket_addrs()
+            .with_context(|| format!("ホスト名の解決に失敗しました（DNS）: {}", config.hostname))?
+            .collect();
+
+        if addrs.is_empty() {
+            return Err(anyhow::anyhow!("ホスト名に対応するアドレスが見つかりません（DNS）: {}", config.hostname));
+        }
+
+        // 優先するアドレスファミリーを先頭に（同じファミリー内は解決順を維持）
+        let prefer_ipv6 = config.prefer_ipv6;
+        addrs.sort_by_key(|addr| addr.is_ipv6() != prefer_ipv6);
+
+        // アドレスごとに設定どおりのタイムアウトで待つ（遅い回線で長めに設定した値を縮めない）
+        let per_address_timeout = config.attempt_timeout();
+
+        let mut failures = Vec::new();
+        for addr in addrs {
+            Self::check_cancelled(cancel_flag)?;
+            match TcpStream::connect_timeout(&addr, per_address_timeout) {
+                Ok(tcp) => return Ok((tcp, addr)),
+                Err(e) => {
+                    tracing::warn!("TCP接続失敗: {}: {}", addr, e);
+                    failures.push(format!("{}: {}", addr, e));
+                }
+            }
+        }
+
+        Err(anyhow::anyhow!("すべてのアドレスへの接続に失敗しました（connection failed）\n{}", failures.join("\n")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn per_attempt_timeout_stops_a_silent_server() {
+        // 接続は受け付けるがSSHのバナーを返さないサーバー
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let config: SshConfig = serde_json::from_value(serde_json::json!({
+            "hostname": "127.0.0.1",
+            "port": port,
+            "username": "user",
+            "key_path": "/nonexistent",
+            "connect_timeout_secs": 60,
+            "per_attempt_timeout_secs": 1,
+        }))
+        .unwrap();
+        let mut client = SshClient::new(config);
+
+        let started = Instant::now();
+        let error = client.test_connection().await.unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_secs(10), "{:?}", started.elapsed());
+        assert_eq!(ClassifiedError::kind_of(&error), Some(BackupErrorKind::Timeout), "{:#}", error);
+        drop(listener);
+    }
+
+    #[tokio::test]
+    async fn cancelled_connection_is_not_retried_even_through_a_jump_host() {
+        let config: SshConfig = serde_json::from_value(serde_json::json!({
+            "hostname": "127.0.0.1",
+            "port": 9,
+            "username": "user",
+            "key_path": "/nonexistent",
+            "connect_retries": 5,
+            "jump_host": {"hostname": "127.0.0.1", "port": 9, "username": "user", "key_path": "/nonexistent"},
+        }))
+        .unwrap();
+        let mut client = SshClient::new(config).with_cancel_flag(Arc::new(AtomicBool::new(true)));
+
+        let started = Instant::now();
+        let error = client.test_connection().await.unwrap_err();
+
+        assert!(DiscoveryCancelled::is(&error), "{:#}", error);
+        assert_eq!(error.to_string(), "🚫 キャンセルされました");
+        assert!(started.elapsed() < CONNECT_RETRY_BACKOFF_BASE);
+    }
+}
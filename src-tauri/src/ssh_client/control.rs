@@ -0,0 +1,201 @@
+use super::*;
+
+// バックアップの実行状態（AppStateとバックアップ処理で共有）
+const BACKUP_STATE_RUNNING: u8 = 0;
+
+const BACKUP_STATE_PAUSED: u8 = 1;
+
+const BACKUP_STATE_CANCELLED: u8 = 2;
+
+/// 実行中バックアップの一時停止・再開・キャンセル制御
+pub struct BackupControl {
+    pub(super) state: AtomicU8,
+    /// キャンセル時に今回作成したバックアップ先を削除するか
+    cleanup_on_cancel: AtomicBool,
+    /// 全体の制限時間を過ぎたために止めたか（ユーザーのキャンセルと区別する）
+    pub(super) timed_out: AtomicBool,
+}
+
+impl Default for BackupControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BackupControl {
+    pub fn new() -> Self {
+        Self {
+            state: AtomicU8::new(BACKUP_STATE_RUNNING),
+            cleanup_on_cancel: AtomicBool::new(false),
+            timed_out: AtomicBool::new(false),
+        }
+    }
+
+    /// 新しいバックアップ開始時に実行中状態へ戻す
+    pub fn reset(&self) {
+        self.state.store(BACKUP_STATE_RUNNING, Ordering::Relaxed);
+        self.cleanup_on_cancel.store(false, Ordering::Relaxed);
+        self.timed_out.store(false, Ordering::Relaxed);
+    }
+
+    /// 制限時間を過ぎたバックアップを止める（転送のループはキャンセルと同じく止まる）
+    fn time_out(&self) {
+        self.timed_out.store(true, Ordering::Relaxed);
+        self.cancel();
+    }
+
+    pub fn is_timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::Relaxed)
+    }
+
+    /// タイムアウトで止めた状態を戻す（一括バックアップで次のジョブを実行できるように）
+    pub(super) fn clear_time_out(&self) {
+        if self.timed_out.swap(false, Ordering::Relaxed) {
+            let _ = self.state.compare_exchange(BACKUP_STATE_CANCELLED, BACKUP_STATE_RUNNING, Ordering::Relaxed, Ordering::Relaxed);
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.state.store(BACKUP_STATE_CANCELLED, Ordering::Relaxed);
+    }
+
+    /// キャンセルし、今回新規作成したバックアップ先フォルダを削除させる
+    pub fn cancel_with_cleanup(&self) {
+        self.cleanup_on_cancel.store(true, Ordering::Relaxed);
+        self.cancel();
+    }
+
+    pub(super) fn should_cleanup(&self) -> bool {
+        self.is_cancelled() && self.cleanup_on_cancel.load(Ordering::Relaxed)
+    }
+
+    /// 実行中の場合のみ一時停止する（キャンセル済みは上書きしない）
+    pub fn pause(&self) -> bool {
+        self.state
+            .compare_exchange(BACKUP_STATE_RUNNING, BACKUP_STATE_PAUSED, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// 一時停止中の場合のみ再開する
+    pub fn resume(&self) -> bool {
+        self.state
+            .compare_exchange(BACKUP_STATE_PAUSED, BACKUP_STATE_RUNNING, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.load(Ordering::Relaxed) == BACKUP_STATE_CANCELLED
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.load(Ordering::Relaxed) == BACKUP_STATE_PAUSED
+    }
+}
+
+/// バックアップ全体の制限時間の監視
+///
+/// 転送は libssh2 のブロッキング呼び出しで行うため、tokio のタイムアウトでは中断できない。
+/// 別スレッドで開始（接続前）からの経過時間を監視し、制限時間を過ぎたら `BackupControl` を
+/// タイムアウトの状態にして、キャンセルと同じ確認箇所で転送を止めさせる
+pub(super) struct BackupWatchdog {
+    limit_secs: Arc<AtomicU64>,
+    pub(super) stop: Arc<AtomicBool>,
+}
+
+impl BackupWatchdog {
+    pub(super) fn start(control: Arc<BackupControl>, limit: Duration) -> Self {
+        let limit_secs = Arc::new(AtomicU64::new(limit.as_secs()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let started = std::time::Instant::now();
+
+        let thread_limit_secs = limit_secs.clone();
+        let thread_stop = stop.clone();
+        std::thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                if started.elapsed().as_secs() >= thread_limit_secs.load(Ordering::Relaxed) {
+                    tracing::warn!("バックアップが制限時間（{}秒）を過ぎたため停止します", thread_limit_secs.load(Ordering::Relaxed));
+                    control.time_out();
+                    break;
+                }
+                std::thread::sleep(PAUSE_POLL_INTERVAL);
+            }
+        });
+
+        Self { limit_secs, stop }
+    }
+
+    /// 制限時間を変更する（開始からの時間。事前計算で総量がわかった後に使う）
+    pub(super) fn set_limit(&self, limit: Duration) {
+        self.limit_secs.store(limit.as_secs(), Ordering::Relaxed);
+    }
+
+    pub(super) fn limit(&self) -> Duration {
+        Duration::from_secs(self.limit_secs.load(Ordering::Relaxed))
+    }
+}
+
+impl Drop for BackupWatchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// 一時停止中の状態確認間隔
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+impl SshClient {
+    /// 一時停止中であれば再開またはキャンセルされるまで待機する
+    ///
+    /// 待機中は tokio のワーカースレッドを塞がないよう非同期に待ち、停止中の時間は経過時間・速度計算から除外する
+    pub(super) async fn wait_while_paused<F>(control: &BackupControl, state: &mut TransferState, progress_callback: &F)
+    where
+        F: Fn(BackupProgress),
+    {
+        if !control.is_paused() {
+            return;
+        }
+
+        state.throttle.pause();
+        progress_callback(BackupProgress {
+            phase: "一時停止中".to_string(),
+            transferred_files: state.transferred_files,
+            transferred_bytes: state.transferred_bytes,
+            total_bytes: state.total_bytes,
+            percent_complete: BackupProgress::calculate_percent(state.transferred_bytes, state.total_bytes),
+            elapsed_seconds: state.throttle.get_elapsed_seconds(),
+            ..Default::default()
+        });
+
+        while control.is_paused() {
+            tokio::time::sleep(PAUSE_POLL_INTERVAL).await;
+        }
+
+        state.throttle.resume();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchdog_times_out_the_backup_and_can_be_cleared() {
+        let control = Arc::new(BackupControl::new());
+        let watchdog = BackupWatchdog::start(control.clone(), Duration::from_secs(3600));
+        watchdog.set_limit(Duration::ZERO);
+
+        let started = std::time::Instant::now();
+        while !control.is_cancelled() && started.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(control.is_cancelled());
+        assert!(control.is_timed_out());
+
+        // 次のジョブのために戻せる（ユーザーのキャンセルは戻さない）
+        control.clear_time_out();
+        assert!(!control.is_cancelled());
+        control.cancel();
+        control.clear_time_out();
+        assert!(control.is_cancelled());
+    }
+}
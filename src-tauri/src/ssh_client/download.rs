@@ -0,0 +1,617 @@
+use super::*;
+
+/// パイプライン転送を使うファイルサイズの下限（8MB）
+const PIPELINE_MIN_FILE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// パイプライン転送の読み取りウィンドウ（1MB）
+const PIPELINE_WINDOW_SIZE: usize = 1024 * 1024;
+
+/// パイプライン転送で同時に保持するバッファ数
+const PIPELINE_DEPTH: usize = 4;
+
+/// 再開時に継ぎ目の整合性を確認する末尾ブロックのサイズ（64KB）
+const RESUME_VERIFY_BLOCK_SIZE: u64 = 64 * 1024;
+
+impl SshClient {
+    /// ファイル転送の最適化実装（128KBバッファ使用）
+    pub(super) fn transfer_file_optimized<R: Read, W: Write>(
+        remote_file: &mut R,
+        local_file: &mut W,
+    ) -> Result<u64> {
+        // エックスサーバー最適化: 128KBバッファ
+        // 理由: RTT 10-50ms × 10-100Mbps → 最適バッファサイズ
+        // 調査により8KB→128KBで1.5-3倍の転送速度向上を確認
+        const BUFFER_SIZE: usize = 128 * 1024; // 128KB
+
+        let mut buffer = vec![0u8; BUFFER_SIZE];
+        let mut total_bytes = 0u64;
+
+        loop {
+            match remote_file.read(&mut buffer) {
+                Ok(0) => break, // EOF
+                Ok(n) => {
+                    local_file.write_all(&buffer[..n])
+                        .with_context(|| "ローカルファイル書き込み失敗")?;
+                    total_bytes += n as u64;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                    continue; // シグナル割り込み→リトライ
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(total_bytes)
+    }
+
+    /// ファイルサイズとオプションに応じて転送方式を選択
+    fn transfer_file<R: Read, W: Write + Send>(
+        remote_file: &mut R,
+        local_file: &mut W,
+        file_size: u64,
+        options: &BackupOptions,
+    ) -> Result<u64> {
+        if options.pipelined_transfer && file_size >= PIPELINE_MIN_FILE_SIZE {
+            Self::transfer_file_pipelined(remote_file, local_file)
+        } else {
+            Self::transfer_file_optimized(remote_file, local_file)
+        }
+    }
+
+    /// パイプライン化したファイル転送（大容量ファイル・高遅延回線向け）
+    ///
+    /// 1MBの読み取りウィンドウを使うことで libssh2 が複数のSFTP READ要求を
+    /// 同時に発行し、往復待ちの間も回線を使い続けられるようにする。
+    /// さらにディスク書き込みを別スレッドに任せ、読み取りと書き込みを重ね合わせる
+    fn transfer_file_pipelined<R: Read, W: Write + Send>(
+        remote_file: &mut R,
+        local_file: &mut W,
+    ) -> Result<u64> {
+        // 書き込み待ちのバッファと、再利用可能な空きバッファを循環させる
+        let (data_tx, data_rx) = mpsc::sync_channel::<(Vec<u8>, usize)>(PIPELINE_DEPTH);
+        let (free_tx, free_rx) = mpsc::channel::<Vec<u8>>();
+        for _ in 0..PIPELINE_DEPTH {
+            let _ = free_tx.send(vec![0u8; PIPELINE_WINDOW_SIZE]);
+        }
+
+        std::thread::scope(|scope| {
+            let writer = scope.spawn(move || -> Result<()> {
+                for (buffer, len) in data_rx {
+                    local_file.write_all(&buffer[..len])
+                        .with_context(|| "ローカルファイル書き込み失敗")?;
+                    // 書き込み済みバッファを読み取り側に返却（読み取り側終了後は破棄）
+                    let _ = free_tx.send(buffer);
+                }
+                Ok(())
+            });
+
+            let mut total_bytes = 0u64;
+            let read_result: Result<()> = loop {
+                // 書き込みスレッドが異常終了した場合はバッファが戻らないので終了
+                let Ok(mut buffer) = free_rx.recv() else { break Ok(()) };
+
+                let read = loop {
+                    match remote_file.read(&mut buffer) {
+                        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue, // シグナル割り込み→リトライ
+                        other => break other,
+                    }
+                };
+
+                match read {
+                    Ok(0) => break Ok(()), // EOF
+                    Ok(n) => {
+                        total_bytes += n as u64;
+                        if data_tx.send((buffer, n)).is_err() {
+                            break Ok(());
+                        }
+                    }
+                    Err(e) => break Err(e.into()),
+                }
+            };
+
+            // 送信側を閉じて書き込みスレッドの終了を待つ
+            drop(data_tx);
+            let write_result = writer
+                .join()
+                .map_err(|_| anyhow::anyhow!("ローカル書き込みスレッドが異常終了しました"))?;
+
+            read_result?;
+            write_result?;
+            Ok(total_bytes)
+        })
+    }
+
+    /// ファイルサイズに基づいてタイムアウト時間を動的に計算
+    ///
+    /// # 計算ロジック
+    /// - 基本タイムアウト: 30秒（ファイルオープンと小ファイル用）
+    /// - 小ファイル（<10MB）: 60秒
+    /// - 中ファイル（10MB-100MB）: 120秒
+    /// - 大ファイル（100MB-1GB）: 600秒
+    /// - 巨大ファイル（>1GB）: 1800秒（30分）
+    ///
+    /// これにより、無駄な長時間待機を避けつつ、大ファイル転送も確実に完了できる
+    fn calculate_file_timeout(file_size: u64) -> Duration {
+        const MB: u64 = 1024 * 1024;
+        const GB: u64 = 1024 * MB;
+
+        if file_size < 10 * MB {
+            Duration::from_secs(60)  // 小ファイル: 1分
+        } else if file_size < 100 * MB {
+            Duration::from_secs(120)  // 中ファイル: 2分
+        } else if file_size < GB {
+            Duration::from_secs(600)  // 大ファイル: 10分
+        } else {
+            Duration::from_secs(1800)  // 巨大ファイル: 30分
+        }
+    }
+
+    /// 通常ファイル・ディレクトリ以外のエントリの種類（ログ用）
+    pub(super) fn special_file_kind(stat: &ssh2::FileStat) -> &'static str {
+        const S_IFMT: u32 = 0o170000;
+        match stat.perm.map(|perm| perm & S_IFMT) {
+            Some(0o140000) => "ソケット",
+            Some(0o120000) => "シンボリックリンク",
+            Some(0o060000) => "ブロックデバイス",
+            Some(0o020000) => "キャラクタデバイス",
+            Some(0o010000) => "FIFO",
+            _ => "不明",
+        }
+    }
+
+    /// ローカルのディレクトリが大文字・小文字を区別しないファイルシステム上にあるか判定
+    pub(super) fn is_case_insensitive_dir(dir: &Path) -> bool {
+        let probe = dir.join(".kyosho_case_probe");
+        if std::fs::write(&probe, b"").is_err() {
+            return false;
+        }
+
+        let insensitive = dir.join(".KYOSHO_CASE_PROBE").exists();
+        let _ = std::fs::remove_file(&probe);
+        insensitive
+    }
+
+    /// 大文字・小文字のみ異なる名前の衝突を検出し、保存に使うローカル名を決定する
+    ///
+    /// 衝突時は設定に応じてエラーにするか、`<名前>~<番号>.<拡張子>` に変更して警告を通知する
+    pub(super) fn resolve_case_collision<F>(
+        entry_name: &OsStr,
+        remote_path: &Path,
+        used_names: &mut HashSet<String>,
+        options: &BackupOptions,
+        state: &mut TransferState,
+        progress_callback: &F,
+    ) -> Result<OsString>
+    where
+        F: Fn(BackupProgress),
+    {
+        let name = entry_name.to_string_lossy();
+        if used_names.insert(name.to_lowercase()) {
+            return Ok(entry_name.to_os_string());
+        }
+
+        state.case_collisions += 1;
+
+        if options.case_collision == CaseCollisionStrategy::Error {
+            return Err(anyhow::anyhow!(
+                "大文字・小文字のみ異なるファイル名が衝突しました（ローカルのファイルシステムが区別しません）: {:?}",
+                remote_path
+            ));
+        }
+
+        let (stem, extension) = match name.rfind('.') {
+            Some(pos) if pos > 0 => (&name[..pos], &name[pos..]),
+            _ => (name.as_ref(), ""),
+        };
+
+        let renamed = (1..)
+            .map(|n| format!("{}~{}{}", stem, n, extension))
+            .find(|candidate| used_names.insert(candidate.to_lowercase()))
+            .expect("連番は必ず見つかる");
+
+        tracing::warn!("ファイル名の衝突のため名前を変更: {:?} -> {}", remote_path, renamed);
+        progress_callback(BackupProgress {
+            phase: "ファイル名の衝突".to_string(),
+            transferred_files: state.transferred_files,
+            transferred_bytes: state.transferred_bytes,
+            current_file: Some(format!("{} -> {}", remote_path.to_string_lossy(), renamed)),
+            elapsed_seconds: state.throttle.get_elapsed_seconds(),
+            ..Default::default()
+        });
+
+        Ok(OsString::from(renamed))
+    }
+
+    /// 保存先に同名のファイルがある場合に、上書きポリシーに従って書き込み先を決める
+    ///
+    /// 転送しない場合は None。KeepBoth では既存のファイルと連番のファイルのいずれかと内容が
+    /// 同じなら保存せず、異なれば空いている連番の名前を返す（繰り返し実行しても増え続けないように）
+    pub(super) fn resolve_overwrite(
+        &self,
+        sftp: &ssh2::Sftp,
+        remote_path: &Path,
+        local_path: &Path,
+        file_size: u64,
+        options: &BackupOptions,
+        state: &mut TransferState,
+    ) -> Result<Option<PathBuf>> {
+        if !local_path.is_file() {
+            return Ok(Some(local_path.to_path_buf()));
+        }
+
+        match options.overwrite_policy {
+            OverwritePolicy::Overwrite => {
+                state.overwrite_counts.overwritten += 1;
+                Ok(Some(local_path.to_path_buf()))
+            }
+            OverwritePolicy::Skip => {
+                tracing::info!("既存のファイルがあるためスキップ: {:?}", local_path);
+                state.overwrite_counts.skipped_existing += 1;
+                Ok(None)
+            }
+            OverwritePolicy::KeepBoth => {
+                let file_name = local_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                let (stem, extension) = match file_name.rfind('.') {
+                    Some(pos) if pos > 0 => (&file_name[..pos], &file_name[pos..]),
+                    _ => (file_name.as_str(), ""),
+                };
+
+                // 既存のファイルと連番のファイルを順に比較し、最初の空き番号を保存先にする
+                let mut remote_hash = None;
+                let mut candidate = local_path.to_path_buf();
+                let mut number = 0;
+                while candidate.is_file() {
+                    // 連番で保存したファイルもリモートにあるものとしてミラー削除の対象外にする
+                    if options.mirror_delete {
+                        state.remote_entries.insert(candidate.clone());
+                    }
+
+                    if self.same_content(sftp, remote_path, &candidate, file_size, &mut remote_hash)? {
+                        tracing::info!("既存のファイルと内容が同じため保存しません: {:?}", candidate);
+                        state.overwrite_counts.identical += 1;
+                        return Ok(None);
+                    }
+
+                    number += 1;
+                    candidate = local_path.with_file_name(format!("{} ({}){}", stem, number, extension));
+                }
+
+                tracing::info!("既存のファイルを残して別名で保存: {:?} -> {:?}", local_path, candidate);
+                state.overwrite_counts.kept_both += 1;
+                if options.mirror_delete {
+                    state.remote_entries.insert(candidate.clone());
+                }
+                Ok(Some(candidate))
+            }
+        }
+    }
+
+    /// リモートのファイルとローカルのファイルの内容が同じか
+    ///
+    /// サイズが異なれば読み取らない。サーバーで sha256sum を実行できればハッシュで比較し
+    /// （`remote_hash` に保持して使い回す）、できなければリモートの内容を読み取って比較する
+    fn same_content(
+        &self,
+        sftp: &ssh2::Sftp,
+        remote_path: &Path,
+        local_path: &Path,
+        file_size: u64,
+        remote_hash: &mut Option<Option<String>>,
+    ) -> Result<bool> {
+        if std::fs::metadata(local_path).map(|m| m.len()).ok() != Some(file_size) {
+            return Ok(false);
+        }
+
+        let remote_hash = remote_hash.get_or_insert_with(|| {
+            self.session
+                .as_ref()
+                .and_then(|session| Self::remote_file_sha256(session, &remote_path.to_string_lossy()).ok())
+        });
+        if let Some(remote_hash) = remote_hash {
+            return Ok(transfer_index::file_sha256(local_path)? == *remote_hash);
+        }
+
+        let mut remote_file = sftp.open(remote_path)
+            .with_context(|| format!("リモートファイルのオープンに失敗: {:?}", remote_path))?;
+        let mut local_file = std::fs::File::open(local_path)
+            .with_context(|| format!("ローカルファイルのオープンに失敗: {:?}", local_path))?;
+
+        let mut remote_buffer = vec![0u8; 128 * 1024];
+        let mut local_buffer = vec![0u8; 128 * 1024];
+        loop {
+            let read = remote_file.read(&mut remote_buffer)
+                .with_context(|| format!("リモートファイルの読み取りに失敗: {:?}", remote_path))?;
+            if read == 0 {
+                return Ok(true);
+            }
+            if local_file.read_exact(&mut local_buffer[..read]).is_err()
+                || remote_buffer[..read] != local_buffer[..read]
+            {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// 1ファイルをダウンロードする（ファイルサイズに応じた動的タイムアウト付き）
+    ///
+    /// ディレクトリの再帰転送と単一ファイル転送で共通に使用する。
+    /// 次回以降の link_dest 判定のため、ローカルファイルの更新日時をリモートに合わせる。
+    /// `encryptor` を指定した場合は暗号化しながら書き込む（再開はしない）
+    pub(super) fn download_file(
+        sftp: &ssh2::Sftp,
+        remote_path: &Path,
+        local_path: &Path,
+        file_size: u64,
+        remote_mtime: Option<u64>,
+        options: &BackupOptions,
+        encryptor: Option<&FileEncryptor>,
+    ) -> Result<u64> {
+        // ファイルサイズに基づいて動的にタイムアウトを計算（オプションの倍率を適用）
+        let file_timeout = Self::calculate_file_timeout(file_size).mul_f64(options.file_timeout_multiplier());
+        let resume = options.resume && encryptor.is_none();
+        let part_path = Self::part_path(local_path);
+        let started = Instant::now();
+
+        let file_transfer = || -> Result<u64> {
+            let mut remote_file = sftp.open(remote_path)
+                .with_context(|| format!("リモートファイルのオープンに失敗: {:?}", remote_path))?;
+
+            // 一時ファイルに書き込み、完了後に本来のファイル名へ変更（失敗しても前回のバックアップを壊さない）。
+            // 再開モードでは既存の一時ファイルの続きから書き込む
+            let mut local_file = if resume {
+                Self::open_part_file(&mut remote_file, &part_path, file_size)?
+            } else {
+                std::fs::File::create(&part_path)
+                    .with_context(|| format!("ローカルファイルの作成に失敗: {:?}", part_path))?
+            };
+
+            // 大容量ファイルはパイプライン転送、それ以外は128KBバッファで転送 - 転送バイト数を返す
+            let mut remote_file = DeadlineReader { inner: &mut remote_file, deadline: started + file_timeout };
+            let transferred = match encryptor {
+                Some(encryptor) => {
+                    let mut writer = encryptor.writer(&mut local_file);
+                    let transferred = Self::transfer_file(&mut remote_file, &mut writer, file_size, options)
+                        .with_context(|| format!("ファイル転送に失敗: {:?}", remote_path))?;
+                    writer.finish()
+                        .with_context(|| format!("ローカルファイル書き込み失敗: {:?}", local_path))?;
+                    transferred
+                }
+                None => Self::transfer_file(&mut remote_file, &mut local_file, file_size, options)
+                    .with_context(|| format!("ファイル転送に失敗: {:?}", remote_path))?,
+            };
+
+            if let Some(mtime) = remote_mtime {
+                local_file
+                    .set_modified(std::time::UNIX_EPOCH + Duration::from_secs(mtime))
+                    .with_context(|| format!("更新日時の設定に失敗: {:?}", part_path))?;
+            }
+
+            drop(local_file);
+            std::fs::rename(&part_path, local_path)
+                .with_context(|| format!("ダウンロード済みファイルの名前変更に失敗: {:?}", part_path))?;
+
+            Ok(transferred)
+        };
+
+        // 制限時間を過ぎて読み取りが打ち切られた場合は、タイムアウトしたファイルとして報告する
+        let result = file_transfer().map_err(|e| {
+            let timed_out = e.chain().any(|cause| {
+                cause.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::TimedOut)
+            });
+            if timed_out && started.elapsed() >= file_timeout {
+                anyhow::Error::from(BackupError::FileTimeout(TimedOutFile {
+                    path: remote_path.to_string_lossy().to_string(),
+                    size: file_size,
+                    limit_seconds: file_timeout.as_secs(),
+                }))
+            } else {
+                e
+            }
+        });
+
+        if let Err(e) = &result {
+            tracing::warn!("ファイル転送エラー: {:?}: {:#}", remote_path, e);
+            // 再開しない場合、途中まで書き込んだ一時ファイルは使わないため削除する
+            if !resume && part_path.is_file() {
+                if let Err(e) = std::fs::remove_file(&part_path) {
+                    tracing::warn!("一時ファイルの削除に失敗: {:?}: {}", part_path, e);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// 暗号化して保存するファイルのパス（`<ファイル名>.enc`）
+    pub(super) fn encrypted_path(local_path: &Path) -> PathBuf {
+        let mut name = local_path.file_name().unwrap_or_default().to_os_string();
+        name.push(backup_crypto::ENCRYPTED_EXTENSION);
+        local_path.with_file_name(name)
+    }
+
+    /// 暗号化して保存したファイルをマニフェストに記録
+    pub(super) fn record_encrypted(state: &mut TransferState, local_path: &Path, encryptor: Option<FileEncryptor>, file_size: u64) {
+        let (Some(encryption), Some(encryptor)) = (&mut state.encryption, encryptor) else {
+            return;
+        };
+        if let Some(relative) = Self::index_key(&state.local_root, local_path) {
+            encryption.record(relative, &encryptor, file_size);
+        }
+    }
+
+    /// 転送中の一時ファイルのパス（`<ファイル名>.kyosho-part`。再開時はこのファイルの続きから書き込む）
+    fn part_path(local_path: &Path) -> PathBuf {
+        let mut name = local_path.file_name().unwrap_or_default().to_os_string();
+        name.push(PART_FILE_EXTENSION);
+        local_path.with_file_name(name)
+    }
+
+    /// 再開用の `.kyosho-part` ファイルを開く
+    ///
+    /// 既存の `.kyosho-part` がリモートより小さければ末尾から続きをダウンロードできるよう
+    /// ローカル・リモートの両方を既存サイズの位置に合わせる。
+    /// 継ぎ目の検証に失敗した場合は最初からダウンロードし直す
+    fn open_part_file(
+        remote_file: &mut ssh2::File,
+        part_path: &Path,
+        remote_size: u64,
+    ) -> Result<std::fs::File> {
+        let existing = std::fs::metadata(part_path).map(|m| m.len()).unwrap_or(0);
+
+        if existing > 0 && existing <= remote_size {
+            match Self::verify_resume_seam(remote_file, part_path, existing) {
+                Ok(local_file) => {
+                    tracing::info!("ダウンロードを再開: {:?} ({} / {} バイト)", part_path, existing, remote_size);
+                    return Ok(local_file);
+                }
+                Err(e) => {
+                    tracing::warn!("再開できないため最初からダウンロードします: {:?}: {:#}", part_path, e);
+                    remote_file.seek(SeekFrom::Start(0))
+                        .context("リモートファイルの読み取り位置のリセットに失敗しました")?;
+                }
+            }
+        }
+
+        std::fs::File::create(part_path)
+            .with_context(|| format!("ローカルファイルの作成に失敗: {:?}", part_path))
+    }
+
+    /// 既存の `.kyosho-part` の末尾ブロックをリモートの同じ範囲と比較し、継ぎ目の破損がないか確認
+    ///
+    /// 一致すればローカル・リモートとも既存サイズの位置に移動した状態で返す
+    fn verify_resume_seam(
+        remote_file: &mut ssh2::File,
+        part_path: &Path,
+        existing: u64,
+    ) -> Result<std::fs::File> {
+        let block_size = existing.min(RESUME_VERIFY_BLOCK_SIZE);
+        let seam_start = existing - block_size;
+
+        let mut local_file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(part_path)
+            .with_context(|| format!("再開用ファイルのオープンに失敗: {:?}", part_path))?;
+
+        let mut local_block = vec![0u8; block_size as usize];
+        local_file.seek(SeekFrom::Start(seam_start))?;
+        local_file.read_exact(&mut local_block)?;
+
+        let mut remote_block = vec![0u8; block_size as usize];
+        remote_file.seek(SeekFrom::Start(seam_start))
+            .context("リモートファイルのシークに失敗しました")?;
+        remote_file.read_exact(&mut remote_block)
+            .context("リモートファイルの末尾ブロックの読み取りに失敗しました")?;
+
+        if local_block != remote_block {
+            return Err(anyhow::anyhow!("既存データの末尾がリモートと一致しません"));
+        }
+
+        // シーク後の位置が期待どおりか確認（シーク非対応のサーバー対策）
+        if remote_file.stream_position()? != existing || local_file.stream_position()? != existing {
+            return Err(anyhow::anyhow!("読み取り位置が一致しません"));
+        }
+
+        Ok(local_file)
+    }
+
+    /// 単一のリモートファイルをローカルフォルダにバックアップ
+    #[allow(clippy::too_many_arguments)]
+    pub(super) async fn backup_single_file<F>(
+        &self,
+        sftp: &ssh2::Sftp,
+        remote_path: &Path,
+        local_dir: &Path,
+        file_size: u64,
+        remote_mtime: Option<u64>,
+        options: &BackupOptions,
+        state: &mut TransferState,
+        progress_callback: &F,
+    ) -> Result<()>
+    where
+        F: Fn(BackupProgress),
+    {
+        let file_name = remote_path
+            .file_name()
+            .with_context(|| format!("リモートファイル名を取得できません: {:?}", remote_path))?;
+
+        let mut local_path = local_dir.join(file_name);
+        if options.encrypt {
+            local_path = Self::encrypted_path(&local_path);
+        }
+
+        match self.incremental_skip_reason(options, state, remote_path, &local_path, file_size, remote_mtime) {
+            Some(IncrementalSkip::TooLarge) => {
+                tracing::info!("サイズ上限によりスキップ: {:?} ({} バイト)", remote_path, file_size);
+                state.skipped_large_files += 1;
+                return Ok(());
+            }
+            Some(IncrementalSkip::OutsideAgeWindow) => {
+                state.skipped_by_age += 1;
+                return Ok(());
+            }
+            Some(IncrementalSkip::Unmodified) => {
+                state.skipped_unmodified_files += 1;
+                return Ok(());
+            }
+            None => {}
+        }
+
+        std::fs::create_dir_all(local_dir)
+            .with_context(|| format!("ローカルディレクトリの作成に失敗: {:?}", local_dir))?;
+
+        progress_callback(BackupProgress {
+            phase: "ファイル転送中".to_string(),
+            transferred_files: 0,
+            total_files: Some(1),
+            transferred_bytes: 0,
+            total_bytes: Some(file_size),
+            current_file: Some(remote_path.to_string_lossy().to_string()),
+            elapsed_seconds: state.throttle.get_elapsed_seconds(),
+            percent_complete: BackupProgress::calculate_percent(0, Some(file_size)),
+            ..Default::default()
+        });
+
+        if local_path.is_dir() {
+            return Err(BackupError::FileSystem(format!("保存先に同名のフォルダがあります: {}", local_path.display())).into());
+        }
+
+        let Some(local_path) = self.resolve_overwrite(sftp, remote_path, &local_path, file_size, options, state)? else {
+            return Ok(());
+        };
+
+        if Self::link_from_reference(options, state, &local_path, file_size, remote_mtime) {
+            state.linked_files += 1;
+            state.transferred_files += 1;
+            return Ok(());
+        }
+
+        let encryptor = state.encryption.as_ref().map(BackupEncryption::file_encryptor);
+        let transferred = Self::download_file(sftp, remote_path, &local_path, file_size, remote_mtime, options, encryptor.as_ref())?;
+
+        state.transferred_bytes += transferred;
+        state.transferred_files += 1;
+        Self::record_encrypted(state, &local_path, encryptor, file_size);
+        self.queue_checksum_verification(state, remote_path, &local_path);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadline_reader_stops_after_deadline() {
+        let data = vec![7u8; 1024];
+        let mut reader = DeadlineReader { inner: data.as_slice(), deadline: Instant::now() + Duration::from_secs(60) };
+        let mut copied = Vec::new();
+        assert_eq!(SshClient::transfer_file_optimized(&mut reader, &mut copied).unwrap(), 1024);
+
+        let mut expired = DeadlineReader { inner: data.as_slice(), deadline: Instant::now() };
+        let error = SshClient::transfer_file_optimized(&mut expired, &mut Vec::new()).unwrap_err();
+        let io_error = error.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(io_error.kind(), std::io::ErrorKind::TimedOut);
+    }
+}
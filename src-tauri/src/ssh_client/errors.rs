@@ -0,0 +1,270 @@
+use super::*;
+
+/// SFTPのステータスコード（SSH_FX_NO_SUCH_FILE・SSH_FX_PERMISSION_DENIED）
+const SFTP_FX_NO_SUCH_FILE: i32 = 2;
+
+const SFTP_FX_PERMISSION_DENIED: i32 = 3;
+
+impl SshClient {
+    /// エラーを分類してユーザーフレンドリーなメッセージを生成
+    ///
+    /// # エラー分類
+    /// 1. 認証エラー: 秘密鍵の問題、パスフレーズ不正など
+    /// 2. ネットワークエラー: 接続タイムアウト、DNS解決失敗など
+    /// 3. パーミッションエラー: 読み取り/書き込み権限不足
+    /// 4. ファイルシステムエラー: ディスク容量不足、パス不正など
+    /// 5. タイムアウトエラー: 転送タイムアウト
+    /// 6. その他のエラー
+    pub(super) fn classify_error(error: &anyhow::Error) -> ClassifiedError {
+        // 変換済みのエラー（接続テストのエラーなど）は二重に変換しない
+        if let Some(classified) = error.chain().find_map(|e| e.downcast_ref::<ClassifiedError>()) {
+            return classified.clone();
+        }
+
+        // 分類済みエラーはそのまま対応するカテゴリで表示
+        // （`context` で付けたもの（踏み台サーバーのエラー）は chain からはダウンキャストできないため先に探す）
+        let backup_error = error
+            .downcast_ref::<BackupError>()
+            .or_else(|| error.chain().find_map(|e| e.downcast_ref::<BackupError>()));
+        if let Some(backup_error) = backup_error {
+            return Self::classify_backup_error(backup_error, error);
+        }
+
+        let error_str = error.to_string().to_lowercase();
+        Self::classify_authentication_error(&error_str, error)
+            .unwrap_or_else(|| Self::classify_error_message(&error_str, error))
+    }
+
+    /// 分類済みのエラー（`BackupError`）を対応するカテゴリで表示する
+    fn classify_backup_error(backup_error: &BackupError, error: &anyhow::Error) -> ClassifiedError {
+        match backup_error {
+            BackupError::DiskSpace { .. } => {
+                ClassifiedError::new(BackupErrorKind::DiskSpace, format!(
+                    "💾 ディスク容量エラー: ストレージに空き容量がありません\n\
+                     - ローカルディスクの空き容量を確保してください\n\
+                     - 不要なファイルを削除するか、別のディスクを選択してください\n\n\
+                     詳細: {}", error
+                ))
+            }
+            BackupError::Timeout { limit_seconds } => {
+                ClassifiedError::new(BackupErrorKind::Timeout, format!(
+                    "⏱️ タイムアウトエラー: バックアップ処理が{}分でタイムアウトしました\n\
+                     - 非常に大容量のデータをバックアップしようとしている可能性があります\n\
+                     - ネットワーク速度が極端に遅い可能性があります\n\
+                     - バックアップ対象を分割することをお勧めします",
+                    limit_seconds / 60
+                ))
+            }
+            BackupError::FileTimeout(file) => {
+                let mut classified = ClassifiedError::new(BackupErrorKind::Timeout, format!(
+                    "⏱️ タイムアウトエラー: ファイルの転送が{}秒でタイムアウトしました\n\
+                     - ファイル: {}（{} バイト）\n\
+                     - 回線が遅い場合や特定のファイルだけ遅い場合は、タイムアウトの倍率を大きくしてください",
+                    file.limit_seconds, file.path, file.size
+                ));
+                classified.timed_out_file = Some(file.clone());
+                classified
+            }
+            BackupError::FileSystem(_) => {
+                ClassifiedError::new(BackupErrorKind::FileSystem, format!(
+                    "📁 ファイルシステムエラー: 保存先を作成できません\n\
+                     - 保存先に同じ名前のファイルがないか確認してください\n\
+                     - 別の保存先フォルダを選択してください\n\n\
+                     詳細: {}", error
+                ))
+            }
+            BackupError::JumpHost { stage, .. } => Self::classify_jump_host_error(stage, error),
+        }
+    }
+
+    /// 踏み台サーバーでのエラーは、接続先サーバーのエラーと区別して段階ごとに表示
+    fn classify_jump_host_error(stage: &JumpHostStage, error: &anyhow::Error) -> ClassifiedError {
+        let (kind, summary, hints) = match stage {
+            JumpHostStage::Connect => (
+                BackupErrorKind::Network,
+                "踏み台サーバーに接続できません",
+                "- 踏み台サーバーのホスト名・ポート番号を確認してください\n\
+                 - ファイアウォールで接続が遮断されていないか確認してください",
+            ),
+            JumpHostStage::HostKey => (
+                BackupErrorKind::Authentication,
+                "踏み台サーバーのホスト鍵を確認できません",
+                "- サーバーの鍵を更新した場合は、known_hosts から踏み台サーバーの古い行を削除してください\n\
+                 - 心当たりがない場合は、なりすましの可能性があるため接続しないでください",
+            ),
+            JumpHostStage::Authentication => (
+                BackupErrorKind::Authentication,
+                "踏み台サーバーでの認証に失敗しました",
+                "- 踏み台サーバーのユーザー名を確認してください\n\
+                 - 踏み台サーバー用の秘密鍵が登録されているか確認してください",
+            ),
+            JumpHostStage::Forward => (
+                BackupErrorKind::Network,
+                "踏み台サーバーから接続先に接続できません",
+                "- 接続先のホスト名・ポート番号を確認してください\n\
+                 - 踏み台サーバーでポート転送（AllowTcpForwarding）が許可されているか確認してください",
+            ),
+        };
+        ClassifiedError::new(kind, format!(
+            "🌉 踏み台サーバーエラー: {}\n{}\n\n詳細: {:#}", summary, hints, error
+        ))
+    }
+
+    /// 認証に関するエラーメッセージを分類する（認証のエラーでなければ None）
+    fn classify_authentication_error(error_str: &str, error: &anyhow::Error) -> Option<ClassifiedError> {
+        // 認証試行回数の上限による切断（一般の認証エラーより先に判定）
+        if error_str.contains("too many authentication failures") {
+            return Some(ClassifiedError::new(BackupErrorKind::Authentication, format!(
+                "🔐 認証エラー: 認証の試行回数が多すぎるためサーバーに切断されました\n\
+                 - 追加の秘密鍵をX-Serverに登録済みの鍵だけに絞ってください\n\
+                 - 短時間に接続を繰り返した場合は、少し時間をおいてから再試行してください\n\n\
+                 詳細: {}", error
+            )));
+        }
+
+        // パスワード認証のエラー（秘密鍵の案内は当てはまらないため先に判定）
+        if error_str.contains("パスワード認証") {
+            return Some(ClassifiedError::new(BackupErrorKind::Authentication, format!(
+                "🔐 認証エラー: パスワード認証に失敗しました\n\
+                 - ユーザー名とパスワードを確認してください\n\
+                 - サーバーがパスワード認証を無効にしている場合は公開鍵認証が必要です\n\
+                 - パスワード認証は緊急時の代替手段です。公開鍵認証の利用を強く推奨します\n\n\
+                 詳細: {}", error
+            )));
+        }
+
+        // 認証エラー
+        if error_str.contains("authentication")
+            || error_str.contains("publickey")
+            || error_str.contains("passphrase")
+            || error_str.contains("permission denied (publickey)") {
+            return Some(ClassifiedError::new(BackupErrorKind::Authentication, format!(
+                "🔐 認証エラー: SSH秘密鍵の確認が必要です\n\
+                 - 秘密鍵のパスが正しいか確認してください\n\
+                 - 秘密鍵のパーミッションが600または400になっているか確認してください\n\
+                 - サーバーに公開鍵が正しく登録されているか確認してください\n\n\
+                 詳細: {}", error
+            )));
+        }
+
+        None
+    }
+
+    /// 認証以外のエラーメッセージを分類する
+    fn classify_error_message(error_str: &str, error: &anyhow::Error) -> ClassifiedError {
+        // ネットワークエラー
+        if error_str.contains("connection")
+            || error_str.contains("timeout")
+            || error_str.contains("dns")
+            || error_str.contains("network")
+            || error_str.contains("host") {
+            return ClassifiedError::new(BackupErrorKind::Network, format!(
+                "🌐 ネットワークエラー: サーバーへの接続に失敗しました\n\
+                 - インターネット接続を確認してください\n\
+                 - サーバーのホスト名とポート番号が正しいか確認してください\n\
+                 - ファイアウォールやVPNの設定を確認してください\n\n\
+                 詳細: {}", error
+            ));
+        }
+
+        // パーミッションエラー
+        if error_str.contains("permission denied")
+            || error_str.contains("access denied")
+            || error_str.contains("forbidden") {
+            return ClassifiedError::new(BackupErrorKind::Permission, format!(
+                "🚫 権限エラー: ファイルやディレクトリへのアクセスが拒否されました\n\
+                 - サーバー上のファイル/ディレクトリの権限を確認してください\n\
+                 - ローカルの保存先ディレクトリの書き込み権限を確認してください\n\n\
+                 詳細: {}", error
+            ));
+        }
+
+        // ディスク容量エラー
+        if error_str.contains("no space")
+            || error_str.contains("disk full")
+            || error_str.contains("quota") {
+            return ClassifiedError::new(BackupErrorKind::DiskSpace, format!(
+                "💾 ディスク容量エラー: ストレージに空き容量がありません\n\
+                 - ローカルディスクの空き容量を確保してください\n\
+                 - 不要なファイルを削除するか、別のディスクを選択してください\n\n\
+                 詳細: {}", error
+            ));
+        }
+
+        // タイムアウトエラー
+        if error_str.contains("timeout") || error_str.contains("timed out") {
+            return ClassifiedError::new(BackupErrorKind::Timeout, format!(
+                "⏱️ タイムアウトエラー: 処理時間が制限を超えました\n\
+                 - ネットワーク速度が遅い可能性があります\n\
+                 - 大容量ファイルの場合、時間をおいて再試行してください\n\
+                 - サーバーの応答が遅い可能性があります\n\n\
+                 詳細: {}", error
+            ));
+        }
+
+        // ファイルシステムエラー
+        if error_str.contains("no such file")
+            || error_str.contains("not found")
+            || error_str.contains("invalid path") {
+            return ClassifiedError::new(BackupErrorKind::FileSystem, format!(
+                "📁 ファイルシステムエラー: ファイルまたはディレクトリが見つかりません\n\
+                 - 指定したパスが正しいか確認してください\n\
+                 - サーバー上にファイル/ディレクトリが存在するか確認してください\n\n\
+                 詳細: {}", error
+            ));
+        }
+
+        // その他のエラー（詳細をそのまま表示）
+        ClassifiedError::new(BackupErrorKind::Other, format!("❌ エラーが発生しました: {}", error))
+    }
+
+    /// 権限がない・途中で削除されたなど、そのディレクトリだけの読み取りエラーか
+    ///
+    /// セッションの切断などは再接続の判定に回すため含めない
+    pub(super) fn is_inaccessible_dir_error(error: &anyhow::Error) -> bool {
+        error
+            .downcast_ref::<ssh2::Error>()
+            .is_some_and(Self::is_inaccessible_sftp_error)
+    }
+
+    pub(super) fn is_inaccessible_sftp_error(error: &ssh2::Error) -> bool {
+        matches!(
+            error.code(),
+            ssh2::ErrorCode::SFTP(SFTP_FX_PERMISSION_DENIED) | ssh2::ErrorCode::SFTP(SFTP_FX_NO_SUCH_FILE)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_error_separates_jump_host_stages() {
+        let failed_at = |stage| {
+            anyhow::anyhow!("[Session(-18)] Username/PublicKey combination invalid")
+                .context(BackupError::JumpHost {
+                    jump_host: "user@bastion.example.jp:22".to_string(),
+                    target: "sv1.example.jp:10022".to_string(),
+                    stage,
+                })
+                .context("接続に失敗しました")
+        };
+
+        let connect = SshClient::classify_error(&failed_at(JumpHostStage::Connect));
+        assert_eq!(connect.kind, BackupErrorKind::Network);
+        assert!(connect.message.contains("踏み台サーバーに接続できません"));
+
+        let host_key = SshClient::classify_error(&failed_at(JumpHostStage::HostKey));
+        assert_eq!(host_key.kind, BackupErrorKind::Authentication);
+        assert!(host_key.message.contains("known_hosts"));
+
+        let auth = SshClient::classify_error(&failed_at(JumpHostStage::Authentication));
+        assert_eq!(auth.kind, BackupErrorKind::Authentication);
+        assert!(auth.message.contains("踏み台サーバーでの認証"));
+
+        let forward = SshClient::classify_error(&failed_at(JumpHostStage::Forward));
+        assert_eq!(forward.kind, BackupErrorKind::Network);
+        assert!(forward.message.contains("sv1.example.jp:10022"));
+    }
+}
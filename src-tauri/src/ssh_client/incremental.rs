@@ -0,0 +1,372 @@
+use super::*;
+
+/// ファイルを開く前に判定できる、転送しない理由
+pub(super) enum IncrementalSkip {
+    /// サイズ上限を超える
+    TooLarge,
+    /// 更新からの経過日数が指定の範囲外
+    OutsideAgeWindow,
+    /// 前回のバックアップ以降の更新がない、またはインデックスと一致する
+    Unmodified,
+}
+
+impl SshClient {
+    /// 実際には転送せずにリモートを走査し、バックアップと同じ判定で転送量を見積もる
+    ///
+    /// サイズ上限・前回以降の更新のみ・インデックス・上書きの扱い・参照バックアップの判定は
+    /// バックアップと同じ処理を使う（rsync での転送を選んでいる場合もSFTPでの判定で見積もる）。
+    /// 中断フラグで中止でき、`time_budget` を超えた場合はそれまでの集計を返す
+    pub async fn estimate_incremental(
+        &mut self,
+        remote_path: &str,
+        local_path: &str,
+        options: &BackupOptions,
+        time_budget: Duration,
+    ) -> Result<IncrementalEstimate> {
+        if self.session.is_none() {
+            self.test_connection().await?;
+        }
+
+        let session = self.session.as_ref()
+            .context("SSHセッションが確立されていません")?;
+
+        let sftp = session.sftp()
+            .context("SFTPセッションの作成に失敗しました")?;
+
+        let remote_stat = sftp.stat(Path::new(remote_path))
+            .with_context(|| format!("リモートフォルダが見つかりません: {}", remote_path))?;
+
+        let options = &Self::with_ignore_rules(remote_path, options, Self::read_ignore_file_with_sftp(&sftp, remote_path));
+        let mut state = TransferState::new(Path::new(local_path), options);
+        let mut estimate = IncrementalEstimate::default();
+        let deadline = Instant::now() + time_budget;
+
+        if remote_stat.is_file() {
+            let file_name = Path::new(remote_path)
+                .file_name()
+                .with_context(|| format!("リモートファイル名を取得できません: {}", remote_path))?;
+            let mut local_file = Path::new(local_path).join(file_name);
+            if options.encrypt {
+                local_file = Self::encrypted_path(&local_file);
+            }
+            self.estimate_file(&sftp, Path::new(remote_path), &local_file, &remote_stat, options, &mut state, &mut estimate)?;
+        } else if remote_stat.is_dir() {
+            if options.use_index {
+                state.index = Some(IndexSession::load(Path::new(local_path)));
+            }
+            self.estimate_directory(&sftp, Path::new(remote_path), Path::new(local_path), 0, options, &mut state, &mut estimate, deadline)?;
+        } else {
+            return Err(anyhow::anyhow!("指定されたリモートパスはファイルでもディレクトリでもありません: {}", remote_path));
+        }
+
+        Ok(estimate)
+    }
+
+    /// フォルダ配下を再帰的に走査して見積もりに加える
+    #[allow(clippy::too_many_arguments)]
+    fn estimate_directory(
+        &self,
+        sftp: &ssh2::Sftp,
+        remote_dir: &Path,
+        local_dir: &Path,
+        depth: usize,
+        options: &BackupOptions,
+        state: &mut TransferState,
+        estimate: &mut IncrementalEstimate,
+        deadline: Instant,
+    ) -> Result<()> {
+        Self::check_cancelled(self.cancel_flag.as_deref())?;
+
+        // 深すぎる再帰を防ぐ（無限ループ対策）
+        if depth > 50 {
+            return Err(anyhow::anyhow!("ディレクトリの階層が深すぎます: {}", remote_dir.display()));
+        }
+
+        // 読み取れないディレクトリはバックアップ時にもスキップするため見積もりに含めない
+        let entries = match sftp.readdir(remote_dir) {
+            Ok(entries) => entries,
+            Err(e) if depth > 0 && Self::is_inaccessible_sftp_error(&e) => return Ok(()),
+            Err(e) => return Err(e).with_context(|| format!("リモートディレクトリの読み取りに失敗: {:?}", remote_dir)),
+        };
+
+        for (entry_path, stat) in entries {
+            Self::check_cancelled(self.cancel_flag.as_deref())?;
+            if Instant::now() >= deadline {
+                estimate.budget_exhausted = true;
+                return Ok(());
+            }
+
+            let Some(entry_name) = entry_path.file_name() else {
+                continue;
+            };
+            if entry_name.to_str().is_some_and(|name| options.skips_hidden(name)) {
+                continue;
+            }
+            if options.is_ignored(&entry_path, stat.is_dir()) {
+                continue;
+            }
+
+            let local_entry_path = local_dir.join(entry_name);
+            if stat.is_file() {
+                let local_entry_path = if options.encrypt {
+                    Self::encrypted_path(&local_entry_path)
+                } else {
+                    local_entry_path
+                };
+                self.estimate_file(sftp, &entry_path, &local_entry_path, &stat, options, state, estimate)?;
+            } else if stat.is_dir() {
+                self.estimate_directory(sftp, &entry_path, &local_entry_path, depth + 1, options, state, estimate, deadline)?;
+                if estimate.budget_exhausted {
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// ファイル1つをバックアップと同じ順序で判定して見積もりに加える
+    #[allow(clippy::too_many_arguments)]
+    fn estimate_file(
+        &self,
+        sftp: &ssh2::Sftp,
+        remote_path: &Path,
+        local_path: &Path,
+        stat: &ssh2::FileStat,
+        options: &BackupOptions,
+        state: &mut TransferState,
+        estimate: &mut IncrementalEstimate,
+    ) -> Result<()> {
+        let file_size = stat.size.unwrap_or(0);
+
+        match self.incremental_skip_reason(options, state, remote_path, local_path, file_size, stat.mtime) {
+            Some(IncrementalSkip::TooLarge | IncrementalSkip::OutsideAgeWindow) => {
+                estimate.excluded_files += 1;
+                estimate.excluded_bytes += file_size;
+                return Ok(());
+            }
+            Some(IncrementalSkip::Unmodified) => {
+                estimate.skipped_files += 1;
+                estimate.skipped_bytes += file_size;
+                return Ok(());
+            }
+            None => {}
+        }
+
+        // 同名のフォルダがある場合はバックアップでも転送しない
+        let target_path = if local_path.is_dir() {
+            None
+        } else {
+            self.resolve_overwrite(sftp, remote_path, local_path, file_size, options, state)?
+        };
+        let Some(target_path) = target_path else {
+            estimate.skipped_files += 1;
+            estimate.skipped_bytes += file_size;
+            return Ok(());
+        };
+
+        if Self::reference_file(options, state, &target_path, file_size, stat.mtime).is_some() {
+            estimate.linked_files += 1;
+            estimate.linked_bytes += file_size;
+        } else {
+            estimate.transfer_files += 1;
+            estimate.transfer_bytes += file_size;
+        }
+
+        Ok(())
+    }
+
+    /// ファイルを開く前に判定できる、転送しない理由
+    ///
+    /// バックアップと転送量の見積もりで同じ判定を使う
+    pub(super) fn incremental_skip_reason(
+        &self,
+        options: &BackupOptions,
+        state: &mut TransferState,
+        remote_path: &Path,
+        local_path: &Path,
+        file_size: u64,
+        remote_mtime: Option<u64>,
+    ) -> Option<IncrementalSkip> {
+        // 以下の3つはリモートにあるファイルを開かずにスキップするため、前回の転送時の情報を引き継ぐ
+        // （引き継がないと、完了時にインデックスから消えて次回以降に比較できなくなる）
+        let skip = if options.exceeds_max_file_size(file_size) {
+            // サイズ上限を超えるファイルは開かずにスキップ
+            Some(IncrementalSkip::TooLarge)
+        } else if options.is_outside_age_window(remote_mtime) {
+            // 更新からの経過日数が範囲外のファイルはスキップ
+            Some(IncrementalSkip::OutsideAgeWindow)
+        } else if options.is_unmodified_since(remote_mtime) {
+            // 前回のバックアップ以降に更新されていないファイルはスキップ
+            Some(IncrementalSkip::Unmodified)
+        } else {
+            None
+        };
+        if skip.is_some() {
+            if let (Some(index), Some(relative)) = (&mut state.index, Self::index_key(&state.local_root, local_path)) {
+                index.carry_forward(&relative);
+            }
+            return skip;
+        }
+
+        // インデックスに記録した前回の転送時から変わっていないファイルはスキップ
+        if self.matches_index(options, state, remote_path, local_path, file_size, remote_mtime) {
+            return Some(IncrementalSkip::Unmodified);
+        }
+
+        None
+    }
+
+    /// インデックスに記録した前回の転送時とサイズ・更新日時（有効ならSHA-256も）が一致し、
+    /// ローカルのファイルも残っているか確認する
+    ///
+    /// 一致した場合は今回のインデックスに引き継ぐ。ハッシュを取得できない場合は転送する
+    fn matches_index(
+        &self,
+        options: &BackupOptions,
+        state: &mut TransferState,
+        remote_path: &Path,
+        local_path: &Path,
+        file_size: u64,
+        remote_mtime: Option<u64>,
+    ) -> bool {
+        let Some(index) = &mut state.index else {
+            return false;
+        };
+        let Some(relative) = Self::index_key(&state.local_root, local_path) else {
+            return false;
+        };
+        let Some(previous) = index.previous(&relative) else {
+            return false;
+        };
+        if remote_mtime.is_none() || previous.size != file_size || previous.mtime != remote_mtime {
+            return false;
+        }
+
+        // ローカルのファイルが消えたりサイズが変わったりしていれば転送し直す
+        let local_size = if options.encrypt { backup_crypto::encrypted_size(file_size) } else { file_size };
+        if !std::fs::metadata(local_path).is_ok_and(|metadata| metadata.is_file() && metadata.len() == local_size) {
+            return false;
+        }
+
+        // 暗号化したファイルはローカルのハッシュを記録できないため、サイズ・更新日時のみで判定する
+        if options.index_hashes && !options.encrypt {
+            let remote_hash = self.session
+                .as_ref()
+                .context("SSHセッションが確立されていません")
+                .and_then(|session| Self::remote_file_sha256(session, &remote_path.to_string_lossy()));
+            match remote_hash {
+                Ok(hash) if previous.sha256.as_deref() == Some(hash.as_str()) => {}
+                Ok(_) => return false,
+                Err(e) => {
+                    tracing::warn!("リモートのハッシュを取得できないため転送します: {:?}: {:#}", remote_path, e);
+                    return false;
+                }
+            }
+        }
+
+        let previous = previous.clone();
+        index.record(relative, previous);
+        true
+    }
+
+    /// 転送・ハードリンクしたファイルをインデックスに記録
+    pub(super) fn record_in_index(
+        options: &BackupOptions,
+        state: &mut TransferState,
+        local_path: &Path,
+        file_size: u64,
+        remote_mtime: Option<u64>,
+    ) {
+        let Some(index) = &mut state.index else {
+            return;
+        };
+        let Some(relative) = Self::index_key(&state.local_root, local_path) else {
+            return;
+        };
+
+        let sha256 = if options.index_hashes && !options.encrypt {
+            transfer_index::file_sha256(local_path)
+                .map_err(|e| tracing::warn!("インデックス用のハッシュの計算に失敗: {:?}: {:#}", local_path, e))
+                .ok()
+        } else {
+            None
+        };
+
+        index.record(relative, IndexEntry { size: file_size, mtime: remote_mtime, sha256 });
+    }
+
+    /// インデックスのキー（保存先からの相対パス、区切りは "/"）
+    pub(super) fn index_key(local_root: &Path, local_path: &Path) -> Option<String> {
+        let relative = local_path.strip_prefix(local_root).ok()?;
+        Some(
+            relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join("/"),
+        )
+    }
+
+    /// link_dest の同じ相対パスにある、サイズ・更新日時が一致するファイル
+    fn reference_file(
+        options: &BackupOptions,
+        state: &TransferState,
+        local_path: &Path,
+        file_size: u64,
+        remote_mtime: Option<u64>,
+    ) -> Option<PathBuf> {
+        // 暗号化したファイルは参照バックアップとNonce・鍵が異なるため共有しない
+        if options.encrypt {
+            return None;
+        }
+        let (Some(link_dest), Some(remote_mtime)) = (&options.link_dest, remote_mtime) else {
+            return None;
+        };
+        let relative = local_path.strip_prefix(&state.local_root).ok()?;
+
+        let reference_path = link_dest.join(relative);
+        let metadata = std::fs::metadata(&reference_path).ok()?;
+
+        let reference_mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+        if !metadata.is_file() || metadata.len() != file_size || reference_mtime != Some(remote_mtime) {
+            return None;
+        }
+
+        Some(reference_path)
+    }
+
+    /// link_dest の同じ相対パスにサイズ・更新日時が一致するファイルがあればハードリンクを作成
+    ///
+    /// リンクできた場合はtrueを返す。ハードリンク非対応のファイルシステムなど
+    /// リンクに失敗した場合はfalseを返し、呼び出し側で通常のダウンロードを行う
+    pub(super) fn link_from_reference(
+        options: &BackupOptions,
+        state: &TransferState,
+        local_path: &Path,
+        file_size: u64,
+        remote_mtime: Option<u64>,
+    ) -> bool {
+        let Some(reference_path) = Self::reference_file(options, state, local_path, file_size, remote_mtime) else {
+            return false;
+        };
+
+        // 参照先とバックアップ先が同じファイルなら何もしない
+        if let (Ok(a), Ok(b)) = (reference_path.canonicalize(), local_path.canonicalize()) {
+            if a == b {
+                return true;
+            }
+        }
+
+        // 既存ファイルがあるとハードリンクを作成できないため削除してから作成
+        if local_path.exists() && std::fs::remove_file(local_path).is_err() {
+            return false;
+        }
+
+        std::fs::hard_link(&reference_path, local_path).is_ok()
+    }
+}
@@ -0,0 +1,159 @@
+use super::*;
+
+/// ミラー削除で残すローカルのエントリの判定に使う情報
+struct DeletionProtection<'a> {
+    pub(super) local_root: &'a Path,
+    /// リモートにあるエントリ（ローカルのパス）
+    pub(super) remote_entries: &'a HashSet<PathBuf>,
+    /// リモートで読み取れなかったディレクトリ（ローカルのパス）
+    pub(super) inaccessible_dirs: &'a HashSet<PathBuf>,
+    pub(super) ignore_rules: &'a IgnoreRules,
+}
+
+impl DeletionProtection<'_> {
+    /// 除外パターンに一致するローカルのエントリか（バックアップ元からの相対パスで照合する）
+    pub(super) fn is_ignored(&self, local_path: &Path, is_dir: bool) -> bool {
+        local_path
+            .strip_prefix(self.local_root)
+            .is_ok_and(|relative| self.ignore_rules.is_ignored_relative(relative, is_dir))
+    }
+}
+
+impl SshClient {
+    /// ミラー削除: リモートに存在しないローカルのファイル・ディレクトリを削除する
+    ///
+    /// 削除中は転送と同じ間隔で「削除中」の進捗を通知する。キャンセルされた場合は
+    /// それまでに削除した件数をエラーに含めて中断する
+    pub(super) fn delete_extraneous_local_entries<F>(
+        local_root: &Path,
+        ignore_rules: &IgnoreRules,
+        control: &BackupControl,
+        state: &mut TransferState,
+        progress_callback: &F,
+    ) -> Result<()>
+    where
+        F: Fn(BackupProgress),
+    {
+        let mut candidates = Vec::new();
+        let protected = DeletionProtection {
+            local_root,
+            remote_entries: &state.remote_entries,
+            inaccessible_dirs: &state.inaccessible_local_dirs,
+            ignore_rules,
+        };
+        Self::collect_deletion_candidates(local_root, &protected, 0, &mut candidates)?;
+        let total_candidates = candidates.len();
+
+        for (index, path) in candidates.into_iter().enumerate() {
+            if control.is_cancelled() {
+                return Err(anyhow::anyhow!(
+                    "🚫 バックアップがキャンセルされました（削除済み: {} / {}）",
+                    state.deleted_files,
+                    total_candidates
+                ));
+            }
+
+            let result = if path.is_dir() {
+                std::fs::remove_dir_all(&path).map(|_| 0)
+            } else {
+                let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                std::fs::remove_file(&path).map(|_| size)
+            };
+
+            match result {
+                Ok(size) => {
+                    tracing::info!("リモートにないため削除: {:?}", path);
+                    state.deleted_files += 1;
+                    state.deleted_bytes += size;
+                }
+                Err(e) => tracing::warn!("ミラー削除に失敗: {:?}: {}", path, e),
+            }
+
+            if state.throttle.should_update_without_speed_sample(state.transferred_bytes + state.deleted_bytes) {
+                progress_callback(BackupProgress {
+                    phase: "削除中".to_string(),
+                    transferred_files: state.deleted_files,
+                    total_files: Some(total_candidates),
+                    transferred_bytes: state.deleted_bytes,
+                    current_file: Some(path.to_string_lossy().to_string()),
+                    elapsed_seconds: state.throttle.get_elapsed_seconds(),
+                    percent_complete: Some((index + 1) as f64 / total_candidates as f64 * 100.0),
+                    ..Default::default()
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// ミラー削除の対象（リモートにない隠しファイル以外のエントリ）を列挙
+    ///
+    /// ディレクトリごと削除できるものは中身を列挙しない。リモートで読み取れなかったディレクトリは
+    /// 中身の有無が分からないため、その配下は削除しない。除外パターンに一致するものは
+    /// 転送していないだけなので、配下も含めて削除しない
+    fn collect_deletion_candidates(
+        local_dir: &Path,
+        protected: &DeletionProtection,
+        depth: usize,
+        candidates: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        // 深すぎる再帰を防ぐ（無限ループ対策）
+        if depth > 50 {
+            return Err(anyhow::anyhow!("ディレクトリの階層が深すぎます: {}", local_dir.display()));
+        }
+
+        let entries = std::fs::read_dir(local_dir)
+            .with_context(|| format!("ローカルディレクトリの読み取りに失敗: {:?}", local_dir))?;
+
+        for entry in entries {
+            let entry = entry.with_context(|| format!("ローカルディレクトリの読み取りに失敗: {:?}", local_dir))?;
+            let path = entry.path();
+
+            let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            if is_hidden || protected.is_ignored(&path, is_dir) {
+                continue;
+            }
+
+            if !protected.remote_entries.contains(&path) {
+                candidates.push(path);
+            } else if is_dir && !protected.inaccessible_dirs.contains(&path) {
+                Self::collect_deletion_candidates(&path, protected, depth + 1, candidates)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn mirror_delete_keeps_ignored_local_entries() {
+        let local = TempDir::new("mirror-delete");
+        let root = local.path();
+        local.write("index.html", b"remote");
+        local.write("stale.html", b"removed on remote");
+        local.write("debug.log", b"ignored file");
+        local.write("node_modules/pkg/index.js", b"ignored dir");
+        local.write("wp-content/cache/page.html", b"ignored nested dir");
+        local.write("wp-content/old.php", b"removed on remote");
+
+        let remote_entries: HashSet<PathBuf> = [root.join("index.html"), root.join("wp-content")].into();
+        let ignore_rules = IgnoreRules::new(Path::new("/remote"), ["*.log", "node_modules/", "wp-content/cache"]);
+        let protected = DeletionProtection {
+            local_root: root,
+            remote_entries: &remote_entries,
+            inaccessible_dirs: &HashSet::new(),
+            ignore_rules: &ignore_rules,
+        };
+
+        let mut candidates = Vec::new();
+        SshClient::collect_deletion_candidates(root, &protected, 0, &mut candidates).unwrap();
+        candidates.sort();
+        assert_eq!(candidates, vec![root.join("stale.html"), root.join("wp-content/old.php")]);
+    }
+}
@@ -0,0 +1,368 @@
+use super::*;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupConfig {
+    /// プロファイル名（設定内で一意）
+    #[serde(default)]
+    pub name: String,
+    pub ssh: SshConfig,
+    pub remote_folder: String,
+    pub local_folder: String,
+    /// このプロファイルで除外するファイルサイズの既定値（バイト）
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+    /// バックアップ前に実行するコマンド（0以外で終了した場合はバックアップを中止）
+    #[serde(default)]
+    pub pre_hook: Option<String>,
+    /// バックアップ後に成否を問わず実行するコマンド
+    #[serde(default)]
+    pub post_hook: Option<String>,
+    /// フックの実行時間の上限（秒、未指定は10分）。過ぎた場合はフックを停止して失敗として扱う
+    #[serde(default)]
+    pub hook_timeout_secs: Option<u64>,
+    /// 日付入りの保存先フォルダを新しい順にこの数だけ残し、成功したバックアップの後に古いものを削除する
+    ///
+    /// 保存先の最後のフォルダ名に `{date}` か `{datetime}` を含む場合のみ有効
+    #[serde(default)]
+    pub keep_last_n_backups: Option<usize>,
+    /// 最後に計測したサーバーの時計のずれ（サーバー − ローカル、秒。前回以降の更新の判定に使う）
+    #[serde(default)]
+    pub clock_skew_secs: Option<i64>,
+    /// 設定ファイル内に保存した秘密鍵（ある場合は `ssh.key_path` の鍵ファイルの代わりに使う）
+    ///
+    /// 設定ファイルと同じく暗号化して保存する。フロントエンドには渡さない
+    #[serde(default)]
+    pub stored_private_key: Option<StoredPrivateKey>,
+}
+
+impl BackupConfig {
+    /// 接続に使うSSHの設定（設定に保存した鍵があれば、その鍵で認証する）
+    pub fn ssh_config(&self) -> SshConfig {
+        let mut ssh = self.ssh.clone();
+        if let Some(key) = &self.stored_private_key {
+            ssh.auth_method = SshAuthMethod::StoredKey(key.clone());
+        }
+        ssh
+    }
+}
+
+// バックアップ実行オプション
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupOptions {
+    /// 転送前にリモートの総ファイル数・総バイト数を計算する
+    pub precount: bool,
+    /// 事前計算の結果を使ってローカルの空き容量をチェックする
+    pub check_disk_space: bool,
+    /// 大容量ファイルをパイプライン転送する（無効時は従来の逐次転送）
+    pub pipelined_transfer: bool,
+    /// 全体タイムアウト計算に使う最低想定スループット（MB/s）
+    pub min_throughput_mbps: f64,
+    /// 前回のバックアップフォルダ（rsyncの --link-dest 相当）
+    ///
+    /// サイズと更新日時が一致するファイルはダウンロードせず、ここにあるファイルへのハードリンクを作成する
+    pub link_dest: Option<PathBuf>,
+    /// 中断したダウンロードを `.kyosho-part` ファイルから再開する
+    pub resume: bool,
+    /// このサイズ（バイト）を超えるファイルは転送せずにスキップする
+    pub max_file_size: Option<u64>,
+    /// 大文字・小文字を区別しないローカルファイルシステムでファイル名が衝突した場合の扱い
+    pub case_collision: CaseCollisionStrategy,
+    /// フォルダの転送方式
+    pub transfer_backend: TransferBackend,
+    /// 進捗通知の細かさ
+    pub progress_granularity: ProgressGranularity,
+    /// 前回の成功したバックアップ以降に更新されたファイルのみ転送する
+    ///
+    /// 基準時刻は呼び出し側が履歴から求めて `modified_since` に設定する
+    pub since_last_backup: bool,
+    /// この時刻（Unix秒）より前に更新されたファイルはスキップする
+    pub modified_since: Option<u64>,
+    /// サーバーの時計のずれ（サーバー − ローカル、秒）
+    ///
+    /// `modified_since` はローカルの時刻のため、サーバーのファイルの更新日時と比べる前にこの分ずらす
+    pub clock_skew_secs: i64,
+    /// 最終更新からこの日数が経っていないファイルはスキップする
+    pub min_age_days: Option<u64>,
+    /// 最終更新からこの日数より長く経っているファイルはスキップする
+    pub max_age_days: Option<u64>,
+    /// 隠しファイルでも転送する名前（`.htaccess` などの完全一致、または `*.ini` 形式の拡張子）
+    pub always_include: Vec<String>,
+    /// 転送しないファイル・ディレクトリのパターン（`.gitignore` と同様の書式）
+    ///
+    /// バックアップ元の直下に `.kyoshoignore` があれば、その内容も合わせて適用する
+    pub exclude_patterns: Vec<String>,
+    /// `exclude_patterns` と `.kyoshoignore` から作成した除外パターン（バックアップの開始時に設定する）
+    #[serde(skip)]
+    pub ignore_rules: IgnoreRules,
+    /// リモートに存在しないローカルのファイル・ディレクトリを転送後に削除する（ミラー）
+    ///
+    /// 隠しファイルは転送対象外のため削除しない
+    pub mirror_delete: bool,
+    /// 総ファイル数・総バイト数の集計を転送前に済ませず、転送と並行して行う（`precount` 有効時）
+    ///
+    /// 深いツリーでも転送をすぐ開始できる。集計が終わる前に転送を始めるため、
+    /// 空き容量の事前チェックと総量に基づく全体タイムアウトの見積もりは行わない
+    pub concurrent_precount: bool,
+    /// 保存先のインデックス（`.kyosho-index.json`）に前回転送したファイルのサイズ・更新日時を記録し、
+    /// 一致するファイルは転送しない（SFTPでのフォルダのバックアップのみ）
+    pub use_index: bool,
+    /// インデックスにSHA-256も記録し、サイズ・更新日時が同じでも内容が変わったファイルを検出する
+    ///
+    /// 一致の確認ごとにサーバーで sha256sum を実行するため、その分遅くなる
+    pub index_hashes: bool,
+    /// 保存先に同名のファイルが既にある場合の扱い（SFTPでの転送のみ。既定以外では rsync を使わない）
+    ///
+    /// 前回のバックアップ以降の更新なし・インデックスと一致でスキップするファイルには適用しない
+    pub overwrite_policy: OverwritePolicy,
+    /// 転送したファイルを AES-256-GCM で暗号化し、`.enc` を付けた名前で保存する（SFTPでの転送のみ）
+    ///
+    /// 鍵はパスフレーズから Argon2id で導出し、ソルトとファイルごとのNonceは保存先の
+    /// `.kyosho-encryption.json` に記録する。暗号化する場合は再開（`resume`）と参照バックアップ
+    /// （`link_dest`）を使わない
+    pub encrypt: bool,
+    /// 暗号化のパスフレーズ（履歴に残さないよう保存しない。呼び出し側で設定する）
+    #[serde(skip)]
+    pub encryption_passphrase: Option<Passphrase>,
+    /// ファイルサイズから求めた1ファイルあたりのタイムアウトに掛ける倍率（特に遅いファイルがある場合に大きくする）
+    pub timeout_multiplier: f64,
+    /// サーバーでSFTPが使えない場合に、`tar cf -` をSSHで実行してフォルダを転送する
+    ///
+    /// 進捗はバイト数のみで、一時停止・インデックスは使わない（隠しファイルの除外・always_include・
+    /// 除外パターンは適用する）。暗号化・サイズや更新日時での絞り込み・ミラー削除・上書きの扱いを
+    /// 指定している場合は、指定と異なるバックアップにならないようエラーにする
+    pub allow_tar_fallback: bool,
+    /// 保存先として許可するフォルダ（空の場合は制限しない）
+    ///
+    /// フロントエンドから指定させないよう、呼び出し側がアプリの設定から設定する
+    #[serde(skip)]
+    pub allowed_backup_roots: Vec<PathBuf>,
+    /// チェックポイントから再開する場合の、前回最後に処理を終えたリモートのディレクトリ
+    ///
+    /// 名前順でこれより前のエントリは処理済みとして飛ばす。飛ばしたファイルはリモートにあるか
+    /// 確認しないため、再開したバックアップではミラー削除をしない。呼び出し側でチェックポイントから設定する
+    #[serde(skip)]
+    pub resume_after_dir: Option<PathBuf>,
+    /// 実行したプロファイル名（チェックポイントに記録し、再開時に同じプロファイルの鍵・フックなどを使う）
+    ///
+    /// 呼び出し側がプロファイルを指定して実行した場合に設定する
+    #[serde(skip)]
+    pub profile_name: Option<String>,
+    /// 転送したファイルのSHA-256をリモートの sha256sum の結果と照合し、不一致を完了時に報告する
+    ///
+    /// ローカルのハッシュ計算は別スレッドで行い、次のファイルの転送と並行させる（SFTPでの転送のみ。暗号化時は行わない）
+    pub verify_checksums: bool,
+    /// 同時に実行するハッシュ計算数の上限（0の場合はCPU数に応じて決める）
+    pub max_hash_jobs: usize,
+    /// 数十万件のエントリがあるフォルダでもメモリ使用量が増えないよう、一覧を保持せず読み取りながら転送する
+    ///
+    /// エントリは名前順に並ばないためチェックポイントは書き出さない。処理を終えたファイルも記録しないため、
+    /// 再接続した場合は途中だったフォルダのファイルを判定し直す（SFTPでのフォルダ転送のみ）。
+    ///
+    /// 次の記録はこのモードでもエントリ数に応じて増える: 大文字・小文字を区別しないファイルシステムでの
+    /// 名前の衝突検出（ディレクトリごとのファイル名）と、`mirror_delete` で残すローカルのパス（全件）
+    pub low_memory: bool,
+    /// ファイルの転送に失敗しても、そのファイルを失敗として記録してバックアップを続ける
+    ///
+    /// セッションが切れた場合は従来どおり再接続する。失敗したファイルがあれば完了時に件数と一覧を報告する
+    pub continue_on_error: bool,
+}
+
+/// 保存先に同名のファイルが既にある場合の扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OverwritePolicy {
+    /// 上書きする（従来の動作）
+    #[default]
+    Overwrite,
+    /// 既存のファイルを残し、転送しない
+    Skip,
+    /// 既存のファイルを残し、`name (1).ext` のような連番の名前で保存する
+    ///
+    /// 既存のファイル（連番のものを含む）と内容が同じ場合は保存しない
+    KeepBoth,
+}
+
+/// フォルダの転送方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferBackend {
+    /// SFTPで1ファイルずつ転送（従来方式）
+    #[default]
+    Sftp,
+    /// ローカルの rsync を ssh 経由で実行（差分転送）。rsync がない場合や踏み台を経由する場合、
+    /// パスワード認証の場合はSFTPで転送する
+    Rsync,
+}
+
+/// 大文字・小文字のみ異なるファイル名が衝突した場合の扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaseCollisionStrategy {
+    /// 後から来たファイルに `~1` などの接尾辞を付けて保存する
+    #[default]
+    Rename,
+    /// バックアップをエラーで中止する
+    Error,
+}
+
+impl Default for BackupOptions {
+    fn default() -> Self {
+        Self {
+            precount: true,
+            check_disk_space: true,
+            pipelined_transfer: true,
+            min_throughput_mbps: 1.0,
+            link_dest: None,
+            resume: false,
+            max_file_size: None,
+            case_collision: CaseCollisionStrategy::default(),
+            transfer_backend: TransferBackend::default(),
+            progress_granularity: ProgressGranularity::default(),
+            since_last_backup: false,
+            modified_since: None,
+            min_age_days: None,
+            max_age_days: None,
+            clock_skew_secs: 0,
+            always_include: Vec::new(),
+            exclude_patterns: Vec::new(),
+            ignore_rules: IgnoreRules::default(),
+            mirror_delete: false,
+            concurrent_precount: false,
+            use_index: false,
+            index_hashes: false,
+            overwrite_policy: OverwritePolicy::default(),
+            encrypt: false,
+            encryption_passphrase: None,
+            timeout_multiplier: 1.0,
+            allow_tar_fallback: false,
+            allowed_backup_roots: Vec::new(),
+            resume_after_dir: None,
+            profile_name: None,
+            verify_checksums: false,
+            max_hash_jobs: 0,
+            low_memory: false,
+            continue_on_error: false,
+        }
+    }
+}
+
+impl BackupOptions {
+    /// 1ファイルあたりのタイムアウトの倍率（0以下や不正な値は1倍、上限は100倍）
+    pub(super) fn file_timeout_multiplier(&self) -> f64 {
+        if self.timeout_multiplier.is_finite() && self.timeout_multiplier > 0.0 {
+            self.timeout_multiplier.min(100.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// サイズ上限を超えるためスキップすべきファイルか
+    pub(super) fn exceeds_max_file_size(&self, file_size: u64) -> bool {
+        self.max_file_size.is_some_and(|max| file_size > max)
+    }
+
+    /// 隠しファイル/ディレクトリとしてスキップすべきか（always_include に一致するものは除く）
+    pub(super) fn skips_hidden(&self, name: &str) -> bool {
+        if !name.starts_with('.') {
+            return false;
+        }
+
+        let included = self.always_include.iter().any(|pattern| match pattern.strip_prefix('*') {
+            Some(extension) => name.ends_with(extension),
+            None => name == pattern,
+        });
+        !included
+    }
+
+    /// 除外パターンに一致するためスキップすべきリモートのファイル・ディレクトリか
+    pub(super) fn is_ignored(&self, remote_path: &Path, is_dir: bool) -> bool {
+        self.ignore_rules.is_ignored(remote_path, is_dir)
+    }
+
+    /// 基準時刻より前に更新されたためスキップすべきファイルか（更新日時が不明なファイルは転送する）
+    pub(super) fn is_unmodified_since(&self, remote_mtime: Option<u64>) -> bool {
+        // 基準時刻をサーバーの時計に換算して比べる
+        self.modified_since.is_some_and(|since| {
+            let since = (since as i64).saturating_add(self.clock_skew_secs);
+            remote_mtime.is_some_and(|mtime| (mtime as i64) < since)
+        })
+    }
+
+    /// 更新からの経過日数が `min_age_days`〜`max_age_days` の範囲外のためスキップすべきファイルか
+    ///
+    /// 経過日数はサーバーの時計に換算した現在時刻から求める（更新日時が不明なファイルは転送する）
+    pub(super) fn is_outside_age_window(&self, remote_mtime: Option<u64>) -> bool {
+        if self.min_age_days.is_none() && self.max_age_days.is_none() {
+            return false;
+        }
+        let Some(mtime) = remote_mtime else {
+            return false;
+        };
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        let age_secs = now.saturating_add(self.clock_skew_secs).saturating_sub(mtime as i64);
+        let days_to_secs = |days: u64| (days.saturating_mul(86400)).min(i64::MAX as u64) as i64;
+
+        self.min_age_days.is_some_and(|days| age_secs < days_to_secs(days))
+            || self.max_age_days.is_some_and(|days| age_secs > days_to_secs(days))
+    }
+}
+
+impl SshClient {
+    /// 設定の除外パターンと除外設定ファイルの内容から除外パターンを作成し、オプションに設定して返す
+    pub(super) fn with_ignore_rules(remote_path: &str, options: &BackupOptions, ignore_file: Option<Vec<String>>) -> BackupOptions {
+        let mut options = options.clone();
+        options.ignore_rules = IgnoreRules::new(Path::new(remote_path), &options.exclude_patterns);
+        if let Some(patterns) = ignore_file {
+            options.ignore_rules.extend(patterns);
+        }
+        if !options.ignore_rules.is_empty() {
+            tracing::info!("除外パターン: {} 件", options.ignore_rules.len());
+        }
+        options
+    }
+
+    /// バックアップ元の除外設定ファイルを SFTP で読み取る（ない場合・読み取れない場合はNone）
+    pub(super) fn read_ignore_file_with_sftp(sftp: &ssh2::Sftp, remote_path: &str) -> Option<Vec<String>> {
+        let path = Path::new(remote_path).join(ignore_rules::IGNORE_FILE_NAME);
+        let file = sftp.open(&path).ok()?;
+
+        // 上限を超えたかどうかを判定するため1バイト多く読む
+        let mut buffer = Vec::new();
+        if let Err(e) = file.take(ignore_rules::IGNORE_FILE_MAX_BYTES + 1).read_to_end(&mut buffer) {
+            tracing::warn!("除外設定ファイルの読み取りに失敗したため使用しません: {:?}: {}", path, e);
+            return None;
+        }
+        Some(Self::parse_ignore_file_content(&path, buffer))
+    }
+
+    /// バックアップ元の除外設定ファイルを `head` で読み取る（SFTPが使えない場合）
+    pub(super) fn read_ignore_file_with_exec(session: &Session, remote_path: &str) -> Option<Vec<String>> {
+        let path = Path::new(remote_path).join(ignore_rules::IGNORE_FILE_NAME);
+        let command = format!(
+            "head -c {} {}",
+            ignore_rules::IGNORE_FILE_MAX_BYTES + 1,
+            Self::shell_quote(&path.to_string_lossy())
+        );
+        let output = Self::exec_command(session, &command).ok()?;
+        Some(Self::parse_ignore_file_content(&path, output.into_bytes()))
+    }
+
+    /// 読み取った除外設定ファイルからパターンを取り出す（上限を超えた部分は使わない）
+    fn parse_ignore_file_content(path: &Path, mut content: Vec<u8>) -> Vec<String> {
+        let truncated = content.len() as u64 > ignore_rules::IGNORE_FILE_MAX_BYTES;
+        if truncated {
+            tracing::warn!(
+                "除外設定ファイルが大きすぎるため先頭 {} バイトのみ使用します: {:?}",
+                ignore_rules::IGNORE_FILE_MAX_BYTES,
+                path
+            );
+            content.truncate(ignore_rules::IGNORE_FILE_MAX_BYTES as usize);
+        }
+
+        let patterns = ignore_rules::parse_ignore_file(&String::from_utf8_lossy(&content), truncated);
+        tracing::info!("除外設定ファイルを読み込みました: {:?}（{} 件）", path, patterns.len());
+        patterns
+    }
+}
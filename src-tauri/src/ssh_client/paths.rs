@@ -0,0 +1,98 @@
+use super::*;
+
+impl SshClient {
+    /// 保存先を Windows の拡張長パス（`\\?\` 付き）にする
+    ///
+    /// 基準のフォルダに付けておけば、その下に連結したパスにも付くため、MAX_PATH（260文字）を
+    /// 超える深い階層のファイルも作成できる。相対パスは絶対パスにしてから付ける。Windows 以外ではそのまま返す
+    pub fn extended_length_path(path: &Path) -> PathBuf {
+        #[cfg(windows)]
+        {
+            let Ok(absolute) = std::path::absolute(path) else {
+                return path.to_path_buf();
+            };
+            let Some(text) = absolute.to_str() else {
+                return absolute;
+            };
+            if text.starts_with(r"\\?\") || text.starts_with(r"\\.\") {
+                absolute
+            } else if let Some(unc) = text.strip_prefix(r"\\") {
+                PathBuf::from(format!(r"\\?\UNC\{}", unc))
+            } else {
+                PathBuf::from(format!(r"\\?\{}", text))
+            }
+        }
+        #[cfg(not(windows))]
+        {
+            path.to_path_buf()
+        }
+    }
+
+    /// 利用者に見せるパスの文字列（拡張長パスの `\\?\` を外す）
+    ///
+    /// `\\?\UNC\server\share` は `\\server\share` に戻す。付いていなければそのまま返す
+    pub fn display_path(path: &Path) -> String {
+        let text = path.to_string_lossy();
+        if let Some(unc) = text.strip_prefix(r"\\?\UNC\") {
+            format!(r"\\{}", unc)
+        } else if let Some(local) = text.strip_prefix(r"\\?\") {
+            local.to_string()
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// 保存先が許可されたフォルダの配下か確認する（許可するフォルダが空の場合は確認しない）
+    ///
+    /// まだ存在しない保存先は存在する親フォルダまでを正規化して判定するため、
+    /// シンボリックリンクで許可されたフォルダの外を指す場合も拒否する
+    pub fn check_allowed_backup_root(local_path: &Path, allowed_roots: &[PathBuf]) -> Result<()> {
+        if allowed_roots.is_empty() {
+            return Ok(());
+        }
+
+        let rejected = || BackupError::FileSystem(format!(
+            "保存先が許可されたフォルダの外です: {}", local_path.display()
+        ));
+
+        if local_path.components().any(|component| component == std::path::Component::ParentDir) {
+            return Err(rejected().into());
+        }
+
+        // 存在する親フォルダまで遡って正規化し、残りの名前をつなげ直す
+        let mut existing = local_path;
+        let mut remaining = Vec::new();
+        let resolved = loop {
+            if let Ok(canonical) = existing.canonicalize() {
+                break remaining.iter().rev().fold(canonical, |path: PathBuf, name| path.join(name));
+            }
+            let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+                return Err(rejected().into());
+            };
+            remaining.push(name);
+            existing = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+        };
+
+        let allowed = allowed_roots
+            .iter()
+            .filter_map(|root| root.canonicalize().ok())
+            .any(|root| resolved.starts_with(root));
+        if !allowed {
+            return Err(rejected().into());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_path_removes_the_extended_length_prefix() {
+        assert_eq!(SshClient::display_path(Path::new(r"\\?\C:\backup\site")), r"C:\backup\site");
+        assert_eq!(SshClient::display_path(Path::new(r"\\?\UNC\nas\share\site")), r"\\nas\share\site");
+        assert_eq!(SshClient::display_path(Path::new("/home/user/backup")), "/home/user/backup");
+    }
+}
@@ -0,0 +1,298 @@
+use super::*;
+
+// 進捗報告用の構造体
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BackupProgress {
+    pub phase: String,
+    pub transferred_files: usize,
+    pub total_files: Option<usize>,
+    pub transferred_bytes: u64,
+    pub total_bytes: Option<u64>,
+    pub current_file: Option<String>,
+    pub elapsed_seconds: u64,
+    /// 直近の転送速度（MB/s、通知ごとの速度の指数移動平均）
+    pub transfer_speed: Option<f64>,
+    /// 開始からの平均転送速度（MB/s、一時停止中の時間を除く）
+    pub average_speed: Option<f64>,
+    pub timeout_seconds: Option<u64>,
+    /// 参照バックアップへのハードリンクで済ませたファイル数
+    pub linked_files: usize,
+    /// フェーズ別の所要時間（バックアップ完了時のみ）
+    pub phase_timings: Option<PhaseTimings>,
+    /// 総バイト数に対する進捗率（0〜100、総量が不明な間はNone）
+    pub percent_complete: Option<f64>,
+    /// スキップした特殊ファイル（ソケット・FIFO・デバイス等）の数
+    pub skipped_special_files: usize,
+    /// オプション（サイズ上限・更新からの経過日数・前回以降の更新のみ）により転送対象から除外したファイルの数
+    pub excluded_files: usize,
+    /// 複数フォルダを続けてバックアップする場合のジョブ番号（1始まり）と総ジョブ数
+    pub job_index: Option<usize>,
+    pub job_count: Option<usize>,
+    /// 総ファイル数・総バイト数を転送と並行して集計中か（集計中は総数が増えていく）
+    pub counting: bool,
+    /// 保存先に既存のファイルがあった場合の処理結果の件数（バックアップ完了時のみ）
+    pub overwrite_counts: Option<OverwriteCounts>,
+    /// 読み取り中のリモートディレクトリで、ここまでに読み取ったエントリ数（ディレクトリ読み取り中のみ）
+    pub listed_entries: Option<usize>,
+    /// セッションが切れて再接続した回数
+    pub reconnect_attempts: usize,
+    /// ここまでに作成したローカルのディレクトリ数
+    pub created_directories: usize,
+    /// 権限がないなどで読み取れずスキップしたリモートのディレクトリ数
+    pub inaccessible_dirs: usize,
+    /// 転送に失敗してスキップしたファイル数（`continue_on_error` 有効時）
+    pub failed_files: usize,
+}
+
+/// 保存先に既存のファイルがあった場合の処理結果の件数
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OverwriteCounts {
+    /// 既存のファイルを上書きした数（Overwrite）
+    pub overwritten: usize,
+    /// 既存のファイルを残して転送しなかった数（Skip）
+    pub skipped_existing: usize,
+    /// 連番の名前で別に保存した数（KeepBoth）
+    pub kept_both: usize,
+    /// 既存のファイル（連番のものを含む）と内容が同じため保存しなかった数（KeepBoth）
+    pub identical: usize,
+}
+
+impl BackupProgress {
+    /// 転送バイト数と総バイト数から進捗率（0〜100）を計算
+    pub fn calculate_percent(transferred_bytes: u64, total_bytes: Option<u64>) -> Option<f64> {
+        let total_bytes = total_bytes?;
+        if total_bytes == 0 {
+            return Some(100.0);
+        }
+        Some((transferred_bytes as f64 / total_bytes as f64 * 100.0).clamp(0.0, 100.0))
+    }
+}
+
+/// バックアップのフェーズ別所要時間（秒）
+///
+/// ネットワーク律速かディスク律速かの切り分けに使う
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PhaseTimings {
+    pub connecting_seconds: f64,
+    pub scanning_seconds: f64,
+    pub transferring_seconds: f64,
+    /// 転送後の検証（検証を行わない場合は0）
+    pub verifying_seconds: f64,
+    /// バックアップ後のアーカイブ作成（main側で計測）
+    pub archiving_seconds: f64,
+}
+
+// 進捗更新の間隔制御
+pub struct ProgressThrottle {
+    last_update: Instant,
+    last_bytes: u64,
+    start_time: Instant,
+    update_interval: Duration,
+    pub(super) byte_threshold: u64,
+    paused_duration: Duration,
+    pause_started: Option<Instant>,
+    /// 直近の転送速度（バイト/秒の指数移動平均、最初の計測まではNone）
+    smoothed_speed: Option<f64>,
+    /// 前回速度を計測した時点の実行時間と転送バイト数
+    sample_elapsed: Duration,
+    sample_bytes: u64,
+}
+
+/// 直近の転送速度の平滑化の時定数（秒）
+///
+/// 大きいほど表示が安定し、小さいほど速度の変化にすばやく追従する
+const SPEED_SMOOTHING_SECS: f64 = 10.0;
+
+/// 進捗通知の間隔の既定値（3秒）
+pub const DEFAULT_PROGRESS_INTERVAL_MS: u64 = 3000;
+
+/// 進捗通知のバイト数閾値の既定値（50MB）
+pub const DEFAULT_PROGRESS_BYTE_THRESHOLD: u64 = 50 * 1024 * 1024;
+
+/// 進捗通知の細かさ（間隔と転送バイト数のどちらかを超えたら通知）
+///
+/// 小さくすると応答性が上がるが、`backup-progress` イベントの送信回数が増える
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProgressGranularity {
+    pub interval_ms: u64,
+    pub byte_threshold: u64,
+}
+
+impl Default for ProgressGranularity {
+    fn default() -> Self {
+        Self {
+            interval_ms: DEFAULT_PROGRESS_INTERVAL_MS,
+            byte_threshold: DEFAULT_PROGRESS_BYTE_THRESHOLD,
+        }
+    }
+}
+
+impl ProgressThrottle {
+    pub fn new(update_interval: Duration, byte_threshold: u64) -> Self {
+        Self {
+            last_update: Instant::now(),
+            last_bytes: 0,
+            start_time: Instant::now(),
+            update_interval,
+            byte_threshold,
+            paused_duration: Duration::ZERO,
+            pause_started: None,
+            smoothed_speed: None,
+            sample_elapsed: Duration::ZERO,
+            sample_bytes: 0,
+        }
+    }
+
+    /// 一時停止の開始を記録（一時停止中の時間は経過時間から除外する）
+    pub fn pause(&mut self) {
+        if self.pause_started.is_none() {
+            self.pause_started = Some(Instant::now());
+        }
+    }
+
+    /// 一時停止の終了を記録
+    pub fn resume(&mut self) {
+        if let Some(started) = self.pause_started.take() {
+            self.paused_duration += started.elapsed();
+        }
+    }
+
+    /// 一時停止時間を除いた実行時間
+    fn active_elapsed(&self) -> Duration {
+        let current_pause = self.pause_started.map(|started| started.elapsed()).unwrap_or_default();
+        self.start_time
+            .elapsed()
+            .saturating_sub(self.paused_duration + current_pause)
+    }
+
+    /// 進捗を通知する時期か判定し、通知する場合はその時点の転送速度を計測する
+    pub fn should_update(&mut self, transferred_bytes: u64) -> bool {
+        if !self.should_update_without_speed_sample(transferred_bytes) {
+            return false;
+        }
+
+        self.record_speed_sample(transferred_bytes);
+        true
+    }
+
+    /// 転送速度を計測せずに、進捗を通知する時期かだけ判定する
+    ///
+    /// ミラー削除など、転送以外の処理量で通知する場合に使う
+    pub fn should_update_without_speed_sample(&mut self, transferred_bytes: u64) -> bool {
+        let now = Instant::now();
+        let time_elapsed = now.duration_since(self.last_update) >= self.update_interval;
+        let bytes_elapsed = transferred_bytes.saturating_sub(self.last_bytes) >= self.byte_threshold;
+
+        if time_elapsed || bytes_elapsed {
+            self.last_update = now;
+            self.last_bytes = transferred_bytes;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn get_elapsed_seconds(&self) -> u64 {
+        self.active_elapsed().as_secs()
+    }
+
+    /// 直近の転送速度（MB/s）
+    ///
+    /// 速度を計測する前（最初の通知まで）は開始からの平均転送速度を返す
+    pub fn calculate_speed(&self, total_bytes: u64) -> Option<f64> {
+        match self.smoothed_speed {
+            Some(speed) => Some(speed / (1024.0 * 1024.0)), // MB/s
+            None => self.calculate_average_speed(total_bytes),
+        }
+    }
+
+    /// 開始からの平均転送速度（MB/s）
+    pub fn calculate_average_speed(&self, total_bytes: u64) -> Option<f64> {
+        let elapsed = self.active_elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            Some((total_bytes as f64) / elapsed / (1024.0 * 1024.0)) // MB/s
+        } else {
+            None
+        }
+    }
+
+    /// 前回の計測からの転送速度を求め、指数移動平均に反映する
+    fn record_speed_sample(&mut self, transferred_bytes: u64) {
+        let elapsed = self.active_elapsed();
+        let interval = elapsed.saturating_sub(self.sample_elapsed).as_secs_f64();
+        // 時間がほとんど経っていない場合は次の計測にまとめる
+        if interval <= 0.0 {
+            return;
+        }
+
+        let bytes = transferred_bytes.saturating_sub(self.sample_bytes);
+        let sample = bytes as f64 / interval;
+        self.smoothed_speed = Some(Self::smooth_speed(self.smoothed_speed, sample, interval));
+        self.sample_elapsed = elapsed;
+        self.sample_bytes = transferred_bytes;
+    }
+
+    /// 指数移動平均に速度のサンプルを反映する
+    ///
+    /// 通知の間隔は一定ではないため、重みはサンプルの計測時間から求める
+    /// （時定数と同じ時間の計測なら、前回までの値の影響は約37%に減る）。最初のサンプルはそのまま使う
+    fn smooth_speed(previous: Option<f64>, sample: f64, interval_secs: f64) -> f64 {
+        match previous {
+            Some(previous) => {
+                let weight = 1.0 - (-interval_secs / SPEED_SMOOTHING_SECS).exp();
+                previous + weight * (sample - previous)
+            }
+            None => sample,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const EPSILON: f64 = 1e-9;
+
+    #[test]
+    fn smooth_speed_uses_first_sample_as_is() {
+        assert!((ProgressThrottle::smooth_speed(None, 1000.0, 3.0) - 1000.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn smooth_speed_moves_toward_sample_by_time_weight() {
+        // 時定数と同じ時間の計測では、差の (1 - e^-1) だけ近づく
+        let smoothed = ProgressThrottle::smooth_speed(Some(100.0), 200.0, SPEED_SMOOTHING_SECS);
+        let expected = 100.0 + (1.0 - (-1.0f64).exp()) * 100.0;
+        assert!((smoothed - expected).abs() < EPSILON);
+    }
+
+    #[test]
+    fn smooth_speed_is_independent_of_sampling_interval() {
+        // 同じ速度が続く場合、3秒ごとに2回反映しても6秒で1回反映しても同じ値になる
+        let twice = ProgressThrottle::smooth_speed(
+            Some(ProgressThrottle::smooth_speed(Some(0.0), 500.0, 3.0)),
+            500.0,
+            3.0,
+        );
+        let once = ProgressThrottle::smooth_speed(Some(0.0), 500.0, 6.0);
+        assert!((twice - once).abs() < EPSILON);
+    }
+
+    #[test]
+    fn smooth_speed_follows_slowdown_after_burst() {
+        // 高速な転送の後に遅くなった場合、累積平均よりも早く遅い速度に近づく
+        let mut smoothed = ProgressThrottle::smooth_speed(None, 100.0 * 1024.0 * 1024.0, 3.0);
+        for _ in 0..20 {
+            smoothed = ProgressThrottle::smooth_speed(Some(smoothed), 1024.0 * 1024.0, 3.0);
+        }
+        let cumulative = (100.0 + 20.0) * 1024.0 * 1024.0 / 21.0;
+        assert!(smoothed < cumulative / 2.0);
+        assert!(smoothed > 1024.0 * 1024.0);
+    }
+
+    #[test]
+    fn smooth_speed_keeps_steady_speed() {
+        let smoothed = ProgressThrottle::smooth_speed(Some(42.0), 42.0, 1.5);
+        assert!((smoothed - 42.0).abs() < EPSILON);
+    }
+}
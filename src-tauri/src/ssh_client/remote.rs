@@ -0,0 +1,409 @@
+use super::*;
+
+/// mysqldump の標準エラー出力を履歴・エラーに含める上限（行数）
+const MYSQL_DUMP_MAX_STDERR_LINES: usize = 50;
+
+impl SshClient {
+    /// リモートのパスが存在し、ディレクトリであることを確認する
+    pub async fn check_remote_directory(&mut self, path: &str) -> Result<()> {
+        if self.session.is_none() {
+            self.test_connection().await?;
+        }
+
+        let session = self.session.as_ref()
+            .context("SSHセッションが確立されていません")?;
+
+        let sftp = session.sftp()
+            .context("SFTPセッションの作成に失敗しました")?;
+
+        let stat = sftp.stat(Path::new(path))
+            .with_context(|| format!("リモートフォルダが見つかりません: {}", path))?;
+
+        if !stat.is_dir() {
+            return Err(anyhow::anyhow!("リモートパスがディレクトリではありません: {}", path));
+        }
+
+        Ok(())
+    }
+
+    /// リモートのパスがあるファイルシステムの空き容量（バイト）を取得する
+    ///
+    /// `df -kP` の空き容量と、ディスククォータが設定されていれば `quota` の残り容量のうち小さい方を返す。
+    /// 出力形式はサーバーによって異なるため、解釈できない場合は取得できた方のみを使う
+    pub async fn get_remote_free_space(&mut self, path: &str) -> Result<u64> {
+        let free_space_future = async {
+            if self.session.is_none() {
+                self.test_connection().await?;
+            }
+
+            let session = self.session.as_ref()
+                .context("SSHセッションが確立されていません")?;
+
+            let df_output = Self::exec_command(session, &format!("df -kP {}", Self::shell_quote(path)))?;
+            let df_available = Self::parse_df_available(&df_output);
+
+            // quota コマンドがない・クォータ未設定のサーバーもあるため失敗は無視する
+            let quota_available = match Self::exec_command(session, "quota -w 2>/dev/null") {
+                Ok(output) => Self::parse_quota_available(&output),
+                Err(e) => {
+                    tracing::info!("quota を取得できませんでした: {:#}", e);
+                    None
+                }
+            };
+
+            match (df_available, quota_available) {
+                (Some(df), Some(quota)) => Ok(df.min(quota)),
+                (Some(available), None) | (None, Some(available)) => Ok(available),
+                (None, None) => Err(anyhow::anyhow!(
+                    "リモートの空き容量を解釈できませんでした:\n{}",
+                    df_output.trim()
+                )),
+            }
+        };
+
+        timeout(Duration::from_secs(30), free_space_future)
+            .await
+            .context("リモートの空き容量の取得がタイムアウトしました")?
+    }
+
+    /// 接続を診断し、サーバーの対応状況を返す
+    ///
+    /// 合意したアルゴリズム・認証方法・バナー・ホームディレクトリと、オプション機能に使う
+    /// コマンド（rsync・sha256sum・du）の有無をまとめる。コマンドの有無はサーバーで確認する
+    pub async fn diagnose_connection(&mut self) -> Result<ConnectionDiagnostics> {
+        if self.session.is_none() {
+            self.test_connection().await?;
+        }
+
+        let session = self.session.as_ref()
+            .context("SSHセッションが確立されていません")?;
+
+        let method = |method_type: MethodType| session.methods(method_type).map(str::to_string);
+        let algorithms = NegotiatedAlgorithms {
+            kex: method(MethodType::Kex),
+            host_key: method(MethodType::HostKey),
+            cipher_client_to_server: method(MethodType::CryptCs),
+            cipher_server_to_client: method(MethodType::CryptSc),
+            mac_client_to_server: method(MethodType::MacCs),
+            mac_server_to_client: method(MethodType::MacSc),
+        };
+
+        let home_directory = session
+            .sftp()
+            .and_then(|sftp| sftp.realpath(Path::new(".")))
+            .map(|path| path.to_string_lossy().to_string())
+            .map_err(|e| tracing::warn!("ホームディレクトリの取得に失敗: {}", e))
+            .ok();
+
+        Self::check_cancelled(self.cancel_flag.as_deref())?;
+        let rsync_available = Self::remote_command_exists(session, "rsync");
+        let remote_rsync_supported = rsync_available && Self::remote_rsync_supported(session);
+        let sha256sum_available = Self::remote_command_exists(session, "sha256sum");
+        let du_available = Self::remote_command_exists(session, "du");
+
+        let rsync_backend_usable = remote_rsync_supported
+            && self.config.jump_host.is_none()
+            && self.rsync_auth_supported()
+            && Self::local_rsync_supported();
+
+        Ok(ConnectionDiagnostics {
+            banner: session.banner().map(str::to_string),
+            algorithms,
+            auth_methods: self.server_auth_methods
+                .as_deref()
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|method| !method.is_empty())
+                .map(str::to_string)
+                .collect(),
+            authenticated_key_path: self.authenticated_key_path.clone(),
+            home_directory,
+            rsync_available,
+            sha256sum_available,
+            du_available,
+            rsync_backend_usable,
+        })
+    }
+
+    /// サーバーにコマンドがあるか（`command -v` で確認）
+    pub(super) fn remote_command_exists(session: &Session, name: &str) -> bool {
+        Self::exec_command(session, &format!("command -v {}", Self::shell_quote(name)))
+            .is_ok_and(|output| !output.trim().is_empty())
+    }
+
+    /// リモートでコマンドを実行し、標準出力を返す
+    pub(super) fn exec_command(session: &Session, command: &str) -> Result<String> {
+        let mut channel = session.channel_session()
+            .context("チャンネルの作成に失敗しました")?;
+
+        channel.exec(command)
+            .with_context(|| format!("コマンドの実行に失敗しました: {}", command))?;
+
+        let mut output = String::new();
+        channel.read_to_string(&mut output)
+            .with_context(|| format!("コマンド出力の読み取りに失敗しました: {}", command))?;
+
+        channel.wait_close()
+            .context("チャンネルのクローズに失敗しました")?;
+
+        let exit_status = channel.exit_status()
+            .context("終了ステータスの取得に失敗しました")?;
+        if exit_status != 0 {
+            return Err(anyhow::anyhow!("コマンドが終了ステータス {} で失敗しました: {}", exit_status, command));
+        }
+
+        Ok(output)
+    }
+
+    /// シェルに渡す引数をシングルクォートで囲む
+    pub(super) fn shell_quote(value: &str) -> String {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+
+    /// `df -kP` の出力から空き容量（バイト）を取り出す
+    ///
+    /// ファイルシステム名が長いと1エントリが複数行に折り返されることがあるため、
+    /// ヘッダー以降の項目をまとめてから末尾側の列で解釈する
+    fn parse_df_available(output: &str) -> Option<u64> {
+        let fields: Vec<&str> = output
+            .lines()
+            .skip_while(|line| !line.trim_start().starts_with("Filesystem"))
+            .skip(1)
+            .flat_map(|line| line.split_whitespace())
+            .collect();
+
+        // 末尾から: マウント先, 使用率, 空き, 使用, 合計
+        if fields.len() < 5 {
+            return None;
+        }
+        let available_kb: u64 = fields[fields.len() - 3].parse().ok()?;
+        Some(available_kb * 1024)
+    }
+
+    /// `quota -w` の出力から残り容量（バイト）を取り出す
+    ///
+    /// ハードリミット（なければソフトリミット）から使用量を引いた値。クォータがなければNone
+    fn parse_quota_available(output: &str) -> Option<u64> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                // ファイルシステム, 使用量, ソフトリミット, ハードリミット, ...
+                if fields.len() < 4 {
+                    return None;
+                }
+                // 超過時は使用量に '*' が付く
+                let used: u64 = fields[1].trim_end_matches('*').parse().ok()?;
+                let soft: u64 = fields[2].parse().ok()?;
+                let hard: u64 = fields[3].parse().ok()?;
+                let limit = if hard > 0 { hard } else { soft };
+                // 0 は無制限
+                (limit > 0).then(|| limit.saturating_sub(used) * 1024)
+            })
+            .min()
+    }
+
+    /// リモートのテキストファイルを先頭から最大 `max_bytes` バイトだけ読み取る
+    ///
+    /// UTF-8 として不正なバイトは置換文字にする。ディレクトリの場合はエラー
+    pub async fn read_remote_file(&mut self, remote_path: &str, max_bytes: u64) -> Result<String> {
+        if self.session.is_none() {
+            self.test_connection().await?;
+        }
+
+        let session = self.session.as_ref()
+            .context("SSHセッションが確立されていません")?;
+
+        let sftp = session.sftp()
+            .context("SFTPセッションの作成に失敗しました")?;
+
+        let path = Path::new(remote_path);
+        let stat = sftp.stat(path)
+            .with_context(|| format!("リモートファイルが見つかりません: {}", remote_path))?;
+        if stat.is_dir() {
+            return Err(anyhow::anyhow!("ディレクトリは読み取れません: {}", remote_path));
+        }
+        if !stat.is_file() {
+            return Err(anyhow::anyhow!("通常のファイルではありません: {}", remote_path));
+        }
+
+        let file = sftp.open(path)
+            .with_context(|| format!("リモートファイルのオープンに失敗: {}", remote_path))?;
+
+        // 上限を超えて読み取らないよう take で打ち切る
+        let mut buffer = Vec::new();
+        file.take(max_bytes)
+            .read_to_end(&mut buffer)
+            .with_context(|| format!("リモートファイルの読み取りに失敗: {}", remote_path))?;
+
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+
+    /// サーバーで `date +%s` を実行し、ローカルの時計とのずれを求める
+    ///
+    /// ローカルの時刻は問い合わせの往復の中間時点とみなす
+    pub async fn get_server_time(&mut self) -> Result<ServerTime> {
+        if self.session.is_none() {
+            self.test_connection().await?;
+        }
+
+        let session = self.session.as_ref()
+            .context("SSHセッションが確立されていません")?;
+
+        let unix_millis = || {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64
+        };
+        let sent_at = unix_millis();
+        let output = Self::exec_command(session, "date +%s")?;
+        let received_at = unix_millis();
+
+        let server_time: u64 = output
+            .trim()
+            .parse()
+            .with_context(|| format!("サーバーの時刻を解釈できませんでした: {}", output.trim()))?;
+        let local_millis = sent_at + (received_at.saturating_sub(sent_at)) / 2;
+        let skew_seconds = ((server_time as i64 * 1000 - local_millis as i64) as f64 / 1000.0).round() as i64;
+        let large_skew = skew_seconds.abs() > CLOCK_SKEW_WARNING_SECS;
+
+        if large_skew {
+            tracing::warn!("サーバーの時計が {} 秒ずれています: {}", skew_seconds, self.config.hostname);
+        } else {
+            tracing::info!("サーバーの時計のずれ: {} 秒: {}", skew_seconds, self.config.hostname);
+        }
+
+        Ok(ServerTime {
+            server_time,
+            local_time: local_millis / 1000,
+            skew_seconds,
+            round_trip_ms: received_at.saturating_sub(sent_at),
+            large_skew,
+        })
+    }
+
+    /// サーバーで mysqldump を実行し、ダンプをリモートのファイルに書き出す
+    ///
+    /// 続けてフォルダをバックアップすればダンプも含まれる。パスワードはコマンドラインに
+    /// 載せず標準入力から `MYSQL_PWD` に渡すため、プロセス一覧やログには残らない。
+    /// 書き込み途中のダンプで既存のファイルを壊さないよう `.tmp` に書いてから置き換える
+    pub async fn dump_remote_mysql(
+        &mut self,
+        db_name: &str,
+        db_user: &str,
+        db_pass: &str,
+        remote_dump_path: &str,
+        time_limit: Duration,
+    ) -> Result<MysqlDumpResult> {
+        if db_pass.contains('\n') {
+            return Err(anyhow::anyhow!("パスワードに改行は使用できません"));
+        }
+
+        if self.session.is_none() {
+            self.test_connection().await?;
+        }
+
+        let session = self.session.as_ref()
+            .context("SSHセッションが確立されていません")?;
+
+        let started = Instant::now();
+        let temp_path = format!("{}.tmp", remote_dump_path);
+        let command = format!(
+            "umask 077 && IFS= read -r MYSQL_PWD && export MYSQL_PWD && \
+             mysqldump --single-transaction --quick --no-tablespaces --user={} -- {} > {} && mv -f {} {}",
+            Self::shell_quote(db_user),
+            Self::shell_quote(db_name),
+            Self::shell_quote(&temp_path),
+            Self::shell_quote(&temp_path),
+            Self::shell_quote(remote_dump_path),
+        );
+
+        tracing::info!("データベースのダンプを開始: {} -> {}", db_name, remote_dump_path);
+
+        // 応答しないサーバーで待ち続けないよう、ダンプの間だけタイムアウトを設定する
+        let previous_timeout = session.timeout();
+        session.set_timeout(time_limit.as_millis().min(u32::MAX as u128) as u32);
+        let result = Self::run_mysql_dump(session, &command, db_pass);
+        session.set_timeout(previous_timeout);
+
+        let (exit_status, stderr_lines) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                // タイムアウトした場合は書き込み途中のファイルを残さない
+                let _ = Self::exec_command(session, &format!("rm -f {}", Self::shell_quote(&temp_path)));
+                if started.elapsed() >= time_limit {
+                    return Err(anyhow::anyhow!(
+                        "データベースのダンプが {} 秒以内に終わりませんでした",
+                        time_limit.as_secs()
+                    ));
+                }
+                return Err(e);
+            }
+        };
+
+        if exit_status != 0 {
+            let _ = Self::exec_command(session, &format!("rm -f {}", Self::shell_quote(&temp_path)));
+            return Err(anyhow::anyhow!(
+                "mysqldump が終了ステータス {} で失敗しました\n{}",
+                exit_status,
+                stderr_lines.join("\n")
+            ));
+        }
+
+        let size_bytes = session.sftp()
+            .context("SFTPセッションの作成に失敗しました")?
+            .stat(Path::new(remote_dump_path))
+            .with_context(|| format!("ダンプファイルの情報の取得に失敗: {}", remote_dump_path))?
+            .size
+            .unwrap_or(0);
+
+        tracing::info!("データベースのダンプが完了: {} ({} バイト)", remote_dump_path, size_bytes);
+
+        Ok(MysqlDumpResult {
+            remote_path: remote_dump_path.to_string(),
+            size_bytes,
+            elapsed_seconds: started.elapsed().as_secs(),
+            warnings: stderr_lines,
+        })
+    }
+
+    /// ダンプのコマンドを実行し、終了ステータスと標準エラー出力（先頭のみ）を返す
+    ///
+    /// 標準エラー出力は届いた行から順にログに出す（パスワードは含まれない）
+    fn run_mysql_dump(session: &Session, command: &str, db_pass: &str) -> Result<(i32, Vec<String>)> {
+        let mut channel = session.channel_session()
+            .context("チャンネルの作成に失敗しました")?;
+
+        channel.exec(command)
+            .context("mysqldump の実行に失敗しました")?;
+
+        channel.write_all(format!("{}\n", db_pass).as_bytes())
+            .context("パスワードの送信に失敗しました")?;
+        channel.send_eof()
+            .context("パスワードの送信に失敗しました")?;
+
+        let mut stderr_lines = Vec::new();
+        for line in std::io::BufReader::new(channel.stderr()).lines() {
+            let line = line.context("mysqldump の出力の読み取りに失敗しました")?;
+            tracing::warn!("mysqldump: {}", line);
+            if stderr_lines.len() < MYSQL_DUMP_MAX_STDERR_LINES {
+                stderr_lines.push(line);
+            }
+        }
+
+        // 標準出力はファイルにリダイレクトしているため通常は空
+        let mut stdout = String::new();
+        channel.read_to_string(&mut stdout)
+            .context("mysqldump の出力の読み取りに失敗しました")?;
+
+        channel.wait_close()
+            .context("チャンネルのクローズに失敗しました")?;
+
+        let exit_status = channel.exit_status()
+            .context("終了ステータスの取得に失敗しました")?;
+
+        Ok((exit_status, stderr_lines))
+    }
+}
@@ -0,0 +1,296 @@
+use super::*;
+
+/// rsync の出力を待つ間にキャンセルを確認する間隔
+const RSYNC_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// rsync での転送に必要なバージョン（`--info=progress2` は 3.1 から）
+const RSYNC_MIN_VERSION: (u32, u32) = (3, 1);
+
+impl SshClient {
+    /// rsync（ssh コマンド）で使える認証方法か（パスワード認証は ssh コマンドに渡せない）
+    pub(super) fn rsync_auth_supported(&self) -> bool {
+        matches!(self.config.auth_method, SshAuthMethod::Key | SshAuthMethod::StoredKey(_))
+    }
+
+    /// ローカルに `--info=progress2` に対応した rsync（3.1以降）がインストールされているか
+    pub(super) fn local_rsync_supported() -> bool {
+        std::process::Command::new("rsync")
+            .arg("--version")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| Self::parse_rsync_version(&String::from_utf8_lossy(&output.stdout)))
+            .is_some_and(|version| version >= RSYNC_MIN_VERSION)
+    }
+
+    /// サーバーの rsync が 3.1 以降か
+    pub(super) fn remote_rsync_supported(session: &Session) -> bool {
+        Self::exec_command(session, "rsync --version 2>/dev/null")
+            .ok()
+            .and_then(|output| Self::parse_rsync_version(&output))
+            .is_some_and(|version| version >= RSYNC_MIN_VERSION)
+    }
+
+    /// `rsync --version` の1行目（例: "rsync  version 3.2.7  protocol version 31"）から (メジャー, マイナー) を取り出す
+    fn parse_rsync_version(output: &str) -> Option<(u32, u32)> {
+        let version = output
+            .lines()
+            .next()?
+            .split_whitespace()
+            .skip_while(|word| *word != "version")
+            .nth(1)?;
+        let mut parts = version.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts
+            .next()
+            .map(|minor| minor.chars().take_while(char::is_ascii_digit).collect::<String>())
+            .and_then(|minor| minor.parse().ok())
+            .unwrap_or(0);
+        Some((major, minor))
+    }
+
+    /// ローカルとサーバーの rsync が使えるか確認（使えない場合はSFTPにフォールバック）
+    pub(super) fn rsync_available(session: &Session) -> bool {
+        if !Self::local_rsync_supported() {
+            tracing::warn!("rsync 3.1 以降が見つからないためSFTPで転送します");
+            return false;
+        }
+        if !Self::remote_rsync_supported(session) {
+            tracing::warn!("サーバーに rsync 3.1 以降がないためSFTPで転送します");
+            return false;
+        }
+        true
+    }
+
+    /// ローカルの rsync を ssh 経由で実行し、リモートフォルダをミラーする
+    ///
+    /// `--info=progress2` の出力を進捗イベントに変換する。キャンセル時は子プロセスを終了する。
+    /// SFTP転送と同様に隠しファイルは対象外（always_include に一致するものは転送）とし、一時停止には対応しない。
+    /// ファイルサイズの上限は `--max-size`、前回のバックアップは `--link-dest` で渡す
+    pub(super) fn backup_directory_with_rsync<F>(
+        &self,
+        remote_path: &str,
+        local_path: &str,
+        options: &BackupOptions,
+        control: &BackupControl,
+        state: &mut TransferState,
+        progress_callback: &F,
+    ) -> Result<()>
+    where
+        F: Fn(BackupProgress),
+    {
+        // 設定に保存した鍵は ssh コマンドに渡せるよう、rsync が終わるまで一時ファイルに書き出しておく
+        let temporary_key = match &self.config.auth_method {
+            SshAuthMethod::StoredKey(key) => Some(ssh_key::TemporaryKeyFile::write(key)?),
+            _ => None,
+        };
+        let key_path = match &temporary_key {
+            Some(temporary_key) => temporary_key.path().to_string_lossy().to_string(),
+            None => self.authenticated_key_path.clone().unwrap_or_else(|| self.config.key_path.clone()),
+        };
+
+        let mut child = self.rsync_command(remote_path, local_path, options, &key_path)
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .context("rsync の起動に失敗しました")?;
+
+        let stdout = child.stdout.take().context("rsync の出力を取得できません")?;
+        let stderr = child.stderr.take().context("rsync のエラー出力を取得できません")?;
+        let stderr_reader = Self::spawn_rsync_stderr_reader(stderr);
+        let (receiver, reader) = Self::spawn_rsync_progress_reader(stdout);
+
+        let cancelled = Self::follow_rsync_progress(&mut child, &receiver, control, state, progress_callback);
+
+        let status = child.wait().context("rsync の終了待ちに失敗しました")?;
+        let _ = reader.join();
+        let error_output = stderr_reader.join().unwrap_or_default();
+
+        if cancelled {
+            return Err(anyhow::anyhow!("🚫 バックアップがキャンセルされました"));
+        }
+
+        if !status.success() {
+            return Err(anyhow::anyhow!(
+                "rsync が失敗しました ({}): {}",
+                status,
+                error_output.trim()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 接続設定とバックアップオプションから rsync のコマンドを組み立てる
+    fn rsync_command(&self, remote_path: &str, local_path: &str, options: &BackupOptions, key_path: &str) -> std::process::Command {
+        let ssh_command = format!(
+            "ssh -i {} -p {} -o BatchMode=yes -o StrictHostKeyChecking=accept-new -o ConnectTimeout={}{}",
+            Self::shell_quote(key_path), self.config.port, self.config.connect_timeout_secs, self.config.algorithms.ssh_options()
+        );
+        // 末尾の "/" でフォルダの中身を保存先に同期する
+        let source = format!(
+            "{}@{}:{}/",
+            self.config.username,
+            self.config.hostname,
+            remote_path.trim_end_matches('/')
+        );
+
+        // rsync のフィルタは先に一致したものが優先されるため、除外パターン、含めるパターンの順に渡す
+        let ignore_args = options.ignore_rules.rsync_filter_args();
+        let include_args = options.always_include.iter().map(|pattern| format!("--include={}", pattern));
+
+        // ミラー削除は --delete に任せる（除外した隠しファイルは削除されない）
+        let delete_args = options.mirror_delete.then_some("--delete");
+
+        // ファイルサイズの上限と前回のバックアップへのハードリンクは rsync の同等のオプションで指定する
+        let max_size_args = options.max_file_size.map(|max| format!("--max-size={}", max));
+        let link_dest_args = options.link_dest
+            .as_deref()
+            .filter(|dir| dir.is_dir())
+            .and_then(|dir| std::path::absolute(dir).ok())
+            .map(|dir| format!("--link-dest={}", dir.display()));
+
+        let mut command = std::process::Command::new("rsync");
+        command
+            .args(["-az", "--protect-args", "--info=progress2", "--no-inc-recursive"])
+            .args(ignore_args)
+            .args(include_args)
+            .arg("--exclude=.*")
+            .args(delete_args)
+            .args(max_size_args)
+            .args(link_dest_args)
+            .arg("-e")
+            .arg(&ssh_command)
+            .arg(&source)
+            .arg(format!("{}/", local_path.trim_end_matches(['/', '\\'])));
+        command
+    }
+
+    /// エラー出力を読まずにいるとパイプが詰まって rsync が止まるため、別スレッドで読み続ける
+    /// （上限を超えた分は古いものから捨てる）
+    fn spawn_rsync_stderr_reader(mut stderr: std::process::ChildStderr) -> std::thread::JoinHandle<String> {
+        std::thread::spawn(move || {
+            let mut output = Vec::new();
+            let mut buffer = [0u8; 8192];
+            while let Ok(read) = stderr.read(&mut buffer) {
+                if read == 0 {
+                    break;
+                }
+                output.extend_from_slice(&buffer[..read]);
+                if output.len() > MAX_STDERR_BYTES {
+                    output.drain(..output.len() - MAX_STDERR_BYTES);
+                }
+            }
+            String::from_utf8_lossy(&output).to_string()
+        })
+    }
+
+    /// 進捗行は "\r" 区切りのため、別スレッドで読み取って送る
+    fn spawn_rsync_progress_reader(stdout: std::process::ChildStdout) -> (mpsc::Receiver<String>, std::thread::JoinHandle<()>) {
+        let (sender, receiver) = mpsc::channel::<String>();
+        let reader = std::thread::spawn(move || {
+            let mut reader = std::io::BufReader::new(stdout);
+            let mut line = Vec::new();
+            let mut byte = [0u8; 1];
+            while let Ok(1) = reader.read(&mut byte) {
+                if byte[0] == b'\r' || byte[0] == b'\n' {
+                    if !line.is_empty() {
+                        let _ = sender.send(String::from_utf8_lossy(&line).to_string());
+                        line.clear();
+                    }
+                } else {
+                    line.push(byte[0]);
+                }
+            }
+        });
+        (receiver, reader)
+    }
+
+    /// rsync の出力が終わるまで進捗を通知する（キャンセルされた場合は子プロセスを終了して true を返す）
+    fn follow_rsync_progress<F>(
+        child: &mut std::process::Child,
+        receiver: &mpsc::Receiver<String>,
+        control: &BackupControl,
+        state: &mut TransferState,
+        progress_callback: &F,
+    ) -> bool
+    where
+        F: Fn(BackupProgress),
+    {
+        loop {
+            match receiver.recv_timeout(RSYNC_POLL_INTERVAL) {
+                Ok(line) => {
+                    if let Some((bytes, files)) = Self::parse_rsync_progress(&line) {
+                        state.transferred_bytes = bytes;
+                        state.transferred_files = files;
+
+                        if state.throttle.should_update(state.transferred_bytes) {
+                            progress_callback(Self::rsync_transfer_progress(state));
+                        }
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return false,
+            }
+
+            if control.is_cancelled() {
+                let _ = child.kill();
+                return true;
+            }
+        }
+    }
+
+    /// rsync での転送中の進捗（並行集計中は総数が増えていくため進捗率は出さない）
+    fn rsync_transfer_progress(state: &mut TransferState) -> BackupProgress {
+        let (total_files, counting) = state.sync_concurrent_count();
+        BackupProgress {
+            phase: "ファイル転送中".to_string(),
+            transferred_files: state.transferred_files,
+            total_files,
+            transferred_bytes: state.transferred_bytes,
+            total_bytes: state.total_bytes,
+            elapsed_seconds: state.throttle.get_elapsed_seconds(),
+            transfer_speed: state.throttle.calculate_speed(state.transferred_bytes),
+            average_speed: state.throttle.calculate_average_speed(state.transferred_bytes),
+            percent_complete: if counting {
+                None
+            } else {
+                BackupProgress::calculate_percent(state.transferred_bytes, state.total_bytes)
+            },
+            counting,
+            ..Default::default()
+        }
+    }
+
+    /// rsync の `--info=progress2` の1行から (転送バイト数, 転送ファイル数) を取り出す
+    ///
+    /// 例: "  1,234,567  45%  1.23MB/s    0:00:12 (xfr#12, to-chk=3/20)"
+    fn parse_rsync_progress(line: &str) -> Option<(u64, usize)> {
+        let mut fields = line.split_whitespace();
+        let bytes = fields.next()?.replace(',', "").parse().ok()?;
+
+        let files = line
+            .split("xfr#")
+            .nth(1)
+            .and_then(|rest| rest.split(|c: char| !c.is_ascii_digit()).next())
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(0);
+
+        Some((bytes, files))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rsync_version_reads_major_and_minor() {
+        let output = "rsync  version 3.2.7  protocol version 31\nCopyright (C) 1996-2022 by Andrew Tridgell\n";
+        assert_eq!(SshClient::parse_rsync_version(output), Some((3, 2)));
+        assert_eq!(SshClient::parse_rsync_version("rsync  version 3.1.0pre1  protocol version 31"), Some((3, 1)));
+        assert_eq!(SshClient::parse_rsync_version("rsync version v2.6.9"), None);
+        assert!(SshClient::parse_rsync_version("rsync  version 3.0.9  protocol version 30").unwrap() < RSYNC_MIN_VERSION);
+        assert_eq!(SshClient::parse_rsync_version(""), None);
+    }
+}
@@ -0,0 +1,223 @@
+use super::*;
+
+/// 再帰転送中に共有する進捗状態
+pub(super) struct TransferState {
+    pub(super) throttle: ProgressThrottle,
+    /// コピー・ハードリンクを合わせた処理済みファイル数
+    pub(super) transferred_files: usize,
+    pub(super) transferred_bytes: u64,
+    pub(super) linked_files: usize,
+    /// サイズ上限を超えてスキップしたファイル数
+    pub(super) skipped_large_files: usize,
+    /// 前回のバックアップ以降に更新されていないためスキップしたファイル数
+    pub(super) skipped_unmodified_files: usize,
+    /// 更新からの経過日数が範囲外のためスキップしたファイル数
+    pub(super) skipped_by_age: usize,
+    /// 事前計算した総バイト数（進捗率の計算用）
+    pub(super) total_bytes: Option<u64>,
+    /// ローカルに種類の異なる同名エントリがあってスキップした件数
+    pub(super) type_mismatches: usize,
+    /// 通常ファイル・ディレクトリ以外でスキップした件数
+    pub(super) skipped_special_files: usize,
+    /// 大文字・小文字のみ異なる名前の衝突件数
+    pub(super) case_collisions: usize,
+    /// ミラー削除用: リモートのエントリに対応するローカルのパス
+    pub(super) remote_entries: HashSet<PathBuf>,
+    /// ミラー削除で削除したファイル数とバイト数
+    pub(super) deleted_files: usize,
+    pub(super) deleted_bytes: u64,
+    /// ローカルのファイルシステムが大文字・小文字を区別しないか
+    pub(super) case_insensitive: bool,
+    /// link_dest からの相対パス計算に使うローカルのバックアップ先
+    pub(super) local_root: PathBuf,
+    /// 転送と並行して集計している総数（`concurrent_precount` 有効時）
+    pub(super) concurrent_count: Option<Arc<RemoteTreeCount>>,
+    /// 変更検出用のインデックス（`use_index` 有効時）
+    pub(super) index: Option<IndexSession>,
+    /// 既存のファイルの処理結果（`overwrite_policy` ごと）
+    pub(super) overwrite_counts: OverwriteCounts,
+    /// 保存するファイルの暗号化（`encrypt` 有効時）
+    pub(super) encryption: Option<BackupEncryption>,
+    /// セッションが切れて再接続した回数
+    pub(super) reconnects: usize,
+    /// 作成したローカルのディレクトリ数
+    pub(super) created_dirs: usize,
+    /// 再接続後に処理し直さないよう記録する、処理を終えたリモートのディレクトリ
+    pub(super) completed_dirs: HashSet<PathBuf>,
+    /// 処理中のディレクトリで処理を終えたリモートのファイル（ディレクトリの完了時に取り除く）
+    pub(super) processed_files: HashSet<PathBuf>,
+    /// 処理を終えたファイルを記録しない（`low_memory` 有効時）
+    skip_processed_files: bool,
+    /// 中断しても続きから実行できるよう書き出すチェックポイント（SFTPでのフォルダのバックアップのみ）
+    pub(super) checkpoint: Option<CheckpointWriter>,
+    /// 権限がないなどで読み取れずスキップしたリモートのディレクトリと、そのエラー
+    pub(super) inaccessible_dirs: Vec<(PathBuf, String)>,
+    /// 読み取れなかったディレクトリに対応するローカルのパス（ミラー削除で中身を消さない）
+    pub(super) inaccessible_local_dirs: HashSet<PathBuf>,
+    /// 転送後のチェックサム照合（`verify_checksums` 有効時）
+    pub(super) verifier: Option<HashVerifier>,
+    /// リモートのハッシュをまとめて取得するまで待っているファイル（リモートのパス, 保存したパス）
+    pub(super) pending_hashes: Vec<(PathBuf, PathBuf)>,
+    /// 転送に失敗してスキップしたリモートのファイルと、そのエラー（`continue_on_error` 有効時）
+    pub(super) failed_files: Vec<(PathBuf, String)>,
+}
+
+impl TransferState {
+    pub(super) fn new(local_root: &Path, options: &BackupOptions) -> Self {
+        Self {
+            throttle: ProgressThrottle::new(
+                Duration::from_millis(options.progress_granularity.interval_ms),
+                options.progress_granularity.byte_threshold,
+            ),
+            transferred_files: 0,
+            transferred_bytes: 0,
+            linked_files: 0,
+            skipped_large_files: 0,
+            skipped_unmodified_files: 0,
+            skipped_by_age: 0,
+            total_bytes: None,
+            type_mismatches: 0,
+            skipped_special_files: 0,
+            case_collisions: 0,
+            remote_entries: HashSet::new(),
+            deleted_files: 0,
+            deleted_bytes: 0,
+            case_insensitive: false,
+            local_root: local_root.to_path_buf(),
+            concurrent_count: None,
+            index: None,
+            overwrite_counts: OverwriteCounts::default(),
+            encryption: None,
+            reconnects: 0,
+            created_dirs: 0,
+            completed_dirs: HashSet::new(),
+            processed_files: HashSet::new(),
+            skip_processed_files: options.low_memory,
+            checkpoint: None,
+            inaccessible_dirs: Vec::new(),
+            inaccessible_local_dirs: HashSet::new(),
+            verifier: None,
+            pending_hashes: Vec::new(),
+            failed_files: Vec::new(),
+        }
+    }
+
+    /// 最後まで処理したファイルを再接続後の再開用に記録する
+    pub(super) fn mark_processed(&mut self, entry_path: PathBuf) {
+        if !self.skip_processed_files {
+            self.processed_files.insert(entry_path);
+        }
+    }
+
+    /// 並行集計の現時点の結果を総バイト数に反映し、総ファイル数と集計中かどうかを返す
+    ///
+    /// 並行集計をしていない場合は (None, false)。集計に失敗した場合は総数を不明として扱う
+    pub(super) fn sync_concurrent_count(&mut self) -> (Option<usize>, bool) {
+        let Some(count) = &self.concurrent_count else {
+            return (None, false);
+        };
+
+        if count.failed.load(Ordering::Relaxed) {
+            self.total_bytes = None;
+            return (None, false);
+        }
+
+        let counting = !count.finished.load(Ordering::Relaxed);
+        self.total_bytes = Some(count.bytes.load(Ordering::Relaxed));
+        (Some(count.files.load(Ordering::Relaxed)), counting)
+    }
+}
+
+/// リモートの総ファイル数・総バイト数の集計結果
+///
+/// 転送と並行して集計する場合は集計スレッドから更新され、転送側は途中経過を読む
+#[derive(Default)]
+pub(super) struct RemoteTreeCount {
+    pub(super) files: AtomicUsize,
+    pub(super) bytes: AtomicU64,
+    /// 集計が終わった（完了・失敗・打ち切りのいずれか）
+    pub(super) finished: AtomicBool,
+    /// 集計に失敗した（総数は不正確）
+    pub(super) failed: AtomicBool,
+    /// 転送が先に終わった場合などに集計を打ち切る
+    pub(super) stop: AtomicBool,
+}
+
+/// リモートディレクトリのエントリ
+///
+/// 通常は全件を読み取った一覧から返す。`low_memory` では開いたディレクトリから読み取りながら返し、
+/// 一度に保持するのは libssh2 がサーバーから受け取った1回分の応答だけにする
+pub(super) enum RemoteDirEntries {
+    Listed(std::vec::IntoIter<(PathBuf, ssh2::FileStat)>),
+    Streaming { dir: ssh2::File, remote_dir: PathBuf },
+}
+
+impl RemoteDirEntries {
+    /// `streaming` が false の場合は全件を読み取ってから返す
+    pub(super) fn read(sftp: &ssh2::Sftp, remote_dir: &Path, streaming: bool) -> std::result::Result<Self, ssh2::Error> {
+        if streaming {
+            Ok(Self::Streaming {
+                dir: sftp.opendir(remote_dir)?,
+                remote_dir: remote_dir.to_path_buf(),
+            })
+        } else {
+            Ok(Self::Listed(sftp.readdir(remote_dir)?.into_iter()))
+        }
+    }
+}
+
+impl Iterator for RemoteDirEntries {
+    type Item = Result<(PathBuf, ssh2::FileStat)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (dir, remote_dir) = match self {
+            Self::Listed(entries) => return entries.next().map(Ok),
+            Self::Streaming { dir, remote_dir } => (dir, remote_dir),
+        };
+
+        loop {
+            match dir.readdir() {
+                Ok((file_name, _)) if file_name == Path::new(".") || file_name == Path::new("..") => continue,
+                Ok((file_name, stat)) => return Some(Ok((remote_dir.join(file_name), stat))),
+                Err(e) if e.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_FILE) => return None,
+                Err(e) => {
+                    return Some(Err(e).with_context(|| format!("リモートディレクトリの読み取りに失敗: {:?}", remote_dir)));
+                }
+            }
+        }
+    }
+}
+
+/// 読み取ったバイト数を通知するリーダー（tar での転送の進捗用）
+///
+/// `on_read` がエラーを返すと読み取りを中止する
+pub(super) struct ProgressReader<R, F> {
+    pub(super) inner: R,
+    pub(super) on_read: F,
+}
+
+impl<R: Read, F: FnMut(u64) -> std::io::Result<()>> Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        (self.on_read)(n as u64)?;
+        Ok(n)
+    }
+}
+
+/// 読み取りのたびに制限時刻を確認するリーダー
+///
+/// libssh2 の読み取りはスレッドをブロックするため、tokio のタイムアウトでは転送中のファイルを中断できない。
+/// 1回の読み取りが止まった場合はセッションのタイムアウトで戻る
+pub(super) struct DeadlineReader<R> {
+    pub(super) inner: R,
+    pub(super) deadline: Instant,
+}
+
+impl<R: Read> Read for DeadlineReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if Instant::now() >= self.deadline {
+            return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "ファイル転送の制限時間を超えました"));
+        }
+        self.inner.read(buf)
+    }
+}
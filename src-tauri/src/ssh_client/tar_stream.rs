@@ -0,0 +1,272 @@
+use super::*;
+
+/// libssh2 のチャンネル関連のエラーコード（SFTPサブシステムを要求できなかった場合など）
+const LIBSSH2_ERROR_CHANNEL_FAILURE: i32 = -21;
+
+const LIBSSH2_ERROR_CHANNEL_REQUEST_DENIED: i32 = -22;
+
+impl SshClient {
+    /// サーバー側でSFTPサブシステムが無効になっているために失敗したか
+    pub(super) fn is_sftp_unavailable(error: &ssh2::Error) -> bool {
+        matches!(
+            error.code(),
+            ssh2::ErrorCode::Session(LIBSSH2_ERROR_CHANNEL_FAILURE | LIBSSH2_ERROR_CHANNEL_REQUEST_DENIED)
+        ) || error.message().contains("subsystem")
+    }
+
+    /// SFTPが使えないサーバー向けに、`tar cf -` の出力をSSHで受け取ってローカルに展開する
+    ///
+    /// 進捗は受信したバイト数で通知する。隠しファイルは rsync と同様に除外し（always_include に
+    /// 一致するものは展開）、通常ファイル・ディレクトリ以外は展開しない。一時停止には対応しない
+    pub(super) fn backup_directory_with_tar<F>(
+        &self,
+        remote_path: &str,
+        local_path: &str,
+        options: &BackupOptions,
+        control: &BackupControl,
+        state: &mut TransferState,
+        progress_callback: &F,
+    ) -> Result<String>
+    where
+        F: Fn(BackupProgress),
+    {
+        let session = self.session.as_ref()
+            .context("SSHセッションが確立されていません")?;
+
+        Self::prepare_tar_transfer(session, remote_path, local_path, options, state, progress_callback)?;
+
+        let ignore_rules = Self::with_ignore_rules(remote_path, options, Self::read_ignore_file_with_exec(session, remote_path))
+            .ignore_rules;
+
+        let mut channel = session.channel_session()
+            .context("SSHチャンネルの作成に失敗しました")?;
+        channel.exec(&format!("cd {} && tar cf - .", Self::shell_quote(remote_path)))
+            .context("tar の実行に失敗しました")?;
+
+        let total_bytes = state.total_bytes;
+        let transferred_files = std::cell::Cell::new(0usize);
+        let throttle = &mut state.throttle;
+        let transferred_bytes = &mut state.transferred_bytes;
+        // 標準エラー出力を読まずにいるとチャンネルのウィンドウが埋まり、tar の出力も止まるため、
+        // 標準出力を読むたびに溜まっている分を読み取る
+        let mut stderr = channel.stderr();
+        let mut error_output = Vec::new();
+        let reader = ProgressReader {
+            inner: &mut channel,
+            on_read: |bytes: u64| {
+                if control.is_cancelled() {
+                    return Err(std::io::Error::other("🚫 バックアップがキャンセルされました"));
+                }
+                Self::drain_pending_stderr(session, &mut stderr, &mut error_output);
+                *transferred_bytes += bytes;
+                if throttle.should_update(*transferred_bytes) {
+                    progress_callback(BackupProgress {
+                        phase: "ファイル転送中".to_string(),
+                        transferred_files: transferred_files.get(),
+                        transferred_bytes: *transferred_bytes,
+                        total_bytes,
+                        elapsed_seconds: throttle.get_elapsed_seconds(),
+                        transfer_speed: throttle.calculate_speed(*transferred_bytes),
+                        average_speed: throttle.calculate_average_speed(*transferred_bytes),
+                        percent_complete: BackupProgress::calculate_percent(*transferred_bytes, total_bytes),
+                        ..Default::default()
+                    });
+                }
+                Ok(())
+            },
+        };
+
+        let mut archive = tar::Archive::new(reader);
+        let unpack_result = Self::unpack_tar_entries(&mut archive, local_path, options, &ignore_rules, &transferred_files);
+
+        // キャンセルによる読み取りエラーは展開の失敗として扱わない
+        if control.is_cancelled() {
+            return Err(anyhow::anyhow!("🚫 バックアップがキャンセルされました"));
+        }
+        let skipped_special_files = unpack_result?;
+
+        Self::finish_tar_channel(&mut channel, &mut stderr, error_output)?;
+
+        state.transferred_files = transferred_files.get();
+        state.skipped_special_files = skipped_special_files;
+
+        Ok(Self::tar_completion(remote_path, local_path, state, progress_callback))
+    }
+
+    /// tar での転送の前に、オプションを確認して保存先を作成し、総バイト数を求めて開始を通知する
+    fn prepare_tar_transfer<F>(
+        session: &Session,
+        remote_path: &str,
+        local_path: &str,
+        options: &BackupOptions,
+        state: &mut TransferState,
+        progress_callback: &F,
+    ) -> Result<()>
+    where
+        F: Fn(BackupProgress),
+    {
+        // 適用できないオプションを無視して転送すると、暗号化されない・除外したはずのファイルが残るなど
+        // 指定と異なるバックアップになるため、転送せずにエラーにする
+        let unsupported = Self::tar_unsupported_options(options);
+        if !unsupported.is_empty() {
+            return Err(anyhow::anyhow!(
+                "SFTPが使用できないため tar で転送しようとしましたが、tar での転送では次のオプションを適用できません: {}",
+                unsupported.join("、")
+            ));
+        }
+
+        if Path::new(local_path).is_file() {
+            return Err(BackupError::FileSystem(format!("保存先がファイルです: {}", Self::display_path(Path::new(local_path)))).into());
+        }
+        std::fs::create_dir_all(local_path)
+            .context("ローカルバックアップディレクトリの作成に失敗しました")?;
+
+        // 総バイト数は進捗率の目安（du が使えない場合は不明のまま転送する）
+        if options.precount {
+            state.total_bytes = Self::exec_command(session, &format!("du -sb {}", Self::shell_quote(remote_path)))
+                .ok()
+                .and_then(|output| output.split_whitespace().next()?.parse().ok());
+        }
+
+        progress_callback(BackupProgress {
+            phase: "ファイル転送開始".to_string(),
+            total_bytes: state.total_bytes,
+            percent_complete: BackupProgress::calculate_percent(0, state.total_bytes),
+            elapsed_seconds: state.throttle.get_elapsed_seconds(),
+            ..Default::default()
+        });
+        Ok(())
+    }
+
+    /// tar のエントリを保存先に展開し、スキップした特殊ファイルの数を返す
+    ///
+    /// 隠しファイルと除外パターンに一致するもの（親ディレクトリが除外される場合も含む）は展開しない
+    fn unpack_tar_entries<R: Read>(
+        archive: &mut tar::Archive<R>,
+        local_path: &str,
+        options: &BackupOptions,
+        ignore_rules: &IgnoreRules,
+        transferred_files: &std::cell::Cell<usize>,
+    ) -> Result<usize> {
+        let mut skipped_special_files = 0;
+        for entry in archive.entries().context("tar の読み取りに失敗しました")? {
+            let mut entry = entry.context("tar の読み取りに失敗しました")?;
+            let entry_path = entry.path().context("tar のエントリ名が不正です")?.into_owned();
+
+            let hidden = entry_path.components().any(|component| match component {
+                std::path::Component::Normal(name) => options.skips_hidden(&name.to_string_lossy()),
+                _ => false,
+            });
+            if hidden {
+                continue;
+            }
+
+            // 親ディレクトリが除外される場合も含めて除外パターンと照合する
+            let entry_type = entry.header().entry_type();
+            let ancestors: Vec<&Path> = entry_path.ancestors().filter(|path| !path.as_os_str().is_empty()).collect();
+            let ignored = ancestors.iter().enumerate().any(|(index, path)| {
+                let is_dir = index > 0 || entry_type.is_dir();
+                ignore_rules.is_ignored_relative(path, is_dir)
+            });
+            if ignored {
+                continue;
+            }
+
+            if !entry_type.is_file() && !entry_type.is_dir() {
+                skipped_special_files += 1;
+                continue;
+            }
+
+            // unpack_in はフォルダの外に出るパスを展開しない
+            entry.unpack_in(local_path)
+                .with_context(|| format!("ファイルの展開に失敗しました: {}", entry_path.display()))?;
+            if entry_type.is_file() {
+                transferred_files.set(transferred_files.get() + 1);
+            }
+        }
+        Ok(skipped_special_files)
+    }
+
+    /// tar の終端ブロック以降の出力を読み切ってからチャンネルを閉じ、終了ステータスを確認する
+    fn finish_tar_channel(channel: &mut ssh2::Channel, stderr: &mut ssh2::Stream, mut error_output: Vec<u8>) -> Result<()> {
+        std::io::copy(channel, &mut std::io::sink())
+            .context("tar の出力の読み取りに失敗しました")?;
+        let _ = stderr.read_to_end(&mut error_output);
+        let error_output = String::from_utf8_lossy(&error_output);
+        channel.wait_close()
+            .context("SSHチャンネルのクローズに失敗しました")?;
+
+        // GNU tar は読み取り中にファイルが変更された場合に 1 を返すため、警告にとどめる
+        match channel.exit_status().context("終了ステータスの取得に失敗しました")? {
+            0 => {}
+            1 => tracing::warn!("tar が警告を出力しました: {}", error_output.trim()),
+            status => return Err(anyhow::anyhow!(
+                "tar が終了ステータス {} で失敗しました: {}",
+                status,
+                error_output.trim()
+            )),
+        }
+        Ok(())
+    }
+
+    /// tar での転送の完了を通知し、完了メッセージを返す
+    fn tar_completion<F>(remote_path: &str, local_path: &str, state: &TransferState, progress_callback: &F) -> String
+    where
+        F: Fn(BackupProgress),
+    {
+        progress_callback(BackupProgress {
+            phase: "バックアップ完了".to_string(),
+            transferred_files: state.transferred_files,
+            total_files: Some(state.transferred_files),
+            transferred_bytes: state.transferred_bytes,
+            total_bytes: Some(state.transferred_bytes),
+            elapsed_seconds: state.throttle.get_elapsed_seconds(),
+            transfer_speed: state.throttle.calculate_speed(state.transferred_bytes),
+            average_speed: state.throttle.calculate_average_speed(state.transferred_bytes),
+            percent_complete: Some(100.0),
+            skipped_special_files: state.skipped_special_files,
+            ..Default::default()
+        });
+
+        let mut message = format!("✅ バックアップ完了!\n転送ファイル数: {}\nリモート: {}\nローカル: {}\n転送方式: tar（SFTPが使用できないため）",
+            state.transferred_files, remote_path, Self::display_path(Path::new(local_path)));
+        if state.skipped_special_files > 0 {
+            message.push_str(&format!("\n特殊ファイルのスキップ: {}", state.skipped_special_files));
+        }
+        message
+    }
+
+    /// tar での転送で適用できない、指定されたオプションの名前
+    fn tar_unsupported_options(options: &BackupOptions) -> Vec<&'static str> {
+        [
+            (options.encrypt, "暗号化"),
+            (options.max_file_size.is_some(), "ファイルサイズの上限"),
+            (options.min_age_days.is_some() || options.max_age_days.is_some(), "更新日時による絞り込み"),
+            (options.modified_since.is_some(), "前回以降の更新のみ転送"),
+            (options.mirror_delete, "ミラー削除"),
+            (options.overwrite_policy != OverwritePolicy::Overwrite, "既存ファイルの扱い"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, name)| enabled.then_some(name))
+        .collect()
+    }
+
+    /// SSHチャンネルの標準エラー出力のうち、待たずに読める分を読み取る（上限を超えた分は古いものから捨てる）
+    ///
+    /// 標準出力と同じスレッドで読む（libssh2 のセッションはスレッド間で同時に読めないため、
+    /// 別スレッドで待つと標準出力の読み取りが止まる）
+    fn drain_pending_stderr(session: &Session, stderr: &mut ssh2::Stream, output: &mut Vec<u8>) {
+        let mut buffer = [0u8; 8192];
+        session.set_blocking(false);
+        while let Ok(read) = stderr.read(&mut buffer) {
+            if read == 0 {
+                break;
+            }
+            output.extend_from_slice(&buffer[..read]);
+            if output.len() > MAX_STDERR_BYTES {
+                output.drain(..output.len() - MAX_STDERR_BYTES);
+            }
+        }
+        session.set_blocking(true);
+    }
+}
@@ -0,0 +1,587 @@
+use super::*;
+
+/// 事前計算がない場合のバックアップ全体タイムアウト（2時間）
+const BACKUP_TIMEOUT_DEFAULT_SECS: u64 = 7200;
+
+/// 事前計算から求めるバックアップ全体タイムアウトの下限（10分）
+const BACKUP_TIMEOUT_MIN_SECS: u64 = 600;
+
+/// 事前計算から求めるバックアップ全体タイムアウトの上限（24時間）
+const BACKUP_TIMEOUT_MAX_SECS: u64 = 24 * 3600;
+
+/// 1回のバックアップの転送元・保存先と制御（各段階の関数で共有する）
+struct BackupJob<'a, F> {
+    remote_path: &'a str,
+    /// 拡張長パス（Windows のみ）に変換した保存先
+    local_path: &'a str,
+    /// 利用者が指定したままの保存先（メッセージ・チェックポイント用）
+    display_local_path: &'a str,
+    control: &'a Arc<BackupControl>,
+    progress_callback: &'a Arc<F>,
+}
+
+impl SshClient {
+    /// リモートフォルダをローカルにバックアップ
+    pub async fn backup_folder(&mut self, remote_path: &str, local_path: &str) -> Result<String> {
+        let control = Arc::new(BackupControl::new());
+        self.backup_folder_with_cancel(remote_path, local_path, control).await
+    }
+
+    /// キャンセル対応のリモートフォルダバックアップ
+    pub async fn backup_folder_with_progress<F>(&mut self, remote_path: &str, local_path: &str, control: Arc<BackupControl>, options: &BackupOptions, progress_callback: F) -> Result<String>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        Self::check_allowed_backup_root(Path::new(local_path), &options.allowed_backup_roots)?;
+        if let (Some(min), Some(max)) = (options.min_age_days, options.max_age_days) {
+            if min > max {
+                return Err(anyhow::anyhow!("更新からの経過日数の下限（{}日）が上限（{}日）より大きくなっています", min, max));
+            }
+        }
+
+        let callback = Arc::new(progress_callback);
+
+        // 初期進捗を送信
+        callback(BackupProgress {
+            phase: "接続中".to_string(),
+            transferred_files: 0,
+            total_files: None,
+            transferred_bytes: 0,
+            current_file: None,
+            elapsed_seconds: 0,
+            transfer_speed: None,
+            ..Default::default()
+        });
+
+        self.backup_folder_with_cancel_and_progress(remote_path, local_path, control, options, callback).await
+    }
+
+    pub async fn backup_folder_with_cancel(&mut self, remote_path: &str, local_path: &str, control: Arc<BackupControl>) -> Result<String> {
+        // 進捗コールバックなしでバックアップを実行
+        self.backup_folder_with_cancel_and_progress(remote_path, local_path, control, &BackupOptions::default(), Arc::new(|_| {})).await
+    }
+
+    async fn backup_folder_with_cancel_and_progress<F>(&mut self, remote_path: &str, local_path: &str, control: Arc<BackupControl>, options: &BackupOptions, progress_callback: Arc<F>) -> Result<String>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        // 深い階層が MAX_PATH を超えても保存できるよう、以降は拡張長パスで扱う（Windows のみ）
+        // 利用者に見せるメッセージやチェックポイントには指定されたままのパスを使う
+        let extended_local_path = Self::extended_length_path(Path::new(local_path));
+        let extended_local_path = extended_local_path.to_string_lossy();
+        let job = BackupJob {
+            remote_path,
+            local_path: &extended_local_path,
+            display_local_path: local_path,
+            control: &control,
+            progress_callback: &progress_callback,
+        };
+        let local_path = job.local_path;
+
+        // 全体の制限時間は接続・事前計算を含めて数える（総量がわかるまでは既定の時間）
+        let watchdog = BackupWatchdog::start(control.clone(), Duration::from_secs(BACKUP_TIMEOUT_DEFAULT_SECS));
+
+        tracing::info!("バックアップ開始: {} -> {}", remote_path, local_path);
+        let started = Instant::now();
+
+        // キャンセル時の後片付けで既存フォルダを消さないよう、今回作成するかを記録
+        let local_dir_created = !Path::new(local_path).exists();
+
+        // 全体は事前計算に応じた制限時間で打ち切る（エラー分類適用）
+        let backup_result = self.run_backup(&job, options, &watchdog).await;
+        let timed_out = control.is_timed_out();
+        control.clear_time_out();
+        match backup_result {
+            // 事前計算や接続の途中で制限時間を過ぎた場合も、キャンセルではなくタイムアウトとして扱う
+            Err(_) if timed_out => {
+                let limit_seconds = watchdog.limit().as_secs();
+                tracing::error!("バックアップがタイムアウトしました: {} ({}秒)", remote_path, limit_seconds);
+                Err(Self::classify_error(&BackupError::Timeout { limit_seconds }.into()).into())
+            }
+            Ok(result) => {
+                tracing::info!("バックアップ完了: {} ({}秒)", remote_path, started.elapsed().as_secs());
+                Ok(result)
+            }
+            Err(e) => {
+                tracing::error!("バックアップ失敗: {} ({}秒): {:#}", remote_path, started.elapsed().as_secs(), e);
+
+                if local_dir_created && control.should_cleanup() {
+                    match std::fs::remove_dir_all(local_path) {
+                        Ok(()) => tracing::info!("キャンセルにより途中のバックアップを削除: {}", local_path),
+                        Err(e) => tracing::warn!("途中のバックアップの削除に失敗: {}: {}", local_path, e),
+                    }
+                }
+
+                Err(Self::classify_error(&e).into())
+            }
+        }
+    }
+
+    /// 接続・事前計算・転送・完了の通知までを順に実行し、完了メッセージを返す
+    async fn run_backup<F>(&mut self, job: &BackupJob<'_, F>, options: &BackupOptions, watchdog: &BackupWatchdog) -> Result<String>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        let local_root = Path::new(job.local_path);
+        let mut state = TransferState::new(local_root, options);
+        let mut timings = PhaseTimings::default();
+        let connect_started = Instant::now();
+
+        let Some(sftp) = self.open_transfer_sftp(job, options, &state).await? else {
+            return self.backup_directory_with_tar(job.remote_path, job.local_path, options, job.control, &mut state, &**job.progress_callback);
+        };
+        timings.connecting_seconds = connect_started.elapsed().as_secs_f64();
+
+        let remote_stat = Self::prepare_backup_paths(&sftp, job, &mut state)?;
+        let remote_is_file = remote_stat.is_file();
+
+        // 設定の除外パターンに、バックアップ元の .kyoshoignore の内容を加える
+        let options = &if remote_is_file {
+            options.clone()
+        } else {
+            Self::with_ignore_rules(job.remote_path, options, Self::read_ignore_file_with_sftp(&sftp, job.remote_path))
+        };
+
+        let session = self.session.as_ref()
+            .context("SSHセッションが確立されていません")?;
+
+        let scan_started = Instant::now();
+        let precount = Self::precount_backup(session, &sftp, job, &remote_stat, options, &mut state)?;
+        timings.scanning_seconds = scan_started.elapsed().as_secs_f64();
+
+        // ローカルの空き容量を事前チェック
+        if options.check_disk_space {
+            if let Some((_, total_bytes)) = precount {
+                Self::check_disk_space(local_root, total_bytes)?;
+            }
+        }
+
+        // 事前計算したサイズから全体タイムアウトを決定
+        // （接続・事前計算にかかった時間に、総量から見積もった転送の制限時間を足す）
+        let backup_timeout = connect_started.elapsed() + Self::calculate_backup_timeout(
+            precount.map(|(_, bytes)| bytes),
+            options.min_throughput_mbps,
+        );
+        watchdog.set_limit(backup_timeout);
+
+        state.total_bytes = precount.map(|(_, bytes)| bytes);
+        (job.progress_callback)(Self::transfer_start_progress(&state, precount.map(|(files, _)| files), backup_timeout));
+
+        let use_rsync = self.uses_rsync(session, options, remote_is_file);
+        self.prepare_transfer_state(session, job, options, remote_is_file, use_rsync, &mut state)?;
+        self.set_transfer_session_timeout(options);
+
+        let transfer_started = Instant::now();
+        let transfer_result = self.run_transfer(&sftp, job, &remote_stat, options, use_rsync, &mut state).await;
+        self.finish_transfer(job, options, watchdog, transfer_result, &mut state)?;
+        timings.transferring_seconds = transfer_started.elapsed().as_secs_f64();
+
+        self.report_completion(job, options, remote_is_file, use_rsync, &mut state, timings)
+    }
+
+    /// 必要なら接続してSFTPセッションを作成する
+    ///
+    /// サーバーでSFTPが使えず tar での転送が許可されている場合は None
+    async fn open_transfer_sftp<F>(&mut self, job: &BackupJob<'_, F>, options: &BackupOptions, state: &TransferState) -> Result<Option<ssh2::Sftp>>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        // 接続がない場合は接続を確立
+        if self.session.is_none() {
+            (job.progress_callback)(BackupProgress {
+                phase: "SSH接続中".to_string(),
+                transferred_files: 0,
+                total_files: None,
+                transferred_bytes: 0,
+                current_file: None,
+                elapsed_seconds: state.throttle.get_elapsed_seconds(),
+                transfer_speed: None,
+                ..Default::default()
+            });
+            self.test_connection().await?;
+        }
+
+        let session = self.session.as_ref()
+            .context("SSHセッションが確立されていません")?;
+
+        // SFTPチャンネルを作成
+        (job.progress_callback)(BackupProgress {
+            phase: "SFTPセッション作成中".to_string(),
+            transferred_files: 0,
+            total_files: None,
+            transferred_bytes: 0,
+            current_file: None,
+            elapsed_seconds: state.throttle.get_elapsed_seconds(),
+            transfer_speed: None,
+            ..Default::default()
+        });
+
+        match session.sftp() {
+            Ok(sftp) => Ok(Some(sftp)),
+            Err(e) if options.allow_tar_fallback && Self::is_sftp_unavailable(&e) => {
+                tracing::warn!("SFTPが使用できないため tar で転送します: {}", e);
+                Ok(None)
+            }
+            Err(e) => Err(anyhow::Error::new(e).context("SFTPセッションの作成に失敗しました")),
+        }
+    }
+
+    /// 保存先フォルダを作成し、バックアップ元がファイルかディレクトリであることを確認する
+    fn prepare_backup_paths<F>(sftp: &ssh2::Sftp, job: &BackupJob<'_, F>, state: &mut TransferState) -> Result<ssh2::FileStat>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        // 保存先が既にファイルとして存在する場合は作成できない
+        if Path::new(job.local_path).is_file() {
+            return Err(BackupError::FileSystem(format!("保存先がファイルです: {}", job.display_local_path)).into());
+        }
+
+        // ローカルディレクトリを作成
+        std::fs::create_dir_all(job.local_path)
+            .context("ローカルバックアップディレクトリの作成に失敗しました")?;
+        state.case_insensitive = Self::is_case_insensitive_dir(Path::new(job.local_path));
+
+        // リモートディレクトリの存在確認
+        (job.progress_callback)(BackupProgress {
+            phase: "リモートフォルダ確認中".to_string(),
+            transferred_files: 0,
+            total_files: None,
+            transferred_bytes: 0,
+            current_file: Some(job.remote_path.to_string()),
+            elapsed_seconds: state.throttle.get_elapsed_seconds(),
+            transfer_speed: None,
+            ..Default::default()
+        });
+
+        let remote_stat = sftp.stat(Path::new(job.remote_path))
+            .with_context(|| format!("リモートフォルダが見つかりません: {}", job.remote_path))?;
+
+        // 単一ファイルの場合はそのファイルだけをダウンロードする
+        if !remote_stat.is_file() && !remote_stat.is_dir() {
+            return Err(anyhow::anyhow!("指定されたリモートパスはファイルでもディレクトリでもありません: {}", job.remote_path));
+        }
+        Ok(remote_stat)
+    }
+
+    /// 総ファイル数・総バイト数の事前計算（単一ファイルはstat結果を使用、ディレクトリはオプション）
+    ///
+    /// 転送と並行して集計する場合と事前計算しない場合は None
+    fn precount_backup<F>(
+        session: &Session,
+        sftp: &ssh2::Sftp,
+        job: &BackupJob<'_, F>,
+        remote_stat: &ssh2::FileStat,
+        options: &BackupOptions,
+        state: &mut TransferState,
+    ) -> Result<Option<(usize, u64)>>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        if remote_stat.is_file() {
+            return Ok(Some((1, remote_stat.size.unwrap_or(0))));
+        }
+        if !options.precount {
+            return Ok(None);
+        }
+
+        if options.concurrent_precount {
+            // 転送と並行して集計する（総数は転送中の進捗通知で順次反映）
+            let count = Arc::new(RemoteTreeCount::default());
+            Self::spawn_concurrent_count(session.clone(), job.remote_path, job.control.clone(), options.clone(), count.clone());
+            state.concurrent_count = Some(count);
+            return Ok(None);
+        }
+
+        (job.progress_callback)(BackupProgress {
+            phase: "ファイル数計算中".to_string(),
+            transferred_files: 0,
+            total_files: None,
+            transferred_bytes: 0,
+            current_file: Some(job.remote_path.to_string()),
+            elapsed_seconds: state.throttle.get_elapsed_seconds(),
+            transfer_speed: None,
+            ..Default::default()
+        });
+
+        let count = RemoteTreeCount::default();
+        Self::count_remote_tree(sftp, Path::new(job.remote_path), 0, job.control, options, &count)?;
+        Ok(Some((count.files.into_inner(), count.bytes.into_inner())))
+    }
+
+    /// 転送開始時の進捗（事前計算した総数と全体の制限時間を含む）
+    fn transfer_start_progress(state: &TransferState, total_files: Option<usize>, backup_timeout: Duration) -> BackupProgress {
+        BackupProgress {
+            phase: "ファイル転送開始".to_string(),
+            transferred_files: 0,
+            total_files,
+            transferred_bytes: 0,
+            total_bytes: state.total_bytes,
+            percent_complete: BackupProgress::calculate_percent(0, state.total_bytes),
+            current_file: None,
+            elapsed_seconds: state.throttle.get_elapsed_seconds(),
+            transfer_speed: None,
+            timeout_seconds: Some(backup_timeout.as_secs()),
+            counting: state.concurrent_count.is_some(),
+            ..Default::default()
+        }
+    }
+
+    /// rsync で転送するか
+    ///
+    /// 更新からの経過日数での絞り込みは rsync に渡せないため、指定がある場合はSFTPで転送する
+    fn uses_rsync(&self, session: &Session, options: &BackupOptions, remote_is_file: bool) -> bool {
+        !remote_is_file
+            && options.transfer_backend == TransferBackend::Rsync
+            && options.min_age_days.is_none()
+            && options.max_age_days.is_none()
+            && self.config.jump_host.is_none()
+            && self.rsync_auth_supported()
+            && options.overwrite_policy == OverwritePolicy::Overwrite
+            && !options.encrypt
+            && Self::rsync_available(session)
+    }
+
+    /// 転送方式に応じて、インデックス・チェックサム照合・チェックポイント・暗号化を準備する
+    fn prepare_transfer_state<F>(
+        &self,
+        session: &Session,
+        job: &BackupJob<'_, F>,
+        options: &BackupOptions,
+        remote_is_file: bool,
+        use_rsync: bool,
+        state: &mut TransferState,
+    ) -> Result<()>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        let local_root = Path::new(job.local_path);
+
+        // rsync は自前で差分を判定するため、インデックスはSFTPでのフォルダ転送でのみ使う
+        if options.use_index && !remote_is_file && !use_rsync {
+            state.index = Some(IndexSession::load(local_root));
+        }
+
+        // 暗号化したファイルはリモートと内容が異なるため照合しない（rsync は自前で照合する）
+        if options.verify_checksums && !use_rsync {
+            if options.encrypt {
+                tracing::warn!("暗号化して保存するためチェックサムを照合しません");
+            } else if !Self::remote_command_exists(session, "sha256sum") {
+                tracing::warn!("サーバーで sha256sum を実行できないためチェックサムを照合しません");
+            } else {
+                state.verifier = Some(HashVerifier::new(options.max_hash_jobs));
+            }
+        }
+
+        // 電源断などで中断しても続きから実行できるよう、処理を終えたディレクトリを記録する
+        // （低メモリモードでは名前順に処理しないため、続きを判定できない）
+        if options.low_memory && options.mirror_delete {
+            tracing::warn!("ミラー削除のためリモートのエントリを記録するので、低メモリモードでもエントリ数に応じてメモリを使います");
+        }
+        if !remote_is_file && !use_rsync && !options.low_memory {
+            state.checkpoint = Some(self.start_checkpoint(job.remote_path, job.display_local_path, local_root, options));
+        }
+
+        if options.encrypt {
+            // 暗号化したファイルは内容を比較できず、連番のファイルが増え続けるため併用しない
+            if options.overwrite_policy == OverwritePolicy::KeepBoth {
+                return Err(anyhow::anyhow!("暗号化と「既存のファイルを残して別名で保存」は同時に使用できません"));
+            }
+            let passphrase = options.encryption_passphrase
+                .as_ref()
+                .context("暗号化のパスフレーズが指定されていません")?;
+            state.encryption = Some(BackupEncryption::open(local_root, passphrase)?);
+        }
+        Ok(())
+    }
+
+    /// ファイル転送の実行（単一ファイル・rsync・SFTPでの再帰的な転送のいずれか）
+    async fn run_transfer<F>(
+        &mut self,
+        sftp: &ssh2::Sftp,
+        job: &BackupJob<'_, F>,
+        remote_stat: &ssh2::FileStat,
+        options: &BackupOptions,
+        use_rsync: bool,
+        state: &mut TransferState,
+    ) -> Result<()>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        if remote_stat.is_file() {
+            self.backup_single_file(
+                sftp,
+                Path::new(job.remote_path),
+                Path::new(job.local_path),
+                remote_stat.size.unwrap_or(0),
+                remote_stat.mtime,
+                options,
+                state,
+                &**job.progress_callback,
+            ).await
+        } else if use_rsync {
+            self.backup_directory_with_rsync(
+                job.remote_path,
+                job.local_path,
+                options,
+                job.control,
+                state,
+                &**job.progress_callback,
+            )
+        } else {
+            self.backup_directory_with_reconnect(
+                sftp,
+                job.remote_path,
+                job.local_path,
+                job.control,
+                options,
+                state,
+                job.progress_callback.clone()
+            ).await
+        }
+    }
+
+    /// 転送後に転送の記録を保存し、タイムアウト・失敗・キャンセルをエラーとして返す
+    fn finish_transfer<F>(
+        &self,
+        job: &BackupJob<'_, F>,
+        options: &BackupOptions,
+        watchdog: &BackupWatchdog,
+        transfer_result: Result<()>,
+        state: &mut TransferState,
+    ) -> Result<()>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        if let Some(session) = &self.session {
+            session.set_timeout(0);
+        }
+        if let Some(count) = &state.concurrent_count {
+            count.stop.store(true, Ordering::Relaxed);
+        }
+
+        let transfer_completed = transfer_result.is_ok() && !job.control.is_cancelled();
+        let manifest_result = Self::save_transfer_records(state, Path::new(job.local_path), options, transfer_completed);
+        if job.control.is_timed_out() {
+            return Err(BackupError::Timeout { limit_seconds: watchdog.limit().as_secs() }.into());
+        }
+        transfer_result?;
+        manifest_result?;
+
+        if job.control.is_cancelled() {
+            (job.progress_callback)(BackupProgress {
+                phase: "キャンセル完了".to_string(),
+                transferred_files: state.transferred_files,
+                total_files: None,
+                transferred_bytes: state.transferred_bytes,
+                current_file: None,
+                elapsed_seconds: state.throttle.get_elapsed_seconds(),
+                transfer_speed: None,
+                ..Default::default()
+            });
+            return Err(anyhow::anyhow!("🚫 バックアップがキャンセルされました"));
+        }
+        Ok(())
+    }
+
+    /// チェックサムの照合とミラー削除を済ませ、完了の進捗を通知して完了メッセージを返す
+    fn report_completion<F>(
+        &self,
+        job: &BackupJob<'_, F>,
+        options: &BackupOptions,
+        remote_is_file: bool,
+        use_rsync: bool,
+        state: &mut TransferState,
+        mut timings: PhaseTimings,
+    ) -> Result<String>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        let verify_report = self.finish_checksum_verification(state, &mut timings, &**job.progress_callback);
+
+        // ミラー削除（rsync は --delete で同期済み。チェックポイントから再開した場合は行わない）
+        if options.mirror_delete && !remote_is_file && !use_rsync {
+            if options.resume_after_dir.is_some() {
+                tracing::warn!("チェックポイントから再開したためミラー削除を行いません: {}", job.display_local_path);
+            } else {
+                Self::delete_extraneous_local_entries(Path::new(job.local_path), &options.ignore_rules, job.control, state, &**job.progress_callback)?;
+            }
+        }
+
+        (job.progress_callback)(Self::completion_progress(state, timings));
+        Ok(Self::completion_message(job.remote_path, job.display_local_path, options, use_rsync, state, verify_report.as_ref()))
+    }
+
+    /// 中断しても続きから実行できるよう、処理を終えたディレクトリを記録するチェックポイントを書き出す
+    ///
+    /// 保存先は利用者が指定したままのパス（拡張長パスではない）を記録する
+    fn start_checkpoint(&self, remote_path: &str, display_local_path: &str, local_root: &Path, options: &BackupOptions) -> CheckpointWriter {
+        let mut checkpoint = CheckpointWriter::new(local_root, BackupCheckpoint {
+            remote_path: remote_path.to_string(),
+            local_path: display_local_path.to_string(),
+            ssh: self.config.clone(),
+            options: options.clone(),
+            profile_name: options.profile_name.clone(),
+            last_completed_dir: options.resume_after_dir.as_ref().map(|dir| dir.to_string_lossy().to_string()),
+            updated_at: 0,
+        });
+        checkpoint.write_logged();
+        checkpoint
+    }
+
+    /// 転送を終えた後（失敗・キャンセルを含む）に、チェックポイント・インデックス・マニフェストを保存する
+    ///
+    /// インデックスの保存に失敗しても警告にとどめ、マニフェストの保存結果を返す
+    fn save_transfer_records(state: &mut TransferState, local_root: &Path, options: &BackupOptions, transfer_completed: bool) -> Result<()> {
+        // 完了したらチェックポイントを削除し、途中で終わった場合は最新の状態を書き出す
+        if let Some(mut checkpoint) = state.checkpoint.take() {
+            if transfer_completed {
+                checkpoint.remove();
+            } else {
+                checkpoint.write_logged();
+            }
+        }
+
+        // 途中で失敗・キャンセルした場合も、それまでに転送したファイルを次回に活かす
+        // （チェックポイントから再開した場合は飛ばしたファイルの前回の情報も残す）
+        if let Some(index) = state.index.take() {
+            let completed = transfer_completed && options.resume_after_dir.is_none();
+            if let Err(e) = index.save(local_root, completed) {
+                tracing::warn!("インデックスの保存に失敗しました: {:#}", e);
+            }
+        }
+
+        // マニフェストがないと復号できないため、失敗した場合もそれまでの分を保存する
+        match &state.encryption {
+            Some(encryption) if local_root.is_dir() => encryption.save(local_root),
+            _ => Ok(()),
+        }
+    }
+
+    /// バックアップ全体のタイムアウト時間を計算
+    ///
+    /// 事前計算した総バイト数を最低想定スループットで割った時間を基準とし、
+    /// 下限10分・上限24時間に収める。事前計算がない場合は従来どおり2時間
+    fn calculate_backup_timeout(total_bytes: Option<u64>, min_throughput_mbps: f64) -> Duration {
+        let Some(total_bytes) = total_bytes else {
+            return Duration::from_secs(BACKUP_TIMEOUT_DEFAULT_SECS);
+        };
+
+        // 0や負の値が設定された場合は1MB/sとみなす
+        let throughput_mbps = if min_throughput_mbps > 0.0 { min_throughput_mbps } else { 1.0 };
+        let estimated_secs = (total_bytes as f64 / (throughput_mbps * 1024.0 * 1024.0)).ceil() as u64;
+
+        Duration::from_secs(estimated_secs.clamp(BACKUP_TIMEOUT_MIN_SECS, BACKUP_TIMEOUT_MAX_SECS))
+    }
+
+    /// ローカル保存先の空き容量が見積もりサイズ＋余裕分を満たすか確認
+    fn check_disk_space(local_path: &Path, estimated_bytes: u64) -> Result<()> {
+        let available = disk_space::available_space(local_path)?;
+        let required = estimated_bytes.saturating_add(disk_space::DISK_SPACE_MARGIN_BYTES);
+
+        if available < required {
+            return Err(BackupError::DiskSpace { required, available }.into());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,681 @@
+use super::*;
+
+/// バックアップ中にセッションが切れた場合の再接続の上限回数
+const MAX_SESSION_RECONNECTS: usize = 3;
+
+/// セッションが生きているかの確認にかける時間の上限
+const SESSION_PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 転送中にサーバーから応答がないまま待つ時間の上限（無言で切れた回線で止まり続けず、再接続に回すため）
+const TRANSFER_SESSION_TIMEOUT: Duration = Duration::from_secs(120);
+
+impl SshClient {
+    /// 再帰的にディレクトリをバックアップする
+    fn backup_directory_recursive<'a>(
+        &'a self,
+        sftp: &'a ssh2::Sftp,
+        remote_dir: &'a Path,
+        local_dir: &'a Path,
+        depth: usize,
+    ) -> Pin<Box<dyn Future<Output = Result<usize>> + Send + 'a>> {
+        Box::pin(async move {
+        // 深すぎる再帰を防ぐ（無限ループ対策）
+        if depth > 50 {
+            return Err(anyhow::anyhow!("ディレクトリの階層が深すぎます: {}", remote_dir.display()));
+        }
+
+        // ローカルディレクトリを作成
+        std::fs::create_dir_all(local_dir)
+            .with_context(|| format!("ローカルディレクトリの作成に失敗: {:?}", local_dir))?;
+
+        let mut total_files = 0;
+
+        // リモートディレクトリを読み取り（配下のディレクトリが読み取れない場合はスキップして続ける）
+        let entries = match sftp.readdir(remote_dir) {
+            Ok(entries) => entries,
+            Err(e) if depth > 0 && Self::is_inaccessible_sftp_error(&e) => {
+                tracing::warn!("読み取れないフォルダをスキップ: {:?}: {}", remote_dir, e);
+                return Ok(0);
+            }
+            Err(e) => return Err(e).with_context(|| format!("リモートディレクトリの読み取りに失敗: {:?}", remote_dir)),
+        };
+
+        for (entry_path, stat) in entries {
+            if let Some(entry_name) = entry_path.file_name() {
+                // 隠しファイル/ディレクトリをスキップ（. で始まるもの）
+                if let Some(name_str) = entry_name.to_str() {
+                    if name_str.starts_with('.') {
+                        continue;
+                    }
+                }
+
+                let local_entry_path = local_dir.join(entry_name);
+
+                if stat.is_file() {
+                    // ファイルをダウンロード（個別ファイルに10分のタイムアウト）
+                    let file_transfer = async {
+                        let mut remote_file = sftp.open(&entry_path)
+                            .with_context(|| format!("リモートファイルのオープンに失敗: {:?}", entry_path))?;
+
+                        let mut local_file = std::fs::File::create(&local_entry_path)
+                            .with_context(|| format!("ローカルファイルの作成に失敗: {:?}", local_entry_path))?;
+
+                        // 最適化された転送関数を使用（128KBバッファ）- 転送バイト数を返す
+                        let transferred = Self::transfer_file_optimized(&mut remote_file, &mut local_file)
+                            .with_context(|| format!("ファイル転送に失敗: {:?}", entry_path))?;
+
+                        Ok::<u64, anyhow::Error>(transferred)
+                    };
+
+                    let _transferred = timeout(Duration::from_secs(600), file_transfer)
+                        .await
+                        .with_context(|| format!("ファイル転送がタイムアウトしました: {:?}", entry_path))??;
+
+                    // 注: この関数は進捗コールバックなしバージョンのため、transferred_bytesは使用しない
+                    total_files += 1;
+
+                } else if stat.is_dir() {
+                    // ディレクトリを再帰的に処理
+                    let sub_files = self.backup_directory_recursive(
+                        sftp,
+                        &entry_path,
+                        &local_entry_path,
+                        depth + 1
+                    ).await?;
+
+                    total_files += sub_files;
+                }
+            }
+        }
+
+        Ok(total_files)
+        })
+    }
+
+    /// 進捗レポート対応の再帰的ディレクトリバックアップ
+    #[allow(clippy::too_many_arguments)]
+    fn backup_directory_recursive_with_cancel_and_progress<'a, F>(
+        &'a self,
+        sftp: &'a ssh2::Sftp,
+        remote_dir: &'a Path,
+        local_dir: &'a Path,
+        depth: usize,
+        control: &'a BackupControl,
+        options: &'a BackupOptions,
+        state: &'a mut TransferState,
+        progress_callback: Arc<F>,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        Box::pin(async move {
+        // キャンセル確認
+        if control.is_cancelled() {
+            return Err(anyhow::anyhow!("🚫 バックアップがキャンセルされました"));
+        }
+
+        // 深すぎる再帰を防ぐ（無限ループ対策）
+        if depth > 50 {
+            return Err(anyhow::anyhow!("ディレクトリの階層が深すぎます: {}", remote_dir.display()));
+        }
+
+        // 再接続前に処理を終えたディレクトリは処理し直さない
+        if state.completed_dirs.contains(remote_dir) {
+            return Ok(());
+        }
+
+        Self::create_local_dir_with_progress(local_dir, state, &*progress_callback)?;
+
+        let Some(entries) = Self::read_dir_entries(sftp, remote_dir, local_dir, depth, control, options, state, &*progress_callback)? else {
+            return Ok(());
+        };
+
+        // 同じローカルディレクトリに書き込む名前（大文字・小文字の衝突検出用）
+        let mut used_names = HashSet::new();
+
+        for entry in entries {
+            let (entry_path, stat) = entry?;
+
+            // 一時停止中は再開またはキャンセルまで待機
+            Self::wait_while_paused(control, state, &*progress_callback).await;
+
+            // キャンセル確認
+            if control.is_cancelled() {
+                return Err(anyhow::anyhow!("🚫 バックアップがキャンセルされました"));
+            }
+
+            if Self::skips_entry(&entry_path, &stat, options, state) {
+                continue;
+            }
+
+            let Some(local_entry_path) = Self::local_entry_path(local_dir, &entry_path, &stat, &mut used_names, options, state, &*progress_callback)? else {
+                continue;
+            };
+
+            if stat.is_file() {
+                // スキップしたファイルも含め、最後まで処理したファイルを再接続後の再開用に記録する
+                self.backup_entry_file(sftp, remote_dir, &entry_path, &local_entry_path, &stat, control, options, state, &*progress_callback)?;
+                state.mark_processed(entry_path);
+            } else if stat.is_dir() {
+                // ディレクトリを再帰的に処理
+                self.backup_directory_recursive_with_cancel_and_progress(
+                    sftp,
+                    &entry_path,
+                    &local_entry_path,
+                    depth + 1,
+                    control,
+                    options,
+                    state,
+                    progress_callback.clone()
+                ).await?;
+            }
+        }
+
+        // 完了したディレクトリはまとめて記録し、中のファイルの記録は破棄する
+        state.processed_files.retain(|path| path.parent() != Some(remote_dir));
+        state.completed_dirs.insert(remote_dir.to_path_buf());
+        if let Some(checkpoint) = &mut state.checkpoint {
+            checkpoint.record_completed_dir(remote_dir);
+        }
+
+        Ok(())
+        })
+    }
+
+    /// ローカルディレクトリを作成（深いツリーでは作成に時間がかかるため、作成数も進捗として通知する）
+    fn create_local_dir_with_progress<F>(local_dir: &Path, state: &mut TransferState, progress_callback: &F) -> Result<()>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        if local_dir.is_dir() {
+            return Ok(());
+        }
+
+        std::fs::create_dir_all(local_dir)
+            .with_context(|| format!("ローカルディレクトリの作成に失敗: {:?}", local_dir))?;
+        state.created_dirs += 1;
+
+        if state.throttle.should_update(state.transferred_bytes) {
+            progress_callback(BackupProgress {
+                phase: "ディレクトリ作成中".to_string(),
+                transferred_files: state.transferred_files,
+                transferred_bytes: state.transferred_bytes,
+                total_bytes: state.total_bytes,
+                current_file: Some(Self::display_path(local_dir)),
+                elapsed_seconds: state.throttle.get_elapsed_seconds(),
+                percent_complete: BackupProgress::calculate_percent(state.transferred_bytes, state.total_bytes),
+                created_directories: state.created_dirs,
+                ..Default::default()
+            });
+        }
+        Ok(())
+    }
+
+    /// リモートディレクトリを読み取る（エントリが非常に多くても進捗を通知する。低メモリモードでは読み取りながら転送する）
+    ///
+    /// 配下のディレクトリが権限などで読み取れない場合は、そのディレクトリだけスキップして None を返す
+    #[allow(clippy::too_many_arguments)]
+    fn read_dir_entries<F>(
+        sftp: &ssh2::Sftp,
+        remote_dir: &Path,
+        local_dir: &Path,
+        depth: usize,
+        control: &BackupControl,
+        options: &BackupOptions,
+        state: &mut TransferState,
+        progress_callback: &F,
+    ) -> Result<Option<RemoteDirEntries>>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        let entries = if options.low_memory {
+            RemoteDirEntries::read(sftp, remote_dir, true)
+                .with_context(|| format!("リモートディレクトリの読み取りに失敗: {:?}", remote_dir))
+        } else {
+            Self::read_remote_dir_with_progress(sftp, remote_dir, control, state, progress_callback)
+                .map(|entries| RemoteDirEntries::Listed(entries.into_iter()))
+        };
+        match entries {
+            Ok(entries) => Ok(Some(entries)),
+            Err(e) if depth > 0 && Self::is_inaccessible_dir_error(&e) => {
+                tracing::warn!("読み取れないフォルダをスキップ: {:?}: {:#}", remote_dir, e);
+                state.inaccessible_dirs.push((remote_dir.to_path_buf(), format!("{:#}", e)));
+                state.inaccessible_local_dirs.insert(local_dir.to_path_buf());
+                state.completed_dirs.insert(remote_dir.to_path_buf());
+                progress_callback(BackupProgress {
+                    phase: "読み取れないフォルダをスキップ".to_string(),
+                    transferred_files: state.transferred_files,
+                    transferred_bytes: state.transferred_bytes,
+                    total_bytes: state.total_bytes,
+                    current_file: Some(remote_dir.to_string_lossy().to_string()),
+                    elapsed_seconds: state.throttle.get_elapsed_seconds(),
+                    percent_complete: BackupProgress::calculate_percent(state.transferred_bytes, state.total_bytes),
+                    inaccessible_dirs: state.inaccessible_dirs.len(),
+                    ..Default::default()
+                });
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 処理済み・隠しファイル・特殊ファイルなど、保存先を決める前にスキップするエントリか
+    fn skips_entry(entry_path: &Path, stat: &ssh2::FileStat, options: &BackupOptions, state: &mut TransferState) -> bool {
+        // 再接続前に処理を終えたファイルは数え直さない
+        if state.processed_files.contains(entry_path) {
+            return true;
+        }
+
+        // チェックポイントから再開する場合は、前回処理を終えたエントリを飛ばす
+        if let Some(resume_after_dir) = &options.resume_after_dir {
+            if BackupCheckpoint::is_done(resume_after_dir, entry_path) {
+                return true;
+            }
+        }
+
+        // 隠しファイル/ディレクトリをスキップ（. で始まるもの。always_include に一致するものは転送）
+        let Some(entry_name) = entry_path.file_name() else {
+            return true;
+        };
+        if entry_name.to_str().is_some_and(|name| options.skips_hidden(name)) {
+            return true;
+        }
+
+        // ソケット・FIFO・デバイス・シンボリックリンクなどは読み取りで停止する恐れがあるためスキップ
+        if !stat.is_file() && !stat.is_dir() {
+            tracing::info!("特殊ファイルをスキップ: {:?} ({})", entry_path, Self::special_file_kind(stat));
+            state.skipped_special_files += 1;
+            state.mark_processed(entry_path.to_path_buf());
+            return true;
+        }
+        false
+    }
+
+    /// エントリの保存先を決める
+    ///
+    /// 除外パターンに一致する場合と、種類の異なる同名エントリがローカルにあるためスキップする場合は None
+    #[allow(clippy::too_many_arguments)]
+    fn local_entry_path<F>(
+        local_dir: &Path,
+        entry_path: &Path,
+        stat: &ssh2::FileStat,
+        used_names: &mut HashSet<String>,
+        options: &BackupOptions,
+        state: &mut TransferState,
+        progress_callback: &F,
+    ) -> Result<Option<PathBuf>>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        let entry_name = entry_path.file_name().context("エントリ名がありません")?;
+        let local_name = if state.case_insensitive {
+            Self::resolve_case_collision(entry_name, entry_path, used_names, options, state, progress_callback)?
+        } else {
+            entry_name.to_os_string()
+        };
+        let local_entry_path = local_dir.join(local_name);
+        // 暗号化する場合は `.enc` を付けた名前で保存する
+        let local_entry_path = if stat.is_file() && options.encrypt {
+            Self::encrypted_path(&local_entry_path)
+        } else {
+            local_entry_path
+        };
+
+        // 除外パターンに一致するものは転送しない（ディレクトリなら配下も）。
+        // ローカルにある同名のものはミラー削除でも残す（rsync の --exclude と同じ）
+        if options.is_ignored(entry_path, stat.is_dir()) {
+            state.mark_processed(entry_path.to_path_buf());
+            return Ok(None);
+        }
+
+        // スキップするエントリも含め、リモートにあるものはミラー削除の対象外
+        if options.mirror_delete {
+            state.remote_entries.insert(local_entry_path.clone());
+        }
+
+        // リモートとローカルでファイル・ディレクトリの種類が異なる場合はこのエントリだけスキップ
+        let type_mismatch = (stat.is_file() && local_entry_path.is_dir())
+            || (stat.is_dir() && local_entry_path.is_file());
+        if type_mismatch {
+            tracing::warn!("ローカルに種類の異なる同名エントリがあるためスキップ: {:?}", local_entry_path);
+            state.type_mismatches += 1;
+            state.mark_processed(entry_path.to_path_buf());
+            progress_callback(BackupProgress {
+                phase: "種類の不一致".to_string(),
+                transferred_files: state.transferred_files,
+                transferred_bytes: state.transferred_bytes,
+                current_file: Some(Self::display_path(&local_entry_path)),
+                elapsed_seconds: state.throttle.get_elapsed_seconds(),
+                ..Default::default()
+            });
+            return Ok(None);
+        }
+        Ok(Some(local_entry_path))
+    }
+
+    /// ファイルを1つ処理する（差分・上書きポリシー・参照バックアップによるスキップ、ダウンロード、記録）
+    ///
+    /// `continue_on_error` の場合、セッションが切れていなければ失敗を記録して続ける
+    #[allow(clippy::too_many_arguments)]
+    fn backup_entry_file<F>(
+        &self,
+        sftp: &ssh2::Sftp,
+        remote_dir: &Path,
+        entry_path: &Path,
+        local_entry_path: &Path,
+        stat: &ssh2::FileStat,
+        control: &BackupControl,
+        options: &BackupOptions,
+        state: &mut TransferState,
+        progress_callback: &F,
+    ) -> Result<()>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        // 進捗報告（スロットル制御付き - 正確な転送バイト数で更新）
+        if state.throttle.should_update(state.transferred_bytes) {
+            progress_callback(Self::file_transfer_progress(state, entry_path));
+        }
+
+        // ファイルサイズ取得（Noneの場合は0として扱う）
+        let file_size = stat.size.unwrap_or(0);
+
+        match self.incremental_skip_reason(options, state, entry_path, local_entry_path, file_size, stat.mtime) {
+            Some(IncrementalSkip::TooLarge) => {
+                tracing::info!("サイズ上限によりスキップ: {:?} ({} バイト)", entry_path, file_size);
+                state.skipped_large_files += 1;
+                return Ok(());
+            }
+            Some(IncrementalSkip::OutsideAgeWindow) => {
+                state.skipped_by_age += 1;
+                return Ok(());
+            }
+            Some(IncrementalSkip::Unmodified) => {
+                state.skipped_unmodified_files += 1;
+                return Ok(());
+            }
+            None => {}
+        }
+
+        // 保存先に同名のファイルがあれば上書きポリシーに従って保存先を決める
+        let Some(target_path) = self.resolve_overwrite(sftp, entry_path, local_entry_path, file_size, options, state)? else {
+            return Ok(());
+        };
+
+        // 参照バックアップに同じファイルがあればハードリンクで済ませる
+        if Self::link_from_reference(options, state, &target_path, file_size, stat.mtime) {
+            state.linked_files += 1;
+            state.transferred_files += 1;
+            Self::record_in_index(options, state, &target_path, file_size, stat.mtime);
+            return Ok(());
+        }
+
+        // ファイルをダウンロード（ファイルサイズに応じた動的タイムアウト）
+        let encryptor = state.encryption.as_ref().map(BackupEncryption::file_encryptor);
+        let transferred = match Self::download_file(sftp, entry_path, &target_path, file_size, stat.mtime, options, encryptor.as_ref()) {
+            Ok(transferred) => transferred,
+            // セッションが切れた場合は再接続のためにエラーを返す
+            Err(e) if options.continue_on_error
+                && !control.is_cancelled()
+                && !self.is_session_lost(sftp, &remote_dir.to_string_lossy()) =>
+            {
+                Self::record_failed_file(state, entry_path, e, progress_callback);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+
+        state.transferred_bytes += transferred;
+        state.transferred_files += 1;
+        Self::record_encrypted(state, &target_path, encryptor, file_size);
+        self.queue_checksum_verification(state, entry_path, &target_path);
+        Self::record_in_index(options, state, &target_path, file_size, stat.mtime);
+        Ok(())
+    }
+
+    /// ファイル転送中の進捗（並行集計中は総数が増えていくため進捗率は出さない）
+    fn file_transfer_progress(state: &mut TransferState, entry_path: &Path) -> BackupProgress {
+        let (total_files, counting) = state.sync_concurrent_count();
+        BackupProgress {
+            phase: "ファイル転送中".to_string(),
+            transferred_files: state.transferred_files,
+            total_files,
+            transferred_bytes: state.transferred_bytes,
+            total_bytes: state.total_bytes,
+            current_file: Some(entry_path.to_string_lossy().to_string()),
+            elapsed_seconds: state.throttle.get_elapsed_seconds(),
+            transfer_speed: state.throttle.calculate_speed(state.transferred_bytes),
+            average_speed: state.throttle.calculate_average_speed(state.transferred_bytes),
+            percent_complete: if counting {
+                None
+            } else {
+                BackupProgress::calculate_percent(state.transferred_bytes, state.total_bytes)
+            },
+            skipped_special_files: state.skipped_special_files,
+            counting,
+            ..Default::default()
+        }
+    }
+
+    /// フォルダを再帰的に転送し、途中でセッションが切れた場合は再接続して続きから転送する
+    ///
+    /// エラーの後にセッションが応答しなければ切断とみなし、`MAX_SESSION_RECONNECTS` 回まで
+    /// 再接続する。再接続後は処理を終えたディレクトリ・ファイルを飛ばし、転送途中のファイルは
+    /// `resume` が有効なら `.kyosho-part` から再開する
+    #[allow(clippy::too_many_arguments)]
+    pub(super) async fn backup_directory_with_reconnect<F>(
+        &mut self,
+        sftp: &ssh2::Sftp,
+        remote_path: &str,
+        local_path: &str,
+        control: &BackupControl,
+        options: &BackupOptions,
+        state: &mut TransferState,
+        progress_callback: Arc<F>,
+    ) -> Result<()>
+    where
+        F: Fn(BackupProgress) + Send + Sync + 'static,
+    {
+        let mut reconnected_sftp: Option<ssh2::Sftp> = None;
+
+        loop {
+            let current_sftp = reconnected_sftp.as_ref().unwrap_or(sftp);
+            let result = self.backup_directory_recursive_with_cancel_and_progress(
+                current_sftp,
+                Path::new(remote_path),
+                Path::new(local_path),
+                0,
+                control,
+                options,
+                state,
+                progress_callback.clone()
+            ).await;
+
+            let Err(error) = result else {
+                return Ok(());
+            };
+            if control.is_cancelled() || !self.is_session_lost(current_sftp, remote_path) {
+                return Err(error);
+            }
+
+            // 再接続できるまで上限回数まで試す（失敗した試行も1回と数える）
+            let mut last_error = error;
+            loop {
+                if state.reconnects >= MAX_SESSION_RECONNECTS {
+                    return Err(last_error.context(format!(
+                        "セッションが切れたため {} 回再接続しましたが、転送を続けられませんでした",
+                        state.reconnects
+                    )));
+                }
+                state.reconnects += 1;
+                tracing::warn!("セッションが切れたため再接続します（{}/{}回目）: {:#}", state.reconnects, MAX_SESSION_RECONNECTS, last_error);
+
+                progress_callback(BackupProgress {
+                    phase: "再接続中".to_string(),
+                    transferred_files: state.transferred_files,
+                    transferred_bytes: state.transferred_bytes,
+                    total_bytes: state.total_bytes,
+                    elapsed_seconds: state.throttle.get_elapsed_seconds(),
+                    reconnect_attempts: state.reconnects,
+                    ..Default::default()
+                });
+
+                let backoff = CONNECT_RETRY_BACKOFF_BASE * 2u32.pow(state.reconnects as u32 - 1);
+                tokio::time::sleep(backoff.min(CONNECT_RETRY_BACKOFF_MAX)).await;
+                if control.is_cancelled() {
+                    return Err(anyhow::anyhow!("🚫 バックアップがキャンセルされました"));
+                }
+
+                // 古いセッションのSFTPチャンネルを先に閉じてから接続し直す
+                reconnected_sftp = None;
+                self.session = None;
+                let reconnected = match self.test_connection().await {
+                    Ok(_) => self.session
+                        .as_ref()
+                        .context("SSHセッションが確立されていません")
+                        .and_then(|session| session.sftp().context("SFTPセッションの作成に失敗しました")),
+                    Err(e) => Err(e),
+                };
+                match reconnected {
+                    Ok(new_sftp) => {
+                        tracing::info!("再接続しました。続きから転送します: {}", remote_path);
+                        self.set_transfer_session_timeout(options);
+                        reconnected_sftp = Some(new_sftp);
+                        break;
+                    }
+                    Err(e) => last_error = e,
+                }
+            }
+        }
+    }
+
+    /// エラーの後にセッションが応答するか確認し、応答しなければ切断されたとみなす
+    ///
+    /// ローカルの書き込みエラーやファイル単位のエラーでは再接続しないよう、
+    /// リモートのバックアップ元を stat して判定する
+    fn is_session_lost(&self, sftp: &ssh2::Sftp, remote_path: &str) -> bool {
+        let Some(session) = &self.session else {
+            return true;
+        };
+
+        // 切断されたソケットで待ち続けないよう、確認の間だけタイムアウトを設定する
+        let previous_timeout = session.timeout();
+        session.set_timeout(SESSION_PROBE_TIMEOUT.as_millis() as u32);
+        let alive = sftp.stat(Path::new(remote_path)).is_ok();
+        session.set_timeout(previous_timeout);
+
+        !alive
+    }
+
+    /// 転送中のセッションにタイムアウトを設定する
+    ///
+    /// libssh2 の読み書きはブロッキングで tokio のタイムアウトが効かないため、
+    /// セッション側で打ち切ってエラーにし、切断の確認と再接続に回す
+    pub(super) fn set_transfer_session_timeout(&self, options: &BackupOptions) {
+        if let Some(session) = &self.session {
+            let limit = TRANSFER_SESSION_TIMEOUT.mul_f64(options.file_timeout_multiplier());
+            session.set_timeout(limit.as_millis().min(u32::MAX as u128) as u32);
+        }
+    }
+
+    /// リモートディレクトリのエントリを1件ずつ読み取る
+    ///
+    /// `sftp.readdir` は全件を読み終えるまで戻らないため、数万件のフォルダでは止まって見える。
+    /// 読み取り中も転送と同じ間隔で「ディレクトリ読み取り中」の進捗（読み取った件数）を通知し、
+    /// キャンセルにも応じる
+    fn read_remote_dir_with_progress<F>(
+        sftp: &ssh2::Sftp,
+        remote_dir: &Path,
+        control: &BackupControl,
+        state: &mut TransferState,
+        progress_callback: &F,
+    ) -> Result<Vec<(PathBuf, ssh2::FileStat)>>
+    where
+        F: Fn(BackupProgress),
+    {
+        let mut dir = sftp.opendir(remote_dir)
+            .with_context(|| format!("リモートディレクトリの読み取りに失敗: {:?}", remote_dir))?;
+
+        let mut entries = Vec::new();
+        loop {
+            if control.is_cancelled() {
+                return Err(anyhow::anyhow!("🚫 バックアップがキャンセルされました"));
+            }
+
+            let (file_name, stat) = match dir.readdir() {
+                Ok(entry) => entry,
+                Err(e) if e.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_FILE) => break,
+                Err(e) => return Err(e).with_context(|| format!("リモートディレクトリの読み取りに失敗: {:?}", remote_dir)),
+            };
+
+            if file_name == Path::new(".") || file_name == Path::new("..") {
+                continue;
+            }
+            entries.push((remote_dir.join(file_name), stat));
+
+            if state.throttle.should_update(state.transferred_bytes) {
+                progress_callback(BackupProgress {
+                    phase: "ディレクトリ読み取り中".to_string(),
+                    transferred_files: state.transferred_files,
+                    listed_entries: Some(entries.len()),
+                    transferred_bytes: state.transferred_bytes,
+                    total_bytes: state.total_bytes,
+                    current_file: Some(remote_dir.to_string_lossy().to_string()),
+                    elapsed_seconds: state.throttle.get_elapsed_seconds(),
+                    percent_complete: BackupProgress::calculate_percent(state.transferred_bytes, state.total_bytes),
+                    ..Default::default()
+                });
+            }
+        }
+
+        // チェックポイントから続きを判定できるよう、毎回同じ順（名前順）に処理する
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        Ok(entries)
+    }
+
+    /// 転送に失敗したファイルを記録して続ける（`continue_on_error` 有効時）
+    ///
+    /// 転送は一時ファイルに書き込んでから置き換えるため、保存先にある前回のバックアップはそのまま残る
+    fn record_failed_file<F>(
+        state: &mut TransferState,
+        remote_path: &Path,
+        error: anyhow::Error,
+        progress_callback: &F,
+    ) where
+        F: Fn(BackupProgress),
+    {
+        tracing::warn!("ファイルの転送に失敗したため、スキップして続けます: {:?}: {:#}", remote_path, error);
+        state.failed_files.push((remote_path.to_path_buf(), format!("{:#}", error)));
+
+        progress_callback(BackupProgress {
+            phase: "転送に失敗したファイルをスキップ".to_string(),
+            transferred_files: state.transferred_files,
+            transferred_bytes: state.transferred_bytes,
+            total_bytes: state.total_bytes,
+            current_file: Some(remote_path.to_string_lossy().to_string()),
+            elapsed_seconds: state.throttle.get_elapsed_seconds(),
+            percent_complete: BackupProgress::calculate_percent(state.transferred_bytes, state.total_bytes),
+            failed_files: state.failed_files.len(),
+            ..Default::default()
+        });
+    }
+
+    /// キャンセル対応の再帰的ディレクトリバックアップ（進捗なし）
+    #[allow(clippy::too_many_arguments)]
+    fn backup_directory_recursive_with_cancel<'a>(
+        &'a self,
+        sftp: &'a ssh2::Sftp,
+        remote_dir: &'a Path,
+        local_dir: &'a Path,
+        depth: usize,
+        control: &'a BackupControl,
+        options: &'a BackupOptions,
+        state: &'a mut TransferState,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        // 進捗レポートなしで実行
+        self.backup_directory_recursive_with_cancel_and_progress(
+            sftp, remote_dir, local_dir, depth, control, options, state, Arc::new(|_| {})
+        )
+    }
+}
@@ -0,0 +1,400 @@
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// 秘密鍵ファイルの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum KeyFormat {
+    OpenSsh,
+    Pem,
+    Unknown,
+}
+
+impl KeyFormat {
+    /// エラーメッセージ等で使う表示名
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeyFormat::OpenSsh => "OpenSSH",
+            KeyFormat::Pem => "PEM",
+            KeyFormat::Unknown => "不明",
+        }
+    }
+}
+
+/// 設定に保存した秘密鍵の内容
+///
+/// ログに出ないよう Debug では伏せ、破棄するときにメモリ上の内容を0で上書きする
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StoredPrivateKey(String);
+
+impl StoredPrivateKey {
+    /// 秘密鍵ファイルの内容を検証して取り込む（パスフレーズ付きの鍵は取り込めない）
+    pub fn import(key_path: &Path) -> Result<Self> {
+        let key = Self(
+            std::fs::read_to_string(key_path)
+                .with_context(|| format!("秘密鍵ファイルの読み取りに失敗しました: {}", key_path.display()))?,
+        );
+
+        let format = key.format();
+        if format == KeyFormat::Unknown {
+            return Err(anyhow::anyhow!("秘密鍵の形式を判定できません: {}", key_path.display()));
+        }
+        if is_passphrase_protected(key.expose(), format) {
+            return Err(anyhow::anyhow!("パスフレーズで保護された鍵は設定に保存できません: {}", key_path.display()));
+        }
+        Ok(key)
+    }
+
+    /// 鍵の内容（認証に渡すときだけ使う）
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn format(&self) -> KeyFormat {
+        detect_key_format(&self.0)
+    }
+}
+
+impl std::fmt::Debug for StoredPrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StoredPrivateKey(***)")
+    }
+}
+
+impl Drop for StoredPrivateKey {
+    fn drop(&mut self) {
+        wipe_string(std::mem::take(&mut self.0));
+    }
+}
+
+/// 秘密鍵などを含むバッファを0で上書きする
+pub fn wipe_bytes(bytes: &mut [u8]) {
+    bytes.fill(0);
+    // 解放直前の書き込みが最適化で省かれないようにする
+    std::hint::black_box(bytes);
+}
+
+/// 文字列の内容を0で上書きしてから解放する
+pub fn wipe_string(value: String) {
+    wipe_bytes(&mut value.into_bytes());
+}
+
+/// 設定に保存した鍵を認証のために一時的に書き出したファイル（破棄時に上書きして削除）
+///
+/// libssh2 がメモリからの認証に対応していない環境と、rsync（ssh コマンド）に鍵を渡す場合に使う
+pub struct TemporaryKeyFile(std::path::PathBuf);
+
+impl TemporaryKeyFile {
+    pub fn write(key: &StoredPrivateKey) -> Result<Self> {
+        use std::io::Write;
+
+        let path = crate::data_dir::data_dir()?.join(format!("kyosho-key-{}", rand::random::<u64>()));
+        let mut open_options = std::fs::OpenOptions::new();
+        open_options.write(true).create_new(true);
+        // ssh コマンドは他のユーザーが読める鍵を拒否するため、作成時から本人だけが読めるようにする
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut open_options, 0o600);
+        let mut file = open_options
+            .open(&path)
+            .context("秘密鍵の一時ファイルの作成に失敗しました")?;
+        let temp = Self(path);
+
+        // 既定のアクセス権のまま鍵を書き込まないよう、空のうちに本人だけが読めるようにする
+        restrict_to_current_user(&temp.0)?;
+        file.write_all(key.expose().as_bytes())
+            .and_then(|()| file.sync_all())
+            .context("秘密鍵の一時ファイルの書き込みに失敗しました")?;
+        Ok(temp)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+/// ファイルのアクセス権を現在のユーザーのみに制限する（継承したアクセス権を外す）
+#[cfg(windows)]
+fn restrict_to_current_user(path: &Path) -> Result<()> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let user = match (std::env::var("USERDOMAIN"), std::env::var("USERNAME")) {
+        (Ok(domain), Ok(name)) => format!("{}\\{}", domain, name),
+        (Err(_), Ok(name)) => name,
+        _ => return Err(anyhow::anyhow!("現在のユーザー名を取得できないため、秘密鍵の一時ファイルを保護できません")),
+    };
+
+    let output = std::process::Command::new("icacls")
+        .arg(path)
+        .args(["/inheritance:r", "/grant:r"])
+        .arg(format!("{}:F", user))
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .context("秘密鍵の一時ファイルのアクセス権の設定に失敗しました")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "秘密鍵の一時ファイルのアクセス権の設定に失敗しました: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Unix では作成時にアクセス権を指定しているため何もしない
+#[cfg(not(windows))]
+fn restrict_to_current_user(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+impl Drop for TemporaryKeyFile {
+    fn drop(&mut self) {
+        if let Ok(len) = std::fs::metadata(&self.0).map(|metadata| metadata.len()) {
+            let _ = std::fs::write(&self.0, vec![0u8; len as usize]);
+        }
+        if let Err(e) = std::fs::remove_file(&self.0) {
+            tracing::warn!("秘密鍵の一時ファイルの削除に失敗: {:?}: {}", self.0, e);
+        }
+    }
+}
+
+/// 秘密鍵のセキュリティチェック結果（UIのプリフライトチェックリスト用）
+#[derive(Debug, Serialize)]
+pub struct KeySecurityReport {
+    pub key_path: String,
+    pub exists: bool,
+    pub format: KeyFormat,
+    /// 権限が安全か（チェックできない環境ではNone）
+    pub permissions_safe: Option<bool>,
+    /// 現在の権限（8進数表記、Unixのみ）
+    pub permission_mode: Option<String>,
+    pub permission_check_skipped: bool,
+    pub passphrase_protected: bool,
+    pub warnings: Vec<String>,
+}
+
+/// 秘密鍵の内容から形式を判定
+pub fn detect_key_format(key_content: &str) -> KeyFormat {
+    if key_content.contains("BEGIN OPENSSH PRIVATE KEY") {
+        KeyFormat::OpenSsh
+    } else if key_content.contains("BEGIN RSA PRIVATE KEY")
+        || key_content.contains("BEGIN PRIVATE KEY")
+        || key_content.contains("BEGIN ENCRYPTED PRIVATE KEY")
+        || key_content.contains("BEGIN EC PRIVATE KEY")
+        || key_content.contains("BEGIN DSA PRIVATE KEY")
+    {
+        KeyFormat::Pem
+    } else {
+        KeyFormat::Unknown
+    }
+}
+
+/// 秘密鍵ファイルの権限を取得（Unixのみ、他の環境ではNone）
+///
+/// 戻り値は (安全かどうか, 権限ビット)
+pub fn check_key_permissions(key_path: &Path) -> Result<Option<(bool, u32)>> {
+    let metadata = std::fs::metadata(key_path)
+        .context("秘密鍵ファイルのメタデータ取得に失敗しました")?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = metadata.permissions().mode() & 0o777;
+        Ok(Some((mode & 0o077 == 0, mode)))
+    }
+
+    #[cfg(not(unix))]
+    {
+        // WindowsのACLは検査しない
+        let _ = metadata;
+        Ok(None)
+    }
+}
+
+/// 秘密鍵がパスフレーズで保護されているか判定
+pub fn is_passphrase_protected(key_content: &str, format: KeyFormat) -> bool {
+    match format {
+        KeyFormat::Pem => {
+            key_content.contains("Proc-Type: 4,ENCRYPTED")
+                || key_content.contains("BEGIN ENCRYPTED PRIVATE KEY")
+        }
+        KeyFormat::OpenSsh => openssh_cipher_name(key_content)
+            .map(|cipher| cipher != "none")
+            .unwrap_or(false),
+        KeyFormat::Unknown => false,
+    }
+}
+
+/// OpenSSH形式の鍵から暗号化方式名を取り出す
+///
+/// 形式: "openssh-key-v1\0" + string ciphername + ...（stringは4バイト長＋本体）
+fn openssh_cipher_name(key_content: &str) -> Option<String> {
+    let body: String = key_content
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .map(str::trim)
+        .collect();
+
+    let decoded = general_purpose::STANDARD.decode(body).ok()?;
+
+    const MAGIC: &[u8] = b"openssh-key-v1\0";
+    let rest = decoded.strip_prefix(MAGIC)?;
+    let len_bytes: [u8; 4] = rest.get(..4)?.try_into().ok()?;
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    let name = rest.get(4..4 + len)?;
+
+    String::from_utf8(name.to_vec()).ok()
+}
+
+/// 秘密鍵ファイルのセキュリティ状態をまとめて確認
+pub fn check_key_security(key_path: &str) -> Result<KeySecurityReport> {
+    let path = Path::new(key_path);
+    let mut report = KeySecurityReport {
+        key_path: key_path.to_string(),
+        exists: path.exists(),
+        format: KeyFormat::Unknown,
+        permissions_safe: None,
+        permission_mode: None,
+        permission_check_skipped: false,
+        passphrase_protected: false,
+        warnings: Vec::new(),
+    };
+
+    if !report.exists {
+        report.warnings.push(format!("秘密鍵ファイルが見つかりません: {}", key_path));
+        return Ok(report);
+    }
+
+    match check_key_permissions(path)? {
+        Some((safe, mode)) => {
+            report.permissions_safe = Some(safe);
+            report.permission_mode = Some(format!("{:o}", mode));
+            if !safe {
+                report.warnings.push(format!(
+                    "秘密鍵ファイルの権限が安全でありません (現在: {:o})。chmod 600 {} を実行してください。",
+                    mode, key_path
+                ));
+            }
+        }
+        None => {
+            report.permission_check_skipped = true;
+        }
+    }
+
+    let key_content = std::fs::read_to_string(path)
+        .context("秘密鍵ファイルの読み取りに失敗しました")?;
+
+    report.format = detect_key_format(&key_content);
+    report.passphrase_protected = is_passphrase_protected(&key_content, report.format);
+
+    match report.format {
+        KeyFormat::OpenSsh => report.warnings.push(format!(
+            "X-Serverでは PEM 形式の鍵が推奨されています。以下のコマンドで変換できます:\nssh-keygen -p -m PEM -f {}",
+            key_path
+        )),
+        KeyFormat::Unknown => report.warnings.push("秘密鍵の形式を判別できませんでした".to_string()),
+        KeyFormat::Pem => {}
+    }
+
+    Ok(report)
+}
+
+/// OpenSSH形式の秘密鍵をPEM形式に変換（ssh-keygenを利用）
+///
+/// 変換前に元の鍵を `<鍵ファイル>.openssh.bak` へバックアップし、
+/// 同じパスの鍵をPEM形式に書き換える。変換後の鍵のパスを返す
+pub fn convert_key_to_pem(key_path: &str, passphrase: Option<&str>) -> Result<String> {
+    let path = Path::new(key_path);
+    if !path.exists() {
+        return Err(anyhow::anyhow!("秘密鍵ファイルが見つかりません: {}", key_path));
+    }
+
+    let key_content = std::fs::read_to_string(path)
+        .context("秘密鍵ファイルの読み取りに失敗しました")?;
+
+    match detect_key_format(&key_content) {
+        KeyFormat::Pem => return Ok(key_path.to_string()), // 変換不要
+        KeyFormat::Unknown => return Err(anyhow::anyhow!("秘密鍵の形式を判別できないため変換できません")),
+        KeyFormat::OpenSsh => {}
+    }
+
+    let passphrase = passphrase.unwrap_or("");
+    if passphrase.contains(['\n', '\r']) {
+        return Err(anyhow::anyhow!("改行を含むパスフレーズの鍵は変換できません"));
+    }
+
+    // 元の鍵をバックアップ（既存のバックアップは上書きしない）
+    let backup_path = format!("{}.openssh.bak", key_path);
+    if Path::new(&backup_path).exists() {
+        return Err(anyhow::anyhow!("バックアップファイルが既に存在します: {}", backup_path));
+    }
+    std::fs::copy(path, &backup_path)
+        .context("秘密鍵のバックアップに失敗しました")?;
+
+    // パスフレーズは変更せずに形式のみ変換する。
+    // 他のユーザーからプロセス一覧で見えないよう、パスフレーズは引数ではなく標準入力で渡す
+    // （暗号化された鍵なら現在のパスフレーズ、続けて新しいパスフレーズと確認の順に読まれる）
+    let mut input = String::new();
+    if is_passphrase_protected(&key_content, KeyFormat::OpenSsh) {
+        input.push_str(passphrase);
+        input.push('\n');
+    }
+    input.push_str(&format!("{}\n{}\n", passphrase, passphrase));
+
+    let output = run_ssh_keygen_with_input(&["-p", "-m", "PEM", "-f", key_path], &input)
+        .context("ssh-keygen の実行に失敗しました。OpenSSHがインストールされているか確認してください")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "鍵の変換に失敗しました（元の鍵は {} にバックアップされています）: {}",
+            backup_path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    // 変換結果を検証
+    let converted = std::fs::read_to_string(path)
+        .context("変換後の秘密鍵の読み取りに失敗しました")?;
+    if detect_key_format(&converted) != KeyFormat::Pem {
+        return Err(anyhow::anyhow!(
+            "変換後の鍵がPEM形式になっていません（元の鍵は {} にバックアップされています）",
+            backup_path
+        ));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .context("変換後の秘密鍵の権限設定に失敗しました")?;
+    }
+
+    Ok(key_path.to_string())
+}
+
+/// ssh-keygen を実行し、パスフレーズの入力を標準入力から渡す
+///
+/// 端末がない場合に ssh-keygen が SSH_ASKPASS の画面を開かず標準入力を読むよう、askpass を無効にする
+fn run_ssh_keygen_with_input(args: &[&str], input: &str) -> std::io::Result<std::process::Output> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new("ssh-keygen")
+        .args(args)
+        .env_remove("SSH_ASKPASS")
+        .env_remove("DISPLAY")
+        .env_remove("WAYLAND_DISPLAY")
+        .env("SSH_ASKPASS_REQUIRE", "never")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // 書き込みに失敗しても（途中で終了した場合など）、終了状態とエラー出力で判断する
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(input.as_bytes());
+    }
+
+    child.wait_with_output()
+}
@@ -0,0 +1,41 @@
+//! 単体テストで使う一時ディレクトリ
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// テスト用の一時ディレクトリ（終了時に削除）
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(label: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "kyosho-test-{}-{}-{}",
+            label,
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path).expect("一時ディレクトリの作成に失敗しました");
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// 一時ディレクトリの下にファイルを作成する（親ディレクトリも作成）
+    pub fn write(&self, relative: &str, content: &[u8]) -> PathBuf {
+        let path = self.0.join(relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("ディレクトリの作成に失敗しました");
+        }
+        std::fs::write(&path, content).expect("ファイルの作成に失敗しました");
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
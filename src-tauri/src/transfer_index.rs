@@ -0,0 +1,197 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// 保存先フォルダに置くインデックスのファイル名（隠しファイルのため転送・ミラー削除の対象外）
+pub const INDEX_FILE_NAME: &str = ".kyosho-index.json";
+
+/// 前回転送したときのリモートのファイル情報
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub size: u64,
+    pub mtime: Option<u64>,
+    /// 内容のSHA-256（ハッシュ比較が有効な場合のみ）
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+/// 保存先からの相対パス（区切りは "/"）ごとのファイル情報
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TransferIndex {
+    entries: HashMap<String, IndexEntry>,
+}
+
+/// 1回のバックアップで使うインデックス（前回の内容と、今回見たファイルの内容）
+pub struct IndexSession {
+    previous: TransferIndex,
+    current: TransferIndex,
+}
+
+impl IndexSession {
+    /// 保存先のインデックスを読み込む（ない場合や壊れている場合は空として扱う）
+    pub fn load(local_root: &Path) -> Self {
+        let path = local_root.join(INDEX_FILE_NAME);
+        let previous = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                tracing::warn!("インデックスを読み込めないため全ファイルを確認します: {:?}: {}", path, e);
+                TransferIndex::default()
+            }),
+            Err(_) => TransferIndex::default(),
+        };
+
+        Self {
+            previous,
+            current: TransferIndex::default(),
+        }
+    }
+
+    /// 前回転送したときのファイル情報
+    pub fn previous(&self, relative_path: &str) -> Option<&IndexEntry> {
+        self.previous.entries.get(relative_path)
+    }
+
+    /// 今回確認・転送したファイルの情報を記録
+    pub fn record(&mut self, relative_path: String, entry: IndexEntry) {
+        self.current.entries.insert(relative_path, entry);
+    }
+
+    /// リモートにあるが今回は転送しなかったファイルの前回の情報を、今回のインデックスに引き継ぐ
+    ///
+    /// `modified_since` などで開かずにスキップしたファイルが、完了時にインデックスから消えないようにする
+    pub fn carry_forward(&mut self, relative_path: &str) {
+        if let Some(entry) = self.previous.entries.get(relative_path) {
+            self.current.entries.entry(relative_path.to_string()).or_insert_with(|| entry.clone());
+        }
+    }
+
+    /// インデックスを保存する
+    ///
+    /// 完了した場合は今回見たファイルだけを残し（リモートから消えたファイルを除く）、
+    /// 途中で終わった場合は未確認のファイルの前回の情報も残す
+    pub fn save(mut self, local_root: &Path, completed: bool) -> Result<()> {
+        let mut index = self.current;
+        if !completed {
+            for (relative_path, entry) in self.previous.entries.drain() {
+                index.entries.entry(relative_path).or_insert(entry);
+            }
+        }
+
+        let json = serde_json::to_vec(&index)
+            .context("インデックスのシリアライズに失敗しました")?;
+
+        // 書き込み途中で中断しても壊れたインデックスを残さないよう、一時ファイルから置き換える
+        let path = local_root.join(INDEX_FILE_NAME);
+        let temp_path = local_root.join(format!("{}.tmp", INDEX_FILE_NAME));
+        std::fs::write(&temp_path, json)
+            .with_context(|| format!("インデックスの保存に失敗しました: {:?}", temp_path))?;
+        std::fs::rename(&temp_path, &path)
+            .with_context(|| format!("インデックスの保存に失敗しました: {:?}", path))?;
+        Ok(())
+    }
+}
+
+/// ローカルファイルの内容のSHA-256を16進文字列で取得
+pub fn file_sha256(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)
+        .with_context(|| format!("ファイルのオープンに失敗: {:?}", path))?;
+
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("ファイルの読み取りに失敗: {:?}", path))?;
+
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn entry(size: u64, mtime: u64) -> IndexEntry {
+        IndexEntry { size, mtime: Some(mtime), sha256: None }
+    }
+
+    /// 前回のインデックスに a.txt・b.txt・c.txt を記録した保存先
+    fn saved_index(label: &str) -> TempDir {
+        let dir = TempDir::new(label);
+        let mut session = IndexSession::load(dir.path());
+        session.record("a.txt".to_string(), entry(1, 100));
+        session.record("dir/b.txt".to_string(), entry(2, 200));
+        session.record("c.txt".to_string(), entry(3, 300));
+        session.save(dir.path(), true).unwrap();
+        dir
+    }
+
+    fn keys(dir: &TempDir) -> Vec<String> {
+        let session = IndexSession::load(dir.path());
+        let mut keys: Vec<String> = session.previous.entries.keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn completed_run_keeps_only_seen_files() {
+        let dir = saved_index("index-completed");
+
+        let mut session = IndexSession::load(dir.path());
+        session.record("a.txt".to_string(), entry(10, 101));
+        session.save(dir.path(), true).unwrap();
+
+        assert_eq!(keys(&dir), ["a.txt"]);
+        assert_eq!(IndexSession::load(dir.path()).previous("a.txt").unwrap().size, 10);
+    }
+
+    #[test]
+    fn skipped_files_are_carried_forward_across_runs() {
+        let dir = saved_index("index-carry-forward");
+
+        // modified_since で開かずにスキップした2回の実行の後も、前回の情報が残る
+        for _ in 0..2 {
+            let mut session = IndexSession::load(dir.path());
+            session.record("a.txt".to_string(), entry(10, 101));
+            session.carry_forward("dir/b.txt");
+            session.carry_forward("c.txt");
+            session.save(dir.path(), true).unwrap();
+        }
+
+        assert_eq!(keys(&dir), ["a.txt", "c.txt", "dir/b.txt"]);
+        let session = IndexSession::load(dir.path());
+        assert_eq!(session.previous("dir/b.txt").unwrap().mtime, Some(200));
+    }
+
+    #[test]
+    fn carry_forward_does_not_replace_a_new_record_or_add_unknown_files() {
+        let dir = saved_index("index-carry-forward-record");
+
+        let mut session = IndexSession::load(dir.path());
+        session.record("a.txt".to_string(), entry(10, 101));
+        session.carry_forward("a.txt");
+        session.carry_forward("new.txt");
+        session.save(dir.path(), true).unwrap();
+
+        assert_eq!(keys(&dir), ["a.txt"]);
+        assert_eq!(IndexSession::load(dir.path()).previous("a.txt").unwrap().size, 10);
+    }
+
+    #[test]
+    fn interrupted_run_keeps_unseen_files() {
+        let dir = saved_index("index-interrupted");
+
+        let mut session = IndexSession::load(dir.path());
+        session.record("a.txt".to_string(), entry(10, 101));
+        session.save(dir.path(), false).unwrap();
+
+        assert_eq!(keys(&dir), ["a.txt", "c.txt", "dir/b.txt"]);
+        assert_eq!(IndexSession::load(dir.path()).previous("a.txt").unwrap().size, 10);
+    }
+
+    #[test]
+    fn broken_index_is_treated_as_empty() {
+        let dir = TempDir::new("index-broken");
+        dir.write(INDEX_FILE_NAME, b"{not json");
+
+        assert!(keys(&dir).is_empty());
+    }
+}
@@ -0,0 +1,402 @@
+//! ローカルで起動した OpenSSH サーバーに対してバックアップを実行する結合テスト
+//!
+//! `sshd` と `ssh-keygen` が見つからない環境ではスキップする
+#![cfg(unix)]
+
+use kyosho_backup_lib::ssh_client::{BackupControl, BackupOptions, SshAlgorithms, SshAuthMethod, SshClient, SshConfig};
+use std::collections::BTreeMap;
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// パイプライン転送の対象になるサイズ（8MB超）の大容量ファイル
+const LARGE_FILE_SIZE: usize = 12 * 1024 * 1024;
+
+/// パイプライン転送と逐次転送の速度を比べるファイルのサイズ（1GB）
+const BENCHMARK_FILE_SIZE: usize = 1024 * 1024 * 1024;
+
+/// 低メモリモードのメモリ使用量を比べるフォルダのファイル数
+const HUGE_DIRECTORY_FILES: usize = 200_000;
+
+/// sshd が接続を受け付けるまで待つ時間の上限
+const SSHD_STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// テスト用の一時ディレクトリ（終了時に削除）
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(label: &str) -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let path = std::env::temp_dir().join(format!("kyosho-e2e-{}-{}-{}", label, std::process::id(), nanos));
+        std::fs::create_dir_all(&path).expect("一時ディレクトリの作成に失敗しました");
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// テスト用に起動した sshd（終了時に停止）
+struct SshServer {
+    child: Child,
+    port: u16,
+    username: String,
+    client_key: PathBuf,
+}
+
+impl Drop for SshServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// PATH と /usr/sbin から実行ファイルを探す
+fn find_executable(name: &str) -> Option<PathBuf> {
+    let path_dirs = std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).collect::<Vec<_>>())
+        .unwrap_or_default();
+    path_dirs
+        .into_iter()
+        .chain([PathBuf::from("/usr/sbin"), PathBuf::from("/usr/local/sbin")])
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+fn current_username() -> Option<String> {
+    if let Ok(user) = std::env::var("USER") {
+        return Some(user);
+    }
+    let output = Command::new("whoami").output().ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string()).filter(|user| !user.is_empty())
+}
+
+fn generate_key(ssh_keygen: &Path, path: &Path) {
+    // libssh2 が読めるよう PEM 形式で作成する
+    let status = Command::new(ssh_keygen)
+        .args(["-q", "-t", "rsa", "-b", "2048", "-m", "PEM", "-N", "", "-f"])
+        .arg(path)
+        .status()
+        .expect("ssh-keygen の実行に失敗しました");
+    assert!(status.success(), "鍵の作成に失敗しました: {}", path.display());
+}
+
+/// 一時ディレクトリに鍵と設定を作って sshd を起動する（起動できない環境では None）
+fn start_ssh_server(dir: &Path) -> Option<SshServer> {
+    let (Some(sshd), Some(ssh_keygen), Some(username)) =
+        (find_executable("sshd"), find_executable("ssh-keygen"), current_username())
+    else {
+        eprintln!("sshd または ssh-keygen が見つからないためスキップします");
+        return None;
+    };
+
+    let host_key = dir.join("host_key");
+    let client_key = dir.join("client_key");
+    generate_key(&ssh_keygen, &host_key);
+    generate_key(&ssh_keygen, &client_key);
+    std::fs::copy(dir.join("client_key.pub"), dir.join("authorized_keys"))
+        .expect("authorized_keys の作成に失敗しました");
+
+    let port = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("空きポートの取得に失敗しました")
+        .port();
+
+    let config_path = dir.join("sshd_config");
+    std::fs::write(&config_path, format!(
+        "Port {port}\n\
+         ListenAddress 127.0.0.1\n\
+         HostKey {host_key}\n\
+         AuthorizedKeysFile {authorized_keys}\n\
+         PidFile {pid_file}\n\
+         StrictModes no\n\
+         PasswordAuthentication no\n\
+         KbdInteractiveAuthentication no\n\
+         UsePAM no\n\
+         Subsystem sftp internal-sftp\n",
+        host_key = host_key.display(),
+        authorized_keys = dir.join("authorized_keys").display(),
+        pid_file = dir.join("sshd.pid").display(),
+    )).expect("sshd_config の作成に失敗しました");
+
+    // sshd は絶対パスで起動する必要がある
+    let child = Command::new(&sshd)
+        .args(["-D", "-e", "-f"])
+        .arg(&config_path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("sshd の起動に失敗しました");
+    let server = SshServer { child, port, username, client_key };
+
+    let started = Instant::now();
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        if started.elapsed() > SSHD_STARTUP_TIMEOUT {
+            eprintln!("sshd が起動しないためスキップします");
+            return None;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    Some(server)
+}
+
+/// テスト用の sshd に接続するクライアント
+fn client_for(server: &SshServer) -> SshClient {
+    SshClient::new(SshConfig {
+        hostname: "127.0.0.1".to_string(),
+        port: server.port,
+        username: server.username.clone(),
+        key_path: server.client_key.to_string_lossy().to_string(),
+        connect_timeout_secs: 10,
+        prefer_ipv6: false,
+        additional_key_paths: Vec::new(),
+        jump_host: None,
+        algorithms: SshAlgorithms::default(),
+        connect_retries: 0,
+        per_attempt_timeout_secs: None,
+        auth_method: SshAuthMethod::default(),
+    })
+}
+
+/// 入れ子のフォルダ・大容量ファイル・隠しファイル・シンボリックリンクを含むバックアップ元を作成
+fn create_fixture(root: &Path) {
+    std::fs::create_dir_all(root.join("nested/deeper/deepest")).unwrap();
+    std::fs::create_dir_all(root.join("empty")).unwrap();
+    std::fs::write(root.join("index.html"), "<h1>kyosho</h1>\n").unwrap();
+    std::fs::write(root.join("nested/style.css"), "body { margin: 0; }\n").unwrap();
+    std::fs::write(root.join("nested/deeper/deepest/data.txt"), "深い階層のファイル\n").unwrap();
+
+    let large: Vec<u8> = (0..LARGE_FILE_SIZE).map(|i| (i % 251) as u8).collect();
+    std::fs::write(root.join("nested/large.bin"), large).unwrap();
+
+    // 隠しファイルとシンボリックリンクはバックアップしない
+    std::fs::write(root.join(".env"), "SECRET=1\n").unwrap();
+    std::fs::create_dir_all(root.join(".git")).unwrap();
+    std::fs::write(root.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    std::os::unix::fs::symlink(root.join("index.html"), root.join("link.html")).unwrap();
+}
+
+/// フォルダ配下のファイルの内容を相対パスごとに収集（ディレクトリは末尾 "/" で記録）
+fn read_tree(root: &Path) -> BTreeMap<String, Vec<u8>> {
+    fn walk(root: &Path, dir: &Path, tree: &mut BTreeMap<String, Vec<u8>>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let relative = path.strip_prefix(root).unwrap().to_string_lossy().to_string();
+            if path.is_dir() {
+                tree.insert(format!("{}/", relative), Vec::new());
+                walk(root, &path, tree);
+            } else {
+                tree.insert(relative, std::fs::read(&path).unwrap());
+            }
+        }
+    }
+
+    let mut tree = BTreeMap::new();
+    walk(root, root, &mut tree);
+    tree
+}
+
+#[tokio::test]
+async fn backup_folder_copies_remote_tree() {
+    let work_dir = TempDir::new("server");
+    let Some(server) = start_ssh_server(&work_dir.0) else {
+        return;
+    };
+
+    let remote_dir = TempDir::new("remote");
+    create_fixture(&remote_dir.0);
+    let local_dir = TempDir::new("local");
+    let local_path = local_dir.0.join("backup");
+
+    let mut client = client_for(&server);
+
+    let message = client
+        .backup_folder(&remote_dir.0.to_string_lossy(), &local_path.to_string_lossy())
+        .await
+        .expect("バックアップに失敗しました");
+    assert!(message.contains("転送ファイル数: 4"), "想定外の結果: {}", message);
+
+    let mut expected = read_tree(&remote_dir.0);
+    expected.retain(|relative, _| !relative.starts_with('.') && relative != "link.html");
+    assert_eq!(read_tree(&local_path), expected);
+}
+
+/// プロセスの最大使用メモリ（VmHWM）をリセットし、現在の使用メモリ（KB）を返す（Linux のみ）
+fn reset_peak_memory_kb() -> Option<u64> {
+    std::fs::write("/proc/self/clear_refs", "5").ok()?;
+    read_status_kb("VmRSS:")
+}
+
+/// /proc/self/status の値（KB）
+fn read_status_kb(key: &str) -> Option<u64> {
+    std::fs::read_to_string("/proc/self/status")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix(key))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// フォルダをバックアップし、その間の最大使用メモリの増加量（KB）を返す
+async fn peak_memory_growth_kb(server: &SshServer, remote: &Path, local: &Path, file_count: usize, low_memory: bool) -> u64 {
+    let options = BackupOptions { low_memory, ..Default::default() };
+
+    let baseline = reset_peak_memory_kb().unwrap();
+    let message = client_for(server)
+        .backup_folder_with_progress(
+            &remote.to_string_lossy(),
+            &local.to_string_lossy(),
+            std::sync::Arc::new(BackupControl::new()),
+            &options,
+            |_| {},
+        )
+        .await
+        .expect("バックアップに失敗しました");
+    let peak = read_status_kb("VmHWM:").unwrap();
+
+    assert!(message.contains(&format!("転送ファイル数: {}", file_count)), "想定外の結果: {}", message);
+    let growth = peak.saturating_sub(baseline);
+    eprintln!("low_memory={} files={}: 最大使用メモリの増加 {} KB", low_memory, file_count, growth);
+    growth
+}
+
+/// 低メモリモードの最大使用メモリの増加量がファイル数に比例しないこと、通常の転送より小さいことを確認する
+///
+/// 5万件と20万件のフォルダで計測する。時間がかかるため
+/// `cargo test --test backup_e2e -- --ignored --nocapture` で実行する
+#[tokio::test]
+#[ignore]
+async fn low_memory_mode_keeps_peak_memory_flat_for_huge_directory() {
+    if reset_peak_memory_kb().is_none() {
+        eprintln!("/proc/self/clear_refs を使えないためスキップします");
+        return;
+    }
+    let work_dir = TempDir::new("server");
+    let Some(server) = start_ssh_server(&work_dir.0) else {
+        return;
+    };
+
+    let small_count = HUGE_DIRECTORY_FILES / 4;
+    let small_dir = TempDir::new("remote-small");
+    let huge_dir = TempDir::new("remote-huge");
+    for i in 0..HUGE_DIRECTORY_FILES {
+        std::fs::write(huge_dir.0.join(format!("file-{:06}.txt", i)), "").unwrap();
+        if i < small_count {
+            std::fs::write(small_dir.0.join(format!("file-{:06}.txt", i)), "").unwrap();
+        }
+    }
+    let local_dir = TempDir::new("local-huge");
+
+    // 先に実行した転送で確保したメモリが再利用されて増加量が小さく見えないよう、低メモリモードの大きい方から実行する
+    let low_memory_huge = peak_memory_growth_kb(&server, &huge_dir.0, &local_dir.0.join("low-huge"), HUGE_DIRECTORY_FILES, true).await;
+    let low_memory_small = peak_memory_growth_kb(&server, &small_dir.0, &local_dir.0.join("low-small"), small_count, true).await;
+    let default_huge = peak_memory_growth_kb(&server, &huge_dir.0, &local_dir.0.join("default-huge"), HUGE_DIRECTORY_FILES, false).await;
+
+    // ファイル数が4倍でも増加量はほぼ変わらない（読み取りのバッファなどの揺れは許容する）
+    assert!(
+        low_memory_huge <= low_memory_small * 2 + 4096,
+        "低メモリモードの最大使用メモリがファイル数に応じて増えています: {} 件で {} KB、{} 件で {} KB",
+        small_count, low_memory_small, HUGE_DIRECTORY_FILES, low_memory_huge
+    );
+    assert!(
+        low_memory_huge < default_huge,
+        "低メモリモードの方が最大使用メモリが増えています: 低メモリ {} KB、通常 {} KB",
+        low_memory_huge, default_huge
+    );
+}
+
+/// 2つのファイルの内容が同じか（大きなファイルでも全体を読み込まずに比べる）
+fn files_equal(a: &Path, b: &Path) -> bool {
+    use std::io::Read;
+
+    let (Ok(mut file_a), Ok(mut file_b)) = (std::fs::File::open(a), std::fs::File::open(b)) else {
+        return false;
+    };
+    let mut buf_a = vec![0u8; 1024 * 1024];
+    let mut buf_b = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file_a.read(&mut buf_a).unwrap();
+        if read == 0 {
+            return file_b.read(&mut buf_b).unwrap() == 0;
+        }
+        if file_b.read_exact(&mut buf_b[..read]).is_err() || buf_a[..read] != buf_b[..read] {
+            return false;
+        }
+    }
+}
+
+/// 1GBのファイルをバックアップし、かかった時間を返す
+async fn transfer_duration(server: &SshServer, remote: &Path, local: &Path, pipelined_transfer: bool) -> Duration {
+    let options = BackupOptions { pipelined_transfer, ..Default::default() };
+
+    let started = Instant::now();
+    let message = client_for(server)
+        .backup_folder_with_progress(
+            &remote.to_string_lossy(),
+            &local.to_string_lossy(),
+            std::sync::Arc::new(BackupControl::new()),
+            &options,
+            |_| {},
+        )
+        .await
+        .expect("バックアップに失敗しました");
+    let elapsed = started.elapsed();
+
+    assert!(message.contains("転送ファイル数: 1"), "想定外の結果: {}", message);
+    assert!(files_equal(&remote.join("large.bin"), &local.join("large.bin")), "転送したファイルの内容が一致しません");
+    eprintln!(
+        "pipelined_transfer={}: {:.1} 秒（{:.1} MB/s）",
+        pipelined_transfer,
+        elapsed.as_secs_f64(),
+        BENCHMARK_FILE_SIZE as f64 / 1024.0 / 1024.0 / elapsed.as_secs_f64()
+    );
+    elapsed
+}
+
+/// 1GBのファイルでパイプライン転送と逐次転送の所要時間を比べる
+///
+/// ローカルの sshd では往復の遅延がほぼないため、パイプライン転送が遅くならないことだけを確認する。
+/// 遅延のある回線の効果は出力した速度で比べる。時間がかかるため
+/// `cargo test --test backup_e2e -- --ignored --nocapture` で実行する
+#[tokio::test]
+#[ignore]
+async fn pipelined_transfer_is_not_slower_for_large_file() {
+    let work_dir = TempDir::new("server");
+    let Some(server) = start_ssh_server(&work_dir.0) else {
+        return;
+    };
+
+    let remote_dir = TempDir::new("remote-benchmark");
+    {
+        use std::io::Write;
+
+        let chunk: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let mut file = std::fs::File::create(remote_dir.0.join("large.bin")).unwrap();
+        for _ in 0..BENCHMARK_FILE_SIZE / chunk.len() {
+            file.write_all(&chunk).unwrap();
+        }
+    }
+    let local_dir = TempDir::new("local-benchmark");
+
+    // 1回目はページキャッシュの影響を受けるため捨てる
+    transfer_duration(&server, &remote_dir.0, &local_dir.0.join("warmup"), false).await;
+    let serial = transfer_duration(&server, &remote_dir.0, &local_dir.0.join("serial"), false).await;
+    let pipelined = transfer_duration(&server, &remote_dir.0, &local_dir.0.join("pipelined"), true).await;
+
+    assert!(
+        pipelined.as_secs_f64() <= serial.as_secs_f64() * 1.25,
+        "パイプライン転送の方が遅くなっています: パイプライン {:.1} 秒、逐次 {:.1} 秒",
+        pipelined.as_secs_f64(), serial.as_secs_f64()
+    );
+}
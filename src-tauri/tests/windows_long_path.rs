@@ -0,0 +1,29 @@
+//! Windows の MAX_PATH（260文字）を超える保存先にファイルを作成できるかの確認
+#![cfg(windows)]
+
+use kyosho_backup_lib::ssh_client::SshClient;
+
+/// 作成するファイルのパスの長さ（MAX_PATH を十分に超える）
+const LONG_PATH_LENGTH: usize = 320;
+
+#[test]
+fn extended_length_path_creates_files_beyond_max_path() {
+    let root = std::env::temp_dir().join(format!("kyosho-long-path-{}", std::process::id()));
+    let extended_root = SshClient::extended_length_path(&root);
+    assert!(extended_root.to_string_lossy().starts_with(r"\\?\"));
+    // 既に拡張長パスなら二重に付けない
+    assert_eq!(SshClient::extended_length_path(&extended_root), extended_root);
+
+    let mut deep_dir = extended_root.clone();
+    while deep_dir.as_os_str().len() < LONG_PATH_LENGTH {
+        deep_dir.push("deeply-nested-directory");
+    }
+    let file_path = deep_dir.join("index.html");
+
+    let result = std::fs::create_dir_all(&deep_dir)
+        .and_then(|()| std::fs::write(&file_path, b"<html></html>"))
+        .and_then(|()| std::fs::read(&file_path));
+    let _ = std::fs::remove_dir_all(&extended_root);
+
+    assert_eq!(result.expect("MAX_PATH を超えるファイルの作成に失敗しました"), b"<html></html>");
+}
@@ -4,7 +4,7 @@ import { Lock, Shield, Clock } from 'lucide-react';
 
 interface PinAuthModalProps {
   isOpen: boolean;
-  onSuccess: () => void;
+  onSuccess: (pin: string) => void;
   onCancel: () => void;
   mode: 'setup' | 'verify';
   title?: string;
@@ -73,7 +73,7 @@ const PinAuthModal: React.FC<PinAuthModalProps> = ({
       } else {
         const isValid = await invoke<boolean>('verify_pin', { pin });
         if (isValid) {
-          onSuccess();
+          onSuccess(pin);
         } else {
           setError('PINが正しくありません');
         }
@@ -7,7 +7,7 @@ interface AuthContextType {
   isLoading: boolean;
   authenticate: (pin: string) => Promise<boolean>;
   setupPin: (pin: string) => Promise<void>;
-  disablePin: () => Promise<void>;
+  disablePin: (pin: string) => Promise<void>;
   logout: () => void;
   checkPinStatus: () => Promise<void>;
 }
@@ -74,9 +74,9 @@ export const AuthProvider: React.FC<AuthProviderProps> = ({ children }) => {
     }
   };
 
-  const disablePin = async (): Promise<void> => {
+  const disablePin = async (pin: string): Promise<void> => {
     try {
-      await invoke('disable_pin');
+      await invoke('disable_pin', { pin });
       setIsPinEnabled(false);
       setIsAuthenticated(true);
     } catch (error) {
@@ -8,6 +8,7 @@ import {
   ArrowLeft,
   CheckCircle,
   XCircle,
+  AlertTriangle,
   Pause,
   Clock,
   FileText,
@@ -100,6 +101,8 @@ const HistoryPage: React.FC = () => {
         return { color: '#4caf50', background: '#e8f5e8' };
       case 'Failed':
         return { color: '#f44336', background: '#ffebee' };
+      case 'PartialSuccess':
+        return { color: '#ff9800', background: '#fff3e0' };
       default:
         return { color: '#ff9800', background: '#fff3e0' };
     }
@@ -306,6 +309,11 @@ const HistoryPage: React.FC = () => {
                             <XCircle className="w-3 h-3" />
                             失敗
                           </>
+                        ) : entry.status === 'PartialSuccess' ? (
+                          <>
+                            <AlertTriangle className="w-3 h-3" />
+                            一部失敗{entry.failed_files ? ` (${entry.failed_files}件)` : ''}
+                          </>
                         ) : (
                           <>
                             <Pause className="w-3 h-3" />
@@ -371,7 +379,7 @@ const HistoryPage: React.FC = () => {
                 {entry.message && (
                   <div style={{
                     padding: '0.75rem',
-                    background: entry.status === 'Success' ? '#e8f5e8' : '#ffebee',
+                    background: entry.status === 'Success' ? '#e8f5e8' : entry.status === 'PartialSuccess' ? '#fff3e0' : '#ffebee',
                     borderRadius: '0.25rem',
                     fontSize: '0.9rem',
                     whiteSpace: 'pre-wrap'
@@ -81,6 +81,9 @@ const MainPage: React.FC = () => {
             const baseProgress = Math.min(progress.transferred_files * 2, 100);
             setProgressPercent(baseProgress);
           }
+
+          // 受信確認（確認するまでの進捗はバックエンドで間引かれ、最新のものだけが届く）
+          invoke('ack_backup_progress').catch(() => {});
         });
       } catch (error) {
         console.error('進捗イベントリスナーの設定に失敗しました:', error);
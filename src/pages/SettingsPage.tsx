@@ -159,11 +159,11 @@ const SettingsPage: React.FC = () => {
     setTimeout(() => setPinMessage(''), 3000);
   };
 
-  const handlePinDisableSuccess = async () => {
+  const handlePinDisableSuccess = async (pin: string) => {
     setShowPinDisable(false);
 
     try {
-      await disablePin();
+      await disablePin(pin);
       setPinMessage('✅ PINが無効化されました');
     } catch (error) {
       setPinMessage('❌ PIN無効化に失敗しました: ' + String(error));
@@ -35,7 +35,8 @@ export interface BackupProgress {
   transferred_bytes: number;          // 転送済みバイト数
   current_file?: string;              // 現在処理中のファイル名
   elapsed_seconds: number;            // 経過時間
-  transfer_speed?: number;            // 転送速度 (MB/s)
+  transfer_speed?: number;            // 直近の転送速度 (MB/s)
+  average_speed?: number;             // 開始からの平均転送速度 (MB/s)
 }
 
 // バックアップ履歴エントリ型
@@ -46,10 +47,11 @@ export interface BackupHistoryEntry {
   local_path: string;
   transferred_files: number;
   elapsed_seconds: number;
-  status: 'Success' | 'Failed' | 'Cancelled';
+  status: 'Success' | 'Failed' | 'Cancelled' | 'PartialSuccess';
   message: string;
   ssh_host: string;
   ssh_user: string;
+  failed_files?: number;              // 転送に失敗したファイル数（一部失敗時）
 }
 
 // バックアップ統計情報型
@@ -57,6 +59,7 @@ export interface BackupStatistics {
   total_backups: number;
   successful_backups: number;
   failed_backups: number;
+  partial_backups?: number;           // 一部のファイルが失敗したバックアップ数
   success_rate: number;
   total_files_transferred: number;
   total_time_spent: number;
@@ -89,14 +92,14 @@ export interface TauriCommands {
     local_folder: string
   ) => TauriResult<string>;
 
-  save_settings: (settings: AppSettings) => TauriResult<void>;
+  save_settings: (settings: AppSettings, pin?: string) => TauriResult<void>;
   load_settings: () => TauriResult<AppSettings>;
 
   // PIN認証関連
-  setup_pin: (pin: string) => TauriResult<void>;
+  setup_pin: (pin: string, current_pin?: string) => TauriResult<void>;
   verify_pin: (pin: string) => TauriResult<boolean>;
   is_pin_enabled: () => TauriResult<boolean>;
-  disable_pin: () => TauriResult<void>;
+  disable_pin: (pin: string) => TauriResult<void>;
   get_lockout_remaining_minutes: () => TauriResult<number | null>;
 
   // バックアップ履歴関連
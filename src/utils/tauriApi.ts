@@ -39,11 +39,11 @@ export async function backupFolder(data: SettingsFormData): Promise<string> {
 }
 
 /**
- * 設定を保存
+ * 設定を保存（フックのコマンドを変更する場合はPINが必要）
  */
-export async function saveSettings(settings: AppSettings): Promise<void> {
+export async function saveSettings(settings: AppSettings, pin?: string): Promise<void> {
   try {
-    await invoke('save_settings', { settings });
+    await invoke('save_settings', { settings, pin });
   } catch (error) {
     throw new Error(error as string);
   }
@@ -79,6 +79,19 @@ export async function selectFolder(): Promise<string | null> {
   }
 }
 
+/**
+ * 保存先への書き込み速度（MB/s）を計測
+ * バックアップが遅い原因が保存先のディスクかSSH接続かを切り分けるために使う
+ */
+export async function benchmarkLocalWrite(path: string, sizeMb?: number): Promise<number> {
+  try {
+    const result = await invoke<number>('benchmark_local_write', { path, sizeMb });
+    return result;
+  } catch (error) {
+    throw new Error(error as string);
+  }
+}
+
 /**
  * ファイル選択ダイアログを開く
  */